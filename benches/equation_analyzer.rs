@@ -47,5 +47,62 @@ fn bench_plot(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, bench_calculate, bench_plot);
+/// Benchmark calculate() (tokenize, parse and interpret on every call)
+/// against evaluating the same equation compiled to bytecode once
+fn bench_compiled(c: &mut Criterion) {
+    let equations = vec![
+        ("polynomial", "3x^4 - 2x^3 + 5x^2 - x + 7"),
+        ("trigonometric", "sin(π/4) * cos(π/3) + tan(π/6) * x"),
+        ("complex", "sin(x) * cos(2x) + tan(x/2) - sqrt(abs(x))"),
+        (
+            "statistical",
+            "avg(10, 20, x) + min(5, 15, 25) * max(2, 4, 8)",
+        ),
+    ];
+
+    for (name, eq) in equations {
+        c.bench_function(&format!("interpreted_{name}"), |b| {
+            b.iter(|| calculator::calculate(black_box(eq)));
+        });
+        if let Ok(compiled) = calculator::compile(eq) {
+            c.bench_function(&format!("compiled_{name}"), |b| {
+                b.iter(|| compiled.eval(black_box(0.0)));
+            });
+        }
+    }
+}
+
+/// Benchmark a 100k-point grid: eval() per point against eval_batch()
+fn bench_batch(c: &mut Criterion) {
+    let xs: Vec<f32> = (0..100_000).map(|i| i as f32 / 10_000.0 - 5.0).collect();
+    let mut ys = vec![0.0; xs.len()];
+    let equations = vec![
+        ("polynomial", "3x^4 - 2x^3 + 5x^2 - x + 7"),
+        ("trigonometric", "sin(2x) * cos(x) + tan(x/3)"),
+    ];
+
+    for (name, eq) in equations {
+        let Ok(compiled) = calculator::compile(eq) else {
+            continue;
+        };
+        c.bench_function(&format!("eval_loop_100k_{name}"), |b| {
+            b.iter(|| {
+                for (x, y) in xs.iter().zip(ys.iter_mut()) {
+                    *y = compiled.eval(black_box(*x)).unwrap_or(f32::NAN);
+                }
+            });
+        });
+        c.bench_function(&format!("eval_batch_100k_{name}"), |b| {
+            b.iter(|| calculator::eval_batch(&compiled, black_box(&xs), &mut ys));
+        });
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_calculate,
+    bench_plot,
+    bench_compiled,
+    bench_batch
+);
 criterion_main!(benches);
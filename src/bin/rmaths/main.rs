@@ -0,0 +1,283 @@
+//! `rmaths`: evaluate and plot equations from the command line.
+//!
+//! ```text
+//! rmaths eval "2 + 3 * 4"
+//! rmaths plot "x^2" --from -2 --to 2 --step 0.5 --format json
+//! rmaths defs load library.rmath
+//! rmaths --defs library.rmath < expressions.txt
+//! ```
+
+mod render;
+
+use render::{Format, PointWriter};
+use rusty_maths::equation_analyzer::calculator::{calculate_with, plot_iter_with};
+use rusty_maths::equation_analyzer::{Definitions, PlotIter};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufWriter};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage:
+  rmaths eval <expr> [--defs <file>]
+  rmaths plot <expr> --from <x> --to <x> --step <dx> [--format csv|json] [--defs <file>]
+  rmaths defs load <file>
+  rmaths [eval] [--defs <file>]        evaluate stdin, one expression per line
+  rmaths --help";
+
+/// Options that take a value.
+const OPTIONS: [&str; 5] = ["defs", "from", "to", "step", "format"];
+
+/// The command line split into positional arguments and `--name value`
+/// options, plus whether `--help`/`-h` was given.
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    help: bool,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                parsed.help = true;
+                continue;
+            }
+            let Some(option) = arg.strip_prefix("--") else {
+                parsed.positional.push(arg);
+                continue;
+            };
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                // The next argument is the value even if it looks like a
+                // flag: `--from -5`.
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("--{option} needs a value"))?;
+                    (option.to_string(), value)
+                }
+            };
+            if !OPTIONS.contains(&name.as_str()) {
+                return Err(format!("unknown option --{name}"));
+            }
+            parsed.options.insert(name, value);
+        }
+        Ok(parsed)
+    }
+
+    fn number(&self, name: &str) -> Result<f32, String> {
+        let value = self
+            .options
+            .get(name)
+            .ok_or_else(|| format!("missing --{name}"))?;
+        value
+            .parse()
+            .map_err(|_| format!("--{name} expects a number, got '{value}'"))
+    }
+}
+
+/// Reads a definitions file in the `Definitions` text format, rendering
+/// a failure against the offending line.
+fn load_definitions(path: &str) -> Result<Definitions, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut defs = Definitions::new();
+    defs.load_str(&source).map_err(|e| {
+        let line = e.line().unwrap_or(1);
+        let text = source.lines().nth(line - 1).unwrap_or_default();
+        format!(
+            "{path}:{line}: {}",
+            render::error(text, &e, &defs).trim_end()
+        )
+    })?;
+    Ok(defs)
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1)) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("rmaths: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Runs a command; `Err` is a usage error.
+fn run(args: impl IntoIterator<Item = String>) -> Result<ExitCode, String> {
+    let args = Args::parse(args)?;
+    if args.help {
+        println!("{USAGE}");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let defs = match args.options.get("defs") {
+        Some(path) => match load_definitions(path) {
+            Ok(defs) => defs,
+            Err(message) => {
+                eprintln!("{message}");
+                return Ok(ExitCode::FAILURE);
+            }
+        },
+        None => Definitions::new(),
+    };
+
+    let positional: Vec<&str> = args.positional.iter().map(String::as_str).collect();
+    match positional.as_slice() {
+        [] | ["eval"] | ["eval", "-"] => Ok(batch(&defs)),
+        ["eval", expr] => Ok(eval(expr, &defs)),
+        ["plot", expr] => {
+            let format = match args.options.get("format") {
+                Some(name) => {
+                    Format::parse(name).ok_or_else(|| format!("unknown format '{name}'"))?
+                }
+                None => Format::Csv,
+            };
+            let (from, to, step) = (
+                args.number("from")?,
+                args.number("to")?,
+                args.number("step")?,
+            );
+            Ok(plot(expr, from, to, step, format, &defs))
+        }
+        ["defs", "load", path] => Ok(match load_definitions(path) {
+            Ok(loaded) => {
+                print!("{}", loaded.to_source_string());
+                ExitCode::SUCCESS
+            }
+            Err(message) => {
+                eprintln!("{message}");
+                ExitCode::FAILURE
+            }
+        }),
+        _ => Err("unrecognized command".to_string()),
+    }
+}
+
+fn eval(expr: &str, defs: &Definitions) -> ExitCode {
+    match calculate_with(expr, defs) {
+        Ok(value) => {
+            println!("{value}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprint!("{}", render::error(expr, &e, defs));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Streams the plot a row at a time, so even a huge range runs in
+/// constant memory. A failing sample ends the output with its error.
+fn plot(expr: &str, from: f32, to: f32, step: f32, format: Format, defs: &Definitions) -> ExitCode {
+    let samples = match plot_iter_with(expr, from, to, step, defs) {
+        Ok(samples) => samples,
+        Err(e) => {
+            eprint!("{}", render::error(expr, &e, defs));
+            return ExitCode::FAILURE;
+        }
+    };
+    // Failing to write is most likely a closed pipe (`rmaths plot … |
+    // head`): nobody is reading any more, so stop quietly.
+    write_plot(samples, format, expr, defs).unwrap_or(ExitCode::FAILURE)
+}
+
+fn write_plot(
+    samples: PlotIter,
+    format: Format,
+    expr: &str,
+    defs: &Definitions,
+) -> io::Result<ExitCode> {
+    let mut out = PointWriter::new(BufWriter::new(io::stdout().lock()), format)?;
+    for sample in samples {
+        match sample {
+            Ok(point) => out.write(&point)?,
+            Err(e) => {
+                out.finish()?;
+                eprint!("{}", render::error(expr, &e, defs));
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+    out.finish()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Evaluates stdin line by line, skipping blank lines and `#` comments.
+/// Every line is attempted; the exit code reports whether any failed.
+fn batch(defs: &Definitions) -> ExitCode {
+    let mut failed = false;
+    for (i, line) in io::stdin().lock().lines().enumerate() {
+        let Ok(line) = line else {
+            eprintln!("rmaths: stdin is not valid UTF-8");
+            return ExitCode::FAILURE;
+        };
+        let expr = line.trim_end();
+        if expr.trim().is_empty() || expr.trim_start().starts_with('#') {
+            continue;
+        }
+        match calculate_with(expr, defs) {
+            Ok(value) => println!("{value}"),
+            Err(e) => {
+                failed = true;
+                eprint!("line {}: {}", i + 1, render::error(expr, &e, defs));
+            }
+        }
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Result<Args, String> {
+        Args::parse(list.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn options_take_values_that_look_like_flags() {
+        let parsed = args(&["plot", "-x^2", "--from", "-5", "--to=5", "--step", "0.5"]).unwrap();
+        assert_eq!(parsed.positional, ["plot", "-x^2"]);
+        assert_eq!(parsed.number("from").unwrap(), -5.0);
+        assert_eq!(parsed.number("to").unwrap(), 5.0);
+        assert!(parsed.number("format").is_err());
+        assert!(args(&["eval", "1", "--form", "csv"]).is_err());
+        assert!(args(&["plot", "x", "--from"]).is_err());
+    }
+
+    #[test]
+    fn help_is_a_flag() {
+        assert!(args(&["--help"]).unwrap().help);
+        let parsed = args(&["plot", "-h", "x"]).unwrap();
+        assert!(parsed.help);
+        assert_eq!(parsed.positional, ["plot", "x"]);
+        // As an option's value it is just the value.
+        assert!(!args(&["--format", "-h"]).unwrap().help);
+    }
+
+    #[test]
+    fn definitions_files_load_in_order() {
+        // Unique per test and process, so parallel runs don't collide.
+        let name = format!("rmaths-defs-load-in-order-{}.rmath", std::process::id());
+        let path = std::env::temp_dir().join(&name);
+        std::fs::write(
+            &path,
+            "# library\na = 3\ng(x) = a * x^2  # quadratic\nb = g(2)\n",
+        )
+        .unwrap();
+        let defs = load_definitions(path.to_str().unwrap()).unwrap();
+        assert_eq!(defs.value("b"), Some(12.0));
+
+        std::fs::write(&path, "a = 3\nb = sinq(a)\n").unwrap();
+        let err = load_definitions(path.to_str().unwrap()).unwrap_err();
+        assert!(err.contains(&format!("{name}:2: error: Invalid function name sinq")));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+//! Output formatting: caret-marked errors and plot points.
+
+use rusty_maths::equation_analyzer::{Definitions, EquationError, Point};
+use std::fmt::Write;
+use std::io;
+
+/// The error, the source line it points into, and a caret line under its
+/// span. An error inside a user function points into that function's
+/// body, so the body is the line shown.
+///
+/// ```text
+/// error: Invalid function name sinq — did you mean 'sin'?
+///   2 + sinq(3)
+///       ^^^^ help: sin
+/// ```
+pub fn error(source: &str, err: &EquationError, defs: &Definitions) -> String {
+    let mut out = format!("error: {}\n", err.message);
+    let shown = match &err.in_function {
+        Some(name) => {
+            let _ = writeln!(out, "  called from: {source}");
+            // A function that failed to load has no body to show.
+            let Some(body) = defs.function_body(name) else {
+                let _ = writeln!(out, "  in {name}(x)");
+                return out;
+            };
+            let _ = writeln!(out, "  in {name}(x) = {body}");
+            body
+        }
+        None => source,
+    };
+    let Some(span) = err.span else {
+        return out;
+    };
+    let _ = writeln!(out, "  {shown}");
+    let _ = write!(
+        out,
+        "  {}{}",
+        " ".repeat(span.start),
+        "^".repeat(span.len().max(1))
+    );
+    match err.fix() {
+        Some(fix) if fix.replacement.is_empty() => out.push_str(" help: remove this"),
+        Some(fix) if fix.span == span => {
+            let _ = write!(out, " help: {}", fix.replacement);
+        }
+        Some(fix) => {
+            let _ = write!(out, " help: {}", fix.apply(shown));
+        }
+        None => {}
+    }
+    out.push('\n');
+    out
+}
+
+/// Plot output formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A JSON number, or `null` for the values JSON can't represent.
+fn json_number(v: f32) -> String {
+    if v.is_finite() {
+        v.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Writes plot points as they arrive, so a plot of any length streams in
+/// constant memory: a CSV header or JSON `[` up front, one row per point,
+/// and the closing `]` on [`finish`](Self::finish).
+pub struct PointWriter<W: io::Write> {
+    out: W,
+    format: Format,
+    written: usize,
+}
+
+impl<W: io::Write> PointWriter<W> {
+    pub fn new(mut out: W, format: Format) -> io::Result<Self> {
+        match format {
+            Format::Csv => writeln!(out, "x,y")?,
+            Format::Json => write!(out, "[")?,
+        }
+        Ok(PointWriter {
+            out,
+            format,
+            written: 0,
+        })
+    }
+
+    pub fn write(&mut self, p: &Point) -> io::Result<()> {
+        match self.format {
+            Format::Csv => writeln!(self.out, "{},{}", p.x, p.y)?,
+            Format::Json => {
+                let comma = if self.written == 0 { "" } else { "," };
+                write!(
+                    self.out,
+                    "{comma}{{\"x\":{},\"y\":{}}}",
+                    json_number(p.x),
+                    json_number(p.y)
+                )?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.format == Format::Json {
+            writeln!(self.out, "]")?;
+        }
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use rusty_maths::equation_analyzer::calculator::{calculate, calculate_with};
+
+    #[test]
+    fn carets_sit_under_the_span() {
+        let eq = "2 + sinq(3)";
+        let rendered = error(eq, &calculate(eq).unwrap_err(), &Definitions::new());
+        assert_eq!(
+            rendered,
+            "error: Invalid function name sinq — did you mean 'sin'?\n  2 + sinq(3)\n      ^^^^ help: sin\n"
+        );
+    }
+
+    #[test]
+    fn function_errors_show_the_body() {
+        let mut defs = Definitions::new();
+        defs.define_function("g", "x + )").unwrap();
+        let err = calculate_with("g(1)", &defs).unwrap_err();
+        let rendered = error("g(1)", &err, &defs);
+        assert!(rendered.contains("  in g(x) = x + )\n"));
+        assert!(rendered.contains("\n  x + )\n      ^"));
+    }
+
+    fn points(points: &[Point], format: Format) -> String {
+        let mut out = Vec::new();
+        let mut writer = PointWriter::new(&mut out, format).unwrap();
+        for p in points {
+            writer.write(p).unwrap();
+        }
+        writer.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn json_has_no_nan() {
+        let out = points(
+            &[Point::new(0.0, f32::NAN), Point::new(1.0, 2.5)],
+            Format::Json,
+        );
+        assert_eq!(out, "[{\"x\":0,\"y\":null},{\"x\":1,\"y\":2.5}]\n");
+        let out = points(&[Point::new(1.0, 2.5)], Format::Csv);
+        assert_eq!(out, "x,y\n1,2.5\n");
+        assert_eq!(points(&[], Format::Json), "[]\n");
+    }
+}
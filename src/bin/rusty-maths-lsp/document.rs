@@ -0,0 +1,427 @@
+//! One `.rmath` file: a line-oriented list of definitions and expressions.
+//!
+//! ```text
+//! # Comments run from '#' to the end of the line.
+//! a = 3               # a value: the expression is evaluated once
+//! g(x) = a * x^2      # a function of x, read late like Definitions bodies
+//! g(2) + 1            # an expression: checked and evaluated
+//! y = g(x)            # `y = …` is an expression too
+//! ```
+//!
+//! Lines are read by the library's [`SourceLine`] grammar — the format
+//! `Definitions::load_str` and `rmaths --defs` load — with expression lines
+//! evaluated in place rather than rejected.
+//!
+//! Functions are defined first, so they may call each other in any order;
+//! values are then evaluated top to bottom, each seeing the values above.
+//! Columns are character offsets into the line, like every [`Span`].
+
+use rusty_maths::equation_analyzer::calculator::{calculate_with, diagnostics};
+use rusty_maths::equation_analyzer::definitions::SourceLine;
+use rusty_maths::equation_analyzer::diagnostics::Severity;
+use rusty_maths::equation_analyzer::{
+    catalog, complete, lex, Completion, Definition, Definitions, EquationError, Span, TokenKind,
+};
+
+/// What a line holds. Spans are columns into the line.
+#[derive(Debug, Clone, PartialEq)]
+enum LineKind {
+    Empty,
+    Value { name: Span, rhs: Span },
+    Function { name: Span, rhs: Span },
+    Expression { rhs: Span },
+}
+
+#[derive(Debug)]
+struct Line {
+    text: Vec<char>,
+    kind: LineKind,
+    /// What a value or expression line evaluated to.
+    value: Option<f32>,
+}
+
+impl Line {
+    fn slice(&self, span: Span) -> String {
+        self.text[span.start..span.end].iter().collect()
+    }
+
+    /// The equation part of the line: a definition's right-hand side or
+    /// the whole expression.
+    fn rhs(&self) -> Option<Span> {
+        match self.kind {
+            LineKind::Empty => None,
+            LineKind::Value { rhs, .. }
+            | LineKind::Function { rhs, .. }
+            | LineKind::Expression { rhs } => Some(rhs),
+        }
+    }
+
+    fn name(&self) -> Option<Span> {
+        match self.kind {
+            LineKind::Value { name, .. } | LineKind::Function { name, .. } => Some(name),
+            LineKind::Empty | LineKind::Expression { .. } => None,
+        }
+    }
+}
+
+/// A problem on one line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub line: usize,
+    pub span: Span,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct Document {
+    lines: Vec<Line>,
+    defs: Definitions,
+    problems: Vec<Problem>,
+}
+
+/// `start..end` of `text` with surrounding whitespace removed.
+fn trimmed(text: &[char], start: usize, end: usize) -> Span {
+    let mut span = Span::new(start, end);
+    while span.start < span.end && text[span.start].is_whitespace() {
+        span.start += 1;
+    }
+    while span.end > span.start && text[span.end - 1].is_whitespace() {
+        span.end -= 1;
+    }
+    span
+}
+
+/// Classifies one line by the shared text-format grammar; a malformed
+/// line is a problem, and holds nothing.
+fn classify(text: &str) -> (LineKind, Option<(Span, String)>) {
+    match SourceLine::parse(text) {
+        Ok(SourceLine::Blank) => (LineKind::Empty, None),
+        Ok(SourceLine::Value { name, expr }) => (LineKind::Value { name, rhs: expr }, None),
+        Ok(SourceLine::Function { name, body }) => (LineKind::Function { name, rhs: body }, None),
+        Ok(SourceLine::Expression { expr }) => (LineKind::Expression { rhs: expr }, None),
+        Err(e) => (LineKind::Empty, e.span.map(|span| (span, e.message))),
+    }
+}
+
+impl Document {
+    pub fn parse(source: &str) -> Document {
+        let mut doc = Document {
+            lines: Vec::new(),
+            defs: Definitions::new(),
+            problems: Vec::new(),
+        };
+        for (number, text) in source.split('\n').enumerate() {
+            let text = text.trim_end_matches('\r');
+            let (kind, problem) = classify(text);
+            let text: Vec<char> = text.chars().collect();
+            if let Some((span, message)) = problem {
+                doc.error(number, span, message);
+            }
+            doc.lines.push(Line {
+                text,
+                kind,
+                value: None,
+            });
+        }
+
+        for number in 0..doc.lines.len() {
+            let line = &doc.lines[number];
+            if let LineKind::Function { name, rhs } = line.kind {
+                let (name_text, body) = (line.slice(name), line.slice(rhs));
+                if let Err(e) = doc.defs.define_function(&name_text, &body) {
+                    doc.error(number, name, e.message);
+                }
+            }
+        }
+        for number in 0..doc.lines.len() {
+            let line = &doc.lines[number];
+            match line.kind {
+                LineKind::Value { name, rhs } => {
+                    let name_text = line.slice(name);
+                    if let Some(value) = doc.evaluate(number, rhs) {
+                        doc.lines[number].value = Some(value);
+                        if let Err(e) = doc.defs.define_value(&name_text, value) {
+                            doc.error(number, name, e.message);
+                        }
+                    }
+                }
+                LineKind::Expression { rhs } => {
+                    doc.lines[number].value = doc.evaluate(number, rhs);
+                }
+                LineKind::Function { .. } | LineKind::Empty => {}
+            }
+        }
+        // Bodies are checked against every definition in the file.
+        for number in 0..doc.lines.len() {
+            if let LineKind::Function { rhs, .. } = doc.lines[number].kind {
+                doc.check(number, rhs);
+            }
+        }
+        doc.problems.sort_by_key(|p| (p.line, p.span.start));
+        doc
+    }
+
+    fn error(&mut self, line: usize, span: Span, message: impl Into<String>) {
+        self.problems.push(Problem {
+            line,
+            span,
+            severity: Severity::Error,
+            message: message.into(),
+        });
+    }
+
+    /// Reports every diagnostic in the equation at `rhs`; true when there
+    /// are no errors among them.
+    fn check(&mut self, number: usize, rhs: Span) -> bool {
+        let line = &self.lines[number];
+        let source = line.slice(rhs);
+        let mut clean = true;
+        for d in diagnostics(&source, &self.defs) {
+            clean &= !d.is_error();
+            let span = d.span.map_or(trimmed(&line.text, rhs.start, rhs.end), |s| {
+                Span::new(rhs.start + s.start, (rhs.start + s.end).min(rhs.end))
+            });
+            self.problems.push(Problem {
+                line: number,
+                span,
+                severity: d.severity,
+                message: d.message,
+            });
+        }
+        clean
+    }
+
+    /// Checks, then evaluates, the equation at `rhs`.
+    fn evaluate(&mut self, number: usize, rhs: Span) -> Option<f32> {
+        if !self.check(number, rhs) {
+            return None;
+        }
+        let line = &self.lines[number];
+        match calculate_with(&line.slice(rhs), &self.defs) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                let span = Self::error_span(line, rhs, &e);
+                let message = match &e.in_function {
+                    Some(name) => format!("in {name}(x): {}", e.message),
+                    None => e.message,
+                };
+                self.error(number, span, message);
+                None
+            }
+        }
+    }
+
+    /// Where on the line an evaluation error goes: at its span, unless the
+    /// span points into a function body — then the whole equation.
+    fn error_span(line: &Line, rhs: Span, e: &EquationError) -> Span {
+        match e.span {
+            Some(s) if e.in_function.is_none() => {
+                Span::new(rhs.start + s.start, (rhs.start + s.end).min(rhs.end))
+            }
+            _ => trimmed(&line.text, rhs.start, rhs.end),
+        }
+    }
+
+    pub fn problems(&self) -> &[Problem] {
+        &self.problems
+    }
+
+    /// The lexeme under `col`, in line coordinates, with its source text.
+    fn lexeme_at(&self, number: usize, col: usize) -> Option<(TokenKind, Span, String)> {
+        let line = self.lines.get(number)?;
+        let rhs = line.rhs()?;
+        if !(rhs.start..rhs.end).contains(&col) {
+            return None;
+        }
+        lex(&line.slice(rhs), &self.defs)
+            .map(|l| {
+                let span = Span::new(rhs.start + l.span.start, rhs.start + l.span.end);
+                (l.kind, span)
+            })
+            .find(|(_, span)| (span.start..span.end).contains(&col))
+            .map(|(kind, span)| (kind, span, line.slice(span)))
+    }
+
+    /// A definition written out as source, with its current value.
+    fn describe(&self, name: &str) -> Option<String> {
+        self.defs.iter().find_map(|def| match def {
+            Definition::Value { name: n, value } if n == name => Some(format!("{n} = {value}")),
+            Definition::Function { name: n, body } if n == name => Some(format!("{n}(x) = {body}")),
+            _ => None,
+        })
+    }
+
+    /// Markdown for the symbol under `col`: a catalog summary and example,
+    /// a definition's source, or else what the line evaluates to.
+    pub fn hover(&self, number: usize, col: usize) -> Option<String> {
+        let line = self.lines.get(number)?;
+        if let Some(name) = line.name().filter(|n| (n.start..n.end).contains(&col)) {
+            return self.describe(&line.slice(name)).map(|s| format!("`{s}`"));
+        }
+        match self.lexeme_at(number, col) {
+            Some((TokenKind::Function | TokenKind::Constant, _, text)) => {
+                // `log_2` documents as `log`.
+                let sym = catalog::find(text.split('_').next().unwrap_or(&text))?;
+                return Some(format!(
+                    "**{}** — {}\n\n`{}`",
+                    sym.name, sym.summary, sym.example
+                ));
+            }
+            Some((TokenKind::UserFunction | TokenKind::UserValue, _, text)) => {
+                return self.describe(&text).map(|s| format!("`{s}`"));
+            }
+            _ => {}
+        }
+        line.value.map(|v| format!("`= {v}`"))
+    }
+
+    /// Completions for the name before `col`, with spans in line columns.
+    pub fn complete(&self, number: usize, col: usize) -> Vec<Completion> {
+        let Some(line) = self.lines.get(number) else {
+            return Vec::new();
+        };
+        let Some(rhs) = line.rhs().filter(|r| (r.start..=r.end).contains(&col)) else {
+            return Vec::new();
+        };
+        complete(&line.slice(rhs), col - rhs.start, &self.defs)
+            .into_iter()
+            .map(|mut c| {
+                c.span = Span::new(rhs.start + c.span.start, rhs.start + c.span.end);
+                c
+            })
+            .collect()
+    }
+
+    /// The line and name span defining the user name under `col`.
+    pub fn definition(&self, number: usize, col: usize) -> Option<(usize, Span)> {
+        let (kind, _, name) = self.lexeme_at(number, col)?;
+        if !matches!(kind, TokenKind::UserFunction | TokenKind::UserValue) {
+            return None;
+        }
+        // A later definition replaces an earlier one; the last one is live.
+        self.lines.iter().enumerate().rev().find_map(|(i, line)| {
+            line.name()
+                .filter(|&span| line.slice(span) == name)
+                .map(|span| (i, span))
+        })
+    }
+
+    /// `col` in UTF-16 code units, as LSP counts by default.
+    pub fn utf16_col(&self, number: usize, col: usize) -> usize {
+        self.lines.get(number).map_or(col, |line| {
+            line.text.iter().take(col).map(|c| c.len_utf16()).sum()
+        })
+    }
+
+    /// The character column at UTF-16 offset `col`.
+    pub fn char_col(&self, number: usize, col: usize) -> usize {
+        let Some(line) = self.lines.get(number) else {
+            return col;
+        };
+        let mut units = 0;
+        for (i, c) in line.text.iter().enumerate() {
+            if units >= col {
+                return i;
+            }
+            units += c.len_utf16();
+        }
+        line.text.len()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+# a shared formula library
+a = 3
+g(x) = a * x^2  # quadratic
+g(2) + 1
+b = g(a) / 0 + sinq(1)
+h(t) = t
+y = π * x
+";
+
+    fn messages(doc: &Document) -> Vec<(usize, &str)> {
+        doc.problems()
+            .iter()
+            .map(|p| (p.line, p.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn lines_are_defined_and_checked() {
+        let doc = Document::parse(SOURCE);
+        assert_eq!(
+            messages(&doc),
+            [
+                (4, "Invalid function name sinq — did you mean 'sin'?"),
+                // A malformed line defines nothing, as in `load_str`.
+                (5, "Functions take one parameter, always named x"),
+            ]
+        );
+        assert_eq!(doc.problems()[0].span, Span::new(15, 19));
+        assert_eq!(doc.lines[3].value, Some(13.0));
+        assert_eq!(doc.lines[6].value, Some(0.0));
+    }
+
+    #[test]
+    fn lines_follow_the_definitions_grammar() {
+        // Dotted names are definitions, as `load_str` reads them.
+        let doc = Document::parse("phys.g = 9.8\nphys.g * 2");
+        assert_eq!(messages(&doc), []);
+        assert_eq!(
+            doc.lines[0].kind,
+            LineKind::Value {
+                name: Span::new(0, 6),
+                rhs: Span::new(9, 12),
+            }
+        );
+        assert_eq!(doc.lines[1].value, Some(19.6));
+        let doc = Document::parse("a =  # nothing");
+        assert_eq!(messages(&doc), [(0, "Expected a definition after '='")]);
+    }
+
+    #[test]
+    fn values_see_the_values_above() {
+        let doc = Document::parse("b = a + 1\na = 2\nc = a + 1");
+        assert_eq!(messages(&doc), [(0, "Unknown name 'a'")]);
+        assert_eq!(doc.lines[2].value, Some(3.0));
+    }
+
+    #[test]
+    fn function_errors_point_at_the_call() {
+        let doc = Document::parse("f(x) = root(x, 2)\nf(0 - 4)");
+        assert_eq!(doc.problems().len(), 1);
+        assert_eq!(doc.problems()[0].span, Span::new(0, 8));
+        assert!(doc.problems()[0].message.starts_with("in f(x): "));
+    }
+
+    #[test]
+    fn hover_completion_and_definition() {
+        let doc = Document::parse(SOURCE);
+        assert_eq!(doc.hover(3, 0).unwrap(), "`g(x) = a * x^2`");
+        assert_eq!(doc.hover(3, 5).unwrap(), "`= 13`");
+        assert_eq!(doc.hover(1, 0).unwrap(), "`a = 3`");
+        assert!(doc.hover(6, 4).unwrap().starts_with("**π** — "));
+        assert_eq!(doc.hover(0, 3), None);
+
+        let names: Vec<String> = doc.complete(3, 1).into_iter().map(|c| c.label).collect();
+        assert_eq!(names[0], "g");
+        assert_eq!(doc.complete(3, 1)[0].span, Span::new(0, 1));
+
+        assert_eq!(doc.definition(3, 0), Some((2, Span::new(0, 1))));
+        assert_eq!(doc.definition(2, 7), Some((1, Span::new(0, 1))));
+        assert_eq!(doc.definition(3, 5), None);
+    }
+
+    #[test]
+    fn columns_convert_utf16_col() {
+        let doc = Document::parse("y = 𝜋x + π");
+        assert_eq!(doc.utf16_col(0, 6), 7);
+        assert_eq!(doc.char_col(0, 7), 6);
+    }
+}
@@ -0,0 +1,286 @@
+//! `rusty-maths-lsp`: a language server for `.rmath` formula files, over
+//! stdio. Build it with `cargo build --features lsp`.
+//!
+//! It publishes diagnostics as documents change and answers hover,
+//! completion and go-to-definition requests. Documents sync in full; see
+//! [`document`] for the file format.
+
+mod document;
+mod protocol;
+
+use document::Document;
+use protocol::{
+    error_response, notification, read_message, response, write_message, METHOD_NOT_FOUND,
+    REQUEST_FAILED,
+};
+use rusty_maths::equation_analyzer::diagnostics::Severity;
+use rusty_maths::equation_analyzer::{Span, TokenKind};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufReader, Write};
+use std::process::ExitCode;
+
+struct Server<W: Write> {
+    output: W,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+/// The document URI and position a request is about, as (uri, line,
+/// character column).
+fn position(params: &Value) -> Option<(String, usize, usize)> {
+    let uri = params["textDocument"]["uri"].as_str()?.to_string();
+    let line = params["position"]["line"].as_u64()? as usize;
+    let character = params["position"]["character"].as_u64()? as usize;
+    Some((uri, line, character))
+}
+
+/// LSP's `CompletionItemKind` for a lexer token kind.
+fn completion_kind(kind: TokenKind) -> u64 {
+    match kind {
+        TokenKind::Function | TokenKind::UserFunction => 3,
+        TokenKind::Variable | TokenKind::UserValue => 6,
+        TokenKind::Constant => 21,
+        TokenKind::Operator => 24,
+        _ => 1,
+    }
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, message: &Value) -> io::Result<()> {
+        write_message(&mut self.output, message)
+    }
+
+    fn range(doc: &Document, line: usize, span: Span) -> Value {
+        json!({
+            "start": { "line": line, "character": doc.utf16_col(line, span.start) },
+            "end": { "line": line, "character": doc.utf16_col(line, span.end) },
+        })
+    }
+
+    fn publish(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics: Vec<Value> = self.documents.get(uri).map_or(Vec::new(), |doc| {
+            doc.problems()
+                .iter()
+                .map(|p| {
+                    json!({
+                        "range": Self::range(doc, p.line, p.span),
+                        "severity": match p.severity {
+                            Severity::Error => 1,
+                            Severity::Warning => 2,
+                        },
+                        "source": "rusty-maths",
+                        "message": p.message,
+                    })
+                })
+                .collect()
+        });
+        self.send(&notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        ))
+    }
+
+    fn open(&mut self, uri: &str, text: &str) -> io::Result<()> {
+        self.documents
+            .insert(uri.to_string(), Document::parse(text));
+        self.publish(uri)
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.open(uri, text)
+            }
+            "textDocument/didChange" => {
+                // Full sync: the last change holds the whole document.
+                let changes = params["contentChanges"].as_array();
+                match changes
+                    .and_then(|c| c.last())
+                    .and_then(|c| c["text"].as_str())
+                {
+                    Some(text) => self.open(uri, text),
+                    None => Ok(()),
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.publish(uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The result for a request, or an error code and message.
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "rusty-maths-lsp", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" | "textDocument/completion" | "textDocument/definition" => {
+                let (uri, line, character) = position(params)
+                    .ok_or_else(|| (REQUEST_FAILED, "Missing document position".to_string()))?;
+                let doc = self
+                    .documents
+                    .get(&uri)
+                    .ok_or_else(|| (REQUEST_FAILED, format!("Unknown document {uri}")))?;
+                let col = doc.char_col(line, character);
+                Ok(match method {
+                    "textDocument/hover" => doc.hover(line, col).map_or(Value::Null, |text| {
+                        json!({ "contents": { "kind": "markdown", "value": text } })
+                    }),
+                    "textDocument/completion" => {
+                        let items: Vec<Value> = doc
+                            .complete(line, col)
+                            .into_iter()
+                            .map(|c| {
+                                json!({
+                                    "label": c.label,
+                                    "kind": completion_kind(c.kind),
+                                    "detail": c.detail,
+                                    "textEdit": {
+                                        "range": Self::range(doc, line, c.span),
+                                        "newText": c.label,
+                                    },
+                                })
+                            })
+                            .collect();
+                        Value::from(items)
+                    }
+                    _ => doc
+                        .definition(line, col)
+                        .map_or(Value::Null, |(target, span)| {
+                            json!({ "uri": uri, "range": Self::range(doc, target, span) })
+                        }),
+                })
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unsupported method {method}"))),
+        }
+    }
+
+    /// Handles one message; `Some(exit code)` once the client says `exit`.
+    fn handle(&mut self, message: &Value) -> io::Result<Option<ExitCode>> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        if method == "exit" {
+            return Ok(Some(if self.shutdown {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }));
+        }
+        match message.get("id") {
+            // Responses to our own requests (we send none) have no method.
+            Some(id) if !method.is_empty() => {
+                let reply = match self.request(method, params) {
+                    Ok(result) => response(id, result),
+                    Err((code, text)) => error_response(id, code, &text),
+                };
+                self.send(&reply)?;
+            }
+            Some(_) => {}
+            None => self.notify(method, params)?,
+        }
+        Ok(None)
+    }
+}
+
+fn main() -> ExitCode {
+    let mut input = BufReader::new(io::stdin().lock());
+    let mut server = Server {
+        output: io::stdout().lock(),
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return ExitCode::FAILURE,
+            Err(e) => {
+                eprintln!("rusty-maths-lsp: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        match server.handle(&message) {
+            Ok(Some(code)) => return code,
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("rusty-maths-lsp: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn server() -> Server<Vec<u8>> {
+        Server {
+            output: Vec::new(),
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Every message the server wrote.
+    fn sent(server: &Server<Vec<u8>>) -> Vec<Value> {
+        let mut input = io::Cursor::new(server.output.clone());
+        std::iter::from_fn(|| read_message(&mut input).unwrap()).collect()
+    }
+
+    #[test]
+    fn open_publishes_diagnostics() {
+        let mut s = server();
+        let open = notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": "file:///a.rmath", "text": "a = 2\nsinq(a)" } }),
+        );
+        assert_eq!(s.handle(&open).unwrap(), None);
+        let published = sent(&s);
+        let diagnostics = &published[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({ "line": 1, "character": 0 })
+        );
+        assert_eq!(diagnostics[0]["severity"], 1);
+    }
+
+    #[test]
+    fn requests_answer_and_exit_follows_shutdown() {
+        let mut s = server();
+        s.open("file:///a.rmath", "a = 2\na + 1").unwrap();
+        let hover = json!({
+            "jsonrpc": "2.0", "id": 7, "method": "textDocument/hover",
+            "params": { "textDocument": { "uri": "file:///a.rmath" }, "position": { "line": 1, "character": 0 } },
+        });
+        s.handle(&hover).unwrap();
+        let unknown = json!({ "jsonrpc": "2.0", "id": 8, "method": "workspace/symbol" });
+        s.handle(&unknown).unwrap();
+        let replies = sent(&s);
+        assert_eq!(replies[1]["id"], 7);
+        assert_eq!(replies[1]["result"]["contents"]["value"], "`a = 2`");
+        assert_eq!(replies[2]["error"]["code"], METHOD_NOT_FOUND);
+
+        let exit = json!({ "jsonrpc": "2.0", "method": "exit" });
+        assert_eq!(s.handle(&exit).unwrap(), Some(ExitCode::FAILURE));
+        s.handle(&json!({ "jsonrpc": "2.0", "id": 9, "method": "shutdown" }))
+            .unwrap();
+        assert_eq!(s.handle(&exit).unwrap(), Some(ExitCode::SUCCESS));
+    }
+}
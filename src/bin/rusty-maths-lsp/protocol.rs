@@ -0,0 +1,73 @@
+//! JSON-RPC framing for the Language Server Protocol: each message is a
+//! `Content-Length` header block followed by a JSON body.
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+/// Reads the next message, or `None` at end of input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message without Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+pub fn response(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+pub fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// JSON-RPC's "method not found" error code.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// LSP's "request failed" error code, for requests about unknown documents.
+pub const REQUEST_FAILED: i64 = -32803;
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let message = notification("initialized", json!({}));
+        let mut framed = Vec::new();
+        write_message(&mut framed, &message).unwrap();
+        let mut input = io::Cursor::new(framed);
+        assert_eq!(read_message(&mut input).unwrap(), Some(message));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+}
@@ -1,8 +1,20 @@
-use crate::equation_analyzer::definitions::Definitions;
-use crate::equation_analyzer::errors::EquationError;
-use crate::equation_analyzer::pipeline::evaluator::evaluate_with;
+use crate::equation_analyzer::compiled::CompiledEquation;
+use crate::equation_analyzer::definitions::{Definitions, MemoizedDefinitions};
+use crate::equation_analyzer::diagnostics::Diagnostic;
+use crate::equation_analyzer::dual::Dual;
+use crate::equation_analyzer::errors::{EquationError, ErrorKind};
+use crate::equation_analyzer::fit::{fit_template, Fit};
+use crate::equation_analyzer::interval::Interval;
+use crate::equation_analyzer::options::EvalOptions;
+use crate::equation_analyzer::pipeline::bytecode::{BatchEnv, Bytecode};
+use crate::equation_analyzer::pipeline::domain::{evaluate_domain, Domain};
+use crate::equation_analyzer::pipeline::evaluator::{evaluate_with, EvalState};
 use crate::equation_analyzer::pipeline::parser::parse;
 use crate::equation_analyzer::pipeline::tokenizer::StreamingTokenizer;
+use crate::equation_analyzer::plot::{PlotIter, Sample, TolerantPlot};
+use crate::equation_analyzer::taylor::{self, Jet, TaylorSeries};
+use crate::equation_analyzer::trace::Trace;
+use crate::equation_analyzer::units::Quantity;
 use crate::equation_analyzer::utils::{get_x_values, Point};
 
 use rayon::prelude::*;
@@ -58,10 +70,545 @@ pub fn calculate(eq: &str) -> Result<f32, EquationError> {
 /// assert_eq!(calculate_with("g(2)", &defs).unwrap(), 4.0);
 /// ```
 pub fn calculate_with(eq: &str, defs: &Definitions) -> Result<f32, EquationError> {
+    calculate_with_options(eq, defs, &EvalOptions::default())
+}
+
+/// Like [`calculate_with`], with [`EvalOptions`] — e.g. a seed that makes
+/// `rand`, `randint`, `randn` and `choose` reproducible.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::calculate_with_options;
+/// use rusty_maths::equation_analyzer::{Definitions, EvalOptions};
+///
+/// let opts = EvalOptions::new().with_seed(42);
+/// let roll = calculate_with_options("randint(1, 6)", &Definitions::new(), &opts).unwrap();
+/// assert!((1.0..=6.0).contains(&roll));
+/// assert_eq!(
+///     calculate_with_options("randint(1, 6)", &Definitions::new(), &opts).unwrap(),
+///     roll
+/// );
+/// ```
+pub fn calculate_with_options(
+    eq: &str,
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<f32, EquationError> {
+    opts.limits().check_input(eq)?;
+    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
+    let parsed = parse(tokenizer)?;
+    let ctx = defs.compile().memoized(opts.memoizes());
+    let mut state = EvalState::new(Some(&ctx), opts.seed_or_random()).with_limits(opts.limits());
+    evaluate_with(parsed.iter().copied(), None, &mut state)
+}
+
+/// Evaluates an equation like [`calculate_with`], recording each operator
+/// and function applied — see [`trace`](crate::equation_analyzer::trace).
+///
+/// # Returns
+/// * `Ok(Trace)` - the steps in evaluation order, and the result; an
+///   evaluation error is in `Trace::result`, after the steps that
+///   succeeded
+/// * `Err(EquationError)` - the equation doesn't tokenize or parse
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::trace;
+/// use rusty_maths::equation_analyzer::Definitions;
+///
+/// let mut defs = Definitions::new();
+/// defs.define_function("g", "x^2 + 1").unwrap();
+///
+/// let t = trace("g(3) * 2", &defs).unwrap();
+/// let steps: Vec<String> = t.steps.iter().map(|s| s.to_string()).collect();
+/// assert_eq!(
+///     steps,
+///     ["in g(x): 3 ^ 2 = 9", "in g(x): 9 + 1 = 10", "g(3) = 10", "10 * 2 = 20"]
+/// );
+/// ```
+pub fn trace(eq: &str, defs: &Definitions) -> Result<Trace, EquationError> {
+    trace_with_options(eq, defs, &EvalOptions::default())
+}
+
+/// Like [`trace`], with [`EvalOptions`]: a seed for random draws, and
+/// limits on the input and the evaluation. Each recorded step is a step
+/// against the limits, so a trace holds at most `max_steps` of them; when
+/// a limit stops the evaluation, its error is in `Trace::result`. Calls
+/// are never memoized, so every call shows its steps.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::trace_with_options;
+/// use rusty_maths::equation_analyzer::{Definitions, EvalLimits, EvalOptions};
+///
+/// let mut defs = Definitions::new();
+/// defs.define_function("g", "g(x) + 1").unwrap();
+///
+/// let opts = EvalOptions::new().with_limits(EvalLimits::new().with_max_steps(20));
+/// let t = trace_with_options("g(1)", &defs, &opts).unwrap();
+/// assert!(t.steps.len() <= 20);
+/// assert_eq!(t.result.unwrap_err().message, "Step limit (20) exceeded");
+/// ```
+pub fn trace_with_options(
+    eq: &str,
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<Trace, EquationError> {
+    opts.limits().check_input(eq)?;
+    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
+    let parsed = parse(tokenizer)?;
+    let ctx = defs.compile();
+    let mut state = EvalState::new(Some(&ctx), opts.seed_or_random())
+        .with_limits(opts.limits())
+        .tracing();
+    let result = evaluate_with(parsed.iter().copied(), None, &mut state);
+    Ok(Trace {
+        steps: state.take_trace(),
+        result,
+    })
+}
+
+/// Every error and warning in an equation, instead of only the first
+/// error — see [`diagnostics`](crate::equation_analyzer::diagnostics).
+///
+/// Names resolve against `defs`, as in [`calculate_with`]. An empty result
+/// means the equation parses cleanly; it may still fail to evaluate for
+/// value-dependent reasons (a domain error, a call-depth limit).
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::diagnostics;
+/// use rusty_maths::equation_analyzer::diagnostics::Severity;
+/// use rusty_maths::equation_analyzer::Definitions;
+///
+/// let found = diagnostics("-x^2 + (1", &Definitions::new());
+/// assert_eq!(found.len(), 2);
+/// assert_eq!(found[0].severity, Severity::Warning); // -x^2 is -(x^2)
+/// assert_eq!(found[1].severity, Severity::Error); // the unclosed `(`
+///
+/// assert!(diagnostics("2 + 2", &Definitions::new()).is_empty());
+/// ```
+pub fn diagnostics(eq: &str, defs: &Definitions) -> Vec<Diagnostic> {
+    crate::equation_analyzer::diagnostics::check(eq, defs)
+}
+
+/// Compiles an equation for repeated evaluation at different `x` — see
+/// [`compiled`](crate::equation_analyzer::compiled).
+///
+/// Only tokenizer and parser errors surface here; everything else is
+/// reported by [`CompiledEquation::eval`], exactly as [`calculate`] would
+/// report it.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::compile;
+///
+/// let f = compile("3x^2 - 2x + 1").unwrap();
+/// let ys: Vec<f32> = (0..4).map(|i| f.eval(i as f32).unwrap()).collect();
+/// assert_eq!(ys, vec![1.0, 2.0, 9.0, 22.0]);
+///
+/// assert!(compile("root(x - 1, 2)").unwrap().eval(0.0).is_err());
+/// ```
+pub fn compile(eq: &str) -> Result<CompiledEquation, EquationError> {
+    compile_with(eq, &Definitions::default())
+}
+
+/// Like [`compile`], with user [`Definitions`] in scope. The definitions
+/// are bound now: non-recursive functions are inlined, and values fold
+/// into the bytecode.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::compile_with;
+/// use rusty_maths::equation_analyzer::Definitions;
+///
+/// let mut defs = Definitions::new();
+/// defs.define_value("a", 3.0).unwrap();
+/// defs.define_function("g", "a * x^2").unwrap();
+///
+/// let f = compile_with("g(x) + 1", &defs).unwrap();
+/// assert_eq!(f.eval(2.0).unwrap(), 13.0);
+/// ```
+pub fn compile_with(eq: &str, defs: &Definitions) -> Result<CompiledEquation, EquationError> {
+    compile_with_options(eq, defs, &EvalOptions::default())
+}
+
+/// Like [`compile_with`], with [`EvalOptions`]. A seed makes random draws
+/// reproducible, matching [`plot_with_options`] sample for sample; limits
+/// bound every later evaluation.
+pub fn compile_with_options(
+    eq: &str,
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<CompiledEquation, EquationError> {
+    opts.limits().check_input(eq)?;
+    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
+    let parsed = parse(tokenizer)?;
+    let ctx = defs.compile().memoized(opts.memoizes());
+    Ok(CompiledEquation {
+        code: Bytecode::compile(&parsed, &ctx),
+        seed: opts.seed(),
+        limits: opts.limits().clone(),
+        memo: ctx.detach(),
+    })
+}
+
+/// Evaluates a compiled equation at every `xs[i]`, writing `ys[i]`.
+///
+/// Each bytecode instruction runs across a block of `x` values at once,
+/// and Rayon spreads the blocks over threads — much faster than calling
+/// [`CompiledEquation::eval`] per point for large grids. Results are
+/// identical to `eval`'s, random draws included.
+///
+/// # Returns
+/// * `Ok(())` - every `ys[i]` written
+/// * `Err(EquationError)` - the lengths differ, or the error of the first
+///   `x` (in slice order) that fails; `ys` is then partially written
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::{compile, eval_batch};
+///
+/// let f = compile("x^2 + 1").unwrap();
+/// let xs: Vec<f32> = (0..1000).map(|i| i as f32 / 100.0).collect();
+/// let mut ys = vec![0.0; xs.len()];
+/// eval_batch(&f, &xs, &mut ys).unwrap();
+/// assert_eq!(ys[300], 10.0);
+///
+/// let g = compile("root(x, 2)").unwrap();
+/// let err = eval_batch(&g, &[4.0, -1.0, -9.0], &mut [0.0; 3]).unwrap_err();
+/// assert_eq!(err.message, "root(-1, 2) is not a real number"); // the first failure
+/// ```
+pub fn eval_batch(
+    compiled: &CompiledEquation,
+    xs: &[f32],
+    ys: &mut [f32],
+) -> Result<(), EquationError> {
+    if xs.len() != ys.len() {
+        return Err(EquationError::new(format!(
+            "eval_batch needs one output per input, got {} x values and {} y slots",
+            xs.len(),
+            ys.len()
+        ))
+        .with_kind(ErrorKind::InvalidArgument));
+    }
+    let ctx = compiled.memo.as_ref().map(MemoizedDefinitions::compile);
+    let env = BatchEnv {
+        seed: compiled.base_seed(),
+        ctx: ctx.as_ref(),
+        limits: &compiled.limits,
+    };
+    batch(&compiled.code, xs, ys, &env)
+}
+
+/// Points per Rayon task in batch evaluation.
+const BATCH_BLOCK: usize = 1024;
+
+/// Runs `code` over `xs` in parallel blocks, reporting the first failing
+/// point's error in slice order.
+fn batch(code: &Bytecode, xs: &[f32], ys: &mut [f32], env: &BatchEnv) -> Result<(), EquationError> {
+    let results: Vec<Result<(), EquationError>> = xs
+        .par_chunks(BATCH_BLOCK)
+        .zip(ys.par_chunks_mut(BATCH_BLOCK))
+        .map_init(
+            || code.batch_scratch(),
+            |scratch, (xs, ys)| code.run_batch(xs, ys, env, scratch),
+        )
+        .collect();
+    results.into_iter().collect()
+}
+
+/// Evaluates an equation whose values carry physical units — see
+/// [`units`](crate::equation_analyzer::units) for the registry and rules.
+///
+/// Unit names resolve after catalog names and user definitions, so
+/// nothing that works in [`calculate_with`] changes meaning. Dimensional
+/// errors (adding metres to seconds, `sin` of a length) are spanned at the
+/// operator or call that raised them. `x` is the dimensionless 0.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::calculate_units;
+/// use rusty_maths::equation_analyzer::Definitions;
+///
+/// let defs = Definitions::new();
+/// let v = calculate_units("9.8 m/s^2 * 2 s", &defs).unwrap();
+/// assert_eq!(v.to_string(), "19.6 m/s");
+///
+/// let err = calculate_units("3 m + 2 s", &defs).unwrap_err();
+/// assert_eq!(err.message, "Dimension mismatch: cannot add m and s");
+/// assert_eq!(err.span.map(|s| (s.start, s.end)), Some((4, 5))); // "+"
+/// ```
+pub fn calculate_units(eq: &str, defs: &Definitions) -> Result<Quantity, EquationError> {
+    calculate_units_with_options(eq, defs, &EvalOptions::default())
+}
+
+/// Like [`calculate_units`], with [`EvalOptions`]: a seed for random draws,
+/// and limits on the input and the evaluation.
+pub fn calculate_units_with_options(
+    eq: &str,
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<Quantity, EquationError> {
+    opts.limits().check_input(eq)?;
+    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?.with_units();
+    let parsed = parse(tokenizer)?;
+    let ctx = defs.compile_units();
+    let mut state = EvalState::new(Some(&ctx), opts.seed_or_random()).with_limits(opts.limits());
+    evaluate_domain(&parsed, &Quantity::number(0.0), &mut state)
+}
+
+/// Evaluates an equation and its derivative with respect to `x`, by
+/// forward-mode automatic differentiation — exact, with no step size to
+/// choose, including through user functions and variadic catalog
+/// functions. See [`dual`](crate::equation_analyzer::dual) for how
+/// non-differentiable points are treated.
+///
+/// # Returns
+/// * `Ok((f(x), f'(x)))`
+/// * `Err(EquationError)` - as from [`calculate_with`]
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::eval_with_derivative;
+/// use rusty_maths::equation_analyzer::Definitions;
+///
+/// let mut defs = Definitions::new();
+/// defs.define_function("g", "x^3").unwrap();
+///
+/// // d/dx [g(x) + sin(x)] = 3x^2 + cos(x)
+/// let (f, df) = eval_with_derivative("g(x) + sin(x)", 0.0, &defs).unwrap();
+/// assert_eq!((f, df), (0.0, 1.0));
+///
+/// let (_, df) = eval_with_derivative("max(x^2, 2x)", 3.0, &defs).unwrap();
+/// assert_eq!(df, 6.0);
+/// ```
+pub fn eval_with_derivative(
+    eq: &str,
+    x: f32,
+    defs: &Definitions,
+) -> Result<(f32, f32), EquationError> {
+    eval_with_derivative_with_options(eq, x, defs, &EvalOptions::default())
+}
+
+/// Like [`eval_with_derivative`], with [`EvalOptions`]: a seed for random
+/// draws, and limits on the input and the evaluation.
+pub fn eval_with_derivative_with_options(
+    eq: &str,
+    x: f32,
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<(f32, f32), EquationError> {
+    opts.limits().check_input(eq)?;
+    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
+    let parsed = parse(tokenizer)?;
+    let ctx = defs.compile();
+    let mut state = EvalState::new(Some(&ctx), opts.seed_or_random()).with_limits(opts.limits());
+    let y = evaluate_domain(&parsed, &Dual::new(f64::from(x), 1.0), &mut state)?;
+    Ok((y.v as f32, y.d as f32))
+}
+
+/// Fits the free parameters of an equation template to data by
+/// Levenberg–Marquardt least squares.
+///
+/// `initial` names the parameters and their starting values; every other
+/// name in the template must resolve as usual. Nonlinear fits need a
+/// start in the right basin — a sign or an order of magnitude is usually
+/// enough.
+///
+/// # Returns
+/// * `Ok(Fit)` - fitted parameters, fitted values, residuals and R²
+/// * `Err(EquationError)` - mismatched or non-finite data, a template or
+///   parameter-name error, or a template undefined at the starting point
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::fit;
+///
+/// let xs: Vec<f32> = (0..10).map(|i| i as f32 * 0.5).collect();
+/// let ys: Vec<f32> = xs.iter().map(|x| 3.0 * (-0.7 * x).exp() + 1.0).collect();
+///
+/// let fit = fit("a * exp(b*x) + c", &xs, &ys, &[("a", 1.0), ("b", -0.1), ("c", 0.0)]).unwrap();
+/// assert!((fit.parameter("b").unwrap() + 0.7).abs() < 1e-4);
+/// assert!(fit.r_squared > 0.9999);
+/// ```
+pub fn fit(
+    template: &str,
+    xs: &[f32],
+    ys: &[f32],
+    initial: &[(&str, f32)],
+) -> Result<Fit, EquationError> {
+    fit_with(template, xs, ys, initial, &Definitions::default())
+}
+
+/// Like [`fit`], with user [`Definitions`] in scope. The parameters shadow
+/// any user value of the same name; user functions can't read them.
+pub fn fit_with(
+    template: &str,
+    xs: &[f32],
+    ys: &[f32],
+    initial: &[(&str, f32)],
+    defs: &Definitions,
+) -> Result<Fit, EquationError> {
+    fit_with_options(template, xs, ys, initial, defs, &EvalOptions::default())
+}
+
+/// Like [`fit_with`], with [`EvalOptions`]. The input limit applies to the
+/// template and the step limit to each evaluation of it at one `x`; the
+/// deadline and cancellation stop the whole fit.
+pub fn fit_with_options(
+    template: &str,
+    xs: &[f32],
+    ys: &[f32],
+    initial: &[(&str, f32)],
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<Fit, EquationError> {
+    opts.limits().check_input(template)?;
+    fit_template(template, xs, ys, initial, defs, opts.limits())
+}
+
+/// Expands an equation in `x` as a Taylor series about `around`, to
+/// `order` (at most 32).
+///
+/// The coefficients are exact up to rounding — computed by propagating
+/// truncated power series through the evaluator, not by finite
+/// differences. The same expansion is available inside equations as
+/// `taylor(f, a, n)`, for plotting an approximation against the original.
+///
+/// # Returns
+/// * `Ok(TaylorSeries)` - the coefficients and a polynomial equation
+/// * `Err(EquationError)` - a parse or evaluation error, or the equation
+///   is not smooth at `around` (`sqrt(x)` about 0)
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::taylor;
+///
+/// let s = taylor("sin(x)", 0.0, 5).unwrap();
+/// assert_eq!(s.coefficients, vec![0.0, 1.0, 0.0, -1.0 / 6.0, 0.0, 1.0 / 120.0]);
+/// assert_eq!(s.polynomial(), "x - 0.16666667x^3 + 0.008333334x^5");
+///
+/// let s = taylor("x^3", 1.0, 3).unwrap();
+/// assert_eq!(s.to_string(), "1 + 3 * (x - 1) + 3 * (x - 1)^2 + (x - 1)^3");
+/// ```
+pub fn taylor(eq: &str, around: f32, order: usize) -> Result<TaylorSeries, EquationError> {
+    taylor_with(eq, around, order, &Definitions::default())
+}
+
+/// Like [`taylor`], with user [`Definitions`] in scope.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::taylor_with;
+/// use rusty_maths::equation_analyzer::Definitions;
+///
+/// let mut defs = Definitions::new();
+/// defs.define_function("g", "1 / (1 - x)").unwrap();
+///
+/// let s = taylor_with("g(x)", 0.0, 3, &defs).unwrap();
+/// assert_eq!(s.polynomial(), "1 + x + x^2 + x^3");
+/// ```
+pub fn taylor_with(
+    eq: &str,
+    around: f32,
+    order: usize,
+    defs: &Definitions,
+) -> Result<TaylorSeries, EquationError> {
+    taylor_with_options(eq, around, order, defs, &EvalOptions::default())
+}
+
+/// Like [`taylor_with`], with [`EvalOptions`]: a seed for random draws,
+/// and limits on the input and the expansion.
+pub fn taylor_with_options(
+    eq: &str,
+    around: f32,
+    order: usize,
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<TaylorSeries, EquationError> {
+    if order > taylor::MAX_ORDER || !around.is_finite() {
+        return Err(EquationError::new(format!(
+            "Invalid expansion: order must be at most {} and the point finite, got order {order} about {around}",
+            taylor::MAX_ORDER
+        )).with_kind(ErrorKind::InvalidArgument));
+    }
+    opts.limits().check_input(eq)?;
     let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
     let parsed = parse(tokenizer)?;
     let ctx = defs.compile();
-    evaluate_with(parsed.iter().copied(), None, Some(&ctx))
+    let mut state = EvalState::new(Some(&ctx), opts.seed_or_random()).with_limits(opts.limits());
+    let x = Jet::variable(f64::from(around), order);
+    let series = evaluate_domain(&parsed, &x, &mut state)?;
+    Ok(TaylorSeries {
+        around,
+        coefficients: series.padded(order).iter().map(|&c| c as f32).collect(),
+    })
+}
+
+/// Encloses the range of an equation for `x` anywhere in `[lo, hi]` — see
+/// [`interval`](crate::equation_analyzer::interval) for the guarantees.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::calculate_interval;
+///
+/// // sin reaches its peak inside [0, 2], so the enclosure does too.
+/// let y = calculate_interval("sin(x)", [0.0, 2.0]).unwrap();
+/// assert_eq!(y.hi, 1.0);
+/// assert!(y.lo <= 0.0);
+///
+/// // No root of x^2 + 1 can hide in [-10, 10]: the enclosure excludes 0.
+/// assert!(!calculate_interval("x^2 + 1", [-10.0, 10.0]).unwrap().contains(0.0));
+/// ```
+pub fn calculate_interval(eq: &str, x: [f32; 2]) -> Result<Interval, EquationError> {
+    calculate_interval_with(eq, x, &Definitions::default())
+}
+
+/// Like [`calculate_interval`], with user [`Definitions`] in scope.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::calculate_interval_with;
+/// use rusty_maths::equation_analyzer::Definitions;
+///
+/// let mut defs = Definitions::new();
+/// defs.define_function("f", "3x - 1").unwrap();
+///
+/// let y = calculate_interval_with("f(x)^2", [0.0, 1.0], &defs).unwrap();
+/// assert_eq!((y.lo, y.hi), (0.0, 4.0));
+/// ```
+pub fn calculate_interval_with(
+    eq: &str,
+    x: [f32; 2],
+    defs: &Definitions,
+) -> Result<Interval, EquationError> {
+    calculate_interval_with_options(eq, x, defs, &EvalOptions::default())
+}
+
+/// Like [`calculate_interval_with`], with [`EvalOptions`]. Only the limits
+/// matter: an enclosure holds for every draw, so there is no seed to set.
+pub fn calculate_interval_with_options(
+    eq: &str,
+    x: [f32; 2],
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<Interval, EquationError> {
+    let [lo, hi] = x;
+    // NaN fails both comparisons; an inverted box is almost certainly a
+    // swapped call rather than an intended empty set.
+    if lo.is_nan() || hi.is_nan() || lo > hi {
+        return Err(EquationError::new(format!(
+            "Invalid interval [{lo}, {hi}]: lower bound must not exceed upper bound"
+        ))
+        .with_kind(ErrorKind::InvalidArgument));
+    }
+    opts.limits().check_input(eq)?;
+    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
+    let parsed = parse(tokenizer)?;
+    let ctx = defs.compile();
+    let mut state = EvalState::new(Some(&ctx), 0).with_limits(opts.limits());
+    evaluate_domain(&parsed, &Interval::new(lo, hi), &mut state)
 }
 
 /// Plots a mathematical equation over a range of x values.
@@ -109,27 +656,234 @@ pub fn plot_with(
     step_size: f32,
     defs: &Definitions,
 ) -> Result<Vec<Point>, EquationError> {
-    // A non-positive step would loop forever below; NaN fails every
+    plot_with_options(eq, x_min, x_max, step_size, defs, &EvalOptions::default())
+}
+
+/// Like [`plot_with`], with [`EvalOptions`]. Each sample draws from its own
+/// generator, seeded by [`seed_for_x`](crate::equation_analyzer::options::seed_for_x)
+/// from the plot's seed and the sample's `x` — so seeded noise is identical
+/// across runs however Rayon splits the work.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::plot_with_options;
+/// use rusty_maths::equation_analyzer::{Definitions, EvalOptions};
+///
+/// let opts = EvalOptions::new().with_seed(1);
+/// let defs = Definitions::new();
+/// let a = plot_with_options("x + randn(0, 0.1)", 0.0, 1.0, 0.1, &defs, &opts).unwrap();
+/// let b = plot_with_options("x + randn(0, 0.1)", 0.0, 1.0, 0.1, &defs, &opts).unwrap();
+/// assert_eq!(a, b);
+/// ```
+pub fn plot_with_options(
+    eq: &str,
+    x_min: f32,
+    x_max: f32,
+    step_size: f32,
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<Vec<Point>, EquationError> {
+    check_plot(eq, x_min, x_max, step_size, opts)?;
+
+    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
+    let parsed_eq = parse(tokenizer)?;
+    let ctx = defs.compile().memoized(opts.memoizes());
+    let code = Bytecode::compile(&parsed_eq, &ctx);
+    let env = BatchEnv {
+        seed: opts.seed_or_random(),
+        ctx: Some(&ctx),
+        limits: opts.limits(),
+    };
+
+    let x_values = get_x_values(x_min, x_max, step_size, opts.limits())?;
+    let mut y_values = vec![0.0; x_values.len()];
+    batch(&code, &x_values, &mut y_values, &env)?;
+
+    Ok(x_values
+        .into_iter()
+        .zip(y_values)
+        .map(|(x, y)| Point { x, y })
+        .collect())
+}
+
+/// Like [`plot`], but a failing sample doesn't fail the plot: it becomes a
+/// gap in the returned [`TolerantPlot`], and runs of gaps are summarized
+/// as [`Gap`](crate::equation_analyzer::plot::Gap) ranges carrying the
+/// first error of each. Samples are evaluated in parallel, as in `plot`.
+///
+/// # Returns
+/// * `Ok(TolerantPlot)` - every sample, with gaps where evaluation failed
+/// * `Err(EquationError)` - the step size is invalid or the equation
+///   doesn't parse
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::plot_tolerant;
+///
+/// let plot = plot_tolerant("root(x, 2)", -2.0, 2.0, 0.5).unwrap();
+/// assert_eq!(plot.samples.len(), 9);
+/// assert_eq!(plot.points().count(), 5); // x = 0 .. 2
+///
+/// let gap = &plot.gaps[0];
+/// assert_eq!((gap.from, gap.to, gap.samples), (-2.0, -0.5, 4));
+/// assert_eq!(gap.error.message, "root(-2, 2) is not a real number");
+/// ```
+pub fn plot_tolerant(
+    eq: &str,
+    x_min: f32,
+    x_max: f32,
+    step_size: f32,
+) -> Result<TolerantPlot, EquationError> {
+    plot_tolerant_with(eq, x_min, x_max, step_size, &Definitions::default())
+}
+
+/// Like [`plot_tolerant`], with user [`Definitions`] in scope.
+pub fn plot_tolerant_with(
+    eq: &str,
+    x_min: f32,
+    x_max: f32,
+    step_size: f32,
+    defs: &Definitions,
+) -> Result<TolerantPlot, EquationError> {
+    plot_tolerant_with_options(eq, x_min, x_max, step_size, defs, &EvalOptions::default())
+}
+
+/// Like [`plot_tolerant_with`], with [`EvalOptions`]. Every sample, gap or
+/// not, is what [`plot_with_options`] computes at its `x`.
+pub fn plot_tolerant_with_options(
+    eq: &str,
+    x_min: f32,
+    x_max: f32,
+    step_size: f32,
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<TolerantPlot, EquationError> {
+    check_plot(eq, x_min, x_max, step_size, opts)?;
+
+    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
+    let parsed_eq = parse(tokenizer)?;
+    let ctx = defs.compile().memoized(opts.memoizes());
+    let code = Bytecode::compile(&parsed_eq, &ctx);
+    let env = BatchEnv {
+        seed: opts.seed_or_random(),
+        ctx: Some(&ctx),
+        limits: opts.limits(),
+    };
+
+    let x_values = get_x_values(x_min, x_max, step_size, opts.limits())?;
+    let blocks: Vec<Vec<Result<f32, EquationError>>> = x_values
+        .par_chunks(BATCH_BLOCK)
+        .map_init(
+            || code.batch_scratch(),
+            |scratch, xs| {
+                let mut ys = Vec::with_capacity(xs.len());
+                code.run_points(xs, &env, scratch, &mut ys)?;
+                Ok(ys)
+            },
+        )
+        .collect::<Result<_, EquationError>>()?;
+
+    Ok(x_values
+        .into_iter()
+        .zip(blocks.into_iter().flatten())
+        .map(|(x, y)| Sample { x, y })
+        .collect())
+}
+
+/// Validates a plot before anything is allocated: the step, then the
+/// [`EvalLimits`](crate::equation_analyzer::EvalLimits) on input length
+/// and point count.
+fn check_plot(
+    eq: &str,
+    x_min: f32,
+    x_max: f32,
+    step_size: f32,
+    opts: &EvalOptions,
+) -> Result<(), EquationError> {
+    // A non-positive step would sample forever; NaN fails every
     // comparison, so it needs its own check.
     if step_size <= 0.0 || step_size.is_nan() {
         return Err(EquationError::new(format!(
             "Invalid step size {step_size}: step size must be a positive number"
-        )));
+        ))
+        .with_kind(ErrorKind::InvalidArgument));
     }
+    opts.limits().check_input(eq)?;
+    opts.limits().check_points(x_min, x_max, step_size)
+}
 
-    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
-    let parsed_eq = parse(tokenizer)?;
-    let ctx = defs.compile();
+/// Like [`plot`], but lazy and per sample: the returned [`PlotIter`]
+/// yields one `Result<Point, EquationError>` for each `x`, evaluating a
+/// small block at a time as it is advanced.
+///
+/// Nothing is allocated for the whole range, so huge ranges stream; a
+/// consumer can stop early; and an error at one `x` doesn't lose the
+/// points around it.
+///
+/// # Returns
+/// * `Ok(PlotIter)` - the samples, in increasing `x`
+/// * `Err(EquationError)` - the step size is invalid or the equation
+///   doesn't parse; per-sample failures come from the iterator instead
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::plot_iter;
+///
+/// // Stops after the first point above 100 without sampling the rest.
+/// let first = plot_iter("x^2", 0.0, 1e9, 1.0)
+///     .unwrap()
+///     .find_map(|p| p.ok().filter(|p| p.y > 100.0))
+///     .unwrap();
+/// assert_eq!((first.x, first.y), (11.0, 121.0));
+///
+/// let results: Vec<_> = plot_iter("1 / root(x, 2)", -1.0, 1.0, 1.0).unwrap().collect();
+/// assert!(results[0].is_err() && results[1].is_ok() && results[2].is_ok());
+/// ```
+pub fn plot_iter(
+    eq: &str,
+    x_min: f32,
+    x_max: f32,
+    step_size: f32,
+) -> Result<PlotIter, EquationError> {
+    plot_iter_with(eq, x_min, x_max, step_size, &Definitions::default())
+}
 
-    let x_values = get_x_values(x_min, x_max, step_size);
+/// Like [`plot_iter`], with user [`Definitions`] in scope. The definitions
+/// are bound when the iterator is created.
+pub fn plot_iter_with(
+    eq: &str,
+    x_min: f32,
+    x_max: f32,
+    step_size: f32,
+    defs: &Definitions,
+) -> Result<PlotIter, EquationError> {
+    plot_iter_with_options(eq, x_min, x_max, step_size, defs, &EvalOptions::default())
+}
 
-    let points: Result<Vec<Point>, EquationError> = x_values
-        .par_iter()
-        .map(|&x| {
-            let y = evaluate_with(parsed_eq.iter().copied(), x, Some(&ctx))?;
-            Ok(Point { x, y })
-        })
-        .collect();
+/// Like [`plot_iter_with`], with [`EvalOptions`]. Samples draw random
+/// numbers exactly as [`plot_with_options`] does, so a seeded iterator
+/// yields the points of the seeded plot.
+pub fn plot_iter_with_options(
+    eq: &str,
+    x_min: f32,
+    x_max: f32,
+    step_size: f32,
+    defs: &Definitions,
+    opts: &EvalOptions,
+) -> Result<PlotIter, EquationError> {
+    check_plot(eq, x_min, x_max, step_size, opts)?;
 
-    points
+    let tokenizer = StreamingTokenizer::new_with(eq, Some(defs))?;
+    let parsed_eq = parse(tokenizer)?;
+    let ctx = defs.compile().memoized(opts.memoizes());
+    let code = Bytecode::compile(&parsed_eq, &ctx);
+    Ok(PlotIter::new(
+        code,
+        ctx.detach(),
+        opts.seed_or_random(),
+        opts.limits().clone(),
+        x_min,
+        x_max,
+        step_size,
+    ))
 }
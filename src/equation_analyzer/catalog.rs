@@ -11,9 +11,12 @@
 //! The remaining rules: a name must start with an alphabetic character and
 //! contain only alphanumerics, must not be exactly `x` or `y` (reserved for
 //! the variable and the equation marker), and `log` keeps its special
-//! `log_N(...)` surface syntax.
+//! `log_N(...)` surface syntax. Dots are left to user modules (`phys.g`).
 
+use crate::equation_analyzer::finance;
 use crate::utilities::abs_f32;
+use rand::rngs::StdRng;
+use rand::Rng;
 use std::collections::HashMap;
 use std::f32::consts::{E, PI};
 
@@ -44,7 +47,39 @@ impl PartialEq for Symbol {
 
 impl Eq for Symbol {}
 
+/// With the `serde` feature, a symbol serializes as its metadata — `name`,
+/// `aliases`, `category`, `summary` and `example`; its behavior stays in
+/// the catalog. A `&'static Symbol` deserializes by looking its `name` up
+/// there.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut record = serializer.serialize_struct("Symbol", 5)?;
+        record.serialize_field("name", self.name)?;
+        record.serialize_field("aliases", self.aliases)?;
+        record.serialize_field("category", &self.category)?;
+        record.serialize_field("summary", self.summary)?;
+        record.serialize_field("example", self.example)?;
+        record.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for &'static Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct Named {
+            name: String,
+        }
+        let Named { name } = Named::deserialize(deserializer)?;
+        find(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("no catalog symbol named '{name}'")))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Category {
     Constant,
     Arithmetic,
@@ -55,15 +90,35 @@ pub enum Category {
     Statistical,
     AngleConversion,
     Piping,
+    Finance,
+    Random,
+    Units,
+    Calculus,
     Variable,
 }
 
+impl Category {
+    /// Whether a user definition may take a name from this category.
+    ///
+    /// The core vocabulary is reserved. The finance, random, unit and
+    /// calculus families are plain words (`rate`, `choose`, `convert`)
+    /// that definitions may already use, so a definition of the same
+    /// name shadows the built-in instead of being rejected.
+    pub fn is_shadowable(self) -> bool {
+        matches!(
+            self,
+            Category::Finance | Category::Random | Category::Units | Category::Calculus
+        )
+    }
+}
+
 /// The behavior slot of a `Symbol`.
 ///
-/// Function-pointer variants (`Unary`, `UnaryChecked`, `Variadic`) carry the
-/// actual math. Purely descriptive variants (`LogBase`, `Operator`,
-/// `Variable`) are documentation for tokens whose behavior lives in the
-/// tokenizer/evaluator by necessity (special syntax or single-glyph parsing).
+/// Function-pointer variants (`Unary`, `UnaryChecked`, `Variadic`, `Random`)
+/// carry the actual math. Purely descriptive variants (`LogBase`, `Operator`,
+/// `Variable`, `UnitConversion`, `Series`) are documentation for tokens whose behavior
+/// lives in the tokenizer/evaluator by necessity (special syntax,
+/// single-glyph parsing, or a non-f32 value domain).
 #[derive(Debug, Clone, Copy)]
 pub enum SymbolKind {
     Constant(f32),
@@ -74,6 +129,15 @@ pub enum SymbolKind {
         max_args: Option<u8>,
         run: fn(&[f32]) -> Result<f32, String>,
     },
+    /// Like `Variadic`, but draws from the evaluation's random-number
+    /// generator — seeded through
+    /// [`EvalOptions`](crate::equation_analyzer::EvalOptions) when results
+    /// must be reproducible. `min_args` may be 0 (`rand()`).
+    Random {
+        min_args: u8,
+        max_args: Option<u8>,
+        run: fn(&mut StdRng, &[f32]) -> Result<f32, String>,
+    },
     /// `log_N(x)` — base is baked into the surface syntax; the tokenizer parses
     /// the `_N` suffix and stashes the base on the token payload.
     LogBase,
@@ -85,6 +149,14 @@ pub enum SymbolKind {
     },
     /// The variable `x`.
     Variable,
+    /// `convert(quantity, unit)` — re-expresses a quantity in another unit
+    /// of the same dimension. Only meaningful to unit-aware evaluation
+    /// ([`calculate_units`](crate::equation_analyzer::calculator::calculate_units)).
+    UnitConversion,
+    /// `taylor(f, a, n)` — the order-`n` Taylor polynomial of the unary
+    /// function `f` about `a`, evaluated at `x`. Its first argument is a
+    /// function name, so the tokenizer resolves it into the call itself.
+    Series,
 }
 
 impl SymbolKind {
@@ -99,6 +171,36 @@ impl SymbolKind {
     pub fn is_variadic(&self) -> bool {
         matches!(self, SymbolKind::Variadic { .. })
     }
+
+    /// Draws from the evaluation's random-number generator.
+    pub fn is_random(&self) -> bool {
+        matches!(self, SymbolKind::Random { .. })
+    }
+
+    /// Can be called with parentheses: `name(args…)`.
+    pub fn is_callable(&self) -> bool {
+        self.is_unary() || self.is_variadic() || self.is_random()
+    }
+
+    /// `(min_args, max_args)` for anything called with parentheses;
+    /// `None` for constants, operators and other non-call symbols.
+    pub fn arity(&self) -> Option<(u8, Option<u8>)> {
+        match *self {
+            SymbolKind::Unary(_) | SymbolKind::UnaryChecked(_) => Some((1, Some(1))),
+            SymbolKind::Variadic {
+                min_args, max_args, ..
+            }
+            | SymbolKind::Random {
+                min_args, max_args, ..
+            } => Some((min_args, max_args)),
+            SymbolKind::UnitConversion => Some((2, Some(2))),
+            SymbolKind::Series => Some((3, Some(3))),
+            SymbolKind::Constant(_)
+            | SymbolKind::LogBase
+            | SymbolKind::Operator { .. }
+            | SymbolKind::Variable => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -136,6 +238,22 @@ fn counting_params(xs: &[f32]) -> Result<(f64, f64), String> {
     Ok((f64::from(xs[0]), f64::from(xs[1])))
 }
 
+/// Shared validation for `randint`: both bounds must be integers, in order.
+fn randint_bounds(xs: &[f32]) -> Result<(i64, i64), String> {
+    for (i, &v) in xs.iter().enumerate() {
+        if v % 1.0 != 0.0 {
+            return Err(format!("Parameter {} must be an integer, got {}", i + 1, v));
+        }
+    }
+    let (lo, hi) = (xs[0] as i64, xs[1] as i64);
+    if lo > hi {
+        return Err(format!(
+            "randint({lo}, {hi}): lower bound exceeds upper bound"
+        ));
+    }
+    Ok((lo, hi))
+}
+
 fn counting_overflow(name: &str, n: f64, k: f64) -> String {
     format!("{name}({n}, {k}) is too large to represent (max ~3.4e38)")
 }
@@ -153,12 +271,21 @@ macro_rules! sym {
     (variadic $name:literal, [$($alias:literal),* $(,)?], $cat:ident, $summary:literal, $example:literal, min: $min:literal, max: $max:expr, $f:expr) => {
         Symbol { name: $name, aliases: &[$($alias),*], category: Category::$cat, summary: $summary, example: $example, kind: SymbolKind::Variadic { min_args: $min, max_args: $max, run: $f } }
     };
+    (random $name:literal, [$($alias:literal),* $(,)?], $cat:ident, $summary:literal, $example:literal, min: $min:literal, max: $max:expr, $f:expr) => {
+        Symbol { name: $name, aliases: &[$($alias),*], category: Category::$cat, summary: $summary, example: $example, kind: SymbolKind::Random { min_args: $min, max_args: $max, run: $f } }
+    };
     (op $name:literal, [$($alias:literal),* $(,)?], $cat:ident, $summary:literal, $example:literal, glyph: $glyph:literal, prec: $prec:literal, $assoc:ident, $arity:ident) => {
         Symbol { name: $name, aliases: &[$($alias),*], category: Category::$cat, summary: $summary, example: $example, kind: SymbolKind::Operator { glyph: $glyph, precedence: $prec, assoc: Assoc::$assoc, arity: OpArity::$arity } }
     };
     (log_base $name:literal, [$($alias:literal),* $(,)?], $cat:ident, $summary:literal, $example:literal) => {
         Symbol { name: $name, aliases: &[$($alias),*], category: Category::$cat, summary: $summary, example: $example, kind: SymbolKind::LogBase }
     };
+    (unit_conversion $name:literal, [$($alias:literal),* $(,)?], $cat:ident, $summary:literal, $example:literal) => {
+        Symbol { name: $name, aliases: &[$($alias),*], category: Category::$cat, summary: $summary, example: $example, kind: SymbolKind::UnitConversion }
+    };
+    (series $name:literal, [$($alias:literal),* $(,)?], $cat:ident, $summary:literal, $example:literal) => {
+        Symbol { name: $name, aliases: &[$($alias),*], category: Category::$cat, summary: $summary, example: $example, kind: SymbolKind::Series }
+    };
     (variable $name:literal, [$($alias:literal),* $(,)?], $cat:ident, $summary:literal, $example:literal) => {
         Symbol { name: $name, aliases: &[$($alias),*], category: Category::$cat, summary: $summary, example: $example, kind: SymbolKind::Variable }
     };
@@ -304,6 +431,51 @@ pub const CATALOG: &[Symbol] = &[
         }
         Ok(result.round() as f32)
     }),
+    // Finance — spreadsheet conventions: per-period rates, cash paid out is negative
+    sym!(variadic "pv", [], Finance, "present value — pv(rate, nper, pmt[, fv[, type]])", "pv(0, 12, -100) = 1200",
+         min: 3, max: Some(5), finance::pv),
+    sym!(variadic "fv", [], Finance, "future value — fv(rate, nper, pmt[, pv[, type]])", "fv(0, 12, -100) = 1200",
+         min: 3, max: Some(5), finance::fv),
+    sym!(variadic "pmt", [], Finance, "level payment per period — pmt(rate, nper, pv[, fv[, type]])", "pmt(0, 12, 1200) = -100",
+         min: 3, max: Some(5), finance::pmt),
+    sym!(variadic "nper", [], Finance, "number of periods — nper(rate, pmt, pv[, fv[, type]])", "nper(0, -100, 1200) = 12",
+         min: 3, max: Some(5), finance::nper),
+    sym!(variadic "rate", [], Finance, "per-period rate, solved numerically — rate(nper, pmt, pv[, fv[, type]])", "rate(360, -1199.10, 200000) ≈ 0.5%",
+         min: 3, max: Some(5), finance::rate),
+    sym!(variadic "npv", [], Finance, "net present value of cash flows at periods 1, 2, … — npv(rate, cf1, cf2, …)", "npv(10%, 110) = 100",
+         min: 2, max: None, finance::npv),
+    sym!(variadic "irr", [], Finance, "internal rate of return of cash flows at periods 0, 1, … — solved numerically", "irr(-100, 110) = 10%",
+         min: 2, max: None, finance::irr),
+    sym!(variadic "simpleint", [], Finance, "simple interest earned — simpleint(principal, rate, time)", "simpleint(1000, 5%, 2) = 100",
+         min: 3, max: Some(3), finance::simple_interest),
+    sym!(variadic "compoundint", [], Finance, "compound interest earned — compoundint(principal, rate, time[, n per time])", "compoundint(1000, 10%, 2) = 210",
+         min: 3, max: Some(4), finance::compound_interest),
+    // Random — draws come from the evaluation's seedable generator
+    sym!(random "rand", [], Random, "uniform random number in [0, 1)", "rand()", min: 0, max: Some(0),
+         |rng, _| Ok(rng.gen::<f32>())),
+    sym!(random "randint", [], Random, "uniform random integer in [a, b], both ends inclusive", "randint(1, 6)", min: 2, max: Some(2),
+    |rng, xs| {
+        let (lo, hi) = randint_bounds(xs)?;
+        Ok(rng.gen_range(lo..=hi) as f32)
+    }),
+    sym!(random "randn", [], Random, "normally distributed random number — randn(mu, sigma)", "randn(0, 1)", min: 2, max: Some(2),
+    |rng, xs| {
+        let (mu, sigma) = (xs[0], xs[1]);
+        if sigma < 0.0 || sigma.is_nan() {
+            return Err(format!("randn standard deviation must be non-negative, got {sigma}"));
+        }
+        // Box–Muller in f64; 1 - gen() lies in (0, 1], so ln never sees 0.
+        let u1 = 1.0 - rng.gen::<f64>();
+        let u2 = rng.gen::<f64>();
+        let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+        Ok(mu + sigma * z as f32)
+    }),
+    sym!(random "choose", [], Random, "one of the arguments, picked uniformly at random", "choose(1, 5, 9)", min: 1, max: None,
+         |rng, xs| Ok(xs[rng.gen_range(0..xs.len())])),
+    // Units — unit-aware evaluation only (calculator::calculate_units)
+    sym!(unit_conversion "convert", [], Units, "express a quantity in another unit of the same dimension", "convert(1 km, m) = 1000 m"),
+    // Calculus
+    sym!(series "taylor", [], Calculus, "Taylor polynomial of a unary function f about a, to order n, at x — taylor(f, a, n)", "taylor(exp, 0, 3) = 1"),
     // Operators (docs + precedence/assoc — dispatch stays glyph-tokenized in evaluator)
     sym!(op "+", [], Arithmetic, "addition", "2 + 3 = 5", glyph: "+", prec: 2, Left, Binary),
     sym!(op "-", [], Arithmetic, "subtraction (or unary negation)", "5 - 2 = 3", glyph: "-", prec: 2, Left, Binary),
@@ -445,6 +617,8 @@ mod tests {
         assert!(by_category(Category::Constant).count() >= 2); // π e
         assert!(by_category(Category::Statistical).count() >= 6); // min max avg med mode ch
         assert!(by_category(Category::Variable).count() >= 1); // x
+        assert!(by_category(Category::Finance).count() >= 7); // pv fv pmt nper rate npv irr
+        assert!(by_category(Category::Random).all(|s| s.kind.is_random()));
     }
 
     #[test]
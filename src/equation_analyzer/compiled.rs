@@ -0,0 +1,67 @@
+//! Equations compiled once for repeated evaluation.
+//!
+//! [`compile`](crate::equation_analyzer::calculator::compile) turns an
+//! equation into bytecode: catalog functions resolved to function
+//! pointers, constant subtrees folded, user functions inlined, and the
+//! value stack sized up front. Evaluating a [`CompiledEquation`] at many
+//! `x` — a plot, a table, a solver loop — skips the tokenizer, the parser
+//! and the interpreter's per-token dispatch.
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::compile;
+//!
+//! let f = compile("sin(π/4) * 2 + x^2").unwrap();
+//! assert_eq!(f.eval(3.0).unwrap(), 2.0_f32.sqrt() + 9.0);
+//! ```
+//!
+//! [`eval_batch`](crate::equation_analyzer::calculator::eval_batch) runs
+//! each instruction across a block of `x` values at once, with Rayon
+//! spreading the blocks over threads.
+//!
+//! Results and errors are the interpreter's, bit for bit. Definitions are
+//! bound when the equation is compiled: redefining a value afterwards
+//! doesn't change a compiled equation — compile it again.
+
+use crate::equation_analyzer::definitions::MemoizedDefinitions;
+use crate::equation_analyzer::errors::EquationError;
+use crate::equation_analyzer::limits::EvalLimits;
+use crate::equation_analyzer::options::seed_for_x;
+use crate::equation_analyzer::pipeline::bytecode::Bytecode;
+use crate::equation_analyzer::pipeline::evaluator::EvalState;
+
+/// An equation compiled by
+/// [`compile`](crate::equation_analyzer::calculator::compile). Cheap to
+/// share across threads.
+#[derive(Debug, Clone)]
+pub struct CompiledEquation {
+    pub(crate) code: Bytecode,
+    pub(crate) seed: Option<u64>,
+    pub(crate) limits: EvalLimits,
+    /// The memoized definitions calls recall from, when memoization is on;
+    /// shared by every evaluation, and by clones.
+    pub(crate) memo: Option<MemoizedDefinitions>,
+}
+
+impl CompiledEquation {
+    /// Evaluates the equation at `x`.
+    ///
+    /// With a seed from
+    /// [`compile_with_options`](crate::equation_analyzer::calculator::compile_with_options),
+    /// random draws at `x` are those of a seeded plot sample at `x`.
+    pub fn eval(&self, x: f32) -> Result<f32, EquationError> {
+        let ctx = self.memo.as_ref().map(MemoizedDefinitions::compile);
+        let mut state =
+            EvalState::new(ctx.as_ref(), seed_for_x(self.base_seed(), x)).with_limits(&self.limits);
+        self.code.run(x, &mut self.code.scratch(), &mut state)
+    }
+
+    /// The seed each evaluation's per-`x` seed derives from: the configured
+    /// one, a fresh one when the equation draws random numbers unseeded.
+    pub(crate) fn base_seed(&self) -> u64 {
+        match self.seed {
+            Some(seed) => seed,
+            None if self.code.is_random() => rand::random(),
+            None => 0,
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Tab completion and signature help for editors.
+//!
+//! Both take a cursor — a character offset into the equation, like every
+//! [`Span`] — and resolve names against [`Definitions`] exactly as
+//! [`calculate_with`](crate::equation_analyzer::calculator::calculate_with)
+//! would:
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::completion::{complete, signature_help};
+//! use rusty_maths::equation_analyzer::Definitions;
+//!
+//! let mut defs = Definitions::new();
+//! defs.define_function("area", "π * x^2").unwrap();
+//!
+//! let names: Vec<String> = complete("2 * ata", 7, &defs).into_iter().map(|c| c.label).collect();
+//! assert_eq!(names, ["atan", "atan2", "atanh"]);
+//!
+//! let help = signature_help("max(1, area(", 12, &defs).unwrap();
+//! assert_eq!((help.function.as_str(), help.argument), ("area", 0));
+//! assert_eq!(help.summary, "area(x) = π * x^2");
+//! ```
+
+use crate::equation_analyzer::catalog::{self, Symbol, SymbolKind};
+use crate::equation_analyzer::definitions::{Definition, Definitions};
+use crate::equation_analyzer::errors::Span;
+use crate::equation_analyzer::lexer::{lex, TokenKind};
+use crate::equation_analyzer::pipeline::tokenizer::{levenshtein, suggestion_distance};
+
+/// A name that could go at the cursor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    /// The name to insert.
+    pub label: String,
+    /// What the name is: [`TokenKind::Function`], [`TokenKind::UserFunction`],
+    /// [`TokenKind::Constant`], [`TokenKind::UserValue`],
+    /// [`TokenKind::Operator`] (`mod`) or [`TokenKind::Variable`] (`x`).
+    pub kind: TokenKind,
+    /// The partial name before the cursor that `label` replaces; empty
+    /// when the cursor doesn't follow a name.
+    pub span: Span,
+    /// The catalog summary, or a user definition's source (`g(x) = a * x`).
+    pub detail: String,
+}
+
+/// The call the cursor is inside.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureHelp {
+    /// The function's name as written (`arctan`, `log_2`, a user function).
+    pub function: String,
+    /// Where the name is.
+    pub span: Span,
+    /// Which argument the cursor is in, counting from 0.
+    pub argument: usize,
+    /// The catalog summary, or the user function's source.
+    pub summary: String,
+    /// The catalog example; `None` for user functions.
+    pub example: Option<String>,
+}
+
+/// The kind a catalog symbol completes as, or `None` for symbols that
+/// aren't written as names in plain evaluation (glyph operators, `convert`).
+fn completion_kind(sym: &Symbol) -> Option<TokenKind> {
+    match sym.kind {
+        SymbolKind::Constant(_) => Some(TokenKind::Constant),
+        SymbolKind::Variable => Some(TokenKind::Variable),
+        SymbolKind::Operator { .. } => Some(TokenKind::Operator),
+        SymbolKind::LogBase | SymbolKind::Series => Some(TokenKind::Function),
+        SymbolKind::UnitConversion => None,
+        _ if sym.kind.is_callable() => Some(TokenKind::Function),
+        _ => None,
+    }
+}
+
+/// A user definition as source, for details and summaries.
+fn definition_source(def: &Definition) -> String {
+    match def {
+        Definition::Value { name, value } => format!("{name} = {value}"),
+        Definition::Function { name, body } => format!("{name}(x) = {body}"),
+    }
+}
+
+/// Names that could complete the partial name before `cursor`: catalog
+/// names and aliases, and the names in `defs`.
+///
+/// Names starting with what was typed come first, shortest first; after
+/// them come near misses within the same edit distance the "did you mean"
+/// suggestions use (`sinq` offers `sin`), closest first. With nothing
+/// typed, every name is a candidate.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::completion::complete;
+/// use rusty_maths::equation_analyzer::{Definitions, TokenKind};
+///
+/// let found = complete("1 + cosq", 8, &Definitions::new());
+/// assert_eq!(found[0].label, "cos");
+/// assert_eq!(found[0].kind, TokenKind::Function);
+/// assert_eq!((found[0].span.start, found[0].span.end), (4, 8));
+/// ```
+pub fn complete(eq: &str, cursor: usize, defs: &Definitions) -> Vec<Completion> {
+    let chars: Vec<char> = eq.chars().collect();
+    let cursor = cursor.min(chars.len());
+    let mut start = cursor;
+    // Dots belong to qualified names (`phys.`), so they're taken too.
+    while start > 0 && (chars[start - 1].is_alphanumeric() || chars[start - 1] == '.') {
+        start -= 1;
+    }
+    // A number before the name is a coefficient (`2si`, `2.5si`), not part
+    // of it.
+    while start < cursor && !chars[start].is_alphabetic() {
+        start += 1;
+    }
+    let typed: String = chars[start..cursor].iter().collect();
+    let span = Span::new(start, cursor);
+
+    let catalog_names = catalog::all().iter().flat_map(|sym| {
+        completion_kind(sym).into_iter().flat_map(move |kind| {
+            std::iter::once(sym.name)
+                .chain(sym.aliases.iter().copied())
+                // A definition shadowing the name is offered instead.
+                .filter(|name| name.starts_with(char::is_alphabetic) && !defs.contains(name))
+                .map(move |name| (name.to_string(), kind, sym.summary.to_string()))
+        })
+    });
+    let user_names = defs.iter().map(|def| match def {
+        Definition::Value { name, .. } => (
+            name.to_string(),
+            TokenKind::UserValue,
+            definition_source(&def),
+        ),
+        Definition::Function { name, .. } => (
+            name.to_string(),
+            TokenKind::UserFunction,
+            definition_source(&def),
+        ),
+    });
+
+    let max_dist = suggestion_distance(&typed);
+    let mut ranked: Vec<((bool, usize), Completion)> = catalog_names
+        .chain(user_names)
+        .filter_map(|(label, kind, detail)| {
+            let rank = if label.starts_with(typed.as_str()) {
+                (false, label.chars().count())
+            } else {
+                let dist = levenshtein(&typed, &label);
+                if typed.is_empty() || dist > max_dist {
+                    return None;
+                }
+                (true, dist)
+            };
+            Some((
+                rank,
+                Completion {
+                    label,
+                    kind,
+                    span,
+                    detail,
+                },
+            ))
+        })
+        .collect();
+    ranked.sort_by(|(a, x), (b, y)| a.cmp(b).then_with(|| x.label.cmp(&y.label)));
+    ranked.into_iter().map(|(_, c)| c).collect()
+}
+
+/// The innermost call `cursor` is inside, which argument it is in, and
+/// what the function does. `None` outside any call, and inside a call to a
+/// name that doesn't resolve.
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::completion::signature_help;
+/// use rusty_maths::equation_analyzer::Definitions;
+///
+/// let help = signature_help("atan2(1, (2 + 3)", 15, &Definitions::new()).unwrap();
+/// assert_eq!(help.function, "atan2");
+/// assert_eq!(help.argument, 1);
+/// assert_eq!(help.example.as_deref(), Some("atan2(1, 1) = π/4"));
+///
+/// assert!(signature_help("sin(1) + 2", 9, &Definitions::new()).is_none());
+/// ```
+pub fn signature_help(eq: &str, cursor: usize, defs: &Definitions) -> Option<SignatureHelp> {
+    struct Call {
+        kind: TokenKind,
+        span: Span,
+        argument: usize,
+    }
+
+    let chars: Vec<char> = eq.chars().collect();
+    let text = |span: Span| -> String { chars[span.start..span.end].iter().collect() };
+
+    // One entry per open parenthesis: `None` for a plain group.
+    let mut open: Vec<Option<Call>> = Vec::new();
+    let mut previous: Option<(TokenKind, Span)> = None;
+    for lexeme in lex(eq, defs).take_while(|l| l.span.end <= cursor) {
+        let span = lexeme.span;
+        match lexeme.kind {
+            TokenKind::Paren if chars[span.start] == '(' => {
+                // A call's paren directly follows its name.
+                let call = previous
+                    .filter(|(_, name)| name.end == span.start)
+                    .and_then(|(kind, name)| {
+                        matches!(
+                            kind,
+                            TokenKind::Function | TokenKind::UserFunction | TokenKind::Error
+                        )
+                        .then_some(Call {
+                            kind,
+                            span: name,
+                            argument: 0,
+                        })
+                    });
+                open.push(call);
+            }
+            TokenKind::Paren => {
+                open.pop();
+            }
+            TokenKind::Comma => {
+                if let Some(Some(call)) = open.last_mut() {
+                    call.argument += 1;
+                }
+            }
+            _ => {}
+        }
+        previous = Some((lexeme.kind, span));
+    }
+
+    let call = open.into_iter().rev().flatten().next()?;
+    let function = text(call.span);
+    let (summary, example) = match call.kind {
+        TokenKind::Function => {
+            // `log_2` documents as `log`.
+            let name = function.split('_').next().unwrap_or(&function);
+            let sym = catalog::find(name)?;
+            (sym.summary.to_string(), Some(sym.example.to_string()))
+        }
+        TokenKind::UserFunction => {
+            let def = defs.iter().find(
+                |def| matches!(def, Definition::Function { name, .. } if *name == function),
+            )?;
+            (definition_source(&def), None)
+        }
+        _ => return None,
+    };
+    Some(SignatureHelp {
+        function,
+        span: call.span,
+        argument: call.argument,
+        summary,
+        example,
+    })
+}
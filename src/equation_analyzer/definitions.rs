@@ -18,23 +18,151 @@
 //!
 //! Names live in a single namespace: defining a value and then a function
 //! under the same name replaces the value, and vice versa. Catalog names
-//! (`sin`, `pi`, …) and the reserved letters `x`/`y` cannot be redefined.
+//! (`sin`, `pi`, …) and the reserved letters `x`/`y` cannot be redefined,
+//! except the finance, random, unit and calculus functions: a definition
+//! named `rate` or `choose` shadows the built-in of that name.
+//!
+//! # Scopes
+//!
+//! Definitions stack in layers — say a shared library, a session, and one
+//! evaluation. [`Definitions::push_scope`] opens a layer on top;
+//! definitions made there shadow same-named ones below without touching
+//! them, and [`Definitions::pop_scope`] drops the layer and everything in
+//! it. Only the innermost layer is ever changed. Bodies still bind late,
+//! to whatever a name means *now*, so a library function sees a value an
+//! inner scope shadows:
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::calculate_with;
+//! use rusty_maths::equation_analyzer::Definitions;
+//!
+//! let mut defs = Definitions::new();
+//! defs.define_value("growth", 0.25).unwrap();
+//! defs.define_function("grow", "x * (1 + growth)").unwrap();
+//!
+//! defs.push_scope();
+//! defs.define_value("growth", 0.5).unwrap();
+//! assert_eq!(calculate_with("grow(100)", &defs).unwrap(), 150.0);
+//!
+//! defs.pop_scope();
+//! assert_eq!(calculate_with("grow(100)", &defs).unwrap(), 125.0);
+//! ```
 //!
 //! Function bodies are stored as **source text** and resolved late: a body
 //! referencing `a` sees whatever `a` is bound to when the function is
 //! *called*, not when it was defined. The single parameter is always `x` —
 //! bodies parse exactly like top-level equations.
+//!
+//! # Modules
+//!
+//! [`Definitions::import_module`] brings another set in under a module
+//! name, so shared libraries don't compete for flat names — or with the
+//! catalog. Its definitions are referenced by dotted name:
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::calculate_with;
+//! use rusty_maths::equation_analyzer::Definitions;
+//!
+//! let mut phys = Definitions::new();
+//! phys.define_value("g", 9.8).unwrap();
+//! phys.define_function("fall", "g * x^2 / 2").unwrap();
+//!
+//! let mut defs = Definitions::new();
+//! defs.import_module("phys", &phys).unwrap();
+//! assert_eq!(calculate_with("phys.fall(2)", &defs).unwrap(), 19.6);
+//! assert_eq!(defs.function_body("phys.fall"), Some("phys.g * x^2 / 2"));
+//! ```
+//!
+//! # Text format
+//!
+//! [`Definitions::load_str`] and [`Definitions::to_source_string`] read and
+//! write definitions as text, one per line:
+//!
+//! ```text
+//! # Comments run from '#' to the end of the line.
+//! a = 3              # a value: any equation, evaluated when loaded
+//! g(x) = a * x^2     # a function: its body, stored as source
+//! ```
+//!
+//! Functions are defined first, so values may call functions from anywhere
+//! in the file; values are then evaluated top to bottom, each seeing the
+//! values above it.
+//!
+//! [`SourceLine::parse`] classifies a single line the same way, for tools
+//! that read the format themselves. It also recognizes a line that isn't a
+//! definition — no `=`, or `y = …` — as an
+//! [`Expression`](SourceLine::Expression); files for `load_str` can't hold
+//! those, but an editor can evaluate them in place.
+//!
+//! # Dependencies
+//!
+//! Late binding means nothing stops `g` from calling itself until the call
+//! depth runs out. [`Definitions::dependencies`],
+//! [`dependents`](Definitions::dependents),
+//! [`topological_order`](Definitions::topological_order) and
+//! [`find_cycles`](Definitions::find_cycles) read the call graph ahead of
+//! time by lexing each body against the current set:
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::Definitions;
+//!
+//! let mut defs = Definitions::new();
+//! defs.define_value("a", 3.0).unwrap();
+//! defs.define_function("g", "a * h(x)").unwrap();
+//! defs.define_function("h", "x + g(x)").unwrap();
+//!
+//! assert_eq!(defs.dependencies("g"), ["a", "h"]);
+//! assert_eq!(defs.dependents("a"), ["g"]);
+//! assert_eq!(defs.find_cycles(), [vec!["g", "h"]]);
+//! assert!(defs.topological_order().is_err());
+//! ```
 
+use crate::equation_analyzer::calculator::calculate_with;
 use crate::equation_analyzer::catalog;
-use crate::equation_analyzer::errors::EquationError;
+use crate::equation_analyzer::diagnostics;
+use crate::equation_analyzer::errors::{EquationError, ErrorKind, Span};
+use crate::equation_analyzer::lexer::{lex, TokenKind};
+use crate::equation_analyzer::pipeline::bytecode::{callees, draws_random};
 use crate::equation_analyzer::pipeline::parser::parse;
 use crate::equation_analyzer::pipeline::tokenizer::StreamingTokenizer;
 use crate::equation_analyzer::structs::token::SpannedToken;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
 
-/// A set of user definitions, in definition order.
+/// A set of user definitions, in definition order, layered in
+/// [scopes](self#scopes).
 #[derive(Debug, Clone, Default)]
 pub struct Definitions {
+    /// Every layer's entries, outermost layer first; calls resolve to
+    /// indices into it.
     entries: Vec<Entry>,
+    /// Where each pushed layer starts in `entries`.
+    scopes: Vec<usize>,
+    /// Each name's visible entry: the one in the innermost layer defining it.
+    index: HashMap<String, usize>,
+    cache: CompileCache,
+}
+
+/// Every entry's compiled body, in step with the entries (`None` for
+/// values and shadowed functions).
+type Bodies = Vec<Option<Result<Vec<SpannedToken>, EquationError>>>;
+
+/// Compiled bodies, built on the first evaluation after a change and reused
+/// until the next one.
+#[derive(Clone, Default)]
+struct CompileCache {
+    plain: OnceLock<Bodies>,
+    units: OnceLock<Bodies>,
+}
+
+impl fmt::Debug for CompileCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompileCache")
+            .field("plain", &self.plain.get().is_some())
+            .field("units", &self.units.get().is_some())
+            .finish()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -51,12 +179,54 @@ enum DefKind {
 
 /// A read-only view of one definition, for listing (`:fns`-style output)
 /// and persistence.
+///
+/// With the `serde` feature it serializes as a tagged record:
+/// `{"kind": "value", "name": "a", "value": 3.0}` or
+/// `{"kind": "function", "name": "g", "body": "a * x^2"}`.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "lowercase")
+)]
 pub enum Definition<'a> {
     Value { name: &'a str, value: f32 },
     Function { name: &'a str, body: &'a str },
 }
 
+/// With the `serde` feature, a set serializes as the list of its visible
+/// [`Definition`]s in order, flattening any scopes. Deserializing defines each one in turn, so
+/// invalid names are rejected just as [`Definitions::define_value`] would.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Definitions {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Definitions {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// An owned [`Definition`], so escaped strings deserialize too.
+        #[derive(serde::Deserialize)]
+        #[serde(tag = "kind", rename_all = "lowercase")]
+        enum Record {
+            Value { name: String, value: f32 },
+            Function { name: String, body: String },
+        }
+
+        let mut defs = Definitions::new();
+        for record in Vec::<Record>::deserialize(deserializer)? {
+            match record {
+                Record::Value { name, value } => defs.define_value(&name, value),
+                Record::Function { name, body } => defs.define_function(&name, &body),
+            }
+            .map_err(|e| serde::de::Error::custom(e.message))?;
+        }
+        Ok(defs)
+    }
+}
+
 /// What an identifier resolved to, for the tokenizer.
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum Resolved {
@@ -88,9 +258,10 @@ impl Definitions {
     pub fn define_function(&mut self, name: &str, body: &str) -> Result<(), EquationError> {
         validate_name(name)?;
         if body.trim().is_empty() {
-            return Err(EquationError::new(format!(
-                "Function '{name}' has an empty body"
-            )));
+            return Err(
+                EquationError::new(format!("Function '{name}' has an empty body"))
+                    .with_kind(ErrorKind::InvalidDefinition),
+            );
         }
         self.upsert(
             name,
@@ -109,26 +280,95 @@ impl Definitions {
     /// that shunting-yard accepts structurally (`x +`, `x x`) only surface
     /// when the function is called.
     pub fn validate_function(&self, name: &str) -> Result<(), EquationError> {
-        let body = self
-            .function_body(name)
-            .ok_or_else(|| EquationError::new(format!("No function named '{name}' is defined")))?;
+        let body = self.function_body(name).ok_or_else(|| {
+            EquationError::new(format!("No function named '{name}' is defined")).with_kind(
+                ErrorKind::UnknownName {
+                    name: name.to_string(),
+                    suggestion: None,
+                },
+            )
+        })?;
         StreamingTokenizer::new_with(body, Some(self))
             .and_then(parse)
             .map(|_| ())
             .map_err(|e| e.for_function(name))
     }
 
-    /// Removes a definition by name. Returns whether one existed.
+    /// Removes a definition by name from the innermost scope, uncovering
+    /// any it shadowed. Returns whether one existed there.
     pub fn undefine(&mut self, name: &str) -> bool {
-        match self.index_of(name) {
+        match self.index_of(name).filter(|&i| i >= self.scope_start()) {
             Some(i) => {
                 self.entries.remove(i);
+                self.reindex();
                 true
             }
             None => false,
         }
     }
 
+    /// Imports `module`'s visible definitions into the innermost scope
+    /// under `name`: its `g` becomes `name.g`, replacing any earlier import
+    /// under that name. Bodies are rewritten to reach their own module's
+    /// definitions by qualified name; names `module` doesn't define stay
+    /// as written and bind late, like any body's.
+    ///
+    /// Fails, changing nothing, if `name` isn't a valid definition name.
+    pub fn import_module(&mut self, name: &str, module: &Definitions) -> Result<(), EquationError> {
+        validate_name(name)?;
+        let prefix = format!("{name}.");
+        let start = self.scope_start();
+        let mut i = 0;
+        self.entries.retain(|e| {
+            i += 1;
+            i <= start || !e.name.starts_with(&prefix)
+        });
+        self.reindex();
+        for def in module.iter() {
+            match def {
+                Definition::Value { name, value } => {
+                    self.upsert(&format!("{prefix}{name}"), DefKind::Value(value));
+                }
+                Definition::Function { name, body } => self.upsert(
+                    &format!("{prefix}{name}"),
+                    DefKind::Function {
+                        body: qualify(body, module, &prefix),
+                    },
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens a new innermost scope. Definitions made until the matching
+    /// [`pop_scope`](Self::pop_scope) go in it, shadowing outer ones.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(self.entries.len());
+    }
+
+    /// Discards the innermost scope and its definitions. Returns `false`,
+    /// changing nothing, when no scope has been pushed.
+    pub fn pop_scope(&mut self) -> bool {
+        let Some(start) = self.scopes.pop() else {
+            return false;
+        };
+        self.entries.truncate(start);
+        self.reindex();
+        true
+    }
+
+    /// How many scopes are pushed over the base one.
+    pub fn scope_depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// The scope `name`'s visible definition lives in: 0 for the base
+    /// scope, up to [`scope_depth`](Self::scope_depth).
+    pub fn scope_of(&self, name: &str) -> Option<usize> {
+        let i = self.index_of(name)?;
+        Some(self.scopes.iter().take_while(|&&start| start <= i).count())
+    }
+
     /// The value bound to `name`, if it is a value definition.
     pub fn value(&self, name: &str) -> Option<f32> {
         match self.find(name)? {
@@ -150,26 +390,280 @@ impl Definitions {
         self.index_of(name).is_some()
     }
 
-    /// All definitions, in definition order.
+    /// All visible definitions, in definition order; shadowed ones are
+    /// left out.
     pub fn iter(&self) -> impl Iterator<Item = Definition<'_>> {
-        self.entries.iter().map(|e| match &e.kind {
+        self.visible().map(|i| match &self.entries[i].kind {
             DefKind::Value(v) => Definition::Value {
-                name: &e.name,
+                name: &self.entries[i].name,
                 value: *v,
             },
             DefKind::Function { body } => Definition::Function {
-                name: &e.name,
+                name: &self.entries[i].name,
                 body,
             },
         })
     }
 
+    /// The number of visible definitions.
     pub fn len(&self) -> usize {
-        self.entries.len()
+        self.index.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.entries.is_empty()
+        self.index.is_empty()
+    }
+
+    /// Loads definitions in the [text format](self#text-format) into this
+    /// set, replacing any with the same names.
+    ///
+    /// Errors carry the 1-based [`line`](EquationError::line) they are on,
+    /// with spans relative to that line: a malformed line, an invalid name,
+    /// a value that fails to evaluate, or a function body that doesn't
+    /// parse. Names a body uses may stay undefined — they bind late. On
+    /// error the set is unchanged.
+    ///
+    /// # Examples
+    /// ```
+    /// use rusty_maths::equation_analyzer::Definitions;
+    ///
+    /// let mut defs = Definitions::new();
+    /// defs.load_str("a = 3\ng(x) = a * x^2  # quadratic\nb = g(2)").unwrap();
+    /// assert_eq!(defs.value("b"), Some(12.0));
+    ///
+    /// let err = defs.load_str("c = 1\nd = 2 +* c").unwrap_err();
+    /// assert_eq!(err.line(), Some(2));
+    /// assert_eq!(err.to_string(), "line 2: Invalid expression at character 7");
+    /// ```
+    pub fn load_str(&mut self, source: &str) -> Result<(), EquationError> {
+        let mut lines = Vec::new();
+        for (i, text) in source.lines().enumerate() {
+            let slice =
+                |span: Span| -> String { text.chars().skip(span.start).take(span.len()).collect() };
+            let located = |span: Span| (slice(span), span);
+            match SourceLine::parse(text).map_err(|e| e.on_line(i + 1))? {
+                SourceLine::Blank => {}
+                SourceLine::Value { name, expr } => {
+                    lines.push((i + 1, Loaded::Value(located(name), located(expr))));
+                }
+                SourceLine::Function { name, body } => {
+                    lines.push((i + 1, Loaded::Function(located(name), located(body))));
+                }
+                SourceLine::Expression { expr } => {
+                    return Err(EquationError::spanned(
+                        "Expected 'name = value' or 'name(x) = body'",
+                        expr,
+                    )
+                    .with_kind(ErrorKind::InvalidDefinition)
+                    .on_line(i + 1));
+                }
+            }
+        }
+
+        let mut loaded = self.clone();
+        for (number, line) in &lines {
+            if let Loaded::Function(name, body) = line {
+                loaded
+                    .define_function(&name.0, &body.0)
+                    .map_err(|e| name_error(e, name.1, *number))?;
+            }
+        }
+        for (number, line) in &lines {
+            if let Loaded::Value(name, expr) = line {
+                let value = calculate_with(&expr.0, &loaded)
+                    .map_err(|e| e.offset(expr.1.start).on_line(*number))?;
+                loaded
+                    .define_value(&name.0, value)
+                    .map_err(|e| name_error(e, name.1, *number))?;
+            }
+        }
+        // Bodies parse against everything loaded; a name nothing defines
+        // yet is fine — it binds at call time.
+        for (number, line) in &lines {
+            if let Loaded::Function(_, body) = line {
+                let broken = diagnostics::errors(&body.0, &loaded)
+                    .into_iter()
+                    .find(|e| !matches!(e.kind(), ErrorKind::UnknownName { .. }));
+                if let Some(e) = broken {
+                    return Err(e.offset(body.1.start).on_line(*number));
+                }
+            }
+        }
+
+        // New names go in file order, not function-first definition order.
+        let position = |name: &str| {
+            lines.iter().position(|(_, line)| match line {
+                Loaded::Value(n, _) | Loaded::Function(n, _) => n.0 == name,
+            })
+        };
+        loaded.entries[self.entries.len()..].sort_by_key(|e| position(&e.name));
+        loaded.reindex();
+        *self = loaded;
+        Ok(())
+    }
+
+    /// Writes the set in the [text format](self#text-format), one
+    /// definition per line in definition order, so that
+    /// [`load_str`](Self::load_str) reads it back unchanged.
+    ///
+    /// # Examples
+    /// ```
+    /// use rusty_maths::equation_analyzer::Definitions;
+    ///
+    /// let mut defs = Definitions::new();
+    /// defs.define_value("a", 0.5).unwrap();
+    /// defs.define_function("g", "a * x^2").unwrap();
+    /// assert_eq!(defs.to_source_string(), "a = 0.5\ng(x) = a * x^2\n");
+    /// ```
+    pub fn to_source_string(&self) -> String {
+        let mut out = String::new();
+        for def in self.iter() {
+            let line = match def {
+                Definition::Value { name, value } => format!("{name} = {}", value_source(value)),
+                Definition::Function { name, body } => format!("{name}(x) = {body}"),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// The definitions `name`'s body uses directly, in order of first use.
+    /// Empty for values, which are stored evaluated, and for names nothing
+    /// defines.
+    pub fn dependencies(&self, name: &str) -> Vec<&str> {
+        self.index_of(name)
+            .map_or(Vec::new(), |i| self.names(&self.calls(i)))
+    }
+
+    /// The functions whose bodies use `name` directly, in definition order:
+    /// what changes when `name` is redefined.
+    pub fn dependents(&self, name: &str) -> Vec<&str> {
+        let Some(target) = self.index_of(name) else {
+            return Vec::new();
+        };
+        let users: Vec<usize> = self
+            .visible()
+            .filter(|&i| self.calls(i).contains(&target))
+            .collect();
+        self.names(&users)
+    }
+
+    /// Every definition, each after the definitions it uses, and otherwise
+    /// in definition order — the order a file must list them in to load
+    /// without forward references.
+    ///
+    /// Fails when definitions are recursive, naming one cycle; see
+    /// [`find_cycles`](Self::find_cycles) for all of them.
+    pub fn topological_order(&self) -> Result<Vec<&str>, EquationError> {
+        let graph = self.graph();
+        let visible: Vec<usize> = self.visible().collect();
+        let mut placed = vec![false; graph.len()];
+        let mut order = Vec::with_capacity(visible.len());
+        while order.len() < visible.len() {
+            let ready = visible
+                .iter()
+                .copied()
+                .find(|&i| !placed[i] && graph[i].iter().all(|&j| placed[j] || j == i));
+            let Some(i) = ready.filter(|&i| !graph[i].contains(&i)) else {
+                let cycle = self.find_cycles().swap_remove(0);
+                let path: Vec<&str> = cycle.iter().chain(&cycle[..1]).copied().collect();
+                return Err(EquationError::new(format!(
+                    "Definitions are recursive: {}",
+                    path.join(" → ")
+                ))
+                .with_kind(ErrorKind::InvalidDefinition));
+            };
+            placed[i] = true;
+            order.push(i);
+        }
+        Ok(self.names(&order))
+    }
+
+    /// The recursive call chains among the function bodies, each name
+    /// calling the next and the last calling the first: `["g"]` is a `g`
+    /// that calls itself. Every function that can reach itself is in at
+    /// least one cycle, which starts from the first such function in
+    /// definition order not already reported; empty when nothing is
+    /// recursive.
+    pub fn find_cycles(&self) -> Vec<Vec<&str>> {
+        let graph = self.graph();
+        let mut in_cycle = vec![false; graph.len()];
+        let mut cycles = Vec::new();
+        for start in self.visible() {
+            if in_cycle[start] {
+                continue;
+            }
+            if let Some(path) = shortest_path_back(&graph, start) {
+                for &i in &path {
+                    in_cycle[i] = true;
+                }
+                cycles.push(self.names(&path));
+            }
+        }
+        cycles
+    }
+
+    /// One warning per use of a name nothing defines in a function body.
+    /// Bodies bind late, so such a body is legal — it works once the name
+    /// is defined — but until then calling it fails, and a typo looks the
+    /// same. Each warning is the error the call would raise, tagged with
+    /// the function (spans point into its body).
+    pub fn warnings(&self) -> Vec<EquationError> {
+        self.visible()
+            .map(|i| &self.entries[i])
+            .filter_map(|e| match &e.kind {
+                DefKind::Function { body } => Some((e.name.as_str(), body)),
+                DefKind::Value(_) => None,
+            })
+            .flat_map(|(name, body)| {
+                lex(body, self)
+                    .filter_map(|l| l.error)
+                    .filter(|e| matches!(e.kind(), ErrorKind::UnknownName { .. }))
+                    .map(move |e| e.for_function(name))
+            })
+            .collect()
+    }
+
+    /// Each entry's direct uses, by index; none for shadowed entries,
+    /// which nothing can reach.
+    fn graph(&self) -> Vec<Vec<usize>> {
+        (0..self.entries.len())
+            .map(|i| {
+                if self.is_visible(i) {
+                    self.calls(i)
+                } else {
+                    Vec::new()
+                }
+            })
+            .collect()
+    }
+
+    /// The entries a function body resolves names to, in order of first
+    /// use.
+    fn calls(&self, index: usize) -> Vec<usize> {
+        let DefKind::Function { body } = &self.entries[index].kind else {
+            return Vec::new();
+        };
+        let chars: Vec<char> = body.chars().collect();
+        let mut found = Vec::new();
+        for lexeme in lex(body, self) {
+            if !matches!(lexeme.kind, TokenKind::UserFunction | TokenKind::UserValue) {
+                continue;
+            }
+            let name: String = chars[lexeme.span.start..lexeme.span.end].iter().collect();
+            if let Some(i) = self.index_of(&name).filter(|i| !found.contains(i)) {
+                found.push(i);
+            }
+        }
+        found
+    }
+
+    fn names(&self, indices: &[usize]) -> Vec<&str> {
+        indices
+            .iter()
+            .map(|&i| self.entries[i].name.as_str())
+            .collect()
     }
 
     /// Resolves an identifier for the tokenizer. Catalog resolution happens
@@ -182,41 +676,97 @@ impl Definitions {
         }
     }
 
-    /// Compiles every function body once, for one evaluation pass. Broken
-    /// bodies are stored as errors and surface only if actually called —
-    /// which is what makes late binding observable: an equation that never
-    /// calls a broken function is unaffected by it.
+    /// Every function body compiled, for an evaluation pass. Broken bodies
+    /// are stored as errors and surface only if actually called — which is
+    /// what makes late binding observable: an equation that never calls a
+    /// broken function is unaffected by it.
+    ///
+    /// Bodies compile on the first call after the set changes; later calls
+    /// reuse them. Compiling against the whole set is still correct late
+    /// binding, since any change — even to a value a body reads — clears
+    /// them.
     pub(crate) fn compile(&self) -> CompiledDefinitions<'_> {
-        let bodies = self
-            .entries
+        CompiledDefinitions::new(
+            self,
+            self.cache.plain.get_or_init(|| self.compile_bodies(false)),
+        )
+    }
+
+    /// Like [`compile`](Self::compile), with bodies tokenized in unit-aware
+    /// mode so they may mention units (`g(x) = x * 9.8 m/s^2`).
+    pub(crate) fn compile_units(&self) -> CompiledDefinitions<'_> {
+        CompiledDefinitions::new(
+            self,
+            self.cache.units.get_or_init(|| self.compile_bodies(true)),
+        )
+    }
+
+    fn compile_bodies(&self, units: bool) -> Bodies {
+        // Shadowed bodies are unreachable; only visible ones compile.
+        self.entries
             .iter()
-            .map(|e| match &e.kind {
-                DefKind::Value(_) => None,
-                DefKind::Function { body } => {
-                    Some(StreamingTokenizer::new_with(body, Some(self)).and_then(parse))
-                }
+            .enumerate()
+            .map(|(i, e)| match &e.kind {
+                DefKind::Function { body } if self.is_visible(i) => Some(
+                    StreamingTokenizer::new_with(body, Some(self))
+                        .map(|t| if units { t.with_units() } else { t })
+                        .and_then(parse),
+                ),
+                _ => None,
             })
-            .collect();
-        CompiledDefinitions { defs: self, bodies }
+            .collect()
     }
 
     fn find(&self, name: &str) -> Option<&DefKind> {
         self.index_of(name).map(|i| &self.entries[i].kind)
     }
 
-    // Linear scan: definition sets are user-typed and tiny, and this keeps
-    // definition order (needed by iter/persistence) in one flat Vec.
+    /// The visible entry for `name`. Entries stay in one flat Vec for
+    /// definition order (needed by iter/persistence); the map keeps lookups
+    /// fast for large libraries.
     fn index_of(&self, name: &str) -> Option<usize> {
-        self.entries.iter().position(|e| e.name == name)
+        self.index.get(name).copied()
+    }
+
+    fn is_visible(&self, index: usize) -> bool {
+        self.index_of(&self.entries[index].name) == Some(index)
+    }
+
+    /// Indices of the visible entries, in definition order.
+    fn visible(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.entries.len()).filter(|&i| self.is_visible(i))
+    }
+
+    /// Where the innermost scope starts in `entries`.
+    fn scope_start(&self) -> usize {
+        self.scopes.last().copied().unwrap_or(0)
     }
 
+    /// Rebuilds the name map after entries move or go; later (inner)
+    /// entries win.
+    fn reindex(&mut self) {
+        self.cache = CompileCache::default();
+        self.index = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.name.clone(), i))
+            .collect();
+    }
+
+    /// Redefines `name` in place if the innermost scope has it, and
+    /// otherwise adds it there, shadowing any outer definition.
     fn upsert(&mut self, name: &str, kind: DefKind) {
-        match self.index_of(name) {
+        self.cache = CompileCache::default();
+        match self.index_of(name).filter(|&i| i >= self.scope_start()) {
             Some(i) => self.entries[i].kind = kind,
-            None => self.entries.push(Entry {
-                name: name.to_string(),
-                kind,
-            }),
+            None => {
+                self.index.insert(name.to_string(), self.entries.len());
+                self.entries.push(Entry {
+                    name: name.to_string(),
+                    kind,
+                });
+            }
         }
     }
 }
@@ -226,10 +776,140 @@ impl Definitions {
 /// entries (values hold `None`).
 pub(crate) struct CompiledDefinitions<'d> {
     defs: &'d Definitions,
-    bodies: Vec<Option<Result<Vec<SpannedToken>, EquationError>>>,
+    bodies: &'d Bodies,
+    memo: Option<Arc<Memo>>,
+}
+
+/// Results of calls to pure user functions, by function and argument bits.
+/// Behind a lock: plots share one compiled set across threads.
+#[derive(Debug)]
+struct Memo {
+    pure: Vec<bool>,
+    results: Mutex<HashMap<(usize, u32), f32>>,
+}
+
+impl<'d> CompiledDefinitions<'d> {
+    fn new(defs: &'d Definitions, bodies: &'d Bodies) -> Self {
+        CompiledDefinitions {
+            defs,
+            bodies,
+            memo: None,
+        }
+    }
+
+    /// Remembers each successful call to a pure function (one that draws no
+    /// random numbers, directly or through calls) for the rest of the pass,
+    /// when `on`. Errors are never remembered: a depth-limit error depends
+    /// on where the call was made, not only on its argument.
+    pub(crate) fn memoized(mut self, on: bool) -> Self {
+        self.memo = on.then(|| {
+            Arc::new(Memo {
+                pure: self.pure_functions(),
+                results: Mutex::new(HashMap::new()),
+            })
+        });
+        self
+    }
+
+    /// An owned handle on this set and its remembered results, for
+    /// evaluations that outlive the borrow — a [`PlotIter`] or a compiled
+    /// equation. `None` when calls aren't memoized, since an unmemoized
+    /// pass needs nothing from the definitions at run time.
+    ///
+    /// [`PlotIter`]: crate::equation_analyzer::plot::PlotIter
+    pub(crate) fn detach(&self) -> Option<MemoizedDefinitions> {
+        let memo = self.memo.as_ref()?;
+        Some(MemoizedDefinitions {
+            defs: self.defs.clone(),
+            memo: Arc::clone(memo),
+        })
+    }
+}
+
+/// A memoized [`CompiledDefinitions`] detached from its borrow: clones share
+/// one set of remembered results.
+#[derive(Debug, Clone)]
+pub(crate) struct MemoizedDefinitions {
+    defs: Definitions,
+    memo: Arc<Memo>,
+}
+
+impl MemoizedDefinitions {
+    /// The compiled view, recalling and remembering into the shared results.
+    pub(crate) fn compile(&self) -> CompiledDefinitions<'_> {
+        CompiledDefinitions {
+            memo: Some(Arc::clone(&self.memo)),
+            ..self.defs.compile()
+        }
+    }
 }
 
 impl CompiledDefinitions<'_> {
+    /// Whether calls to definition `index` are memoized.
+    pub(crate) fn memoizes(&self, index: usize) -> bool {
+        self.memo
+            .as_ref()
+            .is_some_and(|m| m.pure.get(index).copied().unwrap_or(false))
+    }
+
+    /// A remembered result for `index` at `arg`.
+    pub(crate) fn recall(&self, index: usize, arg: f32) -> Option<f32> {
+        let memo = self.memo.as_ref().filter(|_| self.memoizes(index))?;
+        let results = memo.results.lock().ok()?;
+        results.get(&(index, arg.to_bits())).copied()
+    }
+
+    /// Remembers `value` as the result of `index` at `arg`, if `index` is
+    /// memoized.
+    pub(crate) fn remember(&self, index: usize, arg: f32, value: f32) {
+        let Some(memo) = self.memo.as_ref().filter(|_| self.memoizes(index)) else {
+            return;
+        };
+        if let Ok(mut results) = memo.results.lock() {
+            results.insert((index, arg.to_bits()), value);
+        }
+    }
+
+    /// Per definition: draws no random numbers, directly or through the
+    /// functions it calls, so equal arguments give equal results.
+    pub(crate) fn pure_functions(&self) -> Vec<bool> {
+        let n = self.len();
+        let edges: Vec<Vec<usize>> = (0..n)
+            .map(|i| {
+                self.body_rpn(i)
+                    .map_or(Vec::new(), |b| callees(b).collect())
+            })
+            .collect();
+        let mut pure: Vec<bool> = (0..n)
+            .map(|i| self.body_rpn(i).map_or(true, |b| !draws_random(b)))
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..n {
+                if pure[i]
+                    && edges[i]
+                        .iter()
+                        .any(|&j| !pure.get(j).copied().unwrap_or(true))
+                {
+                    pure[i] = false;
+                    changed = true;
+                }
+            }
+        }
+        pure
+    }
+
+    /// The definitions this was compiled from.
+    pub(crate) fn definitions(&self) -> &Definitions {
+        self.defs
+    }
+
+    /// The number of definitions, functions and values alike.
+    pub(crate) fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
     /// The definition's name, for error messages.
     pub(crate) fn name(&self, index: usize) -> &str {
         self.defs
@@ -243,33 +923,213 @@ impl CompiledDefinitions<'_> {
         match self.bodies.get(index) {
             Some(Some(Ok(rpn))) => Ok(rpn),
             Some(Some(Err(e))) => Err(e.clone()),
-            _ => Err(EquationError::new(
-                "Internal error: call to an unknown user definition",
-            )),
+            _ => Err(
+                EquationError::new("Internal error: call to an unknown user definition")
+                    .with_kind(ErrorKind::Internal),
+            ),
         }
     }
 }
 
+/// The shortest path of calls from `start` back to itself, as the nodes
+/// visited from `start` on, if there is one.
+fn shortest_path_back(graph: &[Vec<usize>], start: usize) -> Option<Vec<usize>> {
+    let mut previous: Vec<Option<usize>> = vec![None; graph.len()];
+    let mut queue = std::collections::VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for &next in &graph[node] {
+            if next == start {
+                let mut path = vec![node];
+                while let Some(p) = previous[*path.last()?] {
+                    path.push(p);
+                }
+                // `previous[start]` stays unset, so the walk ends there.
+                path.reverse();
+                return Some(path);
+            }
+            if previous[next].is_none() {
+                previous[next] = Some(node);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// `body` with every name `module` defines prefixed, so an imported body
+/// keeps calling its own module's definitions.
+fn qualify(body: &str, module: &Definitions, prefix: &str) -> String {
+    let chars: Vec<char> = body.chars().collect();
+    let mut out = String::new();
+    let mut copied = 0;
+    for lexeme in lex(body, module) {
+        if matches!(lexeme.kind, TokenKind::UserFunction | TokenKind::UserValue) {
+            out.extend(&chars[copied..lexeme.span.start]);
+            out.push_str(prefix);
+            copied = lexeme.span.start;
+        }
+    }
+    out.extend(&chars[copied..]);
+    out
+}
+
+/// Text and the character span it came from within its line.
+type Located = (String, Span);
+
+/// A definition line read by `load_str`: its name, then its expression or
+/// body.
+enum Loaded {
+    Value(Located, Located),
+    Function(Located, Located),
+}
+
+/// A rejected name in the text format, pointed at where the line names it.
+fn name_error(mut e: EquationError, span: Span, line: usize) -> EquationError {
+    e.span = Some(span);
+    e.on_line(line)
+}
+
+/// A value as an equation that evaluates back to it. Finite values print
+/// exactly (`f32`'s `Display` round-trips); the rest need arithmetic.
+fn value_source(value: f32) -> String {
+    if value.is_nan() {
+        "0/0".to_string()
+    } else if value == f32::INFINITY {
+        "1/0".to_string()
+    } else if value == f32::NEG_INFINITY {
+        "-1/0".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// One line of the [text format](self#text-format), classified. Spans are
+/// character columns into the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceLine {
+    /// Blank, or only a comment.
+    Blank,
+    /// `name = expr`.
+    Value { name: Span, expr: Span },
+    /// `name(x) = body`.
+    Function { name: Span, body: Span },
+    /// A line without `=`, or an equation `y = …`: not a definition.
+    Expression { expr: Span },
+}
+
+impl SourceLine {
+    /// Classifies one line. Names aren't validated here —
+    /// [`define_value`](Definitions::define_value) and
+    /// [`define_function`](Definitions::define_function) do that — but a
+    /// malformed function head or a missing right-hand side is an error.
+    ///
+    /// # Examples
+    /// ```
+    /// use rusty_maths::equation_analyzer::definitions::SourceLine;
+    /// use rusty_maths::equation_analyzer::Span;
+    ///
+    /// assert_eq!(
+    ///     SourceLine::parse("g(x) = 2x  # double").unwrap(),
+    ///     SourceLine::Function { name: Span::new(0, 1), body: Span::new(7, 9) }
+    /// );
+    /// assert_eq!(
+    ///     SourceLine::parse("g(2) + 1").unwrap(),
+    ///     SourceLine::Expression { expr: Span::new(0, 8) }
+    /// );
+    /// assert!(SourceLine::parse("g(t) = t").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<SourceLine, EquationError> {
+        let chars: Vec<char> = text.chars().collect();
+        let code_end = chars.iter().position(|&c| c == '#').unwrap_or(chars.len());
+        let code = trim_span(&chars, 0, code_end);
+        if code.is_empty() {
+            return Ok(SourceLine::Blank);
+        }
+        let Some(eq) = chars[..code_end].iter().position(|&c| c == '=') else {
+            return Ok(SourceLine::Expression { expr: code });
+        };
+        let head = trim_span(&chars, 0, eq);
+        if chars[head.start..head.end] == ['y'] {
+            return Ok(SourceLine::Expression { expr: code });
+        }
+        let rhs = trim_span(&chars, eq + 1, code_end);
+        if rhs.is_empty() {
+            return Err(EquationError::spanned(
+                "Expected a definition after '='",
+                Span::new(eq, eq + 1),
+            )
+            .with_kind(ErrorKind::InvalidDefinition));
+        }
+
+        let Some(open) = chars[head.start..head.end].iter().position(|&c| c == '(') else {
+            return Ok(SourceLine::Value {
+                name: head,
+                expr: rhs,
+            });
+        };
+        if chars[head.end - 1] != ')' {
+            return Err(EquationError::spanned(
+                "Expected 'name = value' or 'name(x) = body'",
+                code,
+            )
+            .with_kind(ErrorKind::InvalidDefinition));
+        }
+        let param = trim_span(&chars, head.start + open + 1, head.end - 1);
+        if chars[param.start..param.end] != ['x'] {
+            return Err(EquationError::spanned(
+                "Functions take one parameter, always named x",
+                param,
+            )
+            .with_kind(ErrorKind::InvalidDefinition));
+        }
+        Ok(SourceLine::Function {
+            name: trim_span(&chars, head.start, head.start + open),
+            body: rhs,
+        })
+    }
+}
+
+/// `start..end` of `chars` without surrounding whitespace.
+fn trim_span(chars: &[char], start: usize, end: usize) -> Span {
+    let mut span = Span::new(start, end);
+    while span.start < span.end && chars[span.start].is_whitespace() {
+        span.start += 1;
+    }
+    while span.end > span.start && chars[span.end - 1].is_whitespace() {
+        span.end -= 1;
+    }
+    span
+}
+
 /// Names follow the tokenizer's identifier rules (alphabetic start,
-/// alphanumeric continuation) and must not shadow anything built in.
+/// alphanumeric continuation, `.` between qualified segments) and must not
+/// shadow anything built in, except the catalog families that
+/// [`Category::is_shadowable`](catalog::Category::is_shadowable) allows.
 fn validate_name(name: &str) -> Result<(), EquationError> {
-    let mut chars = name.chars();
-    let valid_shape = chars.next().is_some_and(char::is_alphabetic)
-        && chars.all(|c| c.is_alphabetic() || c.is_ascii_digit());
+    let valid_shape = name.split('.').all(|segment| {
+        let mut chars = segment.chars();
+        chars.next().is_some_and(char::is_alphabetic)
+            && chars.all(|c| c.is_alphabetic() || c.is_ascii_digit())
+    });
     if !valid_shape {
         return Err(EquationError::new(format!(
             "Invalid name '{name}': names start with a letter and contain only letters and digits"
-        )));
+        ))
+        .with_kind(ErrorKind::InvalidDefinition));
     }
-    if name == "x" || name == "y" {
+    // `x.g` would scan as the variable, then a stray '.'.
+    let first = name.split('.').next().unwrap_or(name);
+    if first == "x" || first == "y" {
         return Err(EquationError::new(format!(
             "'{name}' is reserved (the plot variable and equation marker)"
-        )));
+        ))
+        .with_kind(ErrorKind::InvalidDefinition));
     }
-    if catalog::find(name).is_some() {
-        return Err(EquationError::new(format!(
-            "Cannot redefine built-in '{name}'"
-        )));
+    if catalog::find(name).is_some_and(|sym| !sym.category.is_shadowable()) {
+        return Err(
+            EquationError::new(format!("Cannot redefine built-in '{name}'"))
+                .with_kind(ErrorKind::InvalidDefinition),
+        );
     }
     Ok(())
 }
@@ -367,4 +1227,200 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn load_str_reports_lines_and_line_spans() {
+        let fails = |source: &str| {
+            let err = Definitions::new().load_str(source).unwrap_err();
+            (err.line(), err.span.map(|s| (s.start, s.end)), err.message)
+        };
+        assert_eq!(
+            fails("a = 1\n\n  sin = 2"),
+            (
+                Some(3),
+                Some((2, 5)),
+                "Cannot redefine built-in 'sin'".into()
+            )
+        );
+        assert_eq!(
+            fails("# header\ng(t) = t"),
+            (
+                Some(2),
+                Some((2, 3)),
+                "Functions take one parameter, always named x".into()
+            )
+        );
+        assert_eq!(fails("a + 1").0, Some(1));
+        assert_eq!(
+            fails("a =   # nothing").2,
+            "Expected a definition after '='"
+        );
+        // Body errors point into the line; unknown names bind late.
+        assert_eq!(fails("g(x) = x + )").1, Some((11, 12)));
+        assert!(Definitions::new().load_str("g(x) = later * x").is_ok());
+    }
+
+    #[test]
+    fn load_str_evaluates_values_after_functions() {
+        let mut defs = Definitions::new();
+        defs.define_value("keep", 1.0).unwrap();
+        defs.load_str("b = g(2)\ng(x) = 2x\nc = b + keep").unwrap();
+        assert_eq!(defs.value("b"), Some(4.0));
+        assert_eq!(defs.value("c"), Some(5.0));
+
+        // A failed load leaves the set as it was.
+        assert!(defs.load_str("d = 1\ne = nope").is_err());
+        assert!(!defs.contains("d"));
+    }
+
+    #[test]
+    fn to_source_string_round_trips() {
+        let mut defs = Definitions::new();
+        defs.define_value("a", 0.1).unwrap();
+        defs.define_value("big", 3.0e20).unwrap();
+        defs.define_value("inf", f32::NEG_INFINITY).unwrap();
+        defs.define_value("nan", f32::NAN).unwrap();
+        defs.define_function("g", "a * x^2 + big").unwrap();
+
+        let mut loaded = Definitions::new();
+        loaded.load_str(&defs.to_source_string()).unwrap();
+        assert_eq!(loaded.to_source_string(), defs.to_source_string());
+        assert_eq!(loaded.value("a"), Some(0.1));
+        assert_eq!(loaded.value("big"), Some(3.0e20));
+        assert_eq!(loaded.value("inf"), Some(f32::NEG_INFINITY));
+        assert!(loaded.value("nan").unwrap().is_nan());
+    }
+
+    #[test]
+    fn dependencies_follow_the_bodies() {
+        let mut defs = Definitions::new();
+        defs.load_str("a = 2\nf(x) = x + 1\ng(x) = a * f(x) + taylor(f, 0, 2)\nh(x) = 4 |> g")
+            .unwrap();
+        assert_eq!(defs.dependencies("g"), ["a", "f"]);
+        assert_eq!(defs.dependencies("h"), ["g"]);
+        assert!(defs.dependencies("a").is_empty());
+        assert!(defs.dependencies("nope").is_empty());
+        assert_eq!(defs.dependents("f"), ["g"]);
+        assert_eq!(defs.dependents("a"), ["g"]);
+        assert!(defs.find_cycles().is_empty());
+
+        // Definition order lists `h` before what it uses; the
+        // topological order doesn't.
+        let mut defs = Definitions::new();
+        defs.define_function("h", "g(x) / 2").unwrap();
+        defs.define_function("g", "a * x").unwrap();
+        defs.define_value("a", 1.0).unwrap();
+        assert_eq!(defs.topological_order().unwrap(), ["a", "g", "h"]);
+    }
+
+    #[test]
+    fn cycles_are_found_and_block_ordering() {
+        let mut defs = Definitions::new();
+        defs.define_function("f", "x").unwrap();
+        defs.define_function("g", "h(x) + f(x)").unwrap();
+        defs.define_function("h", "k(x)").unwrap();
+        defs.define_function("k", "g(x) + k(x - 1)").unwrap();
+        // `k` calling itself is already covered by the g → h → k cycle.
+        assert_eq!(defs.find_cycles(), [vec!["g", "h", "k"]]);
+        let err = defs.topological_order().unwrap_err();
+        assert_eq!(err.message, "Definitions are recursive: g → h → k → g");
+        assert_eq!(err.kind(), &ErrorKind::InvalidDefinition);
+
+        defs.define_function("k", "x^2").unwrap();
+        assert!(defs.find_cycles().is_empty());
+        assert_eq!(defs.topological_order().unwrap(), ["f", "k", "h", "g"]);
+    }
+
+    #[test]
+    fn warnings_name_undefined_references() {
+        let mut defs = Definitions::new();
+        defs.define_value("a", 1.0).unwrap();
+        defs.define_function("g", "a * b + sinq(x)").unwrap();
+        defs.define_function("h", "g(x)").unwrap();
+        let warnings = defs.warnings();
+        let found: Vec<_> = warnings
+            .iter()
+            .map(|w| (w.in_function.as_deref(), w.span.map(|s| (s.start, s.end))))
+            .collect();
+        assert_eq!(
+            found,
+            [(Some("g"), Some((4, 5))), (Some("g"), Some((8, 12)))]
+        );
+        assert!(matches!(
+            warnings[1].kind(),
+            ErrorKind::UnknownName { suggestion: Some(s), .. } if s == "sin"
+        ));
+
+        defs.define_value("b", 2.0).unwrap();
+        assert_eq!(defs.warnings().len(), 1);
+    }
+
+    #[test]
+    fn inner_scopes_shadow_and_pop() {
+        let mut defs = Definitions::new();
+        defs.define_value("a", 1.0).unwrap();
+        defs.define_function("g", "a * x").unwrap();
+        assert!(!defs.pop_scope());
+
+        defs.push_scope();
+        defs.define_function("a", "x + 1").unwrap();
+        defs.define_value("b", 2.0).unwrap();
+        assert_eq!(defs.function_body("a"), Some("x + 1"));
+        assert_eq!((defs.scope_of("a"), defs.scope_of("g")), (Some(1), Some(0)));
+        assert_eq!(defs.scope_depth(), 1);
+        // The listing shows what a name means now, once.
+        let names: Vec<&str> = defs
+            .iter()
+            .map(|d| match d {
+                Definition::Value { name, .. } | Definition::Function { name, .. } => name,
+            })
+            .collect();
+        assert_eq!(names, ["g", "a", "b"]);
+        assert_eq!(defs.len(), 3);
+
+        // Only the innermost scope can be changed.
+        assert!(!defs.undefine("g"));
+        assert!(defs.undefine("a"));
+        assert_eq!(defs.value("a"), Some(1.0));
+        defs.define_value("a", 5.0).unwrap();
+
+        assert!(defs.pop_scope());
+        assert_eq!(defs.value("a"), Some(1.0));
+        assert!(!defs.contains("b"));
+        assert_eq!(defs.len(), 2);
+        assert_eq!(defs.scope_depth(), 0);
+    }
+
+    #[test]
+    fn graph_sees_only_visible_definitions() {
+        let mut defs = Definitions::new();
+        defs.define_function("g", "h(x)").unwrap();
+        defs.define_function("h", "g(x)").unwrap();
+        assert_eq!(defs.find_cycles(), [vec!["g", "h"]]);
+
+        defs.push_scope();
+        defs.define_function("h", "x^2").unwrap();
+        assert!(defs.find_cycles().is_empty());
+        assert_eq!(defs.topological_order().unwrap(), ["h", "g"]);
+        assert_eq!(defs.dependents("h"), ["g"]);
+    }
+
+    #[test]
+    fn compiled_bodies_are_cached_until_a_change() {
+        let mut defs = Definitions::new();
+        defs.define_function("g", "x + 1").unwrap();
+        assert!(defs.cache.plain.get().is_none());
+        let first = defs.compile().bodies.as_ptr();
+        assert_eq!(defs.compile().bodies.as_ptr(), first);
+        assert!(defs.cache.units.get().is_none());
+
+        // Opening a scope changes nothing visible; defining in it does.
+        defs.push_scope();
+        assert!(defs.cache.plain.get().is_some());
+        defs.define_value("a", 1.0).unwrap();
+        assert!(defs.cache.plain.get().is_none());
+        defs.compile();
+        defs.pop_scope();
+        assert!(defs.cache.plain.get().is_none());
+    }
 }
@@ -0,0 +1,503 @@
+//! Every problem in an equation at once, for editors.
+//!
+//! [`calculate`](crate::equation_analyzer::calculator::calculate) and
+//! friends stop at the first [`EquationError`].
+//! [`diagnostics`](crate::equation_analyzer::calculator::diagnostics)
+//! keeps going: after an error it skips ahead to the next operator, comma
+//! or closing parenthesis and resumes there, so one pass reports each
+//! mistake with its span:
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::diagnostics;
+//! use rusty_maths::equation_analyzer::diagnostics::Severity;
+//! use rusty_maths::equation_analyzer::Definitions;
+//!
+//! let found = diagnostics("sine(x) + cosh(2) * tann(x)", &Definitions::new());
+//! let messages: Vec<&str> = found.iter().map(|d| d.message.as_str()).collect();
+//! assert_eq!(
+//!     messages,
+//!     [
+//!         "Invalid function name sine — did you mean 'sin'?",
+//!         "Invalid function name tann — did you mean 'tan'?",
+//!     ]
+//! );
+//! assert!(found.iter().all(|d| d.severity == Severity::Error));
+//! ```
+//!
+//! Alongside the errors come warnings about equations that are valid but
+//! probably don't mean what they say: `-x^2` is `-(x^2)`, and `1,000`
+//! inside a call is two arguments.
+//!
+//! Only what can be known without evaluating is reported: a domain error
+//! such as `root(-1, 2)` shows up when the equation is evaluated, not here.
+
+use crate::equation_analyzer::definitions::{Definition, Definitions};
+use crate::equation_analyzer::errors::{Edit, EquationError, ErrorKind, Span};
+use crate::equation_analyzer::pipeline::parser::Parser;
+use crate::equation_analyzer::pipeline::tokenizer::StreamingTokenizer;
+use crate::equation_analyzer::structs::token::{Callee, SpannedToken, Token};
+use std::fmt;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The equation can't be evaluated.
+    Error,
+    /// The equation evaluates, but likely not as intended.
+    Warning,
+}
+
+/// One problem found in an equation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The same message the failing `calculate` call would report, for
+    /// errors.
+    pub message: String,
+    /// Where the problem is; `None` for the equation as a whole.
+    pub span: Option<Span>,
+    /// The error's kind; `None` for warnings.
+    pub kind: Option<ErrorKind>,
+    /// An edit to the equation that resolves the problem, when there is a
+    /// clear one.
+    pub fix: Option<Edit>,
+}
+
+impl Diagnostic {
+    fn error(e: EquationError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            kind: Some(e.kind().clone()),
+            fix: e.fix().cloned(),
+            message: e.message,
+            span: e.span,
+        }
+    }
+
+    fn warning(message: String, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message,
+            span: Some(span),
+            kind: None,
+            fix: None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// `error: Invalid input at character 3`, 1-based like [`EquationError`].
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.span {
+            Some(span) => write!(
+                f,
+                "{severity}: {} at character {}",
+                self.message,
+                span.start + 1
+            ),
+            None => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+/// Whether the parser can resume at `token` after skipping past an error.
+fn resumes_at(token: Token, parser: &Parser) -> bool {
+    match token {
+        Token::Plus
+        | Token::Minus
+        | Token::Star
+        | Token::Slash
+        | Token::Power
+        | Token::Modulo
+        | Token::Pipe
+        | Token::End => true,
+        Token::Comma => parser.in_call(),
+        Token::CloseParen => parser.open_parens(),
+        _ => false,
+    }
+}
+
+/// Whether `token` completes an operand, so that what follows it is an
+/// operator. `piped` says whether it followed a `|>`, which makes a call
+/// complete rather than open.
+fn ends_operand(token: Token, piped: bool) -> bool {
+    match token {
+        Token::Number(_)
+        | Token::X
+        | Token::Constant(_)
+        | Token::Unit(_)
+        | Token::CloseParen
+        | Token::Factorial
+        | Token::Percent => true,
+        Token::Call(_) => piped,
+        _ => false,
+    }
+}
+
+/// Tokenizes and parses `eq`, resuming after each error; reports the
+/// errors, the infix tokens that did scan, and the RPN.
+///
+/// An error where an operand belongs leaves a placeholder operand in its
+/// place, so the RPN keeps its shape and [`check_shape`] finds only real
+/// problems.
+fn scan(
+    eq: &str,
+    defs: &Definitions,
+) -> (Vec<EquationError>, Vec<SpannedToken>, Vec<SpannedToken>) {
+    let mut errors = Vec::new();
+    let mut infix = Vec::new();
+    let tokens = match StreamingTokenizer::new_with(eq, Some(defs)) {
+        Ok(tokens) => tokens,
+        Err(e) => return (vec![e], infix, Vec::new()),
+    };
+    let mut parser = Parser::new();
+    let mut skipping = false;
+    // Parentheses opened inside the region being skipped.
+    let mut depth = 0usize;
+    // The last token the parser accepted, and whether an operand is
+    // complete there.
+    let mut last: Option<Token> = None;
+    let mut operand = false;
+
+    for item in tokens {
+        let (error, spanned) = match item {
+            Ok(spanned) => {
+                infix.push(spanned);
+                if skipping {
+                    match spanned.token {
+                        Token::OpenParen | Token::Call(_) | Token::Log { .. } => depth += 1,
+                        Token::CloseParen if depth > 0 => depth -= 1,
+                        token if depth == 0 && resumes_at(token, &parser) => skipping = false,
+                        _ => {}
+                    }
+                    if skipping {
+                        continue;
+                    }
+                }
+                match parser.push(spanned) {
+                    Ok(()) => {
+                        operand = ends_operand(spanned.token, last == Some(Token::Pipe));
+                        last = Some(spanned.token);
+                        continue;
+                    }
+                    Err(e) => (e, Some(spanned)),
+                }
+            }
+            Err(e) => (e, None),
+        };
+
+        // Stand in for the operand the error took the place of. After a
+        // `|>` the piped value is already the operand; the parser just
+        // stops waiting for its function.
+        if last == Some(Token::Pipe) {
+            parser.recover();
+            last = None;
+            operand = true;
+        } else if !operand {
+            let span = error.span.or(spanned.map(|s| s.span));
+            let placeholder =
+                SpannedToken::new(Token::Number(f32::NAN), span.unwrap_or(Span::new(0, 0)));
+            if parser.push(placeholder).is_ok() {
+                last = Some(placeholder.token);
+                operand = true;
+            }
+        }
+        errors.push(error);
+        skipping = true;
+        depth = 0;
+    }
+
+    let (rpn, finish_errors) = parser.finish_all();
+    errors.extend(finish_errors);
+    (errors, infix, rpn)
+}
+
+fn plural(n: u8) -> &'static str {
+    if n == 1 {
+        "parameter"
+    } else {
+        "parameters"
+    }
+}
+
+/// The name of user definition `index`, for messages.
+fn user_name(defs: &Definitions, index: usize) -> &str {
+    match defs.iter().nth(index) {
+        Some(Definition::Function { name, .. } | Definition::Value { name, .. }) => name,
+        None => "?",
+    }
+}
+
+/// Walks the RPN counting stack heights, as the evaluator would, and
+/// reports each operator or call short of operands — with the evaluator's
+/// own message. A short operator counts as producing its result anyway, so
+/// one mistake isn't reported again by every operator after it.
+fn check_shape(rpn: &[SpannedToken], defs: &Definitions, errors: &mut Vec<EquationError>) {
+    let mut height = 0usize;
+    let mut frames: Vec<usize> = Vec::new();
+
+    for spanned in rpn {
+        let mut fail = |message: String, kind: ErrorKind| {
+            errors.push(EquationError::spanned(message, spanned.span).with_kind(kind))
+        };
+        match spanned.token {
+            Token::Number(_)
+            | Token::X
+            | Token::Constant(_)
+            | Token::Unit(_)
+            | Token::Parameter(_) => height += 1,
+            Token::UnaryMinus | Token::Factorial | Token::Percent | Token::Log { .. } => {
+                if height == 0 {
+                    let what = match spanned.token {
+                        Token::UnaryMinus => "unary minus operator",
+                        Token::Factorial => "factorial operator",
+                        Token::Percent => "percent operator",
+                        _ => "log function",
+                    };
+                    fail(
+                        format!("Insufficient operands for {what}"),
+                        ErrorKind::Syntax,
+                    );
+                    height = 1;
+                }
+            }
+            Token::Plus
+            | Token::Minus
+            | Token::Star
+            | Token::Slash
+            | Token::Modulo
+            | Token::Power
+            | Token::UnitProduct => {
+                if height < 2 {
+                    fail(String::from("Invalid expression"), ErrorKind::Syntax);
+                }
+                height = height.saturating_sub(1).max(1);
+            }
+            Token::Call(callee) => {
+                if height == 0 {
+                    let name = match callee {
+                        Callee::Catalog(sym) => sym.name,
+                        Callee::User(i) => user_name(defs, i),
+                        Callee::Taylor(_) => "taylor",
+                    };
+                    fail(
+                        format!("Insufficient operands for {name} function"),
+                        ErrorKind::Syntax,
+                    );
+                    height = 1;
+                }
+            }
+            Token::CallStart(_) => frames.push(height),
+            Token::EndCall(callee) => {
+                let start = frames.pop().unwrap_or(0);
+                let n = height.saturating_sub(start);
+                match callee {
+                    Callee::User(i) if n != 1 => {
+                        let name = user_name(defs, i);
+                        fail(
+                            format!("{name} takes exactly 1 parameter (x), got {n}"),
+                            ErrorKind::arity(name, 1, Some(1), n),
+                        );
+                    }
+                    Callee::Taylor(_) if n != 2 => {
+                        fail(
+                            format!("taylor takes exactly 3 parameters (f, a, n), got {}", n + 1),
+                            ErrorKind::arity("taylor", 3, Some(3), n + 1),
+                        );
+                    }
+                    Callee::Catalog(sym) => {
+                        if let Some((min_args, max_args)) = sym.kind.arity() {
+                            let arity = ErrorKind::arity(
+                                sym.name,
+                                usize::from(min_args),
+                                max_args.map(usize::from),
+                                n,
+                            );
+                            if (n as u32) < min_args as u32 {
+                                fail(
+                                    format!(
+                                        "{} requires at least {} {}, got {}",
+                                        sym.name,
+                                        min_args,
+                                        plural(min_args),
+                                        n
+                                    ),
+                                    arity,
+                                );
+                            } else if let Some(max) = max_args.filter(|&max| n as u32 > max as u32)
+                            {
+                                fail(
+                                    format!(
+                                        "{} accepts at most {} {}, got {}",
+                                        sym.name,
+                                        max,
+                                        plural(max),
+                                        n
+                                    ),
+                                    arity,
+                                );
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                height = start + 1;
+            }
+            Token::Y
+            | Token::Equal
+            | Token::Comma
+            | Token::OpenParen
+            | Token::CloseParen
+            | Token::Pipe
+            | Token::End => {
+                fail(
+                    format!("Unexpected token in evaluation: {:?}", spanned.token),
+                    ErrorKind::Syntax,
+                );
+            }
+        }
+    }
+
+    if rpn.is_empty() {
+        errors.push(EquationError::new("Invalid equation supplied"));
+    } else if height != 1 {
+        errors.push(EquationError::new(format!(
+            "Invalid evaluation: expected 1 result, found {height} items in stack"
+        )));
+    }
+}
+
+/// The source text a span covers.
+fn source(eq: &str, span: Span) -> String {
+    eq.chars().skip(span.start).take(span.len()).collect()
+}
+
+/// `-x^2`: a negated operand raised to a power, which negates the power.
+fn ambiguous_negation(eq: &str, infix: &[SpannedToken], out: &mut Vec<Diagnostic>) {
+    for w in infix.windows(3) {
+        let [minus, base, power] = w else { continue };
+        let operand = matches!(base.token, Token::Number(_) | Token::X | Token::Constant(_));
+        // The `2x` expansion gives its tokens the whole lexeme's span; `-2x^2`
+        // reaches here as `-`, `2`, `*`, so it never matches.
+        if minus.token == Token::UnaryMinus && operand && power.token == Token::Power {
+            let base = source(eq, base.span);
+            out.push(Diagnostic::warning(
+                format!("Ambiguous '-{base}^…': parsed as -({base}^…), not (-{base})^…"),
+                Span::new(minus.span.start, power.span.end),
+            ));
+        }
+    }
+}
+
+/// `1,000`: a comma between digits that reads as digit grouping but
+/// separates arguments.
+fn comma_grouping(eq: &str, infix: &[SpannedToken], out: &mut Vec<Diagnostic>) {
+    let is_number = |t: &SpannedToken| matches!(t.token, Token::Number(_));
+    let mut i = 0;
+    while i + 2 < infix.len() {
+        let mut end = i;
+        // Extend over `,ddd` groups written flush against the number.
+        while end + 2 < infix.len()
+            && is_number(&infix[end])
+            && infix[end + 1].token == Token::Comma
+            && is_number(&infix[end + 2])
+            && infix[end].span.end == infix[end + 1].span.start
+            && infix[end + 1].span.end == infix[end + 2].span.start
+            && source(eq, infix[end + 2].span).len() == 3
+            && source(eq, infix[end + 2].span)
+                .chars()
+                .all(|c| c.is_ascii_digit())
+        {
+            end += 2;
+        }
+        if end == i {
+            i += 1;
+            continue;
+        }
+        let span = Span::new(infix[i].span.start, infix[end].span.end);
+        let written = source(eq, span);
+        let grouped = written.replace(',', "_");
+        let mut warning = Diagnostic::warning(
+            format!(
+                "Commas in '{written}' separate arguments, not digit groups; write {grouped} for one number"
+            ),
+            span,
+        );
+        warning.fix = Some(Edit {
+            span,
+            replacement: grouped,
+        });
+        out.push(warning);
+        i = end + 1;
+    }
+}
+
+/// Only the errors in `eq`, ordered by position, as the `EquationError`s
+/// evaluation would raise.
+pub(crate) fn errors(eq: &str, defs: &Definitions) -> Vec<EquationError> {
+    let (mut errors, infix, rpn) = scan(eq, defs);
+    if !infix.is_empty() {
+        check_shape(&rpn, defs, &mut errors);
+    }
+    errors.sort_by_key(|e| e.span.map_or(usize::MAX, |s| s.start));
+    errors
+}
+
+/// Every error and warning in `eq`, ordered by position; see the
+/// [module docs](self). Empty when the equation is clean.
+pub(crate) fn check(eq: &str, defs: &Definitions) -> Vec<Diagnostic> {
+    let (mut errors, infix, rpn) = scan(eq, defs);
+    if !infix.is_empty() {
+        check_shape(&rpn, defs, &mut errors);
+    }
+    let mut found: Vec<Diagnostic> = errors.into_iter().map(Diagnostic::error).collect();
+    ambiguous_negation(eq, &infix, &mut found);
+    comma_grouping(eq, &infix, &mut found);
+
+    // Stable: at one position, errors stay ahead of warnings, and
+    // whole-equation problems go last.
+    found.sort_by_key(|d| (d.span.map_or(usize::MAX, |s| s.start), d.severity));
+    found.dedup();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(eq: &str) -> Vec<String> {
+        check(eq, &Definitions::new())
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn skipping_balances_parentheses() {
+        // Everything inside the bad call is skipped, up to the `+`.
+        assert_eq!(
+            messages("nope(2, (3)) + sine(1)"),
+            [
+                "Invalid function name nope",
+                "Invalid function name sine — did you mean 'sin'?",
+            ]
+        );
+    }
+
+    #[test]
+    fn shape_check_matches_the_evaluator() {
+        assert_eq!(messages("2 +"), ["Invalid expression"]);
+        assert_eq!(
+            messages("ch(1)"),
+            ["ch requires at least 2 parameters, got 1"]
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Dual numbers: forward-mode automatic differentiation through the
+//! evaluator.
+//!
+//! A [`Dual`] carries a value and its derivative with respect to `x`.
+//! Seeding `x` with derivative 1 and evaluating the RPN with the generic
+//! [`Domain`] walker applies the chain rule at every step, so
+//! [`eval_with_derivative`](crate::equation_analyzer::calculator::eval_with_derivative)
+//! gets `f'(x)` exactly — through user functions, variadic catalog
+//! functions, and the numerically solved `rate` and `irr` alike — with no
+//! step size to pick.
+//!
+//! Where a function has no derivative (`abs` at 0, `floor` at an integer)
+//! the one-sided or zero derivative of the formula is reported; integer-only
+//! functions (`!`, `ch`, `perm`, `randint`) are piecewise constant and
+//! differentiate to 0.
+
+use crate::equation_analyzer::catalog::{Category, Symbol, SymbolKind};
+use crate::equation_analyzer::finance::{self, Real};
+use crate::equation_analyzer::pipeline::domain::Domain;
+use crate::equation_analyzer::pipeline::evaluator::EvalState;
+use std::f64::consts::PI;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A value and its derivative. Evaluation runs in f64 and narrows at the
+/// end, like the finance functions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Dual {
+    pub(crate) v: f64,
+    pub(crate) d: f64,
+}
+
+impl Dual {
+    pub(crate) fn new(v: f64, d: f64) -> Dual {
+        Dual { v, d }
+    }
+
+    /// The chain rule for a unary function with value `fv` and slope `df`
+    /// at `self.v`. A zero inner derivative stays zero even where `df` is
+    /// infinite (`sqrt` at 0 of a constant).
+    fn chain(self, fv: f64, df: f64) -> Dual {
+        let d = if self.d == 0.0 { 0.0 } else { df * self.d };
+        Dual::new(fv, d)
+    }
+
+    fn sqrt(self) -> Dual {
+        let r = self.v.sqrt();
+        self.chain(r, 0.5 / r)
+    }
+
+    fn exp(self) -> Dual {
+        let e = self.v.exp();
+        self.chain(e, e)
+    }
+
+    fn powd(self, e: Dual) -> Dual {
+        let v = self.v.powf(e.v);
+        let mut d = 0.0;
+        if self.d != 0.0 {
+            d += e.v * self.v.powf(e.v - 1.0) * self.d;
+        }
+        if e.d != 0.0 {
+            d += v * self.v.ln() * e.d;
+        }
+        Dual::new(v, d)
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+    fn add(self, rhs: Dual) -> Dual {
+        Dual::new(self.v + rhs.v, self.d + rhs.d)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+    fn sub(self, rhs: Dual) -> Dual {
+        Dual::new(self.v - rhs.v, self.d - rhs.d)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+    fn mul(self, rhs: Dual) -> Dual {
+        Dual::new(self.v * rhs.v, self.d * rhs.v + self.v * rhs.d)
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+    fn div(self, rhs: Dual) -> Dual {
+        let q = self.v / rhs.v;
+        Dual::new(q, (self.d - q * rhs.d) / rhs.v)
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+    fn neg(self) -> Dual {
+        Dual::new(-self.v, -self.d)
+    }
+}
+
+impl Real for Dual {
+    fn constant(v: f64) -> Self {
+        Dual::new(v, 0.0)
+    }
+    fn value(self) -> f64 {
+        self.v
+    }
+    fn order(self) -> usize {
+        1
+    }
+    fn powf(self, e: Self) -> Self {
+        self.powd(e)
+    }
+    fn ln(self) -> Self {
+        self.chain(self.v.ln(), 1.0 / self.v)
+    }
+}
+
+/// The derivative of the argument whose value the function returned —
+/// `min`, `max`, `choose` pick one argument.
+fn picked(args: &[Dual], v: f64) -> Dual {
+    let d = args.iter().find(|a| a.v == v).map_or(0.0, |a| a.d);
+    Dual::new(v, d)
+}
+
+fn median(args: &[Dual]) -> Dual {
+    let mut sorted = args.to_vec();
+    sorted.sort_by(|a, b| a.v.total_cmp(&b.v));
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[mid]
+    } else {
+        (sorted[mid - 1] + sorted[mid]) / Dual::constant(2.0)
+    }
+}
+
+fn plain_args(args: &[Dual]) -> Vec<f32> {
+    args.iter().map(|a| a.v as f32).collect()
+}
+
+impl Domain for Dual {
+    fn number(v: f32) -> Self {
+        Dual::constant(f64::from(v))
+    }
+
+    fn parameter(v: f64) -> Self {
+        Dual::constant(v)
+    }
+
+    fn to_number(&self) -> Option<f32> {
+        (self.d == 0.0).then_some(self.v as f32)
+    }
+
+    fn add(&self, rhs: &Self) -> Result<Self, String> {
+        Ok(*self + *rhs)
+    }
+
+    fn sub(&self, rhs: &Self) -> Result<Self, String> {
+        Ok(*self - *rhs)
+    }
+
+    fn mul(&self, rhs: &Self) -> Result<Self, String> {
+        Ok(*self * *rhs)
+    }
+
+    fn div(&self, rhs: &Self) -> Result<Self, String> {
+        Ok(*self / *rhs)
+    }
+
+    fn rem(&self, rhs: &Self) -> Result<Self, String> {
+        // x % y = x − trunc(x/y)·y, with trunc locally constant.
+        let k = (self.v / rhs.v).trunc();
+        Ok(Dual::new(self.v % rhs.v, self.d - k * rhs.d))
+    }
+
+    fn pow(&self, rhs: &Self) -> Result<Self, String> {
+        Ok(self.powd(*rhs))
+    }
+
+    fn neg(&self) -> Self {
+        -*self
+    }
+
+    fn factorial(&self) -> Result<Self, String> {
+        if self.v < 0.0 || self.v % 1.0 != 0.0 {
+            return Err(String::from(
+                "Factorial is only defined for non-negative integers",
+            ));
+        }
+        crate::utilities::factorial(self.v as isize).map(|f| Dual::constant(f as f64))
+    }
+
+    fn log(&self, base: f32) -> Result<Self, String> {
+        let ln_b = f64::from(base).ln();
+        Ok(self.chain(self.v.ln() / ln_b, 1.0 / (self.v * ln_b)))
+    }
+
+    fn call(sym: &'static Symbol, args: &[Self], state: &mut EvalState) -> Result<Self, String> {
+        let name = sym.name;
+        if sym.category == Category::Finance {
+            return finance::eval(name, args);
+        }
+        if let SymbolKind::Random { run, .. } = sym.kind {
+            // Draw exactly as the plain evaluator would; the result moves
+            // with the arguments it was drawn from.
+            let v = f64::from(run(state.rng(), &plain_args(args))?);
+            return Ok(match name {
+                "randn" => {
+                    let (mu, sigma) = (args[0], args[1]);
+                    let z = if sigma.v == 0.0 {
+                        0.0
+                    } else {
+                        (v - mu.v) / sigma.v
+                    };
+                    Dual::new(v, mu.d + z * sigma.d)
+                }
+                "choose" => picked(args, v),
+                _ => Dual::constant(v),
+            });
+        }
+        let Some(&x) = args.first() else {
+            return Err(format!("{name} requires at least 1 parameter, got 0"));
+        };
+        let v = x.v;
+        Ok(match name {
+            "sin" => x.chain(v.sin(), v.cos()),
+            "cos" => x.chain(v.cos(), -v.sin()),
+            "tan" => {
+                let t = v.tan();
+                x.chain(t, 1.0 + t * t)
+            }
+            "sec" => {
+                let s = 1.0 / v.cos();
+                x.chain(s, s * v.tan())
+            }
+            "csc" => {
+                let c = 1.0 / v.sin();
+                x.chain(c, -c / v.tan())
+            }
+            "cot" => {
+                let c = 1.0 / v.tan();
+                x.chain(c, -(1.0 + c * c))
+            }
+            "asin" => x.chain(v.asin(), 1.0 / (1.0 - v * v).sqrt()),
+            "acos" => x.chain(v.acos(), -1.0 / (1.0 - v * v).sqrt()),
+            "atan" => x.chain(v.atan(), 1.0 / (1.0 + v * v)),
+            "atan2" => {
+                // atan2(y, x): dθ = (x·dy − y·dx) / (x² + y²).
+                let (y, x) = (args[0], args[1]);
+                let r2 = x.v * x.v + y.v * y.v;
+                Dual::new(y.v.atan2(x.v), (x.v * y.d - y.v * x.d) / r2)
+            }
+            "sinh" => x.chain(v.sinh(), v.cosh()),
+            "cosh" => x.chain(v.cosh(), v.sinh()),
+            "tanh" => {
+                let t = v.tanh();
+                x.chain(t, 1.0 - t * t)
+            }
+            "asinh" => x.chain(v.asinh(), 1.0 / (v * v + 1.0).sqrt()),
+            "acosh" => x.chain(v.acosh(), 1.0 / (v * v - 1.0).sqrt()),
+            "atanh" => x.chain(v.atanh(), 1.0 / (1.0 - v * v)),
+            "deg" => x.chain(v.to_degrees(), 180.0 / PI),
+            "rad" => x.chain(v.to_radians(), PI / 180.0),
+            "abs" => x.chain(v.abs(), if v < 0.0 { -1.0 } else { 1.0 }),
+            "sqrt" => x.sqrt(),
+            "floor" => Dual::constant(v.floor()),
+            "ceil" => Dual::constant(v.ceil()),
+            "round" => Dual::constant(v.round()),
+            "ln" => x.ln(),
+            "exp" => x.exp(),
+            "pow" => x.powd(args[1]),
+            "root" => {
+                // The catalog version rejects n = 0 and even roots of
+                // negatives; reuse its checks.
+                if let SymbolKind::Variadic { run, .. } = sym.kind {
+                    run(&plain_args(args))?;
+                }
+                let n = args[1];
+                // r = ±|x|^(1/n): dr = r·(dx/(n·x) − ln|x|·dn/n²).
+                let r = if v >= 0.0 {
+                    v.powf(1.0 / n.v)
+                } else {
+                    -(-v).powf(1.0 / n.v)
+                };
+                let mut d = 0.0;
+                if x.d != 0.0 {
+                    d += r / (n.v * v) * x.d;
+                }
+                if n.d != 0.0 {
+                    d -= r * v.abs().ln() / (n.v * n.v) * n.d;
+                }
+                Dual::new(r, d)
+            }
+            "min" => picked(args, args.iter().map(|a| a.v).fold(f64::INFINITY, f64::min)),
+            "max" => picked(
+                args,
+                args.iter().map(|a| a.v).fold(f64::NEG_INFINITY, f64::max),
+            ),
+            "sum" => args[1..].iter().fold(x, |acc, &a| acc + a),
+            "avg" => {
+                args[1..].iter().fold(x, |acc, &a| acc + a) / Dual::constant(args.len() as f64)
+            }
+            "med" => median(args),
+            "mode" => {
+                let value = match sym.kind {
+                    SymbolKind::Variadic { run, .. } => f64::from(run(&plain_args(args))?),
+                    _ => return Err(format!("'{name}' is not callable")),
+                };
+                // The mean of the modes moves with the arguments that tie.
+                let max_count = args
+                    .iter()
+                    .map(|a| args.iter().filter(|b| b.v == a.v).count())
+                    .max()
+                    .unwrap_or(0);
+                let tied: Vec<&Dual> = args
+                    .iter()
+                    .filter(|a| args.iter().filter(|b| b.v == a.v).count() == max_count)
+                    .collect();
+                let d = if value.is_nan() {
+                    f64::NAN
+                } else {
+                    tied.iter().map(|a| a.d).sum::<f64>() / tied.len() as f64
+                };
+                Dual::new(value, d)
+            }
+            _ => match sym.kind {
+                // Integer-valued, so locally constant.
+                SymbolKind::Variadic { run, .. } => {
+                    Dual::constant(f64::from(run(&plain_args(args))?))
+                }
+                SymbolKind::UnitConversion => {
+                    return Err(format!(
+                        "'{name}' requires unit-aware evaluation (calculate_units)"
+                    ))
+                }
+                _ => return Err(format!("No derivative for '{name}'")),
+            },
+        })
+    }
+}
@@ -1,6 +1,22 @@
 //! The equation analyzer's error type: a message plus an optional character
 //! span into the source equation, so consumers (like rm-repl) can point at
 //! the offending input instead of only echoing a sentence.
+//!
+//! Tools that react to errors match on [`EquationError::kind`] rather than
+//! the message, and apply [`EquationError::fix`] where one is offered:
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator;
+//! use rusty_maths::equation_analyzer::errors::ErrorKind;
+//!
+//! let eq = "2 * sqr(9)";
+//! let err = calculator::calculate(eq).unwrap_err();
+//! assert!(matches!(
+//!     err.kind(),
+//!     ErrorKind::UnknownName { name, suggestion: Some(s) } if name == "sqr" && s == "sqrt"
+//! ));
+//! assert_eq!(err.fix().unwrap().apply(eq), "2 * sqrt(9)");
+//! ```
 
 use std::fmt;
 
@@ -10,6 +26,7 @@ use std::fmt;
 /// source with `&eq[span.start..span.end]` would be wrong for multi-byte
 /// input; use `eq.chars().skip(start).take(end - start)` instead.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -30,11 +47,103 @@ impl Span {
     }
 }
 
+/// What went wrong, for tools that react to errors without parsing
+/// messages. New kinds may be added; match with a wildcard arm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The equation is malformed: a stray operator, a misplaced comma,
+    /// an invalid character.
+    Syntax,
+    /// A parenthesis without its partner, or a call never closed.
+    UnbalancedParens,
+    /// A name that nothing defines.
+    UnknownName {
+        name: String,
+        /// The closest known function, when one is close enough.
+        suggestion: Option<String>,
+    },
+    /// A call with the wrong number of arguments.
+    ArityMismatch {
+        function: String,
+        min: usize,
+        /// `None` when the function takes any number from `min` up.
+        max: Option<usize>,
+        actual: usize,
+    },
+    /// A value outside a function's domain: `root(-4, 2)`, `(-1)!`.
+    DomainError,
+    /// Calls nested past the call-depth limit, usually through recursion.
+    DepthLimit { function: String },
+    /// A user definition that can't be made: a reserved or invalid name, an
+    /// empty body.
+    InvalidDefinition,
+    /// An invalid argument to the API itself, such as a plot step size.
+    InvalidArgument,
+    /// The equation is longer than [`EvalLimits`](crate::equation_analyzer::EvalLimits) allows.
+    InputTooLong { len: usize, max: usize },
+    /// The plot has more points than its limits allow.
+    TooManyPoints { points: u64, max: usize },
+    /// Evaluation ran more steps than its limits allow.
+    StepLimit { max: u64 },
+    /// Evaluation ran past its deadline.
+    TimedOut,
+    /// Evaluation was cancelled through a
+    /// [`CancelToken`](crate::equation_analyzer::CancelToken).
+    Cancelled,
+    /// A quantity with the wrong dimension for what was done with it.
+    Units,
+    /// A bug in the analyzer rather than in the equation.
+    Internal,
+}
+
+impl ErrorKind {
+    pub(crate) fn arity(function: &str, min: usize, max: Option<usize>, actual: usize) -> Self {
+        ErrorKind::ArityMismatch {
+            function: function.to_string(),
+            min,
+            max,
+            actual,
+        }
+    }
+}
+
+/// A suggested change to the source equation: replace the characters in
+/// `span` with `replacement`. An empty replacement deletes them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Edit {
+    /// `source` with the edit made. Spans count characters, as everywhere
+    /// in the analyzer.
+    pub fn apply(&self, source: &str) -> String {
+        let before = source.chars().take(self.span.start);
+        let after = source.chars().skip(self.span.end);
+        before
+            .chain(self.replacement.chars())
+            .chain(after)
+            .collect()
+    }
+}
+
 /// An error from tokenizing, parsing, or evaluating an equation.
 ///
 /// `span` locates the offending region when one exists; errors about the
 /// expression as a whole (empty input, leftover operands) carry `None`.
+///
+/// With the `serde` feature, an error serializes as one flat record:
+/// `message`, `span`, `in_function`, `kind`, `fix` and `line`.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "ErrorRecord", from = "ErrorRecord")
+)]
 #[non_exhaustive]
 pub struct EquationError {
     pub message: String,
@@ -45,8 +154,20 @@ pub struct EquationError {
     /// *body* source, not the top-level equation — renderers that draw
     /// carets must point at the body text instead.
     pub in_function: Option<String>,
+    /// The kind and fix, when either is more than the default. Boxed so
+    /// the common error stays small.
+    detail: Option<Box<Detail>>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+struct Detail {
+    kind: ErrorKind,
+    fix: Option<Edit>,
+    line: Option<usize>,
+}
+
+static SYNTAX: ErrorKind = ErrorKind::Syntax;
+
 impl EquationError {
     /// An error with no meaningful location.
     pub fn new(message: impl Into<String>) -> Self {
@@ -54,6 +175,7 @@ impl EquationError {
             message: message.into(),
             span: None,
             in_function: None,
+            detail: None,
         }
     }
 
@@ -63,9 +185,66 @@ impl EquationError {
             message: message.into(),
             span: Some(span),
             in_function: None,
+            detail: None,
         }
     }
 
+    /// A value outside a function's domain, at `span`.
+    pub(crate) fn domain(message: impl Into<String>, span: Span) -> Self {
+        Self::spanned(message, span).with_kind(ErrorKind::DomainError)
+    }
+
+    /// What went wrong; [`ErrorKind::Syntax`] unless something more
+    /// specific applies.
+    pub fn kind(&self) -> &ErrorKind {
+        self.detail.as_ref().map_or(&SYNTAX, |d| &d.kind)
+    }
+
+    /// An edit to the equation that fixes the error, when the analyzer is
+    /// confident of one. Like `span`, it refers to the body source when
+    /// `in_function` is set.
+    pub fn fix(&self) -> Option<&Edit> {
+        self.detail.as_ref().and_then(|d| d.fix.as_ref())
+    }
+
+    /// For errors in multi-line sources such as a
+    /// [definitions file](crate::equation_analyzer::definitions#text-format),
+    /// the 1-based line the error is on; `span` and `fix` are then relative
+    /// to that line.
+    pub fn line(&self) -> Option<usize> {
+        self.detail.as_ref().and_then(|d| d.line)
+    }
+
+    fn detail_mut(&mut self) -> &mut Detail {
+        self.detail.get_or_insert_with(|| {
+            Box::new(Detail {
+                kind: ErrorKind::Syntax,
+                fix: None,
+                line: None,
+            })
+        })
+    }
+
+    pub(crate) fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.detail_mut().kind = kind;
+        self
+    }
+
+    /// Offers replacing `span` with `replacement` as the fix.
+    pub(crate) fn with_fix(mut self, span: Span, replacement: impl Into<String>) -> Self {
+        self.detail_mut().fix = Some(Edit {
+            span,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    /// Places the error on a 1-based source line.
+    pub(crate) fn on_line(mut self, line: usize) -> Self {
+        self.detail_mut().line = Some(line);
+        self
+    }
+
     /// Tags the error as originating inside the named user-defined
     /// function's body. The innermost function wins: an already-tagged
     /// error passes through unchanged as the call stack unwinds.
@@ -89,13 +268,67 @@ impl EquationError {
                 span.start += delta;
                 span.end += delta;
             }
+            if let Some(fix) = self.detail.as_mut().and_then(|d| d.fix.as_mut()) {
+                fix.span.start += delta;
+                fix.span.end += delta;
+            }
         }
         self
     }
 }
 
+/// The serialized form of [`EquationError`]: its boxed detail flattened
+/// into plain fields.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ErrorRecord {
+    message: String,
+    span: Option<Span>,
+    in_function: Option<String>,
+    kind: ErrorKind,
+    fix: Option<Edit>,
+    line: Option<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl From<EquationError> for ErrorRecord {
+    fn from(e: EquationError) -> Self {
+        ErrorRecord {
+            kind: e.kind().clone(),
+            fix: e.fix().cloned(),
+            line: e.line(),
+            message: e.message,
+            span: e.span,
+            in_function: e.in_function,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<ErrorRecord> for EquationError {
+    fn from(r: ErrorRecord) -> Self {
+        let detail =
+            (r.kind != ErrorKind::Syntax || r.fix.is_some() || r.line.is_some()).then(|| {
+                Box::new(Detail {
+                    kind: r.kind,
+                    fix: r.fix,
+                    line: r.line,
+                })
+            });
+        EquationError {
+            message: r.message,
+            span: r.span,
+            in_function: r.in_function,
+            detail,
+        }
+    }
+}
+
 impl fmt::Display for EquationError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line() {
+            write!(f, "line {line}: ")?;
+        }
         if let Some(name) = &self.in_function {
             write!(f, "in {name}(x): ")?;
         }
@@ -131,6 +364,20 @@ mod tests {
         assert_eq!(err.span, None);
     }
 
+    #[test]
+    fn edits_count_characters() {
+        let edit = Edit {
+            span: Span::new(2, 4),
+            replacement: String::from("pi"),
+        };
+        assert_eq!(edit.apply("2*ππ+1"), "2*pi+1");
+
+        let err = EquationError::spanned("x", Span::new(1, 2))
+            .with_fix(Span::new(1, 2), "")
+            .offset(3);
+        assert_eq!(err.fix().map(|f| f.span), Some(Span::new(4, 5)));
+    }
+
     #[test]
     fn span_len_saturates() {
         assert_eq!(Span::new(3, 7).len(), 4);
@@ -0,0 +1,456 @@
+//! Time-value-of-money math behind the catalog's `Finance` entries.
+//!
+//! Argument order and sign conventions follow the spreadsheet functions
+//! finance users already know: money paid out is negative, money received
+//! is positive, rates are per period (`5%/12` for 5% a year paid monthly),
+//! and the optional trailing `type` is 0 for payments at the end of each
+//! period (the default) or 1 for payments at the start.
+//!
+//! Everything computes in f64 and narrows once at the end, so long
+//! horizons (`fv(0.4%, 360, …)`) don't accumulate f32 rounding.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Upper bound on solver iterations for `rate` and `irr`.
+const MAX_ITERATIONS: usize = 200;
+
+/// Convergence tolerance on the rate itself.
+const RATE_TOLERANCE: f64 = 1e-12;
+
+/// The arithmetic the TVM formulas need, so the same code runs on plain
+/// f64, on dual numbers (exact derivatives for
+/// [`eval_with_derivative`](crate::equation_analyzer::calculator::eval_with_derivative))
+/// and on truncated power series (Taylor coefficients). Branches look at
+/// [`value`](Real::value) only.
+pub(crate) trait Real:
+    Copy
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    fn constant(v: f64) -> Self;
+    fn value(self) -> f64;
+    /// How many coefficients ride along with the value: 0 for plain
+    /// numbers, 1 for duals, `n` for an order-`n` series.
+    fn order(self) -> usize;
+    fn powf(self, e: Self) -> Self;
+    fn ln(self) -> Self;
+}
+
+impl Real for f64 {
+    fn constant(v: f64) -> Self {
+        v
+    }
+    fn value(self) -> f64 {
+        self
+    }
+    fn order(self) -> usize {
+        0
+    }
+    fn powf(self, e: Self) -> Self {
+        f64::powf(self, e)
+    }
+    fn ln(self) -> Self {
+        f64::ln(self)
+    }
+}
+
+fn args(xs: &[f32]) -> Vec<f64> {
+    xs.iter().map(|&v| f64::from(v)).collect()
+}
+
+/// Optional trailing argument `i`, or `default` when omitted.
+fn opt<T: Real>(xs: &[T], i: usize, default: f64) -> T {
+    xs.get(i).copied().unwrap_or(T::constant(default))
+}
+
+fn check_rate<T: Real>(name: &str, rate: T) -> Result<(), String> {
+    let rate = rate.value();
+    if rate <= -1.0 {
+        return Err(format!(
+            "{name} rate must be greater than -100%, got {rate}"
+        ));
+    }
+    Ok(())
+}
+
+fn check_type<T: Real>(name: &str, when: T) -> Result<T, String> {
+    let v = when.value();
+    if v == 0.0 || v == 1.0 {
+        // The timing is a switch, not a quantity: it carries no derivative.
+        Ok(T::constant(v))
+    } else {
+        Err(format!(
+            "{name} type must be 0 (end of period) or 1 (start of period), got {v}"
+        ))
+    }
+}
+
+fn finite<T: Real>(name: &str, v: T) -> Result<T, String> {
+    if v.value().is_finite() {
+        Ok(v)
+    } else {
+        Err(format!("{name} has no finite result for these arguments"))
+    }
+}
+
+fn narrow(v: Result<f64, String>) -> Result<f32, String> {
+    v.map(|v| v as f32)
+}
+
+/// The annuity balance identity every TVM function solves for one unknown:
+/// `pv·(1+r)^n + pmt·(1+r·type)·((1+r)^n − 1)/r + fv = 0`.
+fn tvm_residual<T: Real>(rate: T, nper: T, pmt: T, pv: T, fv: T, when: T) -> T {
+    if rate.value() == 0.0 {
+        return pv + pmt * nper + fv;
+    }
+    let one = T::constant(1.0);
+    let growth = (one + rate).powf(nper);
+    pv * growth + pmt * (one + rate * when) * (growth - one) / rate + fv
+}
+
+/// The root of `residual(r, args)` as a function of `args`, given its value
+/// `root`. Chord iterations `r ← r − F(r, args) / F_r` with the slope frozen
+/// at the root: the value is already converged, and each pass fixes one
+/// more derivative coefficient, so `order + 2` passes carry every
+/// coefficient the arguments do (the implicit function theorem, to any
+/// order).
+fn implicit<T: Real>(root: f64, args: &[T], residual: impl Fn(T, &[T]) -> T) -> T {
+    let order = args.iter().map(|a| a.order()).max().unwrap_or(0);
+    if order == 0 {
+        return T::constant(root);
+    }
+    let fixed: Vec<T> = args.iter().map(|a| T::constant(a.value())).collect();
+    let at = |r: f64| residual(T::constant(r), &fixed).value();
+    let h = 1e-6 * root.abs().max(1e-3);
+    let slope = T::constant((at(root + h) - at(root - h)) / (2.0 * h));
+    let mut r = T::constant(root);
+    for _ in 0..order + 2 {
+        r = r - residual(r, args) / slope;
+    }
+    r
+}
+
+/// `pv(rate, nper, pmt[, fv[, type]])` — present value of an annuity.
+pub(crate) fn pv(xs: &[f32]) -> Result<f32, String> {
+    narrow(pv_of(&args(xs)))
+}
+
+fn pv_of<T: Real>(a: &[T]) -> Result<T, String> {
+    let (rate, nper, pmt, fv) = (a[0], a[1], a[2], opt(a, 3, 0.0));
+    let when = check_type("pv", opt(a, 4, 0.0))?;
+    check_rate("pv", rate)?;
+    let pv = if rate.value() == 0.0 {
+        -(pmt * nper + fv)
+    } else {
+        let one = T::constant(1.0);
+        let growth = (one + rate).powf(nper);
+        -(pmt * (one + rate * when) * (growth - one) / rate + fv) / growth
+    };
+    finite("pv", pv)
+}
+
+/// `fv(rate, nper, pmt[, pv[, type]])` — future value of an annuity.
+pub(crate) fn fv(xs: &[f32]) -> Result<f32, String> {
+    narrow(fv_of(&args(xs)))
+}
+
+fn fv_of<T: Real>(a: &[T]) -> Result<T, String> {
+    let (rate, nper, pmt, pv) = (a[0], a[1], a[2], opt(a, 3, 0.0));
+    let when = check_type("fv", opt(a, 4, 0.0))?;
+    check_rate("fv", rate)?;
+    finite(
+        "fv",
+        -tvm_residual(rate, nper, pmt, pv, T::constant(0.0), when),
+    )
+}
+
+/// `pmt(rate, nper, pv[, fv[, type]])` — the level payment that amortizes
+/// `pv` to `fv` over `nper` periods.
+pub(crate) fn pmt(xs: &[f32]) -> Result<f32, String> {
+    narrow(pmt_of(&args(xs)))
+}
+
+fn pmt_of<T: Real>(a: &[T]) -> Result<T, String> {
+    let (rate, nper, pv, fv) = (a[0], a[1], a[2], opt(a, 3, 0.0));
+    let when = check_type("pmt", opt(a, 4, 0.0))?;
+    check_rate("pmt", rate)?;
+    if nper.value() == 0.0 {
+        return Err(String::from("pmt requires a non-zero number of periods"));
+    }
+    let pmt = if rate.value() == 0.0 {
+        -(pv + fv) / nper
+    } else {
+        let one = T::constant(1.0);
+        let growth = (one + rate).powf(nper);
+        -rate * (pv * growth + fv) / ((one + rate * when) * (growth - one))
+    };
+    finite("pmt", pmt)
+}
+
+/// `nper(rate, pmt, pv[, fv[, type]])` — how many periods the payments take.
+pub(crate) fn nper(xs: &[f32]) -> Result<f32, String> {
+    narrow(nper_of(&args(xs)))
+}
+
+fn nper_of<T: Real>(a: &[T]) -> Result<T, String> {
+    let (rate, pmt, pv, fv) = (a[0], a[1], a[2], opt(a, 3, 0.0));
+    let when = check_type("nper", opt(a, 4, 0.0))?;
+    check_rate("nper", rate)?;
+    if rate.value() == 0.0 {
+        if pmt.value() == 0.0 {
+            return Err(String::from(
+                "nper with a zero rate needs a non-zero payment",
+            ));
+        }
+        return finite("nper", -(pv + fv) / pmt);
+    }
+    let one = T::constant(1.0);
+    let level = pmt * (one + rate * when) / rate;
+    let ratio = (level - fv) / (level + pv);
+    if ratio.value() <= 0.0 {
+        return Err(String::from(
+            "nper: these payments never reach the target balance",
+        ));
+    }
+    finite("nper", ratio.ln() / (one + rate).ln())
+}
+
+/// `rate(nper, pmt, pv[, fv[, type]])` — the per-period rate, solved
+/// numerically from the annuity identity.
+pub(crate) fn rate(xs: &[f32]) -> Result<f32, String> {
+    narrow(rate_of(&args(xs)))
+}
+
+fn rate_of<T: Real>(a: &[T]) -> Result<T, String> {
+    let when = check_type("rate", opt(a, 4, 0.0))?;
+    let nper = a[0].value();
+    if nper <= 0.0 {
+        return Err(format!(
+            "rate requires a positive number of periods, got {nper}"
+        ));
+    }
+    let full = [a[0], a[1], a[2], opt(a, 3, 0.0), when];
+    let residual = |r: T, a: &[T]| tvm_residual(r, a[0], a[1], a[2], a[3], a[4]);
+    let v: Vec<f64> = full.iter().map(|a| a.value()).collect();
+    let root = solve_rate(|r| tvm_residual(r, v[0], v[1], v[2], v[3], v[4]))
+        .ok_or_else(|| String::from("rate did not converge; check the signs of pmt, pv and fv"))?;
+    Ok(implicit(root, &full, residual))
+}
+
+/// Discounted sum of `flows`, the first discounted by `first_period`
+/// periods (0 for `irr`'s immediate outlay, 1 for `npv`).
+fn discounted<T: Real>(rate: T, flows: &[T], first_period: f64) -> T {
+    let base = T::constant(1.0) + rate;
+    flows
+        .iter()
+        .enumerate()
+        .fold(T::constant(0.0), |total, (i, &cf)| {
+            total + cf / base.powf(T::constant(first_period + i as f64))
+        })
+}
+
+/// `npv(rate, cf1, cf2, …)` — net present value of cash flows at the end of
+/// periods 1, 2, …. Include a time-0 outlay by adding it outside the call:
+/// `-1000 + npv(10%, 300, 400, 500)`.
+pub(crate) fn npv(xs: &[f32]) -> Result<f32, String> {
+    narrow(npv_of(&args(xs)))
+}
+
+fn npv_of<T: Real>(a: &[T]) -> Result<T, String> {
+    check_rate("npv", a[0])?;
+    finite("npv", discounted(a[0], &a[1..], 1.0))
+}
+
+/// `irr(cf0, cf1, …)` — the rate at which the cash flows' NPV is zero, with
+/// `cf0` at time 0.
+pub(crate) fn irr(xs: &[f32]) -> Result<f32, String> {
+    narrow(irr_of(&args(xs)))
+}
+
+fn irr_of<T: Real>(flows: &[T]) -> Result<T, String> {
+    let has_in = flows.iter().any(|cf| cf.value() > 0.0);
+    let has_out = flows.iter().any(|cf| cf.value() < 0.0);
+    if !(has_in && has_out) {
+        return Err(String::from(
+            "irr needs at least one positive and one negative cash flow",
+        ));
+    }
+    let values: Vec<f64> = flows.iter().map(|v| v.value()).collect();
+    let root = solve_rate(|r| discounted(r, &values, 0.0))
+        .ok_or_else(|| String::from("irr did not converge"))?;
+    Ok(implicit(root, flows, |r, flows| discounted(r, flows, 0.0)))
+}
+
+/// `simpleint(principal, rate, time)` — simple interest earned.
+pub(crate) fn simple_interest(xs: &[f32]) -> Result<f32, String> {
+    narrow(simple_interest_of(&args(xs)))
+}
+
+fn simple_interest_of<T: Real>(a: &[T]) -> Result<T, String> {
+    finite("simpleint", a[0] * a[1] * a[2])
+}
+
+/// `compoundint(principal, rate, time[, n])` — interest earned when `rate`
+/// (per time unit) compounds `n` times per unit (default 1).
+pub(crate) fn compound_interest(xs: &[f32]) -> Result<f32, String> {
+    narrow(compound_interest_of(&args(xs)))
+}
+
+fn compound_interest_of<T: Real>(a: &[T]) -> Result<T, String> {
+    let (principal, rate, time, n) = (a[0], a[1], a[2], opt(a, 3, 1.0));
+    if n.value() <= 0.0 {
+        return Err(format!(
+            "compoundint compounding frequency must be positive, got {}",
+            n.value()
+        ));
+    }
+    check_rate("compoundint", rate / n)?;
+    let one = T::constant(1.0);
+    finite(
+        "compoundint",
+        principal * ((one + rate / n).powf(n * time) - one),
+    )
+}
+
+/// Dispatches a `Finance` catalog entry by name over any [`Real`].
+pub(crate) fn eval<T: Real>(name: &str, a: &[T]) -> Result<T, String> {
+    match name {
+        "pv" => pv_of(a),
+        "fv" => fv_of(a),
+        "pmt" => pmt_of(a),
+        "nper" => nper_of(a),
+        "rate" => rate_of(a),
+        "npv" => npv_of(a),
+        "irr" => irr_of(a),
+        "simpleint" => simple_interest_of(a),
+        "compoundint" => compound_interest_of(a),
+        _ => Err(format!("'{name}' is not a finance function")),
+    }
+}
+
+/// Finds a root of `f` on rates in (-1, ∞): Newton's method from 10% with a
+/// central-difference slope, falling back to bisection over the first
+/// sign change found on a widening grid when Newton wanders off.
+fn solve_rate(f: impl Fn(f64) -> f64) -> Option<f64> {
+    let mut r = 0.1;
+    for _ in 0..MAX_ITERATIONS {
+        let value = f(r);
+        let h = 1e-6 * r.abs().max(1e-3);
+        let slope = (f(r + h) - f(r - h)) / (2.0 * h);
+        if !value.is_finite() || !slope.is_finite() || slope == 0.0 {
+            break;
+        }
+        let next = r - value / slope;
+        if next <= -1.0 || !next.is_finite() {
+            break;
+        }
+        if (next - r).abs() < RATE_TOLERANCE {
+            return Some(next);
+        }
+        r = next;
+    }
+
+    // Bracket: step outward from just above -100% until the sign flips. A
+    // non-finite residual has no sign, so it can never end a bracket.
+    let grid = [
+        -0.99, -0.9, -0.5, -0.2, -0.1, 0.0, 0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 100.0,
+    ];
+    let (mut lo, mut hi) = grid.windows(2).map(|w| (w[0], w[1])).find(|&(a, b)| {
+        let (fa, fb) = (f(a), f(b));
+        fa.is_finite() && fb.is_finite() && fa.signum() != fb.signum()
+    })?;
+    let lo_sign = f(lo).signum();
+    for _ in 0..MAX_ITERATIONS {
+        let mid = 0.5 * (lo + hi);
+        if f(mid).signum() == lo_sign {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+        if hi - lo < RATE_TOLERANCE {
+            break;
+        }
+    }
+    Some(0.5 * (lo + hi))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f64, rel: f64) -> bool {
+        (f64::from(a) - b).abs() <= rel * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn tvm_functions_are_mutually_consistent() {
+        // 30-year mortgage, 6% a year paid monthly.
+        let payment = pmt(&[0.005, 360.0, 200_000.0]).unwrap();
+        assert!(close(payment, -1199.10, 1e-5), "pmt = {payment}");
+        assert!(close(
+            pv(&[0.005, 360.0, payment]).unwrap(),
+            200_000.0,
+            1e-5
+        ));
+        assert!(close(
+            nper(&[0.005, payment, 200_000.0]).unwrap(),
+            360.0,
+            1e-4
+        ));
+        assert!(close(
+            rate(&[360.0, payment, 200_000.0]).unwrap(),
+            0.005,
+            1e-4
+        ));
+        assert!(close(
+            fv(&[0.005, 360.0, payment, 200_000.0]).unwrap(),
+            0.0,
+            1.0
+        ));
+    }
+
+    #[test]
+    fn zero_rate_degenerates_to_arithmetic() {
+        assert_eq!(pmt(&[0.0, 10.0, 1000.0]).unwrap(), -100.0);
+        assert_eq!(fv(&[0.0, 10.0, -100.0]).unwrap(), 1000.0);
+        assert_eq!(nper(&[0.0, -100.0, 1000.0]).unwrap(), 10.0);
+    }
+
+    #[test]
+    fn irr_zeroes_npv() {
+        let r = irr(&[-1000.0, 300.0, 400.0, 500.0]).unwrap();
+        let at_irr = -1000.0 + npv(&[r, 300.0, 400.0, 500.0]).unwrap();
+        assert!(at_irr.abs() < 1e-2, "npv at irr = {at_irr}");
+    }
+
+    #[test]
+    fn non_finite_residuals_do_not_bracket_a_rate() {
+        // Undefined near -100% with no sign change anywhere: no rate.
+        let no_root = |r: f64| if r < -0.95 { f64::NAN } else { 1.0 };
+        assert_eq!(solve_rate(no_root), None);
+
+        // A flat step defeats Newton; bisection must find the real flip.
+        let step = |r: f64| {
+            if r < -0.95 {
+                f64::NAN
+            } else if r < 0.07 {
+                -1.0
+            } else {
+                1.0
+            }
+        };
+        let r = solve_rate(step).unwrap();
+        assert!((r - 0.07).abs() < 1e-6, "rate = {r}");
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert!(pv(&[-1.5, 10.0, 100.0]).is_err());
+        assert!(pmt(&[0.05, 10.0, 100.0, 0.0, 2.0]).is_err());
+        assert!(irr(&[100.0, 200.0]).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Curve fitting: least-squares parameters for an equation template.
+//!
+//! A template is an equation in `x` whose free parameters are named
+//! values — `a * exp(b*x) + c` with `a`, `b`, `c` — fitted by
+//! [`levenberg_marquardt`](crate::gradient_descent::levenberg_marquardt).
+//! The template is parsed once against the caller's [`Definitions`], so it
+//! may use user functions too; each trial only rebinds the parameters'
+//! values, in f64. The parameters are the template's own: a user function
+//! that reads one is rejected rather than left seeing a stale value.
+
+use crate::equation_analyzer::definitions::{CompiledDefinitions, Definitions};
+use crate::equation_analyzer::dual::Dual;
+use crate::equation_analyzer::errors::{EquationError, ErrorKind};
+use crate::equation_analyzer::limits::EvalLimits;
+use crate::equation_analyzer::pipeline::domain::evaluate_domain;
+use crate::equation_analyzer::pipeline::evaluator::EvalState;
+use crate::equation_analyzer::pipeline::parser::parse;
+use crate::equation_analyzer::pipeline::tokenizer::StreamingTokenizer;
+use crate::equation_analyzer::structs::token::{SpannedToken, Token};
+use crate::gradient_descent::levenberg_marquardt;
+use std::cell::RefCell;
+
+/// Upper bound on Levenberg–Marquardt steps.
+const MAX_ITERATIONS: usize = 500;
+
+/// A fitted template, from [`fit`](crate::equation_analyzer::calculator::fit).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fit {
+    /// The fitted parameters, in the order they were given.
+    pub parameters: Vec<(String, f32)>,
+    /// The fitted template at each data `x`.
+    pub fitted: Vec<f32>,
+    /// `y - fitted` at each data point.
+    pub residuals: Vec<f32>,
+    /// The coefficient of determination, `1 - SS_res / SS_tot`; NaN when
+    /// every `y` is the same.
+    pub r_squared: f32,
+}
+
+impl Fit {
+    /// The fitted value of the parameter `name`.
+    pub fn parameter(&self, name: &str) -> Option<f32> {
+        self.parameters
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, v)| v)
+    }
+}
+
+/// Parses the template with the parameters bound over `defs`, then turns
+/// each parameter's occurrences — number literals over its name, as the
+/// tokenizer emits values — into `Parameter` tokens.
+fn parse_template(
+    template: &str,
+    names: &[&str],
+    scoped: &Definitions,
+) -> Result<Vec<SpannedToken>, EquationError> {
+    let chars: Vec<char> = template.chars().collect();
+    let mut rpn = parse(StreamingTokenizer::new_with(template, Some(scoped))?)?;
+    for spanned in &mut rpn {
+        if !matches!(spanned.token, Token::Number(_)) {
+            continue;
+        }
+        let text: String = chars
+            .get(spanned.span.start..spanned.span.end)
+            .unwrap_or_default()
+            .iter()
+            .collect();
+        if let Some(i) = names.iter().position(|&name| name == text) {
+            spanned.token = Token::Parameter(i);
+        }
+    }
+    Ok(rpn)
+}
+
+/// The template at every `x`, with `values` as its parameters. Evaluation
+/// runs in f64 (a dual number with no derivative) so the Jacobian's
+/// differences aren't swamped by f32 rounding. Each `x` is one evaluation
+/// against `limits`.
+fn model(
+    rpn: &[SpannedToken],
+    ctx: &CompiledDefinitions,
+    xs: &[f32],
+    values: &[f64],
+    limits: &EvalLimits,
+) -> Result<Vec<f64>, EquationError> {
+    xs.iter()
+        .map(|&x| {
+            let mut state = EvalState::new(Some(ctx), 0)
+                .with_parameters(values)
+                .with_limits(limits);
+            evaluate_domain(rpn, &Dual::new(f64::from(x), 0.0), &mut state).map(|y| y.v)
+        })
+        .collect()
+}
+
+pub(crate) fn fit_template(
+    template: &str,
+    xs: &[f32],
+    ys: &[f32],
+    initial: &[(&str, f32)],
+    defs: &Definitions,
+    limits: &EvalLimits,
+) -> Result<Fit, EquationError> {
+    if xs.len() != ys.len() || xs.is_empty() {
+        return Err(EquationError::new(format!(
+            "fit needs the same, non-zero number of x and y values, got {} and {}",
+            xs.len(),
+            ys.len()
+        ))
+        .with_kind(ErrorKind::InvalidArgument));
+    }
+    if let Some(v) = xs.iter().chain(ys).find(|v| !v.is_finite()) {
+        return Err(
+            EquationError::new(format!("fit data must be finite, got {v}"))
+                .with_kind(ErrorKind::InvalidArgument),
+        );
+    }
+    let names: Vec<&str> = initial.iter().map(|&(n, _)| n).collect();
+    if let Some((i, name)) = names
+        .iter()
+        .enumerate()
+        .find(|&(i, n)| names[..i].contains(n))
+    {
+        return Err(EquationError::new(format!(
+            "Parameter '{name}' is listed more than once (position {})",
+            i + 1
+        ))
+        .with_kind(ErrorKind::InvalidArgument));
+    }
+
+    // Binding the parameters validates their names, and lets the template
+    // parse with them in scope.
+    let mut scoped = defs.clone();
+    for &(name, v) in initial {
+        scoped.define_value(name, v)?;
+    }
+    if let Some((name, user)) = names
+        .iter()
+        .find_map(|&n| scoped.dependents(n).first().map(|&f| (n, f)))
+    {
+        return Err(EquationError::new(format!(
+            "Parameter '{name}' is used inside '{user}'; parameters can only appear in the template"
+        ))
+        .with_kind(ErrorKind::InvalidArgument));
+    }
+    let rpn = parse_template(template, &names, &scoped)?;
+    let ctx = scoped.compile();
+
+    // Surfaces evaluation errors — an unknown name in a function body, a
+    // template undefined at the start — before the solver turns them
+    // into rejected steps.
+    let start: Vec<f64> = initial.iter().map(|&(_, v)| f64::from(v)).collect();
+    let first = model(&rpn, &ctx, xs, &start, limits)?;
+    if let Some(i) = first.iter().position(|v| !v.is_finite()) {
+        return Err(EquationError::new(format!(
+            "'{template}' is not finite at x = {} for the initial parameters",
+            xs[i]
+        ))
+        .with_kind(ErrorKind::InvalidArgument));
+    }
+
+    // A failing trial is a rejected step, unless the fit was stopped: then
+    // every later trial fails too, and the stop is the error to report.
+    let stopped = RefCell::new(None);
+    let residuals = |p: &Vec<f64>| -> Vec<f64> {
+        match model(&rpn, &ctx, xs, p, limits) {
+            Ok(m) => ys.iter().zip(m).map(|(&y, m)| f64::from(y) - m).collect(),
+            Err(e) => {
+                if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::Cancelled) {
+                    stopped.borrow_mut().get_or_insert(e);
+                }
+                vec![f64::NAN; xs.len()]
+            }
+        }
+    };
+    let best = levenberg_marquardt(&residuals, &start, MAX_ITERATIONS);
+    if let Some(e) = stopped.into_inner() {
+        return Err(e);
+    }
+
+    let fitted: Vec<f32> = model(&rpn, &ctx, xs, &best, limits)?
+        .iter()
+        .map(|&v| v as f32)
+        .collect();
+    let residuals: Vec<f32> = ys.iter().zip(&fitted).map(|(y, f)| y - f).collect();
+    let n = ys.len() as f64;
+    let mean = ys.iter().map(|&y| f64::from(y)).sum::<f64>() / n;
+    let ss_tot: f64 = ys.iter().map(|&y| (f64::from(y) - mean).powi(2)).sum();
+    let ss_res: f64 = residuals.iter().map(|&r| f64::from(r).powi(2)).sum();
+    let r_squared = if ss_tot == 0.0 {
+        f32::NAN
+    } else {
+        (1.0 - ss_res / ss_tot) as f32
+    };
+
+    Ok(Fit {
+        parameters: names
+            .iter()
+            .map(|n| n.to_string())
+            .zip(best.iter().map(|&v| v as f32))
+            .collect(),
+        fitted,
+        residuals,
+        r_squared,
+    })
+}
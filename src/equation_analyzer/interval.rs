@@ -0,0 +1,1034 @@
+//! Interval arithmetic: guaranteed enclosures of an equation's range.
+//!
+//! [`calculate_interval`](crate::equation_analyzer::calculator::calculate_interval)
+//! evaluates the RPN with every value widened to an [`Interval`]. The
+//! result encloses every real value the equation takes for `x` anywhere in
+//! the input box — the basis for root isolation (an enclosure without 0
+//! proves there is no root), plot culling, and propagating measurement
+//! uncertainty through a formula.
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::calculate_interval;
+//!
+//! // Every value of x^2 + 1 on [-1, 2] lies in [1, 5].
+//! let y = calculate_interval("x^2 + 1", [-1.0, 2.0]).unwrap();
+//! assert_eq!((y.lo, y.hi), (1.0, 5.0));
+//!
+//! // A length measured as 2 ± 0.1 gives a square's area within:
+//! let area = calculate_interval("x^2", [1.9, 2.1]).unwrap();
+//! assert!(area.contains(3.61) && area.contains(4.41));
+//! ```
+//!
+//! # Guarantees
+//!
+//! - **Sound:** endpoints are computed in f64 and rounded outward to f32,
+//!   so the enclosure holds for the exact real-number result, with
+//!   literals and constants taken at their f32 values.
+//! - **Not always tight:** each occurrence of `x` varies independently, so
+//!   `x - x` on [0, 1] gives [-1, 1], not [0, 0]. Rewriting to mention `x`
+//!   once (`x^2 - 2x` as `(x - 1)^2 - 1`) or splitting the box tightens it.
+//! - **Undefined points are skipped:** where the plain evaluator would give
+//!   NaN (`sqrt` of a negative, `ln` of 0), those inputs contribute
+//!   nothing; a function undefined on its *whole* input is an error.
+//!   Poles yield infinite endpoints.
+
+use crate::equation_analyzer::catalog::{Category, Symbol, SymbolKind};
+use crate::equation_analyzer::pipeline::domain::Domain;
+use crate::equation_analyzer::pipeline::evaluator::EvalState;
+use std::collections::VecDeque;
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::fmt;
+
+/// Relative slack applied to endpoints that f64 could not compute exactly.
+/// Far above libm's error (a few ulps, ~1e-16) and the rounding of any
+/// chain of operations one catalog call performs, far below f32 precision.
+const SLACK: f64 = 1e-12;
+
+/// Cap on residual evaluations when enclosing the roots behind `rate` and
+/// `irr`; whatever is still unresolved stays in the enclosure.
+const ROOT_BUDGET: usize = 2000;
+
+/// A closed interval `[lo, hi]` of f32 values. Endpoints may be infinite.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    /// Lower bound
+    pub lo: f32,
+    /// Upper bound
+    pub hi: f32,
+}
+
+impl Interval {
+    /// Creates the interval between `a` and `b`, in either order.
+    pub fn new(a: f32, b: f32) -> Interval {
+        Interval {
+            lo: a.min(b),
+            hi: a.max(b),
+        }
+    }
+
+    /// The degenerate interval `[v, v]`.
+    pub fn point(v: f32) -> Interval {
+        Interval { lo: v, hi: v }
+    }
+
+    /// Whether `v` lies inside (endpoints included).
+    pub fn contains(&self, v: f32) -> bool {
+        self.lo <= v && v <= self.hi
+    }
+
+    pub fn width(&self) -> f32 {
+        self.hi - self.lo
+    }
+
+    pub fn midpoint(&self) -> f32 {
+        self.lo / 2.0 + self.hi / 2.0
+    }
+
+    pub fn is_point(&self) -> bool {
+        self.lo == self.hi
+    }
+
+    /// The smallest interval containing both.
+    pub fn hull(&self, other: &Interval) -> Interval {
+        Interval {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+
+    const ENTIRE: Interval = Interval {
+        lo: f32::NEG_INFINITY,
+        hi: f32::INFINITY,
+    };
+
+    /// Rounds f64 endpoints outward; `exact` says whether they are the true
+    /// values or carry rounding error.
+    fn from_f64(lo: f64, hi: f64, exact: bool) -> Interval {
+        Interval {
+            lo: down(lo, exact),
+            hi: up(hi, exact),
+        }
+    }
+
+    fn bounds(&self) -> (f64, f64) {
+        (f64::from(self.lo), f64::from(self.hi))
+    }
+
+    /// Encloses `f`, monotone on `domain`, over the part of `self` inside
+    /// the domain.
+    fn monotone(
+        &self,
+        name: &str,
+        domain: (f64, f64),
+        increasing: bool,
+        f: impl Fn(f64) -> f64,
+    ) -> Result<Interval, String> {
+        let lo = f64::from(self.lo).max(domain.0);
+        let hi = f64::from(self.hi).min(domain.1);
+        if lo > hi {
+            return Err(undefined(name, self));
+        }
+        let ((a, fa), (b, fb)) = if increasing {
+            ((lo, f(lo)), (hi, f(hi)))
+        } else {
+            ((hi, f(hi)), (lo, f(lo)))
+        };
+        Ok(Interval {
+            lo: down(fa, trivially_exact(a, fa)),
+            hi: up(fb, trivially_exact(b, fb)),
+        })
+    }
+
+    fn abs(&self) -> Interval {
+        if self.lo >= 0.0 {
+            *self
+        } else if self.hi <= 0.0 {
+            self.neg()
+        } else {
+            Interval {
+                lo: 0.0,
+                hi: (-self.lo).max(self.hi),
+            }
+        }
+    }
+
+    fn recip(&self) -> Result<Interval, String> {
+        Interval::point(1.0).div(self)
+    }
+
+    /// `self` clipped to `[lo, hi]`, or `None` when they don't overlap.
+    fn clip(&self, lo: f32, hi: f32) -> Option<Interval> {
+        let out = Interval {
+            lo: self.lo.max(lo),
+            hi: self.hi.min(hi),
+        };
+        (out.lo <= out.hi).then_some(out)
+    }
+
+    /// Integer powers by exact repeated multiplication where possible.
+    fn powi(&self, n: i32) -> Result<Interval, String> {
+        if n < 0 {
+            return self.powi(-n)?.recip();
+        }
+        let n = n.unsigned_abs();
+        let base = if n.is_multiple_of(2) {
+            self.abs()
+        } else {
+            *self
+        };
+        let (lo, lo_exact) = powi_exact(f64::from(base.lo), n);
+        let (hi, hi_exact) = powi_exact(f64::from(base.hi), n);
+        // Keep the sign through underflow: a power of a non-negative base
+        // never dips below zero.
+        let floor = if base.lo >= 0.0 {
+            0.0
+        } else {
+            f32::NEG_INFINITY
+        };
+        Ok(Interval {
+            lo: down(lo, lo_exact).max(floor),
+            hi: up(hi, hi_exact),
+        })
+    }
+
+    /// `exp(y · ln x)` over the non-negative part of `x`.
+    fn pow_nonneg(x: &Interval, y: &Interval) -> Result<Interval, String> {
+        x.ln()?.mul(y)?.exp()
+    }
+
+    fn ln(&self) -> Result<Interval, String> {
+        self.monotone("ln", (0.0, f64::INFINITY), true, f64::ln)
+    }
+
+    fn exp(&self) -> Result<Interval, String> {
+        self.monotone("exp", (f64::NEG_INFINITY, f64::INFINITY), true, f64::exp)
+    }
+
+    /// `sin` and `cos`: peaks of +1 at `peak + 2kπ`, troughs of -1 half a
+    /// period later, monotone in between.
+    fn periodic(&self, peak: f64, f: fn(f64) -> f64) -> Interval {
+        let (lo, hi) = self.bounds();
+        if (hi - lo).is_nan() || hi - lo >= TAU {
+            return Interval::new(-1.0, 1.0);
+        }
+        let (a, b) = (f(lo), f(hi));
+        let mut out = Interval::from_f64(a.min(b), a.max(b), false);
+        if contains_phase(lo, hi, peak, TAU) {
+            out.hi = 1.0;
+        }
+        if contains_phase(lo, hi, peak + PI, TAU) {
+            out.lo = -1.0;
+        }
+        Interval {
+            lo: out.lo.max(-1.0),
+            hi: out.hi.min(1.0),
+        }
+    }
+
+    /// `tan` (poles at π/2 + kπ, increasing) and `cot` (poles at kπ,
+    /// decreasing).
+    fn between_poles(
+        &self,
+        name: &str,
+        pole: f64,
+        increasing: bool,
+        f: fn(f64) -> f64,
+    ) -> Interval {
+        let (lo, hi) = self.bounds();
+        if (hi - lo).is_nan() || hi - lo >= PI || contains_phase(lo, hi, pole, PI) {
+            return Interval::ENTIRE;
+        }
+        self.monotone(name, (lo, hi), increasing, f)
+            .unwrap_or(Interval::ENTIRE)
+    }
+
+    fn factorial_of(&self) -> Result<Interval, String> {
+        let a = self.lo.ceil().max(0.0);
+        let b = self.hi.floor();
+        if a > b {
+            return Err(String::from(
+                "Factorial is only defined for non-negative integers",
+            ));
+        }
+        let lo = crate::utilities::factorial(a as isize)?;
+        // Past 20! the plain evaluator errors, so those inputs are skipped.
+        let hi = crate::utilities::factorial(b.min(20.0) as isize)?;
+        Ok(Interval::from_f64(lo as f64, hi as f64, true))
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+/// The largest f32 not above `v` (or above `v` minus slack, when inexact).
+fn down(v: f64, exact: bool) -> f32 {
+    if v.is_nan() {
+        return f32::NEG_INFINITY;
+    }
+    let v = if exact {
+        v
+    } else {
+        v - v.abs() * SLACK - f64::MIN_POSITIVE
+    };
+    let f = v as f32;
+    if f64::from(f) > v {
+        f.next_down()
+    } else {
+        f
+    }
+}
+
+/// The smallest f32 not below `v` (or below `v` plus slack, when inexact).
+fn up(v: f64, exact: bool) -> f32 {
+    if v.is_nan() {
+        return f32::INFINITY;
+    }
+    let v = if exact {
+        v
+    } else {
+        v + v.abs() * SLACK + f64::MIN_POSITIVE
+    };
+    let f = v as f32;
+    if f64::from(f) < v {
+        f.next_up()
+    } else {
+        f
+    }
+}
+
+/// libm is exact at the trivial points — `sin(0) = 0`, `exp(0) = 1`,
+/// `ln(1) = 0` and the like — so those need no slack.
+fn trivially_exact(input: f64, output: f64) -> bool {
+    (input == 0.0 || input == 1.0) && (output == 0.0 || output == 1.0)
+}
+
+fn undefined(name: &str, on: &Interval) -> String {
+    format!("{name} is undefined on {on}")
+}
+
+/// Whether `phase + k·period` lies in `[lo, hi]` for some integer `k`,
+/// erring towards yes so the caller's bound stays sound.
+fn contains_phase(lo: f64, hi: f64, phase: f64, period: f64) -> bool {
+    let slack = 1e-9 * (1.0 + lo.abs().max(hi.abs()));
+    let k = ((lo - slack - phase) / period).ceil();
+    phase + k * period <= hi + slack
+}
+
+fn sum(a: f64, b: f64) -> (f64, bool) {
+    let s = a + b;
+    if a.is_infinite() || b.is_infinite() {
+        return (s, true);
+    }
+    // TwoSum: the rounding error of `a + b`, which is zero iff exact.
+    let bb = s - a;
+    let err = (a - (s - bb)) + (b - bb);
+    (s, err == 0.0)
+}
+
+/// `a · b` with the interval convention `0 · ∞ = 0`.
+fn product(a: f64, b: f64) -> (f64, bool) {
+    if a == 0.0 || b == 0.0 {
+        return (0.0, true);
+    }
+    let p = a * b;
+    if !p.is_finite() {
+        return (p, a.is_infinite() || b.is_infinite());
+    }
+    // The fma residual underflows along with a tiny product, so only
+    // normal results can be certified exact.
+    (p, p.abs() >= f64::MIN_POSITIVE && a.mul_add(b, -p) == 0.0)
+}
+
+fn quotient(a: f64, b: f64) -> (f64, bool) {
+    let q = a / b;
+    if !q.is_finite() || a.is_infinite() || b.is_infinite() {
+        return (q, a.is_infinite() || b.is_infinite());
+    }
+    (q, q.abs() >= f64::MIN_POSITIVE && q.mul_add(b, -a) == 0.0)
+}
+
+fn powi_exact(v: f64, mut n: u32) -> (f64, bool) {
+    let (mut acc, mut base, mut exact) = (1.0, v, true);
+    while n > 0 {
+        if n & 1 == 1 {
+            let (p, e) = product(acc, base);
+            acc = p;
+            exact &= e;
+        }
+        n >>= 1;
+        if n > 0 {
+            let (p, e) = product(base, base);
+            base = p;
+            exact &= e;
+        }
+    }
+    (acc, exact)
+}
+
+/// Applies a binary endpoint operation to the four corners and keeps the
+/// extremes.
+fn corners(a: &Interval, b: &Interval, op: fn(f64, f64) -> (f64, bool)) -> Interval {
+    let (a_lo, a_hi) = a.bounds();
+    let (b_lo, b_hi) = b.bounds();
+    let candidates = [
+        op(a_lo, b_lo),
+        op(a_lo, b_hi),
+        op(a_hi, b_lo),
+        op(a_hi, b_hi),
+    ];
+    let mut out = Interval {
+        lo: f32::INFINITY,
+        hi: f32::NEG_INFINITY,
+    };
+    for (v, exact) in candidates {
+        out.lo = out.lo.min(down(v, exact));
+        out.hi = out.hi.max(up(v, exact));
+    }
+    out
+}
+
+impl Domain for Interval {
+    fn number(v: f32) -> Self {
+        Interval::point(v)
+    }
+
+    fn to_number(&self) -> Option<f32> {
+        self.is_point().then_some(self.lo)
+    }
+
+    fn add(&self, rhs: &Self) -> Result<Self, String> {
+        let (lo, lo_exact) = sum(f64::from(self.lo), f64::from(rhs.lo));
+        let (hi, hi_exact) = sum(f64::from(self.hi), f64::from(rhs.hi));
+        Ok(Interval {
+            lo: down(lo, lo_exact),
+            hi: up(hi, hi_exact),
+        })
+    }
+
+    fn sub(&self, rhs: &Self) -> Result<Self, String> {
+        self.add(&rhs.neg())
+    }
+
+    fn mul(&self, rhs: &Self) -> Result<Self, String> {
+        Ok(corners(self, rhs, product))
+    }
+
+    fn div(&self, rhs: &Self) -> Result<Self, String> {
+        if rhs.lo > 0.0 || rhs.hi < 0.0 {
+            return Ok(corners(self, rhs, quotient));
+        }
+        if rhs.lo == 0.0 && rhs.hi == 0.0 {
+            return Err(String::from("Division by zero"));
+        }
+        if self.lo == 0.0 && self.hi == 0.0 {
+            return Ok(*self);
+        }
+        // A divisor touching 0 from one side sends 1/rhs off to infinity on
+        // that side only; straddling 0 leaves nothing to bound.
+        let recip = if rhs.lo == 0.0 {
+            let (q, exact) = quotient(1.0, f64::from(rhs.hi));
+            Interval {
+                lo: down(q, exact),
+                hi: f32::INFINITY,
+            }
+        } else if rhs.hi == 0.0 {
+            let (q, exact) = quotient(1.0, f64::from(rhs.lo));
+            Interval {
+                lo: f32::NEG_INFINITY,
+                hi: up(q, exact),
+            }
+        } else {
+            return Ok(Interval::ENTIRE);
+        };
+        self.mul(&recip)
+    }
+
+    fn rem(&self, rhs: &Self) -> Result<Self, String> {
+        let divisor = rhs.abs();
+        if divisor.hi == 0.0 {
+            return Err(String::from("Modulo by zero"));
+        }
+        // `%` truncates, so x % y == x % |y|. Within one cell of a fixed
+        // divisor it is x minus a constant; otherwise bound it by |y| and
+        // by |x| itself, with the sign of x.
+        if divisor.is_point() && self.lo.is_finite() && self.hi.is_finite() {
+            let d = f64::from(divisor.lo);
+            let (lo, hi) = self.bounds();
+            let same_sign = lo >= 0.0 || hi <= 0.0;
+            if same_sign && (lo / d).trunc() == (hi / d).trunc() {
+                // fmod is exact in IEEE arithmetic.
+                return Ok(Interval::from_f64(lo % d, hi % d, true));
+            }
+        }
+        Ok(Interval {
+            lo: if self.lo < 0.0 {
+                -divisor.hi.min(-self.lo)
+            } else {
+                0.0
+            },
+            hi: if self.hi > 0.0 {
+                divisor.hi.min(self.hi)
+            } else {
+                0.0
+            },
+        })
+    }
+
+    fn pow(&self, rhs: &Self) -> Result<Self, String> {
+        if rhs.is_point() {
+            let y = rhs.lo;
+            if y % 1.0 == 0.0 && y.abs() < i32::MAX as f32 {
+                return self.powi(y as i32);
+            }
+            if y.is_nan() {
+                return Err(undefined("^", rhs));
+            }
+            // Non-integer exponent: real only for non-negative bases.
+            let y = f64::from(y);
+            return self.monotone("^", (0.0, f64::INFINITY), y > 0.0, |x| x.powf(y));
+        }
+        // A varying exponent: the non-negative bases give exp(y·ln x); a
+        // negative base is real only at integer y, where |x^y| = |x|^y.
+        let nonneg = self.clip(0.0, f32::INFINITY);
+        let negative = self.clip(f32::NEG_INFINITY, 0.0);
+        let integer_exponent = rhs.lo.ceil() <= rhs.hi.floor();
+        let mut out: Option<Interval> = None;
+        if let Some(x) = nonneg {
+            out = Some(Interval::pow_nonneg(&x, rhs)?);
+        }
+        if let Some(x) = negative.filter(|x| x.lo < 0.0 && integer_exponent) {
+            let magnitude = Interval::pow_nonneg(&x.abs(), rhs)?;
+            let both_signs = Interval::new(-magnitude.hi, magnitude.hi);
+            out = Some(out.map_or(both_signs, |o| o.hull(&both_signs)));
+        }
+        out.ok_or_else(|| undefined("^", self))
+    }
+
+    fn neg(&self) -> Self {
+        Interval {
+            lo: -self.hi,
+            hi: -self.lo,
+        }
+    }
+
+    fn factorial(&self) -> Result<Self, String> {
+        self.factorial_of()
+    }
+
+    fn log(&self, base: f32) -> Result<Self, String> {
+        let b = f64::from(base);
+        if b.is_nan() || b <= 0.0 || b == 1.0 {
+            return Err(format!("log base must be positive and not 1, got {base}"));
+        }
+        let ln_b = b.ln();
+        self.monotone("log", (0.0, f64::INFINITY), b > 1.0, |x| x.ln() / ln_b)
+    }
+
+    fn call(sym: &'static Symbol, args: &[Self], _state: &mut EvalState) -> Result<Self, String> {
+        const ALL: (f64, f64) = (f64::NEG_INFINITY, f64::INFINITY);
+        let name = sym.name;
+        // The one nullary function.
+        let Some(x) = args.first() else {
+            return match name {
+                "rand" => Ok(Interval::new(0.0, 1.0)),
+                _ => Err(format!("No interval extension for '{name}'")),
+            };
+        };
+        match name {
+            "sin" => Ok(x.periodic(FRAC_PI_2, f64::sin)),
+            "cos" => Ok(x.periodic(0.0, f64::cos)),
+            "tan" => Ok(x.between_poles(name, FRAC_PI_2, true, f64::tan)),
+            "cot" => Ok(x.between_poles(name, 0.0, false, |v| 1.0 / v.tan())),
+            "sec" => x.periodic(0.0, f64::cos).recip(),
+            "csc" => x.periodic(FRAC_PI_2, f64::sin).recip(),
+            "asin" => x.monotone(name, (-1.0, 1.0), true, f64::asin),
+            "acos" => x.monotone(name, (-1.0, 1.0), false, f64::acos),
+            "atan" => x.monotone(name, ALL, true, f64::atan),
+            "atan2" => Ok(atan2(x, &args[1])),
+            "sinh" => x.monotone(name, ALL, true, f64::sinh),
+            "cosh" => x.abs().monotone(name, ALL, true, f64::cosh),
+            "tanh" => x.monotone(name, ALL, true, f64::tanh),
+            "asinh" => x.monotone(name, ALL, true, f64::asinh),
+            "acosh" => x.monotone(name, (1.0, f64::INFINITY), true, f64::acosh),
+            "atanh" => x.monotone(name, (-1.0, 1.0), true, f64::atanh),
+            "deg" => x.monotone(name, ALL, true, f64::to_degrees),
+            "rad" => x.monotone(name, ALL, true, f64::to_radians),
+            "abs" => Ok(x.abs()),
+            "sqrt" => {
+                let clipped = x
+                    .clip(0.0, f32::INFINITY)
+                    .ok_or_else(|| undefined(name, x))?;
+                let root = |v: f32| {
+                    let v = f64::from(v);
+                    let r = v.sqrt();
+                    (r, r.is_infinite() || r.mul_add(r, -v) == 0.0)
+                };
+                let ((lo, lo_exact), (hi, hi_exact)) = (root(clipped.lo), root(clipped.hi));
+                Ok(Interval {
+                    lo: down(lo, lo_exact),
+                    hi: up(hi, hi_exact),
+                })
+            }
+            // Rounding is monotone and exact in f32.
+            "floor" => Ok(Interval::new(x.lo.floor(), x.hi.floor())),
+            "ceil" => Ok(Interval::new(x.lo.ceil(), x.hi.ceil())),
+            "round" => Ok(Interval::new(x.lo.round(), x.hi.round())),
+            "pow" => x.pow(&args[1]),
+            "root" => root(x, &args[1]),
+            "ln" => x.ln(),
+            "exp" => x.exp(),
+            "min" | "max" => {
+                let pick = if name == "min" { f32::min } else { f32::max };
+                Ok(args[1..].iter().fold(*x, |acc, a| Interval {
+                    lo: pick(acc.lo, a.lo),
+                    hi: pick(acc.hi, a.hi),
+                }))
+            }
+            "sum" | "avg" => {
+                let mut total = *x;
+                for a in &args[1..] {
+                    total = total.add(a)?;
+                }
+                if name == "avg" {
+                    total = total.div(&Interval::point(args.len() as f32))?;
+                }
+                Ok(total)
+            }
+            // Order statistics are monotone in every argument.
+            "med" => {
+                let (lo, lo_exact) = median(args.iter().map(|a| a.lo));
+                let (hi, hi_exact) = median(args.iter().map(|a| a.hi));
+                Ok(Interval {
+                    lo: down(lo, lo_exact),
+                    hi: up(hi, hi_exact),
+                })
+            }
+            "mode" => mode(sym, args),
+            "ch" | "perm" => counting(sym, args),
+            "randint" => {
+                let (a, b) = (args[0].lo.ceil(), args[1].hi.floor());
+                if a > b || args[0].hi.floor() < a || args[1].lo.ceil() > b {
+                    return Err(String::from("randint requires integer bounds with a <= b"));
+                }
+                Ok(Interval::new(a, b))
+            }
+            "randn" => {
+                let sigma = &args[1];
+                if sigma.hi < 0.0 {
+                    return Err(format!("randn sigma must be non-negative, got {sigma}"));
+                }
+                if sigma.hi == 0.0 {
+                    Ok(*x)
+                } else {
+                    Ok(Interval::ENTIRE)
+                }
+            }
+            "choose" => Ok(args[1..].iter().fold(*x, |acc, a| acc.hull(a))),
+            _ => match sym.kind {
+                SymbolKind::UnitConversion => Err(format!(
+                    "'{name}' requires unit-aware evaluation (calculate_units)"
+                )),
+                _ if sym.category == Category::Finance => finance(sym, args),
+                _ => Err(format!("No interval extension for '{name}'")),
+            },
+        }
+    }
+}
+
+/// `atan2(y, x)`. Away from the branch cut along the negative x-axis the
+/// angle over a box is extremal at its corners; across the cut it spans
+/// the full circle.
+fn atan2(y: &Interval, x: &Interval) -> Interval {
+    // Touching the cut (or holding the origin) reaches both -π and π.
+    if x.lo <= 0.0 && y.contains(0.0) {
+        return Interval::from_f64(-PI, PI, false);
+    }
+    let (y_lo, y_hi) = y.bounds();
+    let (x_lo, x_hi) = x.bounds();
+    let angles = [
+        y_lo.atan2(x_lo),
+        y_lo.atan2(x_hi),
+        y_hi.atan2(x_lo),
+        y_hi.atan2(x_hi),
+    ];
+    let lo = angles.iter().copied().fold(f64::INFINITY, f64::min);
+    let hi = angles.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    Interval::from_f64(lo, hi, false)
+}
+
+/// `root(x, n)`: `x^(1/n)` on non-negative `x`, and `-(-x)^(1/n)` on
+/// negative `x` for odd integer `n`.
+fn root(x: &Interval, n: &Interval) -> Result<Interval, String> {
+    if n.lo == 0.0 && n.hi == 0.0 {
+        return Err(String::from("root(x, 0) is undefined"));
+    }
+    let exponent = n.recip()?;
+    let mut out: Option<Interval> = None;
+    if let Some(nonneg) = x.clip(0.0, f32::INFINITY) {
+        out = Some(nonneg.pow(&exponent)?);
+    }
+    let odd_degree = if n.is_point() {
+        n.lo % 2.0 != 0.0 && n.lo % 1.0 == 0.0
+    } else {
+        n.lo.ceil() <= n.hi.floor()
+    };
+    if let Some(negative) = x.clip(f32::NEG_INFINITY, 0.0).filter(|v| v.lo < 0.0) {
+        if odd_degree {
+            let mirrored = negative.neg().pow(&exponent)?.neg();
+            out = Some(out.map_or(mirrored, |o| o.hull(&mirrored)));
+        }
+    }
+    out.ok_or_else(|| format!("root is not a real number on {x}"))
+}
+
+/// The median of f32 values, computed exactly where f64 allows.
+fn median(values: impl Iterator<Item = f32>) -> (f64, bool) {
+    let mut v: Vec<f64> = values.map(f64::from).collect();
+    v.sort_by(|a, b| a.total_cmp(b));
+    let mid = v.len() / 2;
+    if v.len() % 2 == 1 {
+        return (v[mid], true);
+    }
+    let (s, exact) = sum(v[mid - 1], v[mid]);
+    (s / 2.0, exact)
+}
+
+/// Points where every argument is fixed evaluate the catalog function.
+fn scalar_args(args: &[Interval]) -> Option<Vec<f32>> {
+    args.iter().map(|a| a.is_point().then_some(a.lo)).collect()
+}
+
+/// `mode` is the mean of some of its arguments (or NaN), so it stays
+/// within their hull.
+fn mode(sym: &'static Symbol, args: &[Interval]) -> Result<Interval, String> {
+    if let (Some(xs), SymbolKind::Variadic { run, .. }) = (scalar_args(args), sym.kind) {
+        let v = run(&xs)?;
+        if v.is_nan() {
+            return Err(String::from("mode is undefined: every value is unique"));
+        }
+        return Ok(Interval::from_f64(f64::from(v), f64::from(v), false));
+    }
+    Ok(args[1..].iter().fold(args[0], |acc, a| acc.hull(a)))
+}
+
+/// `ch` and `perm` over the integer points of the box: enumerated when
+/// there are few, otherwise bounded by 0 and the largest value any of
+/// them can take.
+fn counting(sym: &'static Symbol, args: &[Interval]) -> Result<Interval, String> {
+    const MAX_POINTS: f32 = 4096.0;
+    let SymbolKind::Variadic { run, .. } = sym.kind else {
+        return Err(format!("'{}' is not callable", sym.name));
+    };
+    let integers = |a: &Interval| (a.lo.ceil().max(0.0), a.hi.floor());
+    let ((n_lo, n_hi), (k_lo, k_hi)) = (integers(&args[0]), integers(&args[1]));
+    if n_lo > n_hi || k_lo > k_hi {
+        return Err(format!(
+            "{} requires non-negative integer parameters",
+            sym.name
+        ));
+    }
+    let exact = |v: f32| {
+        // Integer results below 2^24 are exact in f32.
+        Interval::from_f64(f64::from(v), f64::from(v), v.abs() < 16_777_216.0)
+    };
+    if (n_hi - n_lo + 1.0) * (k_hi - k_lo + 1.0) <= MAX_POINTS {
+        let mut out: Option<Interval> = None;
+        let mut n = n_lo;
+        while n <= n_hi {
+            let mut k = k_lo;
+            while k <= k_hi {
+                let v = run(&[n, k]).map_or(Interval::point(f32::INFINITY), exact);
+                out = Some(out.map_or(v, |o| o.hull(&v)));
+                k += 1.0;
+            }
+            n += 1.0;
+        }
+        return out.ok_or_else(|| format!("{} has no defined value on this box", sym.name));
+    }
+    let k_peak = if sym.name == "ch" {
+        (n_hi / 2.0).floor().clamp(k_lo, k_hi)
+    } else {
+        k_hi.min(n_hi)
+    };
+    let peak = run(&[n_hi, k_peak]).map_or(f32::INFINITY, |v| exact(v).hi);
+    Ok(Interval::new(0.0, peak))
+}
+
+// ---- Finance ----
+
+fn opt(args: &[Interval], i: usize, default: f32) -> Interval {
+    args.get(i).copied().unwrap_or(Interval::point(default))
+}
+
+/// The rate's admissible part: every TVM function needs rate > -100%.
+fn finance_rate(name: &str, rate: &Interval) -> Result<Interval, String> {
+    rate.clip((-1.0f32).next_up(), f32::INFINITY)
+        .ok_or_else(|| format!("{name} rate must be greater than -100%, got {rate}"))
+}
+
+/// Hull of `f` over whichever payment timings (0 = end, 1 = start) the
+/// `type` argument admits.
+fn over_timings(
+    name: &str,
+    when: &Interval,
+    f: impl Fn(&Interval) -> Result<Interval, String>,
+) -> Result<Interval, String> {
+    let mut out: Option<Interval> = None;
+    for t in [0.0, 1.0] {
+        if when.contains(t) {
+            let v = f(&Interval::point(t))?;
+            out = Some(out.map_or(v, |o| o.hull(&v)));
+        }
+    }
+    out.ok_or_else(|| {
+        format!("{name} type must be 0 (end of period) or 1 (start of period), got {when}")
+    })
+}
+
+/// The annuity factors at one `(rate, nper)` corner: `((1+r)^n − 1)/r`
+/// when `accumulate`, else `(1 − (1+r)^−n)/r` — both `n` at `r = 0`.
+fn annuity_at(rate: f32, nper: f32, accumulate: bool) -> Result<Interval, String> {
+    if rate == 0.0 {
+        return Ok(Interval::point(nper));
+    }
+    let (r, one) = (Interval::point(rate), Interval::point(1.0));
+    let n = Interval::point(if accumulate { nper } else { -nper });
+    let g = one.add(&r)?.pow(&n)?;
+    let diff = if accumulate {
+        g.sub(&one)?
+    } else {
+        one.sub(&g)?
+    };
+    diff.div(&r)
+}
+
+/// An annuity factor over a box of rates and periods. Both factors are a
+/// mean of `n·(1+u)^k` over `u` between 0 and `r`, which makes them
+/// monotone — increasing in `n`; in `r`, the accumulating one increases for
+/// `n ≥ 1` and the discounting one decreases for `n ≥ 0` — so the corners
+/// bound them even across `r = 0`, where the closed form divides by zero.
+fn annuity(rate: &Interval, nper: &Interval, accumulate: bool) -> Result<Interval, String> {
+    let monotone = nper.lo >= if accumulate { 1.0 } else { 0.0 };
+    if !monotone {
+        let one = Interval::point(1.0);
+        let n = if accumulate { *nper } else { nper.neg() };
+        let g = one.add(rate)?.pow(&n)?;
+        let diff = if accumulate {
+            g.sub(&one)?
+        } else {
+            one.sub(&g)?
+        };
+        return diff.div(rate);
+    }
+    let (r_small, r_large) = if accumulate {
+        (rate.lo, rate.hi)
+    } else {
+        (rate.hi, rate.lo)
+    };
+    let lo = annuity_at(r_small, nper.lo, accumulate)?;
+    let hi = annuity_at(r_large, nper.hi, accumulate)?;
+    Ok(lo.hull(&hi))
+}
+
+/// `pmt·(1 + r·type)`: payments at the start of a period earn one more.
+fn level_payment(pmt: &Interval, rate: &Interval, when: &Interval) -> Result<Interval, String> {
+    pmt.mul(&Interval::point(1.0).add(&rate.mul(when)?)?)
+}
+
+/// `(1+r)^±n`.
+fn growth(rate: &Interval, nper: &Interval) -> Result<Interval, String> {
+    Interval::point(1.0).add(rate)?.pow(nper)
+}
+
+/// The annuity identity divided through by `(1+r)^n`:
+/// `pv + pmt·(1+r·type)·(1 − (1+r)^−n)/r + fv·(1+r)^−n`. Same roots as the
+/// scalar residual, but bounded for large rates, where the undivided form
+/// is ∞ − ∞.
+fn tvm_residual(
+    rate: &Interval,
+    nper: &Interval,
+    pmt: &Interval,
+    pv: &Interval,
+    fv: &Interval,
+    when: &Interval,
+) -> Result<Interval, String> {
+    let payments = level_payment(pmt, rate, when)?.mul(&annuity(rate, nper, false)?)?;
+    let discount = growth(rate, &nper.neg())?;
+    pv.add(&payments)?.add(&fv.mul(&discount)?)
+}
+
+/// Discounted sum of `flows`, the first `first_period` periods out.
+fn discounted(
+    rate: &Interval,
+    flows: &[Interval],
+    first_period: usize,
+) -> Result<Interval, String> {
+    let base = Interval::point(1.0).add(rate)?;
+    let mut total = Interval::point(0.0);
+    for (i, cf) in flows.iter().enumerate() {
+        let periods = Interval::point((first_period + i) as f32);
+        total = total.add(&cf.div(&base.pow(&periods)?)?)?;
+    }
+    Ok(total)
+}
+
+/// Encloses every root of `residual` on rates in (-1, ∞) by bisection,
+/// discarding pieces whose residual provably excludes 0. Breadth-first, so
+/// when the budget runs out only fine pieces are left unresolved.
+fn enclose_rate_roots(
+    name: &str,
+    residual: impl Fn(&Interval) -> Result<Interval, String>,
+) -> Result<Interval, String> {
+    let start = (-1.0f32).next_up();
+    let mut work = VecDeque::from([
+        Interval::new(start, 0.0),
+        Interval::new(0.0, 1.0),
+        Interval::new(1.0, 1e3),
+        Interval::new(1e3, f32::INFINITY),
+    ]);
+    let mut kept: Option<Interval> = None;
+    let mut evaluations = 0;
+    while let Some(piece) = work.pop_front() {
+        if evaluations >= ROOT_BUDGET {
+            kept = Some(kept.map_or(piece, |k| k.hull(&piece)));
+            continue;
+        }
+        evaluations += 1;
+        // A residual that can't be evaluated can't rule the piece out.
+        let may_hold_root = residual(&piece).map_or(true, |r| r.contains(0.0));
+        if !may_hold_root {
+            continue;
+        }
+        let mid = if piece.hi.is_infinite() {
+            (piece.lo * 4.0).max(piece.lo + 1.0)
+        } else {
+            piece.midpoint()
+        };
+        let resolved = piece.width() <= 1e-7 * piece.lo.abs().max(1.0);
+        if resolved || mid <= piece.lo || mid >= piece.hi {
+            kept = Some(kept.map_or(piece, |k| k.hull(&piece)));
+        } else {
+            work.push_back(Interval::new(piece.lo, mid));
+            work.push_back(Interval::new(mid, piece.hi));
+        }
+    }
+    kept.ok_or_else(|| format!("{name} has no solution for these arguments"))
+}
+
+/// The TVM functions composed from interval operations; `rate` and `irr`,
+/// which the scalar versions solve numerically, enclose all roots.
+fn finance(sym: &'static Symbol, args: &[Interval]) -> Result<Interval, String> {
+    let name = sym.name;
+    let zero = Interval::point(0.0);
+    match name {
+        "pv" | "fv" | "pmt" => {
+            let rate = finance_rate(name, &args[0])?;
+            let (nper, third, fourth) = (&args[1], &args[2], opt(args, 3, 0.0));
+            over_timings(name, &opt(args, 4, 0.0), |when| match name {
+                // -(pmt·(1+r·type)·(1 − (1+r)^−n)/r + fv·(1+r)^−n)
+                "pv" => Ok(tvm_residual(&rate, nper, third, &zero, &fourth, when)?.neg()),
+                // -(pv·(1+r)^n + pmt·(1+r·type)·((1+r)^n − 1)/r)
+                "fv" => {
+                    let payments =
+                        level_payment(third, &rate, when)?.mul(&annuity(&rate, nper, true)?)?;
+                    Ok(fourth.mul(&growth(&rate, nper)?)?.add(&payments)?.neg())
+                }
+                // The payment that zeroes the residual:
+                // -(pv + fv·(1+r)^−n) / ((1+r·type)·(1 − (1+r)^−n)/r)
+                _ => {
+                    if nper.lo == 0.0 && nper.hi == 0.0 {
+                        return Err(String::from("pmt requires a non-zero number of periods"));
+                    }
+                    let one = Interval::point(1.0);
+                    let per_unit =
+                        level_payment(&one, &rate, when)?.mul(&annuity(&rate, nper, false)?)?;
+                    let discount = growth(&rate, &nper.neg())?;
+                    Ok(third.add(&fourth.mul(&discount)?)?.div(&per_unit)?.neg())
+                }
+            })
+        }
+        "nper" => {
+            let rate = finance_rate(name, &args[0])?;
+            let (pmt, pv, fv) = (&args[1], &args[2], opt(args, 3, 0.0));
+            over_timings(name, &opt(args, 4, 0.0), |when| {
+                if rate.lo == 0.0 && rate.hi == 0.0 {
+                    return pv.add(&fv)?.div(pmt).map(|v| v.neg());
+                }
+                let one = Interval::point(1.0);
+                let level = pmt.mul(&one.add(&rate.mul(when)?)?)?.div(&rate)?;
+                let ratio = level.sub(&fv)?.div(&level.add(pv)?)?;
+                ratio.ln()?.div(&one.add(&rate)?.ln()?)
+            })
+        }
+        "rate" => {
+            let (nper, pmt, pv, fv) = (&args[0], &args[1], &args[2], opt(args, 3, 0.0));
+            if nper.hi <= 0.0 {
+                return Err(format!(
+                    "rate requires a positive number of periods, got {nper}"
+                ));
+            }
+            over_timings(name, &opt(args, 4, 0.0), |when| {
+                enclose_rate_roots(name, |r| tvm_residual(r, nper, pmt, pv, &fv, when))
+            })
+        }
+        "npv" => {
+            let rate = finance_rate(name, &args[0])?;
+            discounted(&rate, &args[1..], 1)
+        }
+        "irr" => {
+            if !args.iter().any(|a| a.hi > 0.0) || !args.iter().any(|a| a.lo < 0.0) {
+                return Err(String::from(
+                    "irr needs at least one positive and one negative cash flow",
+                ));
+            }
+            enclose_rate_roots(name, |r| discounted(r, args, 0))
+        }
+        "simpleint" => args[0].mul(&args[1])?.mul(&args[2]),
+        "compoundint" => {
+            let (principal, rate, time, n) = (&args[0], &args[1], &args[2], opt(args, 3, 1.0));
+            let n = n.clip(f32::MIN_POSITIVE, f32::INFINITY).ok_or_else(|| {
+                format!("compoundint compounding frequency must be positive, got {n}")
+            })?;
+            let per_period = finance_rate(name, &rate.div(&n)?)?;
+            let growth = Interval::point(1.0).add(&per_period)?.pow(&n.mul(time)?)?;
+            principal.mul(&growth.sub(&Interval::point(1.0))?)
+        }
+        _ => Err(format!("No interval extension for '{name}'")),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outward_rounding_only_widens_inexact_results() {
+        // 0.1 + 0.2 is inexact in binary; 0.5 + 0.25 is not.
+        let sum = Interval::point(0.1).add(&Interval::point(0.2)).unwrap();
+        assert!(sum.lo < sum.hi);
+        assert!(sum.contains(0.3));
+        let exact = Interval::point(0.5).add(&Interval::point(0.25)).unwrap();
+        assert_eq!(exact, Interval::point(0.75));
+    }
+
+    #[test]
+    fn division_by_an_interval_touching_zero() {
+        let one_two = Interval::new(1.0, 2.0);
+        let q = one_two.div(&Interval::new(0.0, 1.0)).unwrap();
+        assert_eq!((q.lo, q.hi), (1.0, f32::INFINITY));
+        let q = one_two.div(&Interval::new(-1.0, 1.0)).unwrap();
+        assert_eq!(q, Interval::ENTIRE);
+        assert!(one_two.div(&Interval::point(0.0)).is_err());
+    }
+
+    #[test]
+    fn phase_detection_finds_extrema() {
+        assert!(contains_phase(1.0, 2.0, FRAC_PI_2, TAU));
+        assert!(!contains_phase(2.0, 3.0, FRAC_PI_2, TAU));
+        assert!(contains_phase(7.0, 8.0, FRAC_PI_2, TAU)); // π/2 + 2π ≈ 7.85
+    }
+}
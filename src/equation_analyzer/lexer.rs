@@ -0,0 +1,324 @@
+//! A token stream for syntax highlighting.
+//!
+//! [`lex`] runs the analyzer's own tokenizer over an equation and reports
+//! what each piece of the source is, with its character span — the same
+//! resolution `calculate_with` would apply, so a name is colored as a user
+//! function exactly when it would be called as one:
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::lexer::{lex, TokenKind};
+//! use rusty_maths::equation_analyzer::Definitions;
+//!
+//! let mut defs = Definitions::new();
+//! defs.define_function("g", "x + 1").unwrap();
+//!
+//! let kinds: Vec<TokenKind> = lex("g(2x) + sin(π)", &defs).map(|l| l.kind).collect();
+//! assert_eq!(
+//!     kinds,
+//!     [
+//!         TokenKind::UserFunction,
+//!         TokenKind::Paren,
+//!         TokenKind::Number,
+//!         TokenKind::Variable,
+//!         TokenKind::Paren,
+//!         TokenKind::Operator,
+//!         TokenKind::Function,
+//!         TokenKind::Paren,
+//!         TokenKind::Constant,
+//!         TokenKind::Paren,
+//!     ]
+//! );
+//! ```
+//!
+//! Lexing never aborts: an invalid character or unknown name becomes an
+//! [`TokenKind::Error`] lexeme and the stream carries on after it. Spans
+//! never overlap and come in source order; whitespace is not reported.
+
+use crate::equation_analyzer::definitions::Definitions;
+use crate::equation_analyzer::errors::{EquationError, Span};
+use crate::equation_analyzer::pipeline::tokenizer::StreamingTokenizer;
+use crate::equation_analyzer::structs::token::{Callee, FunctionRef, SpannedToken, Token};
+use std::collections::VecDeque;
+
+/// What a [`Lexeme`] is. New kinds may be added; match with a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TokenKind {
+    /// A number literal, including the `2` of `2x`.
+    Number,
+    /// An operator: `+ - * / ^ ! % mod |>` and the `=` of `y = …`.
+    Operator,
+    /// A catalog function name (`sin`, `log_2`, `taylor`).
+    Function,
+    /// A function from the [`Definitions`].
+    UserFunction,
+    /// A catalog constant (`π`, `e`).
+    Constant,
+    /// A value from the [`Definitions`].
+    UserValue,
+    /// The variable `x`, or the `y` of `y = …`.
+    Variable,
+    /// `(` or `)`.
+    Paren,
+    /// The `,` between arguments.
+    Comma,
+    /// Source that doesn't tokenize: an invalid character, an unknown
+    /// name, a misused one.
+    Error,
+}
+
+/// One classified piece of an equation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lexeme {
+    pub kind: TokenKind,
+    /// The characters this lexeme covers.
+    pub span: Span,
+    /// Why the source didn't tokenize, for [`TokenKind::Error`] lexemes.
+    pub error: Option<EquationError>,
+}
+
+impl Lexeme {
+    fn new(kind: TokenKind, start: usize, end: usize) -> Self {
+        Lexeme {
+            kind,
+            span: Span::new(start, end),
+            error: None,
+        }
+    }
+}
+
+/// Classifies `eq` piece by piece, resolving names against `defs`. See the
+/// [module documentation](self).
+///
+/// # Examples
+/// ```
+/// use rusty_maths::equation_analyzer::lexer::{lex, TokenKind};
+/// use rusty_maths::equation_analyzer::Definitions;
+///
+/// let lexemes: Vec<_> = lex("1 $ sinq(2)", &Definitions::new()).collect();
+/// assert_eq!(lexemes[1].kind, TokenKind::Error); // `$`
+/// assert_eq!(lexemes[2].kind, TokenKind::Error); // `sinq`
+/// assert_eq!((lexemes[2].span.start, lexemes[2].span.end), (4, 8));
+/// assert_eq!(lexemes[3].kind, TokenKind::Paren); // lexing went on
+/// ```
+pub fn lex<'a>(eq: &'a str, defs: &'a Definitions) -> Lexer<'a> {
+    Lexer {
+        source: eq.chars().collect(),
+        tokens: StreamingTokenizer::new_with(eq, Some(defs)).ok(),
+        pending: VecDeque::new(),
+        covered: 0,
+    }
+}
+
+/// The iterator returned by [`lex`].
+pub struct Lexer<'a> {
+    source: Vec<char>,
+    /// `None` for the empty equation, which has nothing to report.
+    tokens: Option<StreamingTokenizer<'a>>,
+    /// Lexemes split off one token (`sin(` is a function and a paren).
+    pending: VecDeque<Lexeme>,
+    /// Every character before this one has been reported.
+    covered: usize,
+}
+
+impl Lexer<'_> {
+    fn char_at(&self, i: usize) -> Option<char> {
+        self.source.get(i).copied()
+    }
+
+    /// The first character at or after `i` that isn't whitespace.
+    fn skip_whitespace(&self, mut i: usize, end: usize) -> usize {
+        while i < end && matches!(self.char_at(i), Some(' ' | '\r' | '\t')) {
+            i += 1;
+        }
+        i
+    }
+
+    fn push(&mut self, kind: TokenKind, start: usize, end: usize) {
+        self.pending.push_back(Lexeme::new(kind, start, end));
+    }
+
+    /// A call token's span runs through its `(` when there is one (a pipe
+    /// target has none); the name and the paren are reported apart.
+    fn push_call(&mut self, kind: TokenKind, span: Span) {
+        if span.len() > 1 && self.char_at(span.end - 1) == Some('(') {
+            self.push(kind, span.start, span.end - 1);
+            self.push(TokenKind::Paren, span.end - 1, span.end);
+        } else {
+            self.push(kind, span.start, span.end);
+        }
+    }
+
+    /// `taylor(f,` is one token; split it into name, paren, function and
+    /// comma.
+    fn push_taylor(&mut self, target: FunctionRef, span: Span) {
+        let mut i = span.start;
+        while i < span.end && self.char_at(i) != Some('(') {
+            i += 1;
+        }
+        self.push(TokenKind::Function, span.start, i);
+        self.push(TokenKind::Paren, i, i + 1);
+
+        let name_start = self.skip_whitespace(i + 1, span.end);
+        let mut name_end = name_start;
+        while name_end < span.end
+            && self
+                .char_at(name_end)
+                .is_some_and(|c| c.is_alphanumeric() || c == '.')
+        {
+            name_end += 1;
+        }
+        let kind = match target {
+            FunctionRef::Catalog(_) => TokenKind::Function,
+            FunctionRef::User(_) => TokenKind::UserFunction,
+        };
+        self.push(kind, name_start, name_end);
+        self.push(TokenKind::Comma, span.end - 1, span.end);
+    }
+
+    fn token(&mut self, spanned: SpannedToken) {
+        let span = spanned.span;
+        // The tail of a multi-token expansion, already reported.
+        if span.start < self.covered {
+            return;
+        }
+
+        // A `2x` coefficient expands into several tokens sharing its span:
+        // report the digits and the `x`, once.
+        if span.len() > 1
+            && self.char_at(span.start).is_some_and(|c| c.is_ascii_digit())
+            && self.char_at(span.end - 1) == Some('x')
+        {
+            self.push(TokenKind::Number, span.start, span.end - 1);
+            self.push(TokenKind::Variable, span.end - 1, span.end);
+            self.covered = span.end;
+            return;
+        }
+
+        let kind = match spanned.token {
+            Token::End => return,
+            // User values tokenize as number literals over the name.
+            Token::Number(_) if self.char_at(span.start).is_some_and(char::is_alphabetic) => {
+                TokenKind::UserValue
+            }
+            Token::Number(_) => TokenKind::Number,
+            Token::X | Token::Y => TokenKind::Variable,
+            Token::Constant(_) => TokenKind::Constant,
+            Token::OpenParen | Token::CloseParen => TokenKind::Paren,
+            Token::Comma => TokenKind::Comma,
+            Token::Equal
+            | Token::Factorial
+            | Token::Star
+            | Token::Slash
+            | Token::Plus
+            | Token::Minus
+            | Token::UnaryMinus
+            | Token::Power
+            | Token::Modulo
+            | Token::Percent
+            | Token::Pipe => TokenKind::Operator,
+            Token::Log { .. } | Token::Call(Callee::Catalog(_)) => {
+                self.push_call(TokenKind::Function, span);
+                self.covered = span.end;
+                return;
+            }
+            Token::Call(Callee::User(_)) => {
+                self.push_call(TokenKind::UserFunction, span);
+                self.covered = span.end;
+                return;
+            }
+            Token::Call(Callee::Taylor(target)) => {
+                self.push_taylor(target, span);
+                self.covered = span.end;
+                return;
+            }
+            // Units are off, frame markers come from the parser, and fit
+            // parameters are substituted after it.
+            Token::Unit(_)
+            | Token::UnitProduct
+            | Token::CallStart(_)
+            | Token::EndCall(_)
+            | Token::Parameter(_) => return,
+        };
+        self.push(kind, span.start, span.end);
+        self.covered = span.end;
+    }
+
+    /// An error covers everything the tokenizer consumed for it, which may
+    /// reach either side of the error's own span (`taylor(nope` points at
+    /// `nope`; `foo_` points at `_`).
+    fn error(&mut self, error: EquationError, consumed: usize) {
+        let end = error.span.map_or(consumed, |s| s.end.max(consumed));
+        let start = self.skip_whitespace(self.covered, end);
+        if start < end {
+            self.pending.push_back(Lexeme {
+                error: Some(error),
+                ..Lexeme::new(TokenKind::Error, start, end)
+            });
+        }
+        self.covered = self.covered.max(end);
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Lexeme;
+
+    fn next(&mut self) -> Option<Lexeme> {
+        loop {
+            if let Some(lexeme) = self.pending.pop_front() {
+                return Some(lexeme);
+            }
+            let tokens = self.tokens.as_mut()?;
+            match tokens.next()? {
+                Ok(spanned) => self.token(spanned),
+                Err(error) => {
+                    let consumed = tokens.consumed();
+                    self.error(error, consumed);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(eq: &str) -> Vec<(TokenKind, usize, usize)> {
+        lex(eq, &Definitions::new())
+            .map(|l| (l.kind, l.span.start, l.span.end))
+            .collect()
+    }
+
+    #[test]
+    fn coefficient_expansions_are_reported_once() {
+        use TokenKind::*;
+        assert_eq!(
+            spans("1/2x"),
+            [
+                (Number, 0, 1),
+                (Operator, 1, 2),
+                (Number, 2, 3),
+                (Variable, 3, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn taylor_is_split_into_its_parts() {
+        use TokenKind::*;
+        assert_eq!(
+            spans("taylor( sin, 0, 3)"),
+            [
+                (Function, 0, 6),
+                (Paren, 6, 7),
+                (Function, 8, 11),
+                (Comma, 11, 12),
+                (Number, 13, 14),
+                (Comma, 14, 15),
+                (Number, 16, 17),
+                (Paren, 17, 18),
+            ]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Resource limits for evaluating untrusted equations.
+//!
+//! The call-depth limit stops runaway recursion from overflowing the
+//! stack, but an equation can still be expensive without being deep: each
+//! definition in a chain like `f(x) = g(x) + g(x)` doubles the work, a
+//! variadic call can take a million arguments, and a plot range can ask
+//! for billions of points. [`EvalLimits`] bounds all of these, and a
+//! [`CancelToken`] lets another thread stop an evaluation in flight.
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::calculate_with_options;
+//! use rusty_maths::equation_analyzer::{Definitions, EvalLimits, EvalOptions};
+//!
+//! // h30(x) makes 2^30 calls.
+//! let mut defs = Definitions::new();
+//! defs.define_function("h0", "x").unwrap();
+//! for k in 1..=30 {
+//!     let body = format!("h{0}(x) + h{0}(x)", k - 1);
+//!     defs.define_function(&format!("h{k}"), &body).unwrap();
+//! }
+//!
+//! let opts = EvalOptions::new().with_limits(EvalLimits::new().with_max_steps(10_000));
+//! let err = calculate_with_options("h30(1)", &defs, &opts).unwrap_err();
+//! assert_eq!(err.message, "Step limit (10000) exceeded");
+//! ```
+//!
+//! Limits apply through every `*_with_options` entry point — calculating,
+//! plotting, tracing, units, derivatives, Taylor series, intervals and
+//! fits — and to equations compiled with them. The plain entry points are
+//! unlimited.
+
+use crate::equation_analyzer::errors::{EquationError, ErrorKind};
+use crate::equation_analyzer::utils::sample_count;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Steps between deadline and cancellation checks inside one evaluation.
+const CHECK_EVERY: u64 = 1024;
+
+/// A flag for stopping evaluations from another thread. Clones share the
+/// flag: cancel any one of them and every evaluation watching it stops
+/// with an "Evaluation cancelled" error.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::plot_with_options;
+/// use rusty_maths::equation_analyzer::{CancelToken, Definitions, EvalLimits, EvalOptions};
+///
+/// let token = CancelToken::new();
+/// let opts = EvalOptions::new().with_limits(EvalLimits::new().with_cancel(token.clone()));
+/// token.cancel();
+/// let err = plot_with_options("x", 0.0, 1.0, 0.1, &Definitions::new(), &opts).unwrap_err();
+/// assert_eq!(err.message, "Evaluation cancelled");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops every evaluation watching this token (or a clone of it).
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`cancel`](Self::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Tokens are equal when they share a flag.
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Bounds on one `calculate`/`plot` call; unlimited by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvalLimits {
+    max_steps: Option<u64>,
+    max_points: Option<usize>,
+    max_input_len: Option<usize>,
+    deadline: Option<Instant>,
+    cancel: Option<CancelToken>,
+}
+
+impl EvalLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the instructions one evaluation may run — per plot sample, not
+    /// per plot. Instructions inside user-function calls count. An
+    /// instruction is an RPN token to the interpreter and an op to
+    /// compiled code, so plots and compiled equations, which fold and
+    /// inline, fit more into the same budget than `calculate` does.
+    pub fn with_max_steps(mut self, steps: u64) -> Self {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    /// Caps the samples a plot may have. Checked before any are computed.
+    pub fn with_max_points(mut self, points: usize) -> Self {
+        self.max_points = Some(points);
+        self
+    }
+
+    /// Caps the equation's length, in characters.
+    pub fn with_max_input_len(mut self, chars: usize) -> Self {
+        self.max_input_len = Some(chars);
+        self
+    }
+
+    /// Fails evaluations still running at `deadline`.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Fails evaluations still running `timeout` from now.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    /// Fails evaluations once `token` is cancelled.
+    pub fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// The configured step limit, if any.
+    pub fn max_steps(&self) -> Option<u64> {
+        self.max_steps
+    }
+
+    /// The configured point limit, if any.
+    pub fn max_points(&self) -> Option<usize> {
+        self.max_points
+    }
+
+    /// The configured input-length limit, if any.
+    pub fn max_input_len(&self) -> Option<usize> {
+        self.max_input_len
+    }
+
+    /// The configured deadline, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Whether evaluation itself needs a [`Budget`]: the other limits are
+    /// checked once, up front.
+    pub(crate) fn bounds_evaluation(&self) -> bool {
+        self.max_steps.is_some() || self.deadline.is_some() || self.cancel.is_some()
+    }
+
+    pub(crate) fn check_input(&self, eq: &str) -> Result<(), EquationError> {
+        match self.max_input_len {
+            Some(max) if eq.chars().count() > max => {
+                let len = eq.chars().count();
+                Err(EquationError::new(format!(
+                    "Equation is {len} characters long; the limit is {max}"
+                ))
+                .with_kind(ErrorKind::InputTooLong { len, max }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the number of samples `x_min..=x_max` by `step_size` would
+    /// have — before `get_x_values` allocates them. The samplers generate
+    /// exactly this many.
+    pub(crate) fn check_points(
+        &self,
+        x_min: f32,
+        x_max: f32,
+        step_size: f32,
+    ) -> Result<(), EquationError> {
+        let Some(max) = self.max_points else {
+            return Ok(());
+        };
+        let points = sample_count(x_min, x_max, step_size);
+        if points > max as f64 {
+            let points = points as u64;
+            return Err(EquationError::new(format!(
+                "Plot of {points} points exceeds the limit of {max}"
+            ))
+            .with_kind(ErrorKind::TooManyPoints { points, max }));
+        }
+        Ok(())
+    }
+
+    /// Fails once the deadline has passed or the token is cancelled.
+    pub(crate) fn check_interrupt(&self) -> Result<(), EquationError> {
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(EquationError::new("Evaluation cancelled").with_kind(ErrorKind::Cancelled));
+        }
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(EquationError::new("Evaluation timed out").with_kind(ErrorKind::TimedOut));
+        }
+        Ok(())
+    }
+}
+
+/// One evaluation's running count against its [`EvalLimits`].
+#[derive(Debug)]
+pub(crate) struct Budget<'a> {
+    limits: &'a EvalLimits,
+    steps: u64,
+    unchecked: u64,
+}
+
+impl<'a> Budget<'a> {
+    pub(crate) fn new(limits: &'a EvalLimits) -> Self {
+        Budget {
+            limits,
+            steps: 0,
+            // Check on the first charge: a cancelled token stops even a
+            // one-step evaluation.
+            unchecked: CHECK_EVERY,
+        }
+    }
+
+    /// Records `steps` more instructions.
+    pub(crate) fn charge(&mut self, steps: u64) -> Result<(), EquationError> {
+        self.steps = self.steps.saturating_add(steps);
+        if let Some(max) = self.limits.max_steps.filter(|&max| self.steps > max) {
+            return Err(EquationError::new(format!("Step limit ({max}) exceeded"))
+                .with_kind(ErrorKind::StepLimit { max }));
+        }
+        self.unchecked += steps;
+        if self.unchecked >= CHECK_EVERY {
+            self.unchecked = 0;
+            self.limits.check_interrupt()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_counts_steps() {
+        let limits = EvalLimits::new().with_max_steps(5);
+        let mut budget = Budget::new(&limits);
+        assert!(budget.charge(3).is_ok());
+        assert!(budget.charge(2).is_ok());
+        assert!(budget.charge(1).is_err());
+    }
+
+    #[test]
+    fn point_count_is_checked_before_sampling() {
+        let limits = EvalLimits::new().with_max_points(11);
+        assert!(limits.check_points(0.0, 1.0, 0.1).is_ok());
+        assert!(limits.check_points(0.0, 1.0, 0.05).is_err());
+        assert!(limits.check_points(1.0, 0.0, 0.05).is_ok());
+        assert!(limits.check_points(0.0, f32::INFINITY, 1.0).is_err());
+    }
+
+    #[test]
+    fn cancel_tokens_share_their_flag() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert_eq!(token, clone);
+        assert_ne!(token, CancelToken::new());
+    }
+}
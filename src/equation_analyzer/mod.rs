@@ -15,11 +15,23 @@
 // Public API
 pub mod calculator;
 pub mod catalog;
+pub mod compiled;
+pub mod completion;
 pub mod definitions;
+pub mod diagnostics;
 pub mod errors;
+pub mod fit;
+pub mod interval;
+pub mod lexer;
+pub mod limits;
+pub mod options;
+pub mod plot;
+pub mod taylor;
+pub mod trace;
+pub mod units;
 
-/// The pipeline's error type and its character-span companion, re-exported
-/// for convenience.
+/// The pipeline's error type, its character-span companion, and the kinds
+/// and fixes it carries, re-exported for convenience.
 ///
 /// ```
 /// use rusty_maths::equation_analyzer::{calculator, EquationError};
@@ -27,7 +39,29 @@ pub mod errors;
 /// let err: EquationError = calculator::calculate("2 + foo(3)").unwrap_err();
 /// assert!(err.span.is_some());
 /// ```
-pub use errors::{EquationError, Span};
+pub use errors::{Edit, EquationError, ErrorKind, Span};
+
+/// The syntax-highlighting token stream, re-exported for convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{lex, Definitions, TokenKind};
+///
+/// let defs = Definitions::new();
+/// assert!(lex("sin(x) + $", &defs).any(|l| l.kind == TokenKind::Error));
+/// ```
+pub use lexer::{lex, Lexeme, Lexer, TokenKind};
+
+/// Completion and signature help for editors, re-exported for
+/// convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{complete, signature_help, Definitions};
+///
+/// let defs = Definitions::new();
+/// assert_eq!(complete("sq", 2, &defs)[0].label, "sqrt");
+/// assert_eq!(signature_help("sqrt(", 5, &defs).unwrap().function, "sqrt");
+/// ```
+pub use completion::{complete, signature_help, Completion, SignatureHelp};
 
 /// User definitions — named values and single-parameter functions —
 /// re-exported for convenience.
@@ -41,6 +75,105 @@ pub use errors::{EquationError, Span};
 /// ```
 pub use definitions::{Definition, Definitions};
 
+/// Per-evaluation options (such as the random seed), re-exported for
+/// convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{calculator, Definitions, EvalOptions};
+///
+/// let opts = EvalOptions::new().with_seed(3);
+/// let r = calculator::calculate_with_options("rand()", &Definitions::new(), &opts).unwrap();
+/// assert!((0.0..1.0).contains(&r));
+/// ```
+pub use options::EvalOptions;
+
+/// Resource limits and cancellation for [`EvalOptions`], re-exported for
+/// convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::calculate_with_options;
+/// use rusty_maths::equation_analyzer::{Definitions, EvalLimits, EvalOptions};
+///
+/// let opts = EvalOptions::new().with_limits(EvalLimits::new().with_max_input_len(8));
+/// assert!(calculate_with_options("1 + 2 + 3 + 4", &Definitions::new(), &opts).is_err());
+/// ```
+pub use limits::{CancelToken, EvalLimits};
+
+/// A compiled equation from [`calculator::compile`], re-exported for
+/// convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{calculator, CompiledEquation};
+///
+/// let f: CompiledEquation = calculator::compile("2x + 1").unwrap();
+/// assert_eq!(f.eval(3.0).unwrap(), 7.0);
+/// ```
+pub use compiled::CompiledEquation;
+
+/// A curve fit from [`calculator::fit`], re-exported for convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{calculator, Fit};
+///
+/// let fit: Fit = calculator::fit("m * x + k", &[0.0, 1.0, 2.0], &[1.0, 3.0, 5.0], &[("m", 0.0), ("k", 0.0)]).unwrap();
+/// assert!((fit.parameter("m").unwrap() - 2.0).abs() < 1e-4);
+/// ```
+pub use fit::Fit;
+
+/// A lazy plot from [`calculator::plot_iter`], re-exported for
+/// convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{calculator, PlotIter};
+///
+/// let points: PlotIter = calculator::plot_iter("2x", 0.0, 2.0, 1.0).unwrap();
+/// assert_eq!(points.filter_map(Result::ok).count(), 3);
+/// ```
+pub use plot::PlotIter;
+
+/// A gap-tolerant plot from [`calculator::plot_tolerant`], re-exported for
+/// convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{calculator, TolerantPlot};
+///
+/// let plot: TolerantPlot = calculator::plot_tolerant("1 / x", -1.0, 1.0, 1.0).unwrap();
+/// assert!(plot.is_complete()); // 1/0 is infinite, not an error
+/// ```
+pub use plot::TolerantPlot;
+
+/// An enclosure returned by [`calculator::calculate_interval`], re-exported
+/// for convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{calculator, Interval};
+///
+/// let y: Interval = calculator::calculate_interval("2x + 1", [0.0, 1.0]).unwrap();
+/// assert_eq!((y.lo, y.hi), (1.0, 3.0));
+/// ```
+pub use interval::Interval;
+
+/// A unit-carrying result from [`calculator::calculate_units`], re-exported
+/// for convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{calculator, Definitions, Quantity};
+///
+/// let q: Quantity = calculator::calculate_units("2 km + 500 m", &Definitions::new()).unwrap();
+/// assert_eq!(q.to_string(), "2.5 km");
+/// ```
+pub use units::Quantity;
+
+/// A series from [`calculator::taylor`], re-exported for convenience.
+///
+/// ```
+/// use rusty_maths::equation_analyzer::{calculator, TaylorSeries};
+///
+/// let s: TaylorSeries = calculator::taylor("exp(x)", 0.0, 2).unwrap();
+/// assert_eq!(s.to_string(), "1 + x + 0.5x^2");
+/// ```
+pub use taylor::TaylorSeries;
+
 /// The plot-point type returned by [`calculator::plot`], re-exported so
 /// downstream crates can name it.
 ///
@@ -53,6 +186,8 @@ pub use definitions::{Definition, Definitions};
 pub use utils::Point;
 
 // Internal modules (not part of public API)
+pub(crate) mod dual;
+pub(crate) mod finance;
 pub(crate) mod pipeline;
 pub(crate) mod structs;
 mod tests;
@@ -0,0 +1,132 @@
+//! Per-evaluation options for the equation analyzer — the knobs that sit
+//! alongside an equation and its [`Definitions`](crate::equation_analyzer::Definitions)
+//! but aren't part of either.
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::calculate_with_options;
+//! use rusty_maths::equation_analyzer::{Definitions, EvalOptions};
+//!
+//! let opts = EvalOptions::new().with_seed(7);
+//! let defs = Definitions::new();
+//! let a = calculate_with_options("rand()", &defs, &opts).unwrap();
+//! let b = calculate_with_options("rand()", &defs, &opts).unwrap();
+//! assert_eq!(a, b); // same seed, same draw
+//! ```
+
+use crate::equation_analyzer::limits::EvalLimits;
+
+/// Options for one `calculate`/`plot` call. The default is what the plain
+/// entry points use: an unseeded (non-reproducible) random-number generator,
+/// no [`EvalLimits`], and no memoization.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvalOptions {
+    seed: Option<u64>,
+    limits: EvalLimits,
+    memoize: bool,
+}
+
+impl EvalOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seeds the generator behind `rand`, `randint`, `randn` and `choose`,
+    /// making their draws reproducible.
+    ///
+    /// Plotting seeds each sample independently from this seed and the
+    /// sample's `x` (see [`seed_for_x`]), so a plot is reproducible no
+    /// matter how Rayon schedules the points — and a given `x` draws the
+    /// same noise in any range or step that includes it.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// The configured seed, if any.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Bounds the work the evaluation may do; see [`EvalLimits`].
+    pub fn with_limits(mut self, limits: EvalLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The configured limits.
+    pub fn limits(&self) -> &EvalLimits {
+        &self.limits
+    }
+
+    /// Remembers the result of each user-function call for the rest of the
+    /// evaluation, keyed by the function and its argument's bits, so a
+    /// recursive definition or one called at the same argument by many
+    /// plot points runs once per argument. Functions that draw random
+    /// numbers are never memoized.
+    ///
+    /// A remembered call costs no steps against the [`EvalLimits`]. A
+    /// [`PlotIter`](crate::equation_analyzer::plot::PlotIter) remembers
+    /// across its whole range, and a compiled equation across all of its
+    /// evaluations. Evaluations in other domains — units, derivatives,
+    /// Taylor series, intervals, fits — don't memoize.
+    ///
+    /// # Examples
+    /// ```
+    /// use rusty_maths::equation_analyzer::calculator::calculate_with_options;
+    /// use rusty_maths::equation_analyzer::{Definitions, EvalLimits, EvalOptions};
+    ///
+    /// let mut defs = Definitions::new();
+    /// defs.define_function("g", "sin(x)^2 + cos(x)^2 + tan(x)^2").unwrap();
+    /// let eq = "g(1) + g(1) + g(1) + g(1)";
+    ///
+    /// let opts = EvalOptions::new().with_limits(EvalLimits::new().with_max_steps(50));
+    /// assert!(calculate_with_options(eq, &defs, &opts).is_err());
+    /// let memoized = opts.with_memoization(true);
+    /// assert!(calculate_with_options(eq, &defs, &memoized).is_ok());
+    /// ```
+    pub fn with_memoization(mut self, on: bool) -> Self {
+        self.memoize = on;
+        self
+    }
+
+    /// Whether user-function calls are memoized.
+    pub fn memoizes(&self) -> bool {
+        self.memoize
+    }
+
+    /// The seed to evaluate with: the configured one, or a fresh random
+    /// seed when none is set.
+    pub(crate) fn seed_or_random(&self) -> u64 {
+        self.seed.unwrap_or_else(rand::random)
+    }
+}
+
+/// The per-sample seed a plot uses at `x`, derived from the plot's seed.
+///
+/// SplitMix64's finalizer over the seed and `x`'s bit pattern: neighbouring
+/// samples get unrelated streams, and the result depends only on
+/// `(seed, x)` — never on evaluation order or thread.
+pub fn seed_for_x(seed: u64, x: f32) -> u64 {
+    let mut z = seed ^ u64::from(x.to_bits()).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_for_x_is_deterministic_and_spreads() {
+        assert_eq!(seed_for_x(1, 0.5), seed_for_x(1, 0.5));
+        assert_ne!(seed_for_x(1, 0.5), seed_for_x(1, 0.25));
+        assert_ne!(seed_for_x(1, 0.5), seed_for_x(2, 0.5));
+    }
+
+    #[test]
+    fn default_has_no_seed() {
+        assert_eq!(EvalOptions::new().seed(), None);
+        assert_eq!(EvalOptions::new().with_seed(9).seed(), Some(9));
+    }
+}
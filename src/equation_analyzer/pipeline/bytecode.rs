@@ -0,0 +1,1381 @@
+//! Bytecode for hot evaluation loops.
+//!
+//! The RPN evaluator re-dispatches every token on every evaluation. A plot
+//! or a fit evaluates the same RPN thousands of times, so
+//! [`Bytecode::compile`] does that work once:
+//!
+//! - catalog symbols resolve to their function pointers, and frame, arity
+//!   and operand-count checks happen at compile time;
+//! - constant subtrees fold (`sin(π/4) * 2` becomes a single push), as do
+//!   calls whose arguments are all known;
+//! - non-recursive user functions are inlined, their argument kept in a
+//!   local slot; recursive ones compile once into a function table;
+//! - the value stack is sized up front.
+//!
+//! Compilation is an abstract evaluation of the RPN. Stack heights never
+//! depend on `x`, so every error the interpreter raises for the *shape* of
+//! an equation becomes a `Fail` instruction at the point the interpreter
+//! would reach it. Results, messages, spans and `in_function` tags match
+//! [`evaluate_with`](super::evaluator::evaluate_with) exactly.
+
+use crate::equation_analyzer::catalog::{Symbol, SymbolKind};
+use crate::equation_analyzer::definitions::{CompiledDefinitions, Definitions};
+use crate::equation_analyzer::errors::{EquationError, ErrorKind, Span};
+use crate::equation_analyzer::limits::EvalLimits;
+use crate::equation_analyzer::options::seed_for_x;
+use crate::equation_analyzer::pipeline::evaluator::{EvalState, MAX_CALL_DEPTH};
+use crate::equation_analyzer::structs::token::{Callee, FunctionRef, SpannedToken, Token};
+use crate::equation_analyzer::taylor;
+use crate::utilities::factorial;
+use rand::rngs::StdRng;
+
+/// Inlining stops once a program reaches this many instructions; further
+/// user calls compile to `Call`s into the function table instead.
+const INLINE_BUDGET: usize = 4096;
+
+/// Inlining also stops once this many body tokens have been inlined in
+/// total, folded or not. Known arguments fold through inlined calls, so
+/// without it `h(2)` with `h(x) = g(x) + g(x)`, `g(x) = f(x) + f(x)`, …
+/// would do exponential work at compile time — work that belongs at run
+/// time, under the evaluation's limits.
+const INLINE_WORK: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    /// `+` with a `%`-tagged right operand: `200 + 10%` = 220.
+    AddPercent,
+    SubPercent,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+impl BinOp {
+    fn of(token: Token, percent: bool) -> Option<BinOp> {
+        Some(match token {
+            Token::Plus if percent => BinOp::AddPercent,
+            Token::Plus => BinOp::Add,
+            Token::Minus if percent => BinOp::SubPercent,
+            Token::Minus => BinOp::Sub,
+            Token::Star => BinOp::Mul,
+            Token::Slash => BinOp::Div,
+            Token::Modulo => BinOp::Rem,
+            Token::Power => BinOp::Pow,
+            _ => return None,
+        })
+    }
+
+    fn apply(self, l: f32, r: f32) -> f32 {
+        match self {
+            BinOp::Add => l + r,
+            BinOp::Sub => l - r,
+            BinOp::AddPercent => l + l * r,
+            BinOp::SubPercent => l - l * r,
+            BinOp::Mul => l * r,
+            BinOp::Div => l / r,
+            BinOp::Rem => l % r,
+            BinOp::Pow => l.powf(r),
+        }
+    }
+}
+
+/// Where a body reads `x` from: a local slot, or — for a function inlined
+/// with a constant argument — the constant itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Var {
+    Slot(usize),
+    Known(f32),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Push(f32),
+    Load(usize),
+    Store(usize),
+    Unary(fn(f32) -> f32),
+    UnaryChecked(fn(f32) -> Result<f32, String>),
+    Variadic {
+        run: fn(&[f32]) -> Result<f32, String>,
+        argc: usize,
+    },
+    Random {
+        run: fn(&mut StdRng, &[f32]) -> Result<f32, String>,
+        argc: usize,
+    },
+    Binary(BinOp),
+    /// A binary operator whose right operand folded to a constant.
+    BinaryK(BinOp, f32),
+    Neg,
+    Factorial,
+    Percent,
+    Log(f32),
+    /// Runs `functions[function]` on the top of the stack. `depth` is the
+    /// call site's depth relative to the running program.
+    Call {
+        function: usize,
+        depth: u8,
+    },
+    /// The depth check of a call inlined into a function-table program,
+    /// whose own depth is only known at run time.
+    CheckDepth {
+        function: usize,
+        depth: u8,
+    },
+    /// `taylor(f, a, n)` with `a` or `n` only known at run time.
+    Taylor {
+        f: FunctionRef,
+        x: Var,
+        depth: u8,
+    },
+    /// `taylor(f, a, n)` with the coefficients computed at compile time.
+    Horner {
+        series: usize,
+        a: f32,
+        x: Var,
+    },
+    /// Raises `errors[i]`: the interpreter fails here whatever `x` is.
+    Fail(usize),
+}
+
+/// One straight-line instruction sequence: the equation itself, or a
+/// function-table entry.
+#[derive(Debug, Clone, Default)]
+struct Program {
+    ops: Vec<Op>,
+    /// Per instruction, the span its run-time errors point at.
+    spans: Vec<Span>,
+    /// Per instruction, the user function it was inlined from — errors
+    /// raised there are tagged with that function's name.
+    owners: Vec<Option<usize>>,
+    /// The most values on the stack at once.
+    max_stack: usize,
+    /// Local slots: `x`, then one per level of inlining.
+    locals: usize,
+}
+
+impl Program {
+    fn raise(&self, i: usize, error: EquationError, names: &[String]) -> EquationError {
+        match self.owners.get(i).copied().flatten() {
+            Some(f) => error.for_function(names.get(f).map_or("?", String::as_str)),
+            None => error,
+        }
+    }
+}
+
+/// An equation compiled against one set of definitions. Self-contained:
+/// later changes to the `Definitions` don't reach it.
+#[derive(Debug, Clone)]
+pub(crate) struct Bytecode {
+    main: Program,
+    /// Per user definition, the compiled body of a function that is called
+    /// rather than inlined.
+    functions: Vec<Option<Program>>,
+    names: Vec<String>,
+    /// Taylor coefficients computed at compile time.
+    series: Vec<Vec<f64>>,
+    errors: Vec<EquationError>,
+    /// The definitions, kept only when a `Taylor` instruction must expand
+    /// a user function at run time.
+    defs: Option<Definitions>,
+    random: bool,
+}
+
+/// Reusable evaluation buffers, so a loop over many `x` allocates once.
+pub(crate) struct Scratch {
+    stack: Vec<f32>,
+    locals: Vec<f32>,
+}
+
+/// A compile-time stack entry: the value when it is known.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    known: Option<f32>,
+    percent: bool,
+}
+
+/// Stops compilation after a `Fail`: nothing past it can run.
+struct Halted;
+
+/// One program under construction.
+struct Emitter {
+    program: Program,
+    stack: Vec<Slot>,
+    /// How many `stack` entries, from the bottom, are on the run-time
+    /// stack. The rest are known values not pushed yet.
+    pushed: usize,
+    /// Whether depths are absolute (the main program) or relative to a
+    /// function-table call.
+    absolute: bool,
+}
+
+impl Emitter {
+    fn new(absolute: bool) -> Self {
+        Emitter {
+            program: Program {
+                locals: 1,
+                ..Program::default()
+            },
+            stack: Vec::new(),
+            pushed: 0,
+            absolute,
+        }
+    }
+
+    fn emit(&mut self, op: Op, span: Span, owner: Option<usize>) {
+        self.program.ops.push(op);
+        self.program.spans.push(span);
+        self.program.owners.push(owner);
+    }
+
+    /// Pushes every known value still pending, so the run-time stack holds
+    /// the whole compile-time stack.
+    fn flush(&mut self, span: Span, owner: Option<usize>) {
+        for i in self.pushed..self.stack.len() {
+            let v = self.stack[i].known.unwrap_or(f32::NAN);
+            self.emit(Op::Push(v), span, owner);
+        }
+        self.pushed = self.stack.len();
+        self.program.max_stack = self.program.max_stack.max(self.pushed);
+    }
+
+    /// The top `n` entries, if all are known and not yet pushed.
+    fn pending(&self, n: usize) -> Option<Vec<f32>> {
+        let at = self.stack.len().checked_sub(n)?;
+        if at < self.pushed {
+            return None;
+        }
+        self.stack[at..].iter().map(|s| s.known).collect()
+    }
+
+    fn push_known(&mut self, v: f32, percent: bool) {
+        self.stack.push(Slot {
+            known: Some(v),
+            percent,
+        });
+    }
+
+    /// Replaces the top `pops` entries with a known value.
+    fn fold(&mut self, pops: usize, v: f32) {
+        self.stack.truncate(self.stack.len() - pops);
+        self.push_known(v, false);
+    }
+
+    /// Emits `op`, which pops `pops` values and pushes its result.
+    fn run(&mut self, op: Op, pops: usize, percent: bool, span: Span, owner: Option<usize>) {
+        self.flush(span, owner);
+        self.emit(op, span, owner);
+        self.stack.truncate(self.stack.len() - pops);
+        self.stack.push(Slot {
+            known: None,
+            percent,
+        });
+        self.pushed = self.stack.len();
+        self.program.max_stack = self.program.max_stack.max(self.pushed);
+    }
+
+    fn finish(mut self, span: Span) -> Program {
+        self.flush(span, None);
+        self.program
+    }
+}
+
+/// The body being compiled: where `x` lives, its call depth, and the user
+/// function it belongs to.
+struct Body {
+    x: Var,
+    depth: u8,
+    owner: Option<usize>,
+    slot: usize,
+}
+
+struct Compiler<'c> {
+    ctx: &'c CompiledDefinitions<'c>,
+    /// Per user definition: can reach a call to itself.
+    recursive: Vec<bool>,
+    /// Per user definition: draws no random numbers, so calls may fold.
+    pure: Vec<bool>,
+    /// Body tokens inlined so far, against [`INLINE_WORK`].
+    inlined: usize,
+    code: Bytecode,
+}
+
+/// The user functions a body calls, `taylor` expansions included.
+pub(crate) fn callees(body: &[SpannedToken]) -> impl Iterator<Item = usize> + '_ {
+    body.iter().filter_map(|t| match t.token {
+        Token::Call(Callee::User(i))
+        | Token::EndCall(Callee::User(i))
+        | Token::EndCall(Callee::Taylor(FunctionRef::User(i))) => Some(i),
+        _ => None,
+    })
+}
+
+pub(crate) fn draws_random(body: &[SpannedToken]) -> bool {
+    body.iter()
+        .any(|t| matches!(t.token, Token::EndCall(Callee::Catalog(sym)) if sym.kind.is_random()))
+}
+
+fn plural(n: u8) -> &'static str {
+    if n == 1 {
+        "parameter"
+    } else {
+        "parameters"
+    }
+}
+
+fn depth_error(name: &str, span: Span) -> EquationError {
+    EquationError::spanned(
+        format!("Call depth limit ({MAX_CALL_DEPTH}) exceeded — is '{name}' defined in terms of itself?"),
+        span,
+    )
+    .with_kind(ErrorKind::DepthLimit {
+        function: name.to_string(),
+    })
+}
+
+impl<'c> Compiler<'c> {
+    fn new(ctx: &'c CompiledDefinitions<'c>) -> Self {
+        let n = ctx.len();
+        let edges: Vec<Vec<usize>> = (0..n)
+            .map(|i| ctx.body_rpn(i).map_or(Vec::new(), |b| callees(b).collect()))
+            .collect();
+        let recursive = (0..n)
+            .map(|start| {
+                let mut seen = vec![false; n];
+                let mut todo = edges[start].clone();
+                while let Some(i) = todo.pop() {
+                    if i == start {
+                        return true;
+                    }
+                    if i < n && !seen[i] {
+                        seen[i] = true;
+                        todo.extend(&edges[i]);
+                    }
+                }
+                false
+            })
+            .collect();
+        Compiler {
+            ctx,
+            recursive,
+            pure: ctx.pure_functions(),
+            inlined: 0,
+            code: Bytecode {
+                main: Program::default(),
+                functions: vec![None; n],
+                names: (0..n).map(|i| ctx.name(i).to_string()).collect(),
+                series: Vec::new(),
+                errors: Vec::new(),
+                defs: None,
+                random: false,
+            },
+        }
+    }
+
+    /// Ends the program with an error the interpreter would raise here.
+    fn halt(&mut self, em: &mut Emitter, error: EquationError, span: Span, b: &Body) -> Halted {
+        self.code.errors.push(error);
+        em.emit(Op::Fail(self.code.errors.len() - 1), span, b.owner);
+        Halted
+    }
+
+    fn body(&mut self, em: &mut Emitter, tokens: &[SpannedToken], b: &Body) -> Result<(), Halted> {
+        let base = em.stack.len();
+        let mut frames: Vec<usize> = Vec::new();
+        let end = tokens.last().map_or(Span::new(0, 0), |t| t.span);
+        if tokens.is_empty() {
+            let error = EquationError::new("Invalid equation supplied");
+            return Err(self.halt(em, error, end, b));
+        }
+
+        for spanned in tokens {
+            let span = spanned.span;
+            let fail = |message: String| EquationError::spanned(message, span);
+            let height = em.stack.len() - base;
+            let token = spanned.token;
+
+            match token {
+                Token::Call(Callee::Catalog(sym)) => {
+                    if !sym.kind.is_unary() {
+                        let error =
+                            fail(format!("Non-callable symbol '{}' at Call token", sym.name));
+                        return Err(self.halt(em, error, span, b));
+                    }
+                    if height == 0 {
+                        let error =
+                            fail(format!("Insufficient operands for {} function", sym.name));
+                        return Err(self.halt(em, error, span, b));
+                    }
+                    self.catalog(em, sym, 1, span, b);
+                }
+                Token::Call(Callee::User(i)) => {
+                    if height == 0 {
+                        let error = fail(format!(
+                            "Insufficient operands for {} function",
+                            self.ctx.name(i)
+                        ));
+                        return Err(self.halt(em, error, span, b));
+                    }
+                    self.user(em, i, span, b)?;
+                }
+                Token::Call(Callee::Taylor(_)) => {
+                    let error = fail(String::from("taylor cannot be used after '|>'"));
+                    return Err(self.halt(em, error, span, b));
+                }
+                Token::CallStart(_) => frames.push(em.stack.len()),
+                Token::EndCall(callee) => {
+                    let what = match callee {
+                        Callee::Catalog(sym) => sym.name,
+                        Callee::User(i) => self.ctx.name(i),
+                        Callee::Taylor(_) => "taylor",
+                    };
+                    let Some(frame) = frames.pop() else {
+                        let error = fail(format!("Unexpected end of {what} call"));
+                        return Err(self.halt(em, error, span, b));
+                    };
+                    let n = em.stack.len().saturating_sub(frame);
+                    match callee {
+                        Callee::User(i) => {
+                            if n != 1 {
+                                let error =
+                                    fail(format!("{what} takes exactly 1 parameter (x), got {n}"))
+                                        .with_kind(ErrorKind::arity(what, 1, Some(1), n));
+                                return Err(self.halt(em, error, span, b));
+                            }
+                            self.user(em, i, span, b)?;
+                        }
+                        Callee::Taylor(f) => {
+                            if n != 2 {
+                                let error = fail(format!(
+                                    "taylor takes exactly 3 parameters (f, a, n), got {}",
+                                    n + 1
+                                ))
+                                .with_kind(ErrorKind::arity("taylor", 3, Some(3), n + 1));
+                                return Err(self.halt(em, error, span, b));
+                            }
+                            self.taylor(em, f, span, b);
+                        }
+                        Callee::Catalog(sym) => {
+                            let Some((min, max)) =
+                                sym.kind.arity().filter(|_| sym.kind.is_callable())
+                            else {
+                                let error =
+                                    fail(format!("EndCall for non-callable symbol '{what}'"));
+                                return Err(self.halt(em, error, span, b));
+                            };
+                            let arity =
+                                ErrorKind::arity(what, usize::from(min), max.map(usize::from), n);
+                            if (n as u32) < min as u32 {
+                                let error = fail(format!(
+                                    "{what} requires at least {min} {}, got {n}",
+                                    plural(min)
+                                ))
+                                .with_kind(arity);
+                                return Err(self.halt(em, error, span, b));
+                            }
+                            if let Some(max) = max.filter(|&m| n as u32 > m as u32) {
+                                let error = fail(format!(
+                                    "{what} accepts at most {max} {}, got {n}",
+                                    plural(max)
+                                ))
+                                .with_kind(arity);
+                                return Err(self.halt(em, error, span, b));
+                            }
+                            self.catalog(em, sym, n, span, b);
+                        }
+                    }
+                }
+                Token::Constant(sym) => {
+                    let SymbolKind::Constant(v) = sym.kind else {
+                        let error = fail(format!(
+                            "Constant token for non-constant symbol '{}'",
+                            sym.name
+                        ));
+                        return Err(self.halt(em, error, span, b));
+                    };
+                    em.push_known(v, false);
+                }
+                Token::Number(n) => em.push_known(n, false),
+                Token::Unit(unit) => {
+                    let error = fail(format!(
+                        "Unit '{}' requires unit-aware evaluation",
+                        unit.name
+                    ));
+                    return Err(self.halt(em, error, span, b));
+                }
+                Token::UnitProduct => {
+                    let error = fail(String::from("Unit products require unit-aware evaluation"));
+                    return Err(self.halt(em, error, span, b));
+                }
+                // Only `fit` substitutes parameters, and it evaluates in f64.
+                Token::Parameter(_) => {
+                    let error = fail(String::from("Internal error: fit parameter in bytecode"))
+                        .with_kind(ErrorKind::Internal);
+                    return Err(self.halt(em, error, span, b));
+                }
+                Token::X => match b.x {
+                    Var::Known(v) => em.push_known(v, false),
+                    Var::Slot(s) => em.run(Op::Load(s), 0, false, span, b.owner),
+                },
+                Token::UnaryMinus | Token::Factorial | Token::Percent | Token::Log { .. } => {
+                    if height == 0 {
+                        let what = match token {
+                            Token::UnaryMinus => "unary minus operator",
+                            Token::Factorial => "factorial operator",
+                            Token::Percent => "percent operator",
+                            _ => "log function",
+                        };
+                        let error = fail(format!("Insufficient operands for {what}"));
+                        return Err(self.halt(em, error, span, b));
+                    }
+                    let known = em.pending(1).and_then(|v| v.first().copied());
+                    let (op, folded) = match token {
+                        Token::UnaryMinus => (Op::Neg, known.map(|v| -v)),
+                        Token::Factorial => (
+                            Op::Factorial,
+                            known
+                                .filter(|v| *v >= 0.0 && v % 1.0 == 0.0)
+                                .and_then(|v| factorial(v as isize).ok())
+                                .map(|f| f as f32),
+                        ),
+                        Token::Percent => (Op::Percent, known.map(|v| v / 100.0)),
+                        Token::Log { base } => (Op::Log(base), known.map(|v| v.log(base))),
+                        _ => (Op::Neg, None),
+                    };
+                    let percent = token == Token::Percent;
+                    match folded {
+                        Some(v) => {
+                            em.fold(1, v);
+                            if let Some(top) = em.stack.last_mut() {
+                                top.percent = percent;
+                            }
+                        }
+                        None => em.run(op, 1, percent, span, b.owner),
+                    }
+                }
+                Token::Plus
+                | Token::Minus
+                | Token::Star
+                | Token::Slash
+                | Token::Modulo
+                | Token::Power => {
+                    if height < 2 {
+                        let error = fail(String::from("Invalid expression"));
+                        return Err(self.halt(em, error, span, b));
+                    }
+                    let rhs = em.stack[em.stack.len() - 1];
+                    // Unreachable: constrained by the outer match arm.
+                    let Some(op) = BinOp::of(token, rhs.percent) else {
+                        let error = fail(format!("Unknown token: {:?}", token));
+                        return Err(self.halt(em, error, span, b));
+                    };
+                    match em.pending(2) {
+                        Some(v) => em.fold(2, op.apply(v[0], v[1])),
+                        None => match em.pending(1).and_then(|v| v.first().copied()) {
+                            // The left operand is already on the stack.
+                            Some(r) => {
+                                em.emit(Op::BinaryK(op, r), span, b.owner);
+                                em.stack.pop();
+                                if let Some(top) = em.stack.last_mut() {
+                                    *top = Slot {
+                                        known: None,
+                                        percent: false,
+                                    };
+                                }
+                            }
+                            None => em.run(Op::Binary(op), 2, false, span, b.owner),
+                        },
+                    }
+                }
+                Token::Y
+                | Token::Equal
+                | Token::Comma
+                | Token::OpenParen
+                | Token::CloseParen
+                | Token::Pipe
+                | Token::End => {
+                    let error = fail(format!("Unexpected token in evaluation: {:?}", token));
+                    return Err(self.halt(em, error, span, b));
+                }
+            }
+        }
+
+        let n = em.stack.len() - base;
+        if n != 1 {
+            let error = EquationError::new(format!(
+                "Invalid evaluation: expected 1 result, found {n} items in stack"
+            ));
+            return Err(self.halt(em, error, end, b));
+        }
+        Ok(())
+    }
+
+    /// A catalog call on the top `argc` values, arity already checked.
+    fn catalog(
+        &mut self,
+        em: &mut Emitter,
+        sym: &'static Symbol,
+        argc: usize,
+        span: Span,
+        b: &Body,
+    ) {
+        if let Some(args) = em.pending(argc) {
+            let folded = match sym.kind {
+                SymbolKind::Unary(f) => Some(f(args[0])),
+                SymbolKind::UnaryChecked(f) => f(args[0]).ok(),
+                SymbolKind::Variadic { run, .. } => run(&args).ok(),
+                _ => None,
+            };
+            if let Some(v) = folded {
+                em.fold(argc, v);
+                return;
+            }
+        }
+        let op = match sym.kind {
+            SymbolKind::Unary(f) => Op::Unary(f),
+            SymbolKind::UnaryChecked(f) => Op::UnaryChecked(f),
+            SymbolKind::Variadic { run, .. } => Op::Variadic { run, argc },
+            SymbolKind::Random { run, .. } => {
+                self.code.random = true;
+                Op::Random { run, argc }
+            }
+            // `is_callable` admits nothing else.
+            _ => return,
+        };
+        em.run(op, argc, false, span, b.owner);
+    }
+
+    /// A user call on the top value: inlined when the function is not
+    /// recursive and the program has room, a table call otherwise.
+    fn user(&mut self, em: &mut Emitter, i: usize, span: Span, b: &Body) -> Result<(), Halted> {
+        let ctx = self.ctx;
+        let name = ctx.name(i);
+        if em.absolute && b.depth >= MAX_CALL_DEPTH {
+            return Err(self.halt(em, depth_error(name, span), span, b));
+        }
+        let check = Op::CheckDepth {
+            function: i,
+            depth: b.depth,
+        };
+        let body = match ctx.body_rpn(i) {
+            Ok(body) => body,
+            Err(e) => {
+                if !em.absolute {
+                    em.emit(check, span, b.owner);
+                }
+                return Err(self.halt(em, e.for_function(name), span, b));
+            }
+        };
+
+        // A memoized function stays a call, so its results can be reused.
+        if self.recursive[i]
+            || ctx.memoizes(i)
+            || em.program.ops.len() + body.len() > INLINE_BUDGET
+            || self.inlined + body.len() > INLINE_WORK
+        {
+            self.function(i);
+            let call = Op::Call {
+                function: i,
+                depth: b.depth,
+            };
+            em.run(call, 1, false, span, b.owner);
+            return Ok(());
+        }
+
+        if !em.absolute {
+            em.emit(check, span, b.owner);
+        }
+        let slot = b.slot + 1;
+        let x = match em.pending(1).and_then(|v| v.first().copied()) {
+            Some(v) => {
+                em.stack.pop();
+                Var::Known(v)
+            }
+            None => {
+                em.emit(Op::Store(slot), span, b.owner);
+                em.stack.pop();
+                em.pushed -= 1;
+                em.program.locals = em.program.locals.max(slot + 1);
+                Var::Slot(slot)
+            }
+        };
+        self.inlined += body.len();
+        let inner = Body {
+            x,
+            depth: b.depth.saturating_add(1),
+            owner: Some(i),
+            slot,
+        };
+        self.body(em, body, &inner)?;
+        // A call's result is plain, whatever the body ended with.
+        if let Some(top) = em.stack.last_mut() {
+            top.percent = false;
+        }
+        Ok(())
+    }
+
+    /// Compiles user function `i` into the function table, once.
+    fn function(&mut self, i: usize) {
+        if self.code.functions[i].is_some() {
+            return;
+        }
+        // A placeholder, so recursive calls compiled below find it taken.
+        self.code.functions[i] = Some(Program::default());
+        let mut em = Emitter::new(false);
+        let b = Body {
+            x: Var::Slot(0),
+            depth: 0,
+            owner: None,
+            slot: 0,
+        };
+        let ctx = self.ctx;
+        let end = match ctx.body_rpn(i) {
+            Ok(body) => {
+                let _ = self.body(&mut em, body, &b);
+                body.last().map_or(Span::new(0, 0), |t| t.span)
+            }
+            Err(e) => {
+                let span = Span::new(0, 0);
+                let _ = self.halt(&mut em, e, span, &b);
+                span
+            }
+        };
+        self.code.functions[i] = Some(em.finish(end));
+    }
+
+    /// `taylor(f, a, n)` on the top two values. The coefficients are
+    /// computed now when `a` and `n` are known and `f` is pure.
+    fn taylor(&mut self, em: &mut Emitter, f: FunctionRef, span: Span, b: &Body) {
+        let pure = match f {
+            FunctionRef::Catalog(_) => true,
+            FunctionRef::User(i) => self.pure[i] && em.absolute,
+        };
+        if let Some(args) = em.pending(2).filter(|_| pure) {
+            let (a, n) = (args[0], args[1]);
+            let mut state = EvalState::new(Some(self.ctx), 0);
+            if let Ok(coefficients) = taylor::expand(f, a, n, &mut state, b.depth, span) {
+                em.stack.truncate(em.stack.len() - 2);
+                match b.x {
+                    Var::Known(x) => {
+                        let t = f64::from(x) - f64::from(a);
+                        em.push_known(taylor::horner(&coefficients, t) as f32, false);
+                    }
+                    Var::Slot(_) => {
+                        self.code.series.push(coefficients);
+                        let series = self.code.series.len() - 1;
+                        em.run(Op::Horner { series, a, x: b.x }, 0, false, span, b.owner);
+                    }
+                }
+                return;
+            }
+        }
+        if let FunctionRef::User(_) = f {
+            let ctx = self.ctx;
+            self.code
+                .defs
+                .get_or_insert_with(|| ctx.definitions().clone());
+        }
+        let op = Op::Taylor {
+            f,
+            x: b.x,
+            depth: b.depth,
+        };
+        em.run(op, 2, false, span, b.owner);
+    }
+}
+
+fn pop(stack: &mut Vec<f32>) -> Result<f32, EquationError> {
+    stack.pop().ok_or_else(|| {
+        EquationError::new("Internal error: bytecode stack underflow")
+            .with_kind(ErrorKind::Internal)
+    })
+}
+
+fn top(stack: &mut [f32]) -> Result<&mut f32, EquationError> {
+    stack.last_mut().ok_or_else(|| {
+        EquationError::new("Internal error: bytecode stack underflow")
+            .with_kind(ErrorKind::Internal)
+    })
+}
+
+impl Bytecode {
+    /// Compiles RPN tokenized against the definitions `ctx` was compiled
+    /// from. Never fails: errors are deferred to evaluation, as in the
+    /// interpreter.
+    pub(crate) fn compile(rpn: &[SpannedToken], ctx: &CompiledDefinitions) -> Bytecode {
+        let mut compiler = Compiler::new(ctx);
+        let mut em = Emitter::new(true);
+        let b = Body {
+            x: Var::Slot(0),
+            depth: 0,
+            owner: None,
+            slot: 0,
+        };
+        let _ = compiler.body(&mut em, rpn, &b);
+        let end = rpn.last().map_or(Span::new(0, 0), |t| t.span);
+        compiler.code.main = em.finish(end);
+        compiler.code
+    }
+
+    /// Whether evaluation draws random numbers, and so needs a seed.
+    pub(crate) fn is_random(&self) -> bool {
+        self.random
+    }
+
+    /// Buffers sized for this program.
+    pub(crate) fn scratch(&self) -> Scratch {
+        Scratch {
+            stack: Vec::with_capacity(self.main.max_stack),
+            locals: Vec::with_capacity(self.main.locals),
+        }
+    }
+
+    /// Evaluates at `x`. `state` supplies the random-number generator and,
+    /// when it has them, the definitions for run-time Taylor expansions.
+    pub(crate) fn run(
+        &self,
+        x: f32,
+        scratch: &mut Scratch,
+        state: &mut EvalState,
+    ) -> Result<f32, EquationError> {
+        scratch.stack.clear();
+        scratch.locals.clear();
+        self.exec(&self.main, x, 0, scratch, state)
+    }
+
+    fn exec(
+        &self,
+        program: &Program,
+        x: f32,
+        base: u8,
+        scratch: &mut Scratch,
+        state: &mut EvalState,
+    ) -> Result<f32, EquationError> {
+        // Straight-line code: every op runs unless one fails, so the whole
+        // program is charged at once.
+        state.charge(program.ops.len() as u64)?;
+        let frame = scratch.locals.len();
+        scratch.locals.resize(frame + program.locals.max(1), 0.0);
+        scratch.locals[frame] = x;
+        let read = |locals: &[f32], var: Var| match var {
+            Var::Slot(s) => locals[frame + s],
+            Var::Known(v) => v,
+        };
+
+        for (i, &op) in program.ops.iter().enumerate() {
+            let raise = |error: EquationError| program.raise(i, error, &self.names);
+            let domain = |message: String| {
+                let span = program.spans.get(i).copied().unwrap_or(Span::new(0, 0));
+                raise(EquationError::domain(message, span))
+            };
+            let stack = &mut scratch.stack;
+            match op {
+                Op::Push(v) => stack.push(v),
+                Op::Load(s) => stack.push(scratch.locals[frame + s]),
+                Op::Store(s) => scratch.locals[frame + s] = pop(stack)?,
+                Op::Unary(f) => {
+                    let v = top(stack)?;
+                    *v = f(*v);
+                }
+                Op::UnaryChecked(f) => {
+                    let v = top(stack)?;
+                    *v = f(*v).map_err(domain)?;
+                }
+                Op::Variadic { run, argc } => {
+                    let at = stack.len().saturating_sub(argc);
+                    let v = run(&stack[at..]).map_err(domain)?;
+                    stack.truncate(at);
+                    stack.push(v);
+                }
+                Op::Random { run, argc } => {
+                    let at = stack.len().saturating_sub(argc);
+                    let v = run(state.rng(), &stack[at..]).map_err(domain)?;
+                    stack.truncate(at);
+                    stack.push(v);
+                }
+                Op::Binary(op) => {
+                    let r = pop(stack)?;
+                    let l = top(stack)?;
+                    *l = op.apply(*l, r);
+                }
+                Op::BinaryK(op, r) => {
+                    let l = top(stack)?;
+                    *l = op.apply(*l, r);
+                }
+                Op::Neg => {
+                    let v = top(stack)?;
+                    *v = -*v;
+                }
+                Op::Factorial => {
+                    let v = top(stack)?;
+                    if *v < 0.0 || *v % 1.0 != 0.0 {
+                        return Err(domain(String::from(
+                            "Factorial is only defined for non-negative integers",
+                        )));
+                    }
+                    *v = factorial(*v as isize).map_err(domain)? as f32;
+                }
+                Op::Percent => {
+                    let v = top(stack)?;
+                    *v /= 100.0;
+                }
+                Op::Log(b) => {
+                    let v = top(stack)?;
+                    *v = v.log(b);
+                }
+                Op::Call { function, depth } => {
+                    let name = self.names.get(function).map_or("?", String::as_str);
+                    let depth = base.saturating_add(depth);
+                    if depth >= MAX_CALL_DEPTH {
+                        let span = program.spans.get(i).copied().unwrap_or(Span::new(0, 0));
+                        return Err(raise(depth_error(name, span)));
+                    }
+                    let Some(callee) = self.functions.get(function).and_then(Option::as_ref) else {
+                        return Err(EquationError::new(
+                            "Internal error: call to an uncompiled function",
+                        )
+                        .with_kind(ErrorKind::Internal));
+                    };
+                    let arg = pop(stack)?;
+                    let ctx = state.ctx;
+                    if let Some(v) = ctx.and_then(|c| c.recall(function, arg)) {
+                        stack.push(v);
+                        continue;
+                    }
+                    let v = self
+                        .exec(callee, arg, depth + 1, scratch, state)
+                        .map_err(|e| e.for_function(name))?;
+                    if let Some(c) = ctx {
+                        c.remember(function, arg, v);
+                    }
+                    scratch.stack.push(v);
+                }
+                Op::CheckDepth { function, depth } => {
+                    if base.saturating_add(depth) >= MAX_CALL_DEPTH {
+                        let name = self.names.get(function).map_or("?", String::as_str);
+                        let span = program.spans.get(i).copied().unwrap_or(Span::new(0, 0));
+                        return Err(raise(depth_error(name, span)));
+                    }
+                }
+                Op::Taylor { f, x, depth } => {
+                    let n = pop(stack)?;
+                    let a = pop(stack)?;
+                    let span = program.spans.get(i).copied().unwrap_or(Span::new(0, 0));
+                    let depth = base.saturating_add(depth);
+                    let coefficients = match (&self.defs, state.ctx) {
+                        // Compiled standalone: bring the definitions back
+                        // for this expansion.
+                        (Some(defs), None) => {
+                            let ctx = defs.compile();
+                            let mut inner = EvalState::new(Some(&ctx), 0);
+                            taylor::expand(f, a, n, &mut inner, depth, span)
+                        }
+                        _ => taylor::expand(f, a, n, state, depth, span),
+                    }
+                    .map_err(raise)?;
+                    let t = f64::from(read(&scratch.locals, x)) - f64::from(a);
+                    scratch.stack.push(taylor::horner(&coefficients, t) as f32);
+                }
+                Op::Horner { series, a, x } => {
+                    let coefficients = self.series.get(series).map_or(&[][..], Vec::as_slice);
+                    let t = f64::from(read(&scratch.locals, x)) - f64::from(a);
+                    stack.push(taylor::horner(coefficients, t) as f32);
+                }
+                Op::Fail(k) => {
+                    let error = self.errors.get(k).cloned().unwrap_or_else(|| {
+                        EquationError::new("Internal error: unknown failure")
+                            .with_kind(ErrorKind::Internal)
+                    });
+                    return Err(raise(error));
+                }
+            }
+        }
+
+        scratch.locals.truncate(frame);
+        pop(&mut scratch.stack)
+    }
+}
+
+/// How many `x` values each batch instruction runs across.
+pub(crate) const LANES: usize = 64;
+
+type Lanes = [f32; LANES];
+
+/// Reusable buffers for [`Bytecode::run_batch`]: one lane group per stack
+/// slot and local, plus scalar buffers for the fallback paths.
+pub(crate) struct BatchScratch {
+    stack: Vec<Lanes>,
+    locals: Vec<Lanes>,
+    args: Vec<f32>,
+    scalar: Scratch,
+}
+
+/// A lane failed; the group is re-run point by point for the exact error.
+struct LaneFailed;
+
+impl BinOp {
+    /// `l[j] = l[j] op r[j]`. One loop per operator, so each compiles to
+    /// straight-line SIMD.
+    fn apply_lanes(self, l: &mut [f32], r: &[f32]) {
+        let pairs = l.iter_mut().zip(r);
+        match self {
+            BinOp::Add => pairs.for_each(|(l, r)| *l += r),
+            BinOp::Sub => pairs.for_each(|(l, r)| *l -= r),
+            BinOp::AddPercent => pairs.for_each(|(l, r)| *l += *l * r),
+            BinOp::SubPercent => pairs.for_each(|(l, r)| *l -= *l * r),
+            BinOp::Mul => pairs.for_each(|(l, r)| *l *= r),
+            BinOp::Div => pairs.for_each(|(l, r)| *l /= r),
+            BinOp::Rem => pairs.for_each(|(l, r)| *l %= r),
+            BinOp::Pow => pairs.for_each(|(l, r)| *l = l.powf(*r)),
+        }
+    }
+
+    fn apply_lanes_k(self, l: &mut [f32], r: f32) {
+        let lanes = l.iter_mut();
+        match self {
+            BinOp::Add => lanes.for_each(|l| *l += r),
+            BinOp::Sub => lanes.for_each(|l| *l -= r),
+            BinOp::AddPercent => lanes.for_each(|l| *l += *l * r),
+            BinOp::SubPercent => lanes.for_each(|l| *l -= *l * r),
+            BinOp::Mul => lanes.for_each(|l| *l *= r),
+            BinOp::Div => lanes.for_each(|l| *l /= r),
+            BinOp::Rem => lanes.for_each(|l| *l %= r),
+            BinOp::Pow => lanes.for_each(|l| *l = l.powf(r)),
+        }
+    }
+}
+
+/// Where a batch evaluates: the per-point seeds derive from `seed`, user
+/// calls resolve in `ctx`, and every point is bounded by `limits`.
+#[derive(Clone, Copy)]
+pub(crate) struct BatchEnv<'a> {
+    pub(crate) seed: u64,
+    pub(crate) ctx: Option<&'a CompiledDefinitions<'a>>,
+    pub(crate) limits: &'a EvalLimits,
+}
+
+impl<'a> BatchEnv<'a> {
+    /// The state a scalar evaluation at `x` would use.
+    fn state(&self, x: f32) -> EvalState<'a> {
+        EvalState::new(self.ctx, seed_for_x(self.seed, x)).with_limits(self.limits)
+    }
+}
+
+/// Per-lane evaluation states, built on first use: most programs never
+/// draw or call. Each is charged for the main program, as the scalar
+/// evaluator charges it.
+fn lane_states<'s, 'a>(
+    states: &'s mut Option<Vec<EvalState<'a>>>,
+    xs: &[f32],
+    env: &BatchEnv<'a>,
+    main: &Program,
+) -> Result<&'s mut Vec<EvalState<'a>>, LaneFailed> {
+    match states {
+        Some(lanes) => Ok(lanes),
+        None => {
+            let mut lanes = Vec::with_capacity(xs.len());
+            for &x in xs {
+                let mut state = env.state(x);
+                state
+                    .charge(main.ops.len() as u64)
+                    .map_err(|_| LaneFailed)?;
+                lanes.push(state);
+            }
+            Ok(states.insert(lanes))
+        }
+    }
+}
+
+impl Bytecode {
+    /// Buffers sized for batch evaluation of this program.
+    pub(crate) fn batch_scratch(&self) -> BatchScratch {
+        BatchScratch {
+            stack: vec![[0.0; LANES]; self.main.max_stack.max(1)],
+            locals: vec![[0.0; LANES]; self.main.locals.max(1)],
+            args: Vec::new(),
+            scalar: self.scratch(),
+        }
+    }
+
+    /// Evaluates at every `xs[j]` into `ys[j]` (same length), running each
+    /// instruction across [`LANES`] values at a time. Each point draws from
+    /// its own generator, seeded by `seed_for_x(env.seed, x)` as in
+    /// plotting.
+    ///
+    /// Returns the error of the first failing point, as the scalar
+    /// evaluator reports it; `ys` is then only written up to that point.
+    /// The deadline and cancellation are also checked between lane groups.
+    pub(crate) fn run_batch(
+        &self,
+        xs: &[f32],
+        ys: &mut [f32],
+        env: &BatchEnv,
+        scratch: &mut BatchScratch,
+    ) -> Result<(), EquationError> {
+        for (xs, ys) in xs.chunks(LANES).zip(ys.chunks_mut(LANES)) {
+            env.limits.check_interrupt()?;
+            if self.exec_lanes(xs, ys, env, scratch).is_err() {
+                for (&x, y) in xs.iter().zip(ys) {
+                    *y = self.run(x, &mut scratch.scalar, &mut env.state(x))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`run_batch`](Self::run_batch), but keeps going past failing
+    /// points: appends one result per `x` to `out`, each exactly what the
+    /// scalar evaluator returns there. Fails only when the deadline passes
+    /// or the evaluation is cancelled between lane groups.
+    pub(crate) fn run_points(
+        &self,
+        xs: &[f32],
+        env: &BatchEnv,
+        scratch: &mut BatchScratch,
+        out: &mut Vec<Result<f32, EquationError>>,
+    ) -> Result<(), EquationError> {
+        let mut ys = [0.0; LANES];
+        for xs in xs.chunks(LANES) {
+            env.limits.check_interrupt()?;
+            let ys = &mut ys[..xs.len()];
+            if self.exec_lanes(xs, ys, env, scratch).is_ok() {
+                out.extend(ys.iter().map(|&y| Ok(y)));
+            } else {
+                out.extend(
+                    xs.iter()
+                        .map(|&x| self.run(x, &mut scratch.scalar, &mut env.state(x))),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// One lane group of the main program. Instructions with no lane-wide
+    /// form — user calls, random draws, run-time Taylor expansions — run
+    /// lane by lane, each lane with its own state.
+    fn exec_lanes(
+        &self,
+        xs: &[f32],
+        ys: &mut [f32],
+        env: &BatchEnv,
+        scratch: &mut BatchScratch,
+    ) -> Result<(), LaneFailed> {
+        // Over budget before it starts: the scalar path reports it.
+        if env
+            .limits
+            .max_steps()
+            .is_some_and(|max| self.main.ops.len() as u64 > max)
+        {
+            return Err(LaneFailed);
+        }
+        let n = xs.len();
+        let BatchScratch {
+            stack,
+            locals,
+            args,
+            scalar,
+        } = scratch;
+        locals[0][..n].copy_from_slice(xs);
+        let mut states: Option<Vec<EvalState>> = None;
+        // Stack slots `[0, sp)` are live.
+        let mut sp = 0;
+
+        for &op in &self.main.ops {
+            match op {
+                Op::Push(v) => {
+                    stack[sp][..n].fill(v);
+                    sp += 1;
+                }
+                Op::Load(s) => {
+                    stack[sp] = locals[s];
+                    sp += 1;
+                }
+                Op::Store(s) => {
+                    sp -= 1;
+                    locals[s] = stack[sp];
+                }
+                Op::Unary(f) => stack[sp - 1][..n].iter_mut().for_each(|v| *v = f(*v)),
+                Op::UnaryChecked(f) => {
+                    for v in &mut stack[sp - 1][..n] {
+                        *v = f(*v).map_err(|_| LaneFailed)?;
+                    }
+                }
+                Op::Variadic { run, argc } => {
+                    let at = sp - argc;
+                    for j in 0..n {
+                        args.clear();
+                        args.extend(stack[at..sp].iter().map(|lane| lane[j]));
+                        stack[at][j] = run(args).map_err(|_| LaneFailed)?;
+                    }
+                    sp = at + 1;
+                }
+                Op::Random { run, argc } => {
+                    let at = sp - argc;
+                    let states = lane_states(&mut states, xs, env, &self.main)?;
+                    for (j, state) in states.iter_mut().enumerate() {
+                        args.clear();
+                        args.extend(stack[at..sp].iter().map(|lane| lane[j]));
+                        stack[at][j] = run(state.rng(), args).map_err(|_| LaneFailed)?;
+                    }
+                    sp = at + 1;
+                }
+                Op::Binary(op) => {
+                    sp -= 1;
+                    let (lower, upper) = stack.split_at_mut(sp);
+                    op.apply_lanes(&mut lower[sp - 1][..n], &upper[0][..n]);
+                }
+                Op::BinaryK(op, r) => op.apply_lanes_k(&mut stack[sp - 1][..n], r),
+                Op::Neg => stack[sp - 1][..n].iter_mut().for_each(|v| *v = -*v),
+                Op::Factorial => {
+                    for v in &mut stack[sp - 1][..n] {
+                        if *v < 0.0 || *v % 1.0 != 0.0 {
+                            return Err(LaneFailed);
+                        }
+                        *v = factorial(*v as isize).map_err(|_| LaneFailed)? as f32;
+                    }
+                }
+                Op::Percent => stack[sp - 1][..n].iter_mut().for_each(|v| *v /= 100.0),
+                Op::Log(b) => stack[sp - 1][..n].iter_mut().for_each(|v| *v = v.log(b)),
+                Op::Call { function, depth } => {
+                    let callee = self.functions.get(function).and_then(Option::as_ref);
+                    let (Some(callee), true) = (callee, depth < MAX_CALL_DEPTH) else {
+                        return Err(LaneFailed);
+                    };
+                    let states = lane_states(&mut states, xs, env, &self.main)?;
+                    for (j, state) in states.iter_mut().enumerate() {
+                        let arg = stack[sp - 1][j];
+                        if let Some(v) = env.ctx.and_then(|c| c.recall(function, arg)) {
+                            stack[sp - 1][j] = v;
+                            continue;
+                        }
+                        scalar.stack.clear();
+                        scalar.locals.clear();
+                        let v = self
+                            .exec(callee, arg, depth + 1, scalar, state)
+                            .map_err(|_| LaneFailed)?;
+                        if let Some(c) = env.ctx {
+                            c.remember(function, arg, v);
+                        }
+                        stack[sp - 1][j] = v;
+                    }
+                }
+                Op::Taylor { f, x, depth } => {
+                    // Run-time expansions are rare enough to share the
+                    // scalar path.
+                    let states = lane_states(&mut states, xs, env, &self.main)?;
+                    for (j, state) in states.iter_mut().enumerate() {
+                        let (a, order) = (stack[sp - 2][j], stack[sp - 1][j]);
+                        let at = match x {
+                            Var::Slot(s) => locals[s][j],
+                            Var::Known(v) => v,
+                        };
+                        let coefficients = match (&self.defs, state.ctx) {
+                            (Some(defs), None) => {
+                                let ctx = defs.compile();
+                                let mut inner = EvalState::new(Some(&ctx), 0);
+                                taylor::expand(f, a, order, &mut inner, depth, Span::new(0, 0))
+                            }
+                            _ => taylor::expand(f, a, order, state, depth, Span::new(0, 0)),
+                        }
+                        .map_err(|_| LaneFailed)?;
+                        let t = f64::from(at) - f64::from(a);
+                        stack[sp - 2][j] = taylor::horner(&coefficients, t) as f32;
+                    }
+                    sp -= 1;
+                }
+                Op::Horner { series, a, x } => {
+                    let coefficients = self.series.get(series).map_or(&[][..], Vec::as_slice);
+                    for j in 0..n {
+                        let at = match x {
+                            Var::Slot(s) => locals[s][j],
+                            Var::Known(v) => v,
+                        };
+                        let t = f64::from(at) - f64::from(a);
+                        stack[sp][j] = taylor::horner(coefficients, t) as f32;
+                    }
+                    sp += 1;
+                }
+                Op::CheckDepth { depth, .. } => {
+                    if depth >= MAX_CALL_DEPTH {
+                        return Err(LaneFailed);
+                    }
+                }
+                Op::Fail(_) => return Err(LaneFailed),
+            }
+        }
+
+        if sp != 1 {
+            return Err(LaneFailed);
+        }
+        ys.copy_from_slice(&stack[0][..n]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::equation_analyzer::pipeline::parser::parse;
+    use crate::equation_analyzer::pipeline::tokenizer::StreamingTokenizer;
+
+    fn compiled(eq: &str, defs: &Definitions) -> Bytecode {
+        let rpn = parse(StreamingTokenizer::new_with(eq, Some(defs)).unwrap()).unwrap();
+        Bytecode::compile(&rpn, &defs.compile())
+    }
+
+    #[test]
+    fn constant_subtrees_fold() {
+        let code = compiled("sin(π/4) * 2", &Definitions::new());
+        assert!(matches!(code.main.ops[..], [Op::Push(_)]));
+
+        let code = compiled("x * sin(π/4) + 1", &Definitions::new());
+        assert!(matches!(
+            code.main.ops[..],
+            [
+                Op::Load(0),
+                Op::BinaryK(BinOp::Mul, _),
+                Op::BinaryK(BinOp::Add, _)
+            ]
+        ));
+    }
+
+    #[test]
+    fn user_functions_inline_unless_recursive() {
+        let mut defs = Definitions::new();
+        defs.define_value("a", 3.0).unwrap();
+        defs.define_function("g", "a * x^2").unwrap();
+        defs.define_function("r", "r(x)").unwrap();
+
+        // g(2) folds entirely; g(x) inlines around a local slot.
+        assert!(matches!(compiled("g(2)", &defs).main.ops[..], [Op::Push(v)] if v == 12.0));
+        let code = compiled("g(x)", &defs);
+        assert!(code.functions.iter().all(Option::is_none));
+        assert!(!code.main.ops.iter().any(|op| matches!(op, Op::Call { .. })));
+
+        let code = compiled("r(x)", &defs);
+        assert!(code.functions[2].is_some());
+        assert!(matches!(
+            code.main.ops[..],
+            [
+                Op::Load(0),
+                Op::Call {
+                    function: 2,
+                    depth: 0
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn shape_errors_become_fail() {
+        let code = compiled("max()", &Definitions::new());
+        assert!(matches!(code.main.ops[..], [Op::Fail(0)]));
+        assert_eq!(
+            code.errors[0].message,
+            "max requires at least 1 parameter, got 0"
+        );
+    }
+}
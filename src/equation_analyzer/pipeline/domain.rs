@@ -0,0 +1,332 @@
+//! A generic RPN walker for value domains other than plain `f32`.
+//!
+//! The main evaluator is hand-specialized to `f32` for speed. Evaluation
+//! modes that carry more than a number per stack slot — unit-aware
+//! quantities today — implement [`Domain`] and reuse this walker for
+//! everything structural: call frames, arity checks, user-function calls
+//! with late-bound bodies, the call-depth limit, and `%` tagging. The
+//! domain only supplies the arithmetic.
+
+use crate::equation_analyzer::catalog::{Symbol, SymbolKind};
+use crate::equation_analyzer::errors::{EquationError, ErrorKind, Span};
+use crate::equation_analyzer::pipeline::evaluator::{EvalState, MAX_CALL_DEPTH};
+use crate::equation_analyzer::structs::token::{Callee, FunctionRef, SpannedToken, Token};
+use crate::equation_analyzer::taylor;
+use crate::equation_analyzer::units::Unit;
+
+/// The arithmetic of one evaluation domain. Errors are plain messages; the
+/// walker attaches the offending token's span.
+pub(crate) trait Domain: Clone + Sized {
+    /// A literal, named constant, or user value.
+    fn number(v: f32) -> Self;
+
+    /// A fitted parameter's current value; narrowed to f32 unless the
+    /// domain carries more.
+    fn parameter(v: f64) -> Self {
+        Self::number(v as f32)
+    }
+
+    /// The plain number this value stands for, if it is one — `taylor`'s
+    /// expansion point and order must be.
+    fn to_number(&self) -> Option<f32>;
+
+    /// A physical unit operand. Only unit-aware domains accept one.
+    fn unit(unit: &'static Unit) -> Result<Self, String> {
+        Err(format!(
+            "Unit '{}' requires unit-aware evaluation",
+            unit.name
+        ))
+    }
+
+    fn add(&self, rhs: &Self) -> Result<Self, String>;
+    fn sub(&self, rhs: &Self) -> Result<Self, String>;
+    fn mul(&self, rhs: &Self) -> Result<Self, String>;
+    fn div(&self, rhs: &Self) -> Result<Self, String>;
+    fn rem(&self, rhs: &Self) -> Result<Self, String>;
+    fn pow(&self, rhs: &Self) -> Result<Self, String>;
+    fn neg(&self) -> Self;
+    fn factorial(&self) -> Result<Self, String>;
+    fn log(&self, base: f32) -> Result<Self, String>;
+
+    /// A catalog call — unary, variadic, random, or `convert` — with its
+    /// arity already checked against the symbol.
+    fn call(sym: &'static Symbol, args: &[Self], state: &mut EvalState) -> Result<Self, String>;
+
+    /// What kind of error one of the operations above reported: a value
+    /// outside a function's domain, unless the domain has errors of its own.
+    fn error_kind(message: &str) -> ErrorKind {
+        let _ = message;
+        ErrorKind::DomainError
+    }
+}
+
+/// An error from one of `D`'s operations, at `span`.
+fn value_error<D: Domain>(message: String, span: Span) -> EquationError {
+    let kind = D::error_kind(&message);
+    EquationError::spanned(message, span).with_kind(kind)
+}
+
+/// A stack slot: the value plus the postfix-`%` tag (see the main
+/// evaluator's `StackVal`).
+#[derive(Clone)]
+struct Slot<D> {
+    val: D,
+    is_percent: bool,
+}
+
+fn plain<D>(val: D) -> Slot<D> {
+    Slot {
+        val,
+        is_percent: false,
+    }
+}
+
+/// Evaluates RPN `tokens` in domain `D`, with `x` bound to the given value.
+pub(crate) fn evaluate_domain<D: Domain>(
+    tokens: &[SpannedToken],
+    x: &D,
+    state: &mut EvalState,
+) -> Result<D, EquationError> {
+    walk(tokens, x, state, 0)
+}
+
+/// Runs a user function on `arg` in domain `D`; see the main evaluator's
+/// `call_user`.
+pub(crate) fn call_user<D: Domain>(
+    state: &mut EvalState,
+    index: usize,
+    arg: &D,
+    depth: u8,
+    call_span: Span,
+) -> Result<D, EquationError> {
+    let Some(ctx) = state.ctx else {
+        return Err(EquationError::spanned(
+            "Internal error: user call without definitions in scope",
+            call_span,
+        )
+        .with_kind(ErrorKind::Internal));
+    };
+    let name = ctx.name(index);
+    if depth >= MAX_CALL_DEPTH {
+        return Err(EquationError::spanned(
+            format!("Call depth limit ({MAX_CALL_DEPTH}) exceeded — is '{name}' defined in terms of itself?"),
+            call_span,
+        )
+        .with_kind(ErrorKind::DepthLimit {
+            function: name.to_string(),
+        }));
+    }
+    let body = ctx.body_rpn(index).map_err(|e| e.for_function(name))?;
+    walk(body, arg, state, depth + 1).map_err(|e| e.for_function(name))
+}
+
+/// `taylor(f, a, n)` at `x`: the coefficients come from the series
+/// expansion; the polynomial is evaluated in `D`.
+fn taylor_at<D: Domain>(
+    f: FunctionRef,
+    args: &[D],
+    x: &D,
+    state: &mut EvalState,
+    depth: u8,
+    span: Span,
+) -> Result<D, EquationError> {
+    let fail = |message: String| EquationError::spanned(message, span);
+    if args.len() != 2 {
+        return Err(fail(format!(
+            "taylor takes exactly 3 parameters (f, a, n), got {}",
+            args.len() + 1
+        ))
+        .with_kind(ErrorKind::arity("taylor", 3, Some(3), args.len() + 1)));
+    }
+    let (Some(a), Some(n)) = (args[0].to_number(), args[1].to_number()) else {
+        return Err(fail(String::from(
+            "taylor's expansion point and order must be plain numbers",
+        )));
+    };
+    let coefficients = taylor::expand(f, a, n, state, depth, span)?;
+    let value = |message: String| value_error::<D>(message, span);
+    let t = x.sub(&D::number(a)).map_err(value)?;
+    coefficients
+        .iter()
+        .rev()
+        .try_fold(D::number(0.0), |acc, &c| {
+            acc.mul(&t)?.add(&D::number(c as f32))
+        })
+        .map_err(value)
+}
+
+fn walk<D: Domain>(
+    tokens: &[SpannedToken],
+    x: &D,
+    state: &mut EvalState,
+    depth: u8,
+) -> Result<D, EquationError> {
+    let mut stack: Vec<Slot<D>> = Vec::new();
+    let mut frames: Vec<usize> = Vec::new();
+
+    for spanned in tokens {
+        state.charge(1)?;
+        let token = spanned.token;
+        let span = spanned.span;
+        let fail = |message: String| EquationError::spanned(message, span);
+        let value = |message: String| value_error::<D>(message, span);
+        let pop = |stack: &mut Vec<Slot<D>>, what: &str| {
+            stack
+                .pop()
+                .ok_or_else(|| fail(format!("Insufficient operands for {what}")))
+        };
+
+        match token {
+            Token::Number(n) => stack.push(plain(D::number(n))),
+            Token::Constant(sym) => match sym.kind {
+                SymbolKind::Constant(v) => stack.push(plain(D::number(v))),
+                _ => {
+                    return Err(fail(format!(
+                        "Constant token for non-constant symbol '{}'",
+                        sym.name
+                    )))
+                }
+            },
+            Token::X => stack.push(plain(x.clone())),
+            Token::Parameter(i) => {
+                let v = state.parameter(i).ok_or_else(|| {
+                    fail(format!("Internal error: no value for fit parameter {i}"))
+                        .with_kind(ErrorKind::Internal)
+                })?;
+                stack.push(plain(D::parameter(v)));
+            }
+            Token::Unit(unit) => {
+                let v = D::unit(unit).map_err(|m| fail(m).with_kind(ErrorKind::Units))?;
+                stack.push(plain(v));
+            }
+            Token::Call(Callee::Catalog(sym)) => {
+                let v = pop(&mut stack, sym.name)?;
+                stack.push(plain(D::call(sym, &[v.val], state).map_err(value)?));
+            }
+            Token::Call(Callee::User(i)) => {
+                let v = pop(&mut stack, "function")?;
+                stack.push(plain(call_user(state, i, &v.val, depth, span)?));
+            }
+            Token::Call(Callee::Taylor(_)) => {
+                return Err(fail(String::from("taylor cannot be used after '|>'")));
+            }
+            Token::CallStart(_) => frames.push(stack.len()),
+            Token::EndCall(callee) => {
+                let start = frames
+                    .pop()
+                    .ok_or_else(|| fail(String::from("Unexpected end of call")))?;
+                let args: Vec<D> = stack.split_off(start).into_iter().map(|s| s.val).collect();
+                let n = args.len();
+                let result = match callee {
+                    Callee::User(i) => {
+                        if n != 1 {
+                            let name = state.ctx.map_or("?", |c| c.name(i));
+                            return Err(fail(format!(
+                                "{name} takes exactly 1 parameter (x), got {n}"
+                            ))
+                            .with_kind(ErrorKind::arity(
+                                name,
+                                1,
+                                Some(1),
+                                n,
+                            )));
+                        }
+                        call_user(state, i, &args[0], depth, span)?
+                    }
+                    Callee::Taylor(f) => taylor_at(f, &args, x, state, depth, span)?,
+                    Callee::Catalog(sym) => {
+                        let (min, max) = sym.kind.arity().ok_or_else(|| {
+                            fail(format!("EndCall for non-callable symbol '{}'", sym.name))
+                        })?;
+                        let arity =
+                            ErrorKind::arity(sym.name, usize::from(min), max.map(usize::from), n);
+                        if n < usize::from(min) {
+                            return Err(fail(format!(
+                                "{} requires at least {min} parameter{}, got {n}",
+                                sym.name,
+                                if min == 1 { "" } else { "s" }
+                            ))
+                            .with_kind(arity));
+                        }
+                        if let Some(max) = max.filter(|&m| n > usize::from(m)) {
+                            return Err(fail(format!(
+                                "{} accepts at most {max} parameter{}, got {n}",
+                                sym.name,
+                                if max == 1 { "" } else { "s" }
+                            ))
+                            .with_kind(arity));
+                        }
+                        D::call(sym, &args, state).map_err(value)?
+                    }
+                };
+                stack.push(plain(result));
+            }
+            Token::UnaryMinus => {
+                let v = pop(&mut stack, "unary minus operator")?;
+                stack.push(plain(v.val.neg()));
+            }
+            Token::Factorial => {
+                let v = pop(&mut stack, "factorial operator")?;
+                stack.push(plain(v.val.factorial().map_err(value)?));
+            }
+            Token::Percent => {
+                let v = pop(&mut stack, "percent operator")?;
+                stack.push(Slot {
+                    val: v.val.div(&D::number(100.0)).map_err(value)?,
+                    is_percent: true,
+                });
+            }
+            Token::Log { base } => {
+                let v = pop(&mut stack, "log function")?;
+                stack.push(plain(v.val.log(base).map_err(value)?));
+            }
+            Token::Plus
+            | Token::Minus
+            | Token::Star
+            | Token::UnitProduct
+            | Token::Slash
+            | Token::Modulo
+            | Token::Power => {
+                let (Some(rhs), Some(lhs)) = (stack.pop(), stack.pop()) else {
+                    return Err(fail("Invalid expression".into()));
+                };
+                let (l, r) = (&lhs.val, &rhs.val);
+                let result = match token {
+                    // A percent right operand is relative to the left one.
+                    Token::Plus if rhs.is_percent => l.add(&l.mul(r).map_err(value)?),
+                    Token::Minus if rhs.is_percent => l.sub(&l.mul(r).map_err(value)?),
+                    Token::Plus => l.add(r),
+                    Token::Minus => l.sub(r),
+                    Token::Star | Token::UnitProduct => l.mul(r),
+                    Token::Slash => l.div(r),
+                    Token::Modulo => l.rem(r),
+                    _ => l.pow(r),
+                };
+                stack.push(plain(result.map_err(value)?));
+            }
+            Token::Y
+            | Token::Equal
+            | Token::Comma
+            | Token::OpenParen
+            | Token::CloseParen
+            | Token::Pipe
+            | Token::End => {
+                return Err(fail(format!("Unexpected token in evaluation: {:?}", token)));
+            }
+        }
+    }
+
+    if tokens.is_empty() {
+        return Err(EquationError::new("Invalid equation supplied"));
+    }
+    if stack.len() != 1 {
+        return Err(EquationError::new(format!(
+            "Invalid evaluation: expected 1 result, found {} items in stack",
+            stack.len()
+        )));
+    }
+    stack
+        .pop()
+        .map(|s| s.val)
+        .ok_or_else(|| EquationError::new("Evaluation stack is empty"))
+}
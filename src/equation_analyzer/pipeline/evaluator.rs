@@ -1,12 +1,93 @@
 use crate::equation_analyzer::catalog::SymbolKind;
 use crate::equation_analyzer::definitions::CompiledDefinitions;
-use crate::equation_analyzer::errors::{EquationError, Span};
+use crate::equation_analyzer::errors::{EquationError, ErrorKind, Span};
+use crate::equation_analyzer::limits::{Budget, EvalLimits};
+use crate::equation_analyzer::structs::token::FunctionRef;
 use crate::equation_analyzer::structs::token::{Callee, SpannedToken, Token};
+use crate::equation_analyzer::taylor;
+use crate::equation_analyzer::trace::{Operation, TraceStep};
 use crate::utilities::factorial;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
 /// Deep enough for legitimate composition, shallow enough that a recursive
 /// definition (`g(x) = g(x)`) errors quickly instead of blowing the stack.
-const MAX_CALL_DEPTH: u8 = 32;
+pub(crate) const MAX_CALL_DEPTH: u8 = 32;
+
+/// State shared by one evaluation and every user-function call it makes.
+///
+/// The random-number generator is built lazily from `seed` on the first
+/// draw, so equations that never call a `Random` symbol pay nothing for it.
+/// It is shared across user calls: `g(x) = rand()` called twice draws twice.
+/// So is the step budget, when [`EvalLimits`] bound the evaluation, and the
+/// trace, when one is being recorded.
+pub(crate) struct EvalState<'a> {
+    pub(crate) ctx: Option<&'a CompiledDefinitions<'a>>,
+    seed: u64,
+    rng: Option<StdRng>,
+    budget: Option<Budget<'a>>,
+    trace: Option<Vec<TraceStep>>,
+    /// The user function whose body is being evaluated, for tracing.
+    function: Option<&'a str>,
+    /// The values of a fitted template's parameters.
+    parameters: &'a [f64],
+}
+
+impl<'a> EvalState<'a> {
+    pub(crate) fn new(ctx: Option<&'a CompiledDefinitions<'a>>, seed: u64) -> Self {
+        EvalState {
+            ctx,
+            seed,
+            rng: None,
+            budget: None,
+            trace: None,
+            function: None,
+            parameters: &[],
+        }
+    }
+
+    /// Binds the values a template's `Parameter` tokens read; see `fit`.
+    pub(crate) fn with_parameters(mut self, values: &'a [f64]) -> Self {
+        self.parameters = values;
+        self
+    }
+
+    pub(crate) fn parameter(&self, index: usize) -> Option<f64> {
+        self.parameters.get(index).copied()
+    }
+
+    /// Records every operator and function application from now on.
+    pub(crate) fn tracing(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    /// The steps recorded since [`tracing`](Self::tracing).
+    pub(crate) fn take_trace(&mut self) -> Vec<TraceStep> {
+        self.trace.take().unwrap_or_default()
+    }
+
+    /// Counts this evaluation's steps against `limits`.
+    pub(crate) fn with_limits(mut self, limits: &'a EvalLimits) -> Self {
+        if limits.bounds_evaluation() {
+            self.budget = Some(Budget::new(limits));
+        }
+        self
+    }
+
+    /// Records `steps` instructions; fails once a limit is hit.
+    pub(crate) fn charge(&mut self, steps: u64) -> Result<(), EquationError> {
+        match &mut self.budget {
+            Some(budget) => budget.charge(steps),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn rng(&mut self) -> &mut StdRng {
+        let seed = self.seed;
+        self.rng.get_or_insert_with(|| StdRng::seed_from_u64(seed))
+    }
+}
 
 /// Represents a function call frame for variadic functions
 struct FunctionFrame {
@@ -49,6 +130,7 @@ fn plural(n: u8) -> &'static str {
 /// # Arguments
 /// * `tokens` - An iterator of spanned tokens in RPN format
 /// * `x` - Optional value of the variable x (defaults to 0.0 if None)
+/// * `state` - Per-evaluation state: compiled definitions and the RNG seed
 ///
 /// # Returns
 /// * `Ok(f32)` - The result of the evaluation
@@ -68,19 +150,19 @@ fn plural(n: u8) -> &'static str {
 ///    - User calls: run the callee's compiled body RPN with x = the argument
 /// 4. Returns final stack value (should be exactly 1 value)
 ///
-/// `ctx` carries compiled user definitions for user-call dispatch (`None`
-/// when no definitions are in scope); it must be compiled from the same
-/// `Definitions` the tokens were tokenized against — user-call indices
-/// refer into it.
+/// `state` carries compiled user definitions for user-call dispatch (`None`
+/// when no definitions are in scope) — they must be compiled from the same
+/// `Definitions` the tokens were tokenized against, since user-call indices
+/// refer into them — and the seed for Random symbols.
 pub(crate) fn evaluate_with<I>(
     tokens: I,
     x: impl Into<Option<f32>>,
-    ctx: Option<&CompiledDefinitions>,
+    state: &mut EvalState,
 ) -> Result<f32, EquationError>
 where
     I: IntoIterator<Item = SpannedToken>,
 {
-    evaluate_at_depth(tokens, x.into().unwrap_or(0.0), ctx, 0)
+    evaluate_at_depth(tokens, x.into().unwrap_or(0.0), state, 0)
 }
 
 /// Runs one user-defined function call: depth-checks, fetches the compiled
@@ -88,48 +170,103 @@ where
 /// body are tagged with the function's name (innermost wins) so renderers
 /// know their spans refer to the body source.
 fn call_user(
-    ctx: Option<&CompiledDefinitions>,
+    state: &mut EvalState,
     index: usize,
     arg: f32,
     depth: u8,
     call_span: Span,
 ) -> Result<f32, EquationError> {
-    let Some(ctx) = ctx else {
+    let Some(ctx) = state.ctx else {
         return Err(EquationError::spanned(
             "Internal error: user call without definitions in scope",
             call_span,
-        ));
+        )
+        .with_kind(ErrorKind::Internal));
     };
     let name = ctx.name(index);
     if depth >= MAX_CALL_DEPTH {
         return Err(EquationError::spanned(
             format!("Call depth limit ({MAX_CALL_DEPTH}) exceeded — is '{name}' defined in terms of itself?"),
             call_span,
-        ));
+        )
+        .with_kind(ErrorKind::DepthLimit {
+            function: name.to_string(),
+        }));
+    }
+    if let Some(v) = ctx.recall(index, arg) {
+        return Ok(v);
     }
     let body = ctx.body_rpn(index).map_err(|e| e.for_function(name))?;
-    evaluate_at_depth(body.iter().copied(), arg, Some(ctx), depth + 1)
-        .map_err(|e| e.for_function(name))
+    let caller = state.function.replace(name);
+    let result = evaluate_at_depth(body.iter().copied(), arg, state, depth + 1);
+    state.function = caller;
+    if let Ok(v) = result {
+        ctx.remember(index, arg, v);
+    }
+    result.map_err(|e| e.for_function(name))
+}
+
+/// What `token` applies, for a trace; `None` for operands and frame
+/// markers. `rhs_percent` is whether the right operand of `+`/`-` was
+/// percent-tagged.
+fn traced_operation(
+    token: Token,
+    rhs_percent: bool,
+    ctx: Option<&CompiledDefinitions>,
+) -> Option<Operation> {
+    let user = |i: usize| ctx.map_or("?", |c| c.name(i)).to_string();
+    Some(match token {
+        Token::Plus if rhs_percent => Operation::RelativePercent("+"),
+        Token::Minus if rhs_percent => Operation::RelativePercent("-"),
+        Token::Plus => Operation::Binary("+"),
+        Token::Minus => Operation::Binary("-"),
+        Token::Star => Operation::Binary("*"),
+        Token::Slash => Operation::Binary("/"),
+        Token::Modulo => Operation::Binary("mod"),
+        Token::Power => Operation::Binary("^"),
+        Token::UnaryMinus => Operation::Negate,
+        Token::Factorial => Operation::Factorial,
+        Token::Percent => Operation::Percent,
+        Token::Log { base } => Operation::Call(format!("log_{base}")),
+        Token::Call(Callee::Catalog(sym)) | Token::EndCall(Callee::Catalog(sym)) => {
+            Operation::Call(sym.name.to_string())
+        }
+        Token::Call(Callee::User(i)) | Token::EndCall(Callee::User(i)) => Operation::Call(user(i)),
+        Token::EndCall(Callee::Taylor(FunctionRef::Catalog(sym))) => {
+            Operation::Taylor(sym.name.to_string())
+        }
+        Token::EndCall(Callee::Taylor(FunctionRef::User(i))) => Operation::Taylor(user(i)),
+        _ => return None,
+    })
 }
 
 fn evaluate_at_depth<I>(
     tokens: I,
     x: f32,
-    ctx: Option<&CompiledDefinitions>,
+    state: &mut EvalState,
     depth: u8,
 ) -> Result<f32, EquationError>
 where
     I: IntoIterator<Item = SpannedToken>,
 {
+    let ctx = state.ctx;
     let mut stack: Vec<StackVal> = Vec::new();
     let mut frames: Vec<FunctionFrame> = Vec::new();
     let mut token_count = 0;
 
     for spanned in tokens {
         token_count += 1;
+        state.charge(1)?;
         let token = spanned.token;
+        // The operands as they were, for a trace: whatever the token
+        // consumes is the top of this.
+        let before: Option<(Vec<f32>, bool)> = state.trace.as_ref().map(|_| {
+            let nums = stack.iter().map(|v| v.num).collect();
+            (nums, stack.last().is_some_and(|v| v.is_percent))
+        });
         // Attach the current token's span to an error message.
         let fail = |message: String| EquationError::spanned(message, spanned.span);
+        let domain = |message: String| EquationError::domain(message, spanned.span);
 
         match token {
             // Call: a pipe target (`x |> sin`) — the sole argument is
@@ -146,7 +283,7 @@ where
                     let v = stack.pop().ok_or_else(|| {
                         fail(format!("Insufficient operands for {} function", sym.name))
                     })?;
-                    stack.push(plain(f(v.num).map_err(fail)?));
+                    stack.push(plain(f(v.num).map_err(domain)?));
                 }
                 _ => {
                     return Err(fail(format!(
@@ -161,7 +298,12 @@ where
                     let name = ctx.map_or("?", |c| c.name(i));
                     fail(format!("Insufficient operands for {name} function"))
                 })?;
-                stack.push(plain(call_user(ctx, i, v.num, depth, spanned.span)?));
+                stack.push(plain(call_user(state, i, v.num, depth, spanned.span)?));
+            }
+            // The tokenizer only builds Taylor callees for `taylor(` — never
+            // a pipe target.
+            Token::Call(Callee::Taylor(_)) => {
+                return Err(fail(String::from("taylor cannot be used after '|>'")));
             }
             // CallStart: a parenthesized call opens a frame; its arguments
             // collect on the stack until the matching EndCall.
@@ -179,14 +321,36 @@ where
                     .ok_or_else(|| fail(format!("Unexpected end of {name} call")))?;
                 let n = stack.len().saturating_sub(frame.stack_position);
                 if n != 1 {
-                    return Err(fail(format!(
-                        "{name} takes exactly 1 parameter (x), got {n}"
-                    )));
+                    return Err(
+                        fail(format!("{name} takes exactly 1 parameter (x), got {n}"))
+                            .with_kind(ErrorKind::arity(name, 1, Some(1), n)),
+                    );
                 }
                 let arg = stack
                     .pop()
                     .ok_or_else(|| fail(format!("Insufficient operands for {name}")))?;
-                stack.push(plain(call_user(ctx, i, arg.num, depth, spanned.span)?));
+                stack.push(plain(call_user(state, i, arg.num, depth, spanned.span)?));
+            }
+            // `taylor(f, a, n)` closes: expand `f` about `a`, then evaluate
+            // the polynomial at `x`.
+            Token::EndCall(Callee::Taylor(f)) => {
+                let frame = frames
+                    .pop()
+                    .ok_or_else(|| fail(String::from("Unexpected end of taylor call")))?;
+                let n = stack.len().saturating_sub(frame.stack_position);
+                if n != 2 {
+                    return Err(fail(format!(
+                        "taylor takes exactly 3 parameters (f, a, n), got {}",
+                        n + 1
+                    ))
+                    .with_kind(ErrorKind::arity("taylor", 3, Some(3), n + 1)));
+                }
+                let (Some(order), Some(a)) = (stack.pop(), stack.pop()) else {
+                    return Err(fail(String::from("Insufficient operands for taylor")));
+                };
+                let coefficients = taylor::expand(f, a.num, order.num, state, depth, spanned.span)?;
+                let t = f64::from(x) - f64::from(a.num);
+                stack.push(plain(taylor::horner(&coefficients, t) as f32));
             }
             // EndCall: close the frame, enforce the catalog's arity, and
             // dispatch. Its span covers the whole call (`ch(25, 2)`), so
@@ -197,19 +361,19 @@ where
                     .ok_or_else(|| fail(format!("Unexpected end of {} call", sym.name)))?;
                 let n = stack.len().saturating_sub(frame.stack_position);
 
-                let (min_args, max_args) = match sym.kind {
-                    SymbolKind::Unary(_) | SymbolKind::UnaryChecked(_) => (1, Some(1)),
-                    SymbolKind::Variadic {
-                        min_args, max_args, ..
-                    } => (min_args, max_args),
-                    _ => {
-                        return Err(fail(format!(
-                            "EndCall for non-callable symbol '{}'",
-                            sym.name
-                        )));
-                    }
+                let Some((min_args, max_args)) =
+                    sym.kind.arity().filter(|_| sym.kind.is_callable())
+                else {
+                    return Err(fail(format!(
+                        "EndCall for non-callable symbol '{}'",
+                        sym.name
+                    )));
                 };
 
+                let arity = || {
+                    let max = max_args.map(usize::from);
+                    ErrorKind::arity(sym.name, usize::from(min_args), max, n)
+                };
                 if (n as u32) < min_args as u32 {
                     return Err(fail(format!(
                         "{} requires at least {} {}, got {}",
@@ -217,7 +381,8 @@ where
                         min_args,
                         plural(min_args),
                         n
-                    )));
+                    ))
+                    .with_kind(arity()));
                 }
                 if let Some(max) = max_args {
                     if (n as u32) > max as u32 {
@@ -227,7 +392,8 @@ where
                             max,
                             plural(max),
                             n
-                        )));
+                        ))
+                        .with_kind(arity()));
                     }
                 }
 
@@ -236,7 +402,7 @@ where
                     // stack top — no argument buffer needed.
                     SymbolKind::Unary(f) => stack.pop().map(|v| f(v.num)),
                     SymbolKind::UnaryChecked(f) => match stack.pop() {
-                        Some(v) => Some(f(v.num).map_err(fail)?),
+                        Some(v) => Some(f(v.num).map_err(domain)?),
                         None => None,
                     },
                     SymbolKind::Variadic { run, .. } => {
@@ -245,7 +411,15 @@ where
                             .iter()
                             .map(|v| v.num)
                             .collect();
-                        Some(run(&params).map_err(fail)?)
+                        Some(run(&params).map_err(domain)?)
+                    }
+                    SymbolKind::Random { run, .. } => {
+                        let params: Vec<f32> = stack
+                            .split_off(frame.stack_position)
+                            .iter()
+                            .map(|v| v.num)
+                            .collect();
+                        Some(run(state.rng(), &params).map_err(domain)?)
                     }
                     // Excluded by the arity match above.
                     _ => None,
@@ -266,6 +440,27 @@ where
                 }
             }
             Token::Number(n) => stack.push(plain(n)),
+            // The plain tokenizer never emits units; only the unit-aware
+            // walker (see `domain`) can give them a value.
+            Token::Unit(unit) => {
+                return Err(fail(format!(
+                    "Unit '{}' requires unit-aware evaluation",
+                    unit.name
+                )));
+            }
+            Token::UnitProduct => {
+                return Err(fail(String::from(
+                    "Unit products require unit-aware evaluation",
+                )));
+            }
+            // Only `fit` substitutes parameters, and it evaluates in f64
+            // (see `domain`).
+            Token::Parameter(_) => {
+                return Err(fail(String::from(
+                    "Internal error: fit parameter in plain evaluation",
+                ))
+                .with_kind(ErrorKind::Internal));
+            }
             Token::X => stack.push(plain(x)),
             Token::UnaryMinus => {
                 let temp = stack
@@ -279,11 +474,11 @@ where
                     .ok_or_else(|| fail("Insufficient operands for factorial operator".into()))?
                     .num;
                 if temp < 0.0 || temp % 1.0 != 0.0 {
-                    return Err(fail(
+                    return Err(domain(
                         "Factorial is only defined for non-negative integers".into(),
                     ));
                 }
-                stack.push(plain(factorial(temp as isize).map_err(fail)? as f32));
+                stack.push(plain(factorial(temp as isize).map_err(domain)? as f32));
             }
             // Postfix `%`: divide by 100 and tag the result so a following
             // `+`/`-` can scale it against the left operand (handheld
@@ -351,6 +546,20 @@ where
                 return Err(fail(format!("Unexpected token in evaluation: {:?}", token)));
             }
         }
+
+        if let Some((before, rhs_percent)) = before {
+            let step = traced_operation(token, rhs_percent, ctx).zip(stack.last());
+            if let (Some((operation, output)), Some(trace)) = (step, state.trace.as_mut()) {
+                let consumed = (before.len() + 1).saturating_sub(stack.len());
+                trace.push(TraceStep {
+                    operation,
+                    inputs: before[before.len().saturating_sub(consumed)..].to_vec(),
+                    output: output.num,
+                    span: spanned.span,
+                    in_function: state.function.map(str::to_string),
+                });
+            }
+        }
     }
 
     if token_count == 0 {
@@ -1,3 +1,5 @@
+pub(crate) mod bytecode;
+pub(crate) mod domain;
 pub(crate) mod evaluator;
 pub(crate) mod parser;
 pub(crate) mod tokenizer;
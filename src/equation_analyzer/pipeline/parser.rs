@@ -1,4 +1,4 @@
-use crate::equation_analyzer::errors::{EquationError, Span};
+use crate::equation_analyzer::errors::{EquationError, ErrorKind, Span};
 use crate::equation_analyzer::structs::operands::{get_operator, Assoc, Operand};
 use crate::equation_analyzer::structs::token::{Callee, SpannedToken, Token};
 
@@ -75,25 +75,53 @@ pub(crate) fn parse<I>(tokens: I) -> Result<Vec<SpannedToken>, EquationError>
 where
     I: IntoIterator<Item = Result<SpannedToken, EquationError>>,
 {
-    let mut operator_stack: Vec<Operand> = Vec::new();
-    let mut output: Vec<SpannedToken> = Vec::new();
-    let mut paren_depth = 0;
-    let mut frames: Vec<ParserFrame> = Vec::new();
-    let mut found_end = false;
-    // Span of the pipe operator awaiting its function, when one is armed.
-    let mut expect_piped_function: Option<Span> = None;
-
+    let mut parser = Parser::new();
     for token_result in tokens {
-        let spanned = token_result?;
+        parser.push(token_result?)?;
+    }
+    parser.finish()
+}
+
+/// The Shunting Yard state behind [`parse`], fed one token at a time.
+///
+/// A token that fails leaves the parser ready for the next one, so
+/// [`diagnostics`](crate::equation_analyzer::diagnostics) can keep going
+/// past an error; `parse` simply stops at the first.
+pub(crate) struct Parser {
+    operator_stack: Vec<Operand>,
+    output: Vec<SpannedToken>,
+    paren_depth: i32,
+    frames: Vec<ParserFrame>,
+    /// Where the input ended, once the End token has arrived.
+    end: Option<Span>,
+    /// Span of the pipe operator awaiting its function, when one is armed.
+    expect_piped_function: Option<Span>,
+}
+
+impl Parser {
+    pub(crate) fn new() -> Self {
+        Parser {
+            operator_stack: Vec::new(),
+            output: Vec::new(),
+            paren_depth: 0,
+            frames: Vec::new(),
+            end: None,
+            expect_piped_function: None,
+        }
+    }
+
+    /// Consumes one infix token.
+    pub(crate) fn push(&mut self, spanned: SpannedToken) -> Result<(), EquationError> {
         let token = spanned.token;
+        let operator_stack = &mut self.operator_stack;
+        let output = &mut self.output;
 
         // After a Pipe, the next token must be a unary Call. Emit it directly
         // to the output queue (it's already in postfix position).
-        if expect_piped_function.is_some() {
+        if self.expect_piped_function.take().is_some() {
             if is_unary_call(token) {
                 output.push(spanned);
-                expect_piped_function = None;
-                continue;
+                return Ok(());
             }
             return Err(EquationError::spanned(
                 format!("Expected a unary function after '|>', got {:?}", token),
@@ -103,38 +131,38 @@ where
 
         // Handle variadic function parameter collection
         // With frame-based evaluation, we now allow full expressions in parameters
-        if let Some(frame) = frames.last() {
+        if let Some(frame) = self.frames.last() {
             match token {
                 // Comma: pop all pending operators (they belong to current parameter expression)
                 Token::Comma => {
-                    pop_until_paren_opener(&mut operator_stack, &mut output);
+                    pop_until_paren_opener(operator_stack, output);
 
                     // The comma must sit directly at this frame's boundary;
                     // one inside a nested unary call or plain parens would
                     // otherwise silently re-split the frame's arguments
                     // (`avg(1, sin(2,3))` must not become avg(1, 2, sin(3))).
                     if operator_stack.len() != frame.operator_stack_position + 1 {
-                        return Err(comma_error(&operator_stack, spanned.span));
+                        return Err(comma_error(operator_stack, spanned.span));
                     }
-                    continue;
+                    return Ok(());
                 }
 
                 // CloseParen might end the variadic function, or a nested regular function
                 Token::CloseParen => {
-                    pop_until_paren_opener(&mut operator_stack, &mut output);
+                    pop_until_paren_opener(operator_stack, output);
 
                     // Check if we've drained back to the frame boundary
                     if operator_stack.len() == frame.operator_stack_position + 1 {
                         operator_stack.pop();
-                        paren_depth -= 1;
+                        self.paren_depth -= 1;
                         // The EndCall's span covers the whole call, from the
                         // function name through this closing paren.
                         output.push(SpannedToken::new(
                             Token::EndCall(frame.callee),
                             Span::new(frame.call_span.start, spanned.span.end),
                         ));
-                        frames.pop();
-                        continue;
+                        self.frames.pop();
+                        return Ok(());
                     }
 
                     // Otherwise, it's a regular function or parenthesis - fall through to normal processing
@@ -147,14 +175,18 @@ where
 
         match token {
             // Skip equation markers
-            Token::Y | Token::Equal => continue,
+            Token::Y | Token::Equal => {}
 
             // A comma outside a call's parentheses is an error, not a
             // separator to skip (see comma_error).
-            Token::Comma => return Err(comma_error(&operator_stack, spanned.span)),
+            Token::Comma => return Err(comma_error(operator_stack, spanned.span)),
 
             // Constants and operands go directly to output
-            Token::Constant(_) | Token::Number(_) | Token::X => output.push(spanned),
+            Token::Constant(_)
+            | Token::Number(_)
+            | Token::X
+            | Token::Unit(_)
+            | Token::Parameter(_) => output.push(spanned),
 
             // Every parenthesized call — unary or variadic, catalog or
             // user-defined — starts a frame; the callee's arity is enforced
@@ -162,43 +194,43 @@ where
             // OpenParen; we push a synthetic marker to fence off preceding
             // operators until the matching CloseParen.
             Token::Call(callee) => {
+                let fence = get_operator(SpannedToken::new(Token::OpenParen, spanned.span))?;
                 output.push(SpannedToken::new(Token::CallStart(callee), spanned.span));
-                frames.push(ParserFrame {
+                self.frames.push(ParserFrame {
                     callee,
                     call_span: spanned.span,
                     operator_stack_position: operator_stack.len(),
                 });
-                operator_stack.push(get_operator(SpannedToken::new(
-                    Token::OpenParen,
-                    spanned.span,
-                ))?);
-                paren_depth += 1;
+                operator_stack.push(fence);
+                self.paren_depth += 1;
             }
 
             // log_N and opening parenthesis go on operator stack.
             Token::Log { .. } | Token::OpenParen => {
-                paren_depth += 1;
                 operator_stack.push(get_operator(spanned)?);
+                self.paren_depth += 1;
             }
 
             // Closing parenthesis: pop operators until matching open paren
             Token::CloseParen => {
-                paren_depth -= 1;
-
-                if paren_depth < 0 {
+                if self.paren_depth <= 0 {
                     return Err(EquationError::spanned(
                         "Invalid closing parenthesis",
                         spanned.span,
-                    ));
+                    )
+                    .with_kind(ErrorKind::UnbalancedParens)
+                    .with_fix(spanned.span, ""));
                 }
+                self.paren_depth -= 1;
 
-                pop_until_paren_opener(&mut operator_stack, &mut output);
+                pop_until_paren_opener(operator_stack, output);
 
                 // The fence is either a plain parenthesis (dropped) or a
                 // pending function call / log_N (emitted now that its
                 // argument is complete).
                 let opener = operator_stack.pop().ok_or_else(|| {
                     EquationError::spanned("Mismatched parentheses", spanned.span)
+                        .with_kind(ErrorKind::UnbalancedParens)
                 })?;
                 if opener.is_func {
                     output.push(opener.token);
@@ -207,6 +239,7 @@ where
 
             // Operators: apply precedence and associativity rules
             Token::Star
+            | Token::UnitProduct
             | Token::Slash
             | Token::Plus
             | Token::Minus
@@ -233,6 +266,7 @@ where
 
                     let o_2_new = operator_stack.pop().ok_or_else(|| {
                         EquationError::new("Internal error: operator stack became empty")
+                            .with_kind(ErrorKind::Internal)
                     })?;
                     output.push(o_2_new.token);
                 }
@@ -243,13 +277,13 @@ where
             // Pipe operator: flush pending operators (down to current paren depth)
             // and arm `expect_piped_function` so the next token is emitted directly.
             Token::Pipe => {
-                pop_until_paren_opener(&mut operator_stack, &mut output);
-                expect_piped_function = Some(spanned.span);
+                pop_until_paren_opener(operator_stack, output);
+                self.expect_piped_function = Some(spanned.span);
             }
 
             // End token marks completion
             Token::End => {
-                found_end = true;
+                self.end = Some(spanned.span);
             }
 
             // Parser-synthesized tokens must never appear in the input stream
@@ -260,44 +294,96 @@ where
                 ));
             }
         }
+        Ok(())
     }
 
-    // An unclosed call (`sqrt(4`) — its fence marker is still on the stack;
-    // report it against the call itself.
-    if let Some(frame) = frames.last() {
-        return Err(EquationError::spanned("Invalid function", frame.call_span));
+    /// Whether a call's parentheses are open, so a comma separates its
+    /// arguments.
+    pub(crate) fn in_call(&self) -> bool {
+        !self.frames.is_empty()
     }
 
-    // Pop remaining operators from stack. A leftover parenthesis opener —
-    // a bare `(` or an unclosed `log_N(` — is an error that points at the
-    // opener itself.
-    while let Some(op) = operator_stack.pop() {
-        if op.paren_opener {
-            let message = if matches!(op.token.token, Token::OpenParen) {
-                "Invalid opening parenthesis"
-            } else {
-                "Invalid function"
-            };
-            return Err(EquationError::spanned(message, op.token.span));
-        }
-        output.push(op.token);
+    /// Whether a closing parenthesis has something to close.
+    pub(crate) fn open_parens(&self) -> bool {
+        self.paren_depth > 0
     }
 
-    // Validation
-    if paren_depth != 0 {
-        return Err(EquationError::new("Invalid function"));
+    /// Forgets a pending `|>` whose function failed to tokenize, so the
+    /// same mistake isn't reported again as a dangling pipe.
+    pub(crate) fn recover(&mut self) {
+        self.expect_piped_function = None;
     }
 
-    if !found_end {
-        return Err(EquationError::new("No end token found"));
+    /// Ends the input: the RPN, or the first problem with how it ended.
+    pub(crate) fn finish(self) -> Result<Vec<SpannedToken>, EquationError> {
+        let (output, errors) = self.finish_all();
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(output),
+        }
     }
 
-    if let Some(pipe_span) = expect_piped_function {
-        return Err(EquationError::spanned(
-            "Dangling '|>': expected a unary function on the right side",
-            pipe_span,
-        ));
-    }
+    /// Ends the input, reporting every unclosed call and parenthesis rather
+    /// than the first.
+    pub(crate) fn finish_all(mut self) -> (Vec<SpannedToken>, Vec<EquationError>) {
+        let mut errors = Vec::new();
+        // Each unclosed opener can be fixed by closing it at the end.
+        let end = self.end;
+        let unclosed = |message: &str, span: Span| {
+            let error =
+                EquationError::spanned(message, span).with_kind(ErrorKind::UnbalancedParens);
+            match end {
+                Some(end) => error.with_fix(end, ")"),
+                None => error,
+            }
+        };
+
+        // An unclosed call (`sqrt(4`) — its fence marker is still on the
+        // stack; report it against the call itself, innermost first.
+        for frame in self.frames.iter().rev() {
+            errors.push(unclosed("Invalid function", frame.call_span));
+        }
+
+        // Pop remaining operators from stack. A leftover parenthesis opener —
+        // a bare `(` or an unclosed `log_N(` — is an error that points at the
+        // opener itself; a call's fence was reported above.
+        while let Some(op) = self.operator_stack.pop() {
+            if op.paren_opener {
+                let fence = self
+                    .frames
+                    .iter()
+                    .any(|f| f.operator_stack_position == self.operator_stack.len());
+                if !fence {
+                    let message = if matches!(op.token.token, Token::OpenParen) {
+                        "Invalid opening parenthesis"
+                    } else {
+                        "Invalid function"
+                    };
+                    errors.push(unclosed(message, op.token.span));
+                }
+                continue;
+            }
+            self.output.push(op.token);
+        }
 
-    Ok(output)
+        // Validation
+        if errors.is_empty() && self.paren_depth != 0 {
+            errors.push(
+                EquationError::new("Invalid function").with_kind(ErrorKind::UnbalancedParens),
+            );
+        }
+
+        if self.end.is_none() {
+            errors.push(EquationError::new("No end token found"));
+        }
+
+        if let Some(pipe_span) = self.expect_piped_function {
+            errors.push(EquationError::spanned(
+                "Dangling '|>': expected a unary function on the right side",
+                pipe_span,
+            ));
+        }
+
+        (self.output, errors)
+    }
 }
@@ -1,13 +1,16 @@
 use crate::equation_analyzer::catalog::{self, SymbolKind};
 use crate::equation_analyzer::definitions::{Definitions, Resolved};
-use crate::equation_analyzer::errors::{EquationError, Span};
-use crate::equation_analyzer::structs::token::{Callee, SpannedToken, Token};
+use crate::equation_analyzer::errors::{EquationError, ErrorKind, Span};
+use crate::equation_analyzer::structs::token::{Callee, FunctionRef, SpannedToken, Token};
+use crate::equation_analyzer::units;
 use std::collections::VecDeque;
 use std::iter::Peekable;
 use std::str::Chars;
 
 /// May `c` continue an identifier? The first character must be alphabetic;
 /// continuation characters may also be ASCII digits (`atan2`, `log10`).
+/// A `.` joins the segments of a qualified name (`phys.g`), but only
+/// between a name and a letter; see `scan_identifier`.
 fn continues_identifier(c: char) -> bool {
     c.is_alphabetic() || c.is_ascii_digit()
 }
@@ -15,7 +18,7 @@ fn continues_identifier(c: char) -> bool {
 /// Levenshtein distance using a single rolling row of DP state. Byte-based:
 /// every "did you mean" candidate is an ASCII function name, and a multi-byte
 /// input just inflates the distance toward "no suggestion".
-fn levenshtein(word1: &str, word2: &str) -> usize {
+pub(crate) fn levenshtein(word1: &str, word2: &str) -> usize {
     let (a, b) = (word1.as_bytes(), word2.as_bytes());
     let mut prev: Vec<usize> = (0..=b.len()).collect();
     let mut curr = vec![0usize; b.len() + 1];
@@ -34,6 +37,17 @@ fn levenshtein(word1: &str, word2: &str) -> usize {
     prev[b.len()]
 }
 
+/// How many edits may separate a typed name from a suggestion: 1, or 2
+/// for names of five or more characters. Strict on purpose — a missed
+/// suggestion beats a silly one.
+pub(crate) fn suggestion_distance(name: &str) -> usize {
+    if name.len() >= 5 {
+        2
+    } else {
+        1
+    }
+}
+
 /// A streaming tokenizer that implements Iterator, yielding tokens on demand.
 ///
 /// `chars` is the only cursor; `position` counts characters and doubles as
@@ -49,6 +63,9 @@ pub(crate) struct StreamingTokenizer<'a> {
     pending_tokens: VecDeque<SpannedToken>,
     /// User definitions consulted for identifiers the catalog doesn't claim.
     defs: Option<&'a Definitions>,
+    /// Unit-aware mode: names nothing else claims resolve against the unit
+    /// registry, and `convert(…)` is callable.
+    units: bool,
 }
 
 impl<'a> StreamingTokenizer<'a> {
@@ -68,9 +85,51 @@ impl<'a> StreamingTokenizer<'a> {
             finished: false,
             pending_tokens: VecDeque::new(),
             defs,
+            units: false,
         })
     }
 
+    /// Switches on unit-aware mode (see [`units`](crate::equation_analyzer::units)).
+    pub(crate) fn with_units(mut self) -> Self {
+        self.units = true;
+        self
+    }
+
+    /// Characters consumed so far. After an error this may lie past the
+    /// error's span (`foo_` points at the `_` but has consumed `foo`).
+    pub(crate) fn consumed(&self) -> usize {
+        self.position
+    }
+
+    /// Whether the previous token ends an operand, so that what follows
+    /// it is a binary operator (or, for a unit, an implied product).
+    fn after_operand(&self) -> bool {
+        matches!(
+            self.previous_token,
+            Some(
+                Token::Constant(_)
+                    | Token::Number(_)
+                    | Token::CloseParen
+                    | Token::X
+                    | Token::Factorial
+                    | Token::Percent
+                    | Token::Unit(_)
+            )
+        )
+    }
+
+    /// Emits a unit operand. Juxtaposed after an operand (`3 m`, `2 s`),
+    /// the unit is multiplied in: a `UnitProduct` goes out first and the unit
+    /// waits in the pending queue. Both carry the unit name's span.
+    fn unit_token(&mut self, unit: &'static units::Unit) -> SpannedToken {
+        if self.after_operand() {
+            let span = self.lexeme_span();
+            self.pending_tokens
+                .push_back(SpannedToken::new(Token::Unit(unit), span));
+            return self.emit(Token::UnitProduct);
+        }
+        self.emit(Token::Unit(unit))
+    }
     /// Looks `name` up in the user definitions. Only called after the
     /// catalog has declined the name — catalog resolution always wins.
     fn resolve_user(&self, name: &str) -> Option<Resolved> {
@@ -203,6 +262,24 @@ impl<'a> StreamingTokenizer<'a> {
         first
     }
 
+    /// Scans a full identifier, qualified names included: segments join
+    /// with a `.` only when a letter follows it, so `phys.g` is one name
+    /// while `a.5` is not.
+    fn scan_identifier(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(ch) = self.peek() {
+            let joins_segment =
+                ch == '.' && !name.is_empty() && self.peek_nth(1).is_some_and(char::is_alphabetic);
+            if ch.is_alphabetic() || (!name.is_empty() && ch.is_ascii_digit()) || joins_segment {
+                name.push(ch);
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        name
+    }
+
     fn scan_word(&mut self) -> Result<SpannedToken, EquationError> {
         // The two reserved single letters — the variable and the equation
         // marker — are by far the most common identifiers; resolving them
@@ -216,15 +293,7 @@ impl<'a> StreamingTokenizer<'a> {
             }
         }
 
-        let mut name = String::new();
-        while let Some(ch) = self.peek() {
-            if ch.is_alphabetic() || (!name.is_empty() && ch.is_ascii_digit()) {
-                name.push(ch);
-                self.advance();
-            } else {
-                break;
-            }
-        }
+        let name = self.scan_identifier();
 
         // Pipe target: name must be a unary function — catalog or
         // user-defined (user functions are always unary) — no parens follow.
@@ -297,7 +366,10 @@ impl<'a> StreamingTokenizer<'a> {
             };
         }
 
-        // User definitions resolve after every catalog form: values become
+        // User definitions resolve after the catalog's constants and
+        // operators but before its functions, so a definition shadows the
+        // catalog functions it may be named after (see
+        // `Category::is_shadowable`). Values become
         // number literals carrying the identifier's span, functions become
         // calls. The value is read at tokenize time, which *is* call time
         // for function bodies — they're compiled fresh each evaluation pass.
@@ -319,40 +391,123 @@ impl<'a> StreamingTokenizer<'a> {
             None => {}
         }
 
+        // `convert` is catalogued but only callable when units are on.
+        if let Some(sym) =
+            catalog::find(&name).filter(|s| matches!(s.kind, SymbolKind::UnitConversion))
+        {
+            if !self.units {
+                return Err(self.err_here(format!(
+                    "'{}' requires unit-aware evaluation (calculate_units)",
+                    name
+                )));
+            }
+            if !called_with_parens {
+                return Err(self.err_here(format!("Function '{}' requires parentheses", name)));
+            }
+            self.advance(); // consume '('
+            return Ok(self.emit(Token::Call(Callee::Catalog(sym))));
+        }
+
+        // `taylor(f, a, n)` names a function as its first argument. It is
+        // resolved here, so the parser sees an ordinary frame over `a, n`.
+        if let Some(sym) = catalog::find(&name).filter(|s| matches!(s.kind, SymbolKind::Series)) {
+            if !called_with_parens {
+                return Err(self.err_here(format!("Function '{}' requires parentheses", name)));
+            }
+            self.advance(); // consume '('
+            let target = self.scan_function_ref(sym.name)?;
+            return Ok(self.emit(Token::Call(Callee::Taylor(target))));
+        }
+
+        // Units resolve last, so neither the catalog nor a user definition
+        // can be shadowed by a unit symbol.
+        if self.units && !called_with_parens {
+            if let Some(unit) = units::find(&name) {
+                return Ok(self.unit_token(unit));
+            }
+        }
+
         if !called_with_parens {
             // A known function used bare gets a pointer at the fix; a name
             // nothing claims gets called what it is: unknown.
             return Err(if catalog::find(&name).is_some() {
                 self.err_here(format!("Function '{}' requires parentheses", name))
             } else {
-                self.err_here(format!("Unknown name '{}'", name))
+                self.err_here(format!("Unknown name '{}'", name)).with_kind(
+                    ErrorKind::UnknownName {
+                        name,
+                        suggestion: None,
+                    },
+                )
             });
         }
 
-        let sym = catalog::find(&name)
-            .filter(|s| s.kind.is_unary() || s.kind.is_variadic())
-            .ok_or_else(|| match self.suggest_function(&name) {
-                Some(s) => self.err_here(format!(
-                    "Invalid function name {} — did you mean '{s}'?",
-                    name
-                )),
+        let Some(sym) = catalog::find(&name).filter(|s| s.kind.is_callable()) else {
+            let suggestion = self.suggest_function(&name);
+            let error = match &suggestion {
+                Some(s) => self
+                    .err_here(format!(
+                        "Invalid function name {} — did you mean '{s}'?",
+                        name
+                    ))
+                    .with_fix(self.lexeme_span(), s.as_str()),
                 None => self.err_here(format!("Invalid function name {}", name)),
-            })?;
+            };
+            return Err(error.with_kind(ErrorKind::UnknownName { name, suggestion }));
+        };
 
         self.advance(); // consume '('
         Ok(self.emit(Token::Call(Callee::Catalog(sym))))
     }
 
+    /// Scans the `f,` that opens `taylor(f, a, n)`: a unary function —
+    /// catalog or user-defined — named without parentheses, then the comma.
+    fn scan_function_ref(&mut self, caller: &str) -> Result<FunctionRef, EquationError> {
+        while matches!(self.peek(), Some(' ' | '\r' | '\t')) {
+            self.advance();
+        }
+        let start = self.position;
+        let name = self.scan_identifier();
+        let span = Span::new(start, self.position.max(start + 1));
+
+        let target = match catalog::find(&name).filter(|s| s.kind.is_unary()) {
+            Some(sym) => FunctionRef::Catalog(sym),
+            None => match self.resolve_user(&name) {
+                Some(Resolved::Function(i)) => FunctionRef::User(i),
+                _ => {
+                    return Err(EquationError::spanned(
+                        format!(
+                        "{caller} expects a unary function name first, as in {caller}(sin, 0, 5)"
+                    ),
+                        span,
+                    ))
+                }
+            },
+        };
+
+        while matches!(self.peek(), Some(' ' | '\r' | '\t')) {
+            self.advance();
+        }
+        if self.peek() != Some(',') {
+            return Err(EquationError::spanned(
+                format!("Expected ',' after '{name}' in {caller}(f, a, n)"),
+                span,
+            ));
+        }
+        self.advance(); // consume ','
+        Ok(target)
+    }
+
     /// The closest callable name to `name` — catalog functions, their
     /// aliases, and user-defined functions — for "did you mean" on a bad
-    /// call. Strict on distance (1 edit, or 2 for names of five+ chars):
-    /// a missed suggestion beats a silly one.
+    /// call, within [`suggestion_distance`]. A qualified name is also
+    /// compared by its last segment, so `pmt` finds `fin.pmt`.
     fn suggest_function(&self, name: &str) -> Option<String> {
-        let max_dist = if name.len() >= 5 { 2 } else { 1 };
+        let max_dist = suggestion_distance(name);
 
         let catalog_names = catalog::all()
             .iter()
-            .filter(|s| s.kind.is_unary() || s.kind.is_variadic())
+            .filter(|s| s.kind.is_callable())
             .flat_map(|s| std::iter::once(s.name).chain(s.aliases.iter().copied()));
         let user_names = self
             .defs
@@ -367,7 +522,10 @@ impl<'a> StreamingTokenizer<'a> {
 
         let mut best: Option<(usize, &str)> = None;
         for candidate in catalog_names.chain(user_names) {
-            let dist = levenshtein(name, candidate);
+            let unqualified = candidate
+                .rsplit_once('.')
+                .map_or(usize::MAX, |(_, last)| levenshtein(name, last));
+            let dist = levenshtein(name, candidate).min(unqualified);
             if dist <= max_dist && best.is_none_or(|(d, _)| dist < d) {
                 best = Some((dist, candidate));
             }
@@ -423,17 +581,7 @@ impl<'a> StreamingTokenizer<'a> {
                 // Binary subtraction after an operand, unary negation
                 // otherwise. Percent is in the operand list because it's
                 // postfix: `50% - 3`.
-                if matches!(
-                    self.previous_token,
-                    Some(
-                        Token::Constant(_)
-                            | Token::Number(_)
-                            | Token::CloseParen
-                            | Token::X
-                            | Token::Factorial
-                            | Token::Percent
-                    )
-                ) {
+                if self.after_operand() {
                     self.emit(Token::Minus)
                 } else {
                     self.emit(Token::UnaryMinus)
@@ -0,0 +1,253 @@
+//! Plotting beyond a single `Vec<Point>`: a lazy per-sample iterator, and
+//! plots that keep going past failing samples.
+//!
+//! [`plot`](crate::equation_analyzer::calculator::plot) materializes every
+//! sample and fails the whole range on the first erroring `x`.
+//! [`plot_iter`](crate::equation_analyzer::calculator::plot_iter) yields
+//! one `Result<Point, EquationError>` per sample instead, computing them a
+//! block at a time as it is advanced — huge ranges stream in constant
+//! memory, consumers can stop early, and the valid points around a domain
+//! error survive it:
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::plot_iter;
+//!
+//! // root(x, 2) is undefined left of 0, but the right half still plots.
+//! let ys: Vec<f32> = plot_iter("root(x, 2)", -4.0, 4.0, 1.0)
+//!     .unwrap()
+//!     .filter_map(Result::ok)
+//!     .map(|p| p.y)
+//!     .collect();
+//! assert_eq!(ys, vec![0.0, 1.0, 2.0_f32.sqrt(), 3.0_f32.sqrt(), 2.0]);
+//! ```
+//!
+//! [`plot_tolerant`](crate::equation_analyzer::calculator::plot_tolerant)
+//! evaluates the whole range in parallel, like `plot`, but records each
+//! failing sample as a gap in a [`TolerantPlot`], with the runs of gaps
+//! summarized as [`Gap`] ranges — enough to draw the defined part of a
+//! curve and shade where it is undefined.
+
+use crate::equation_analyzer::definitions::MemoizedDefinitions;
+use crate::equation_analyzer::errors::EquationError;
+use crate::equation_analyzer::limits::EvalLimits;
+use crate::equation_analyzer::pipeline::bytecode::{BatchEnv, BatchScratch, Bytecode, LANES};
+use crate::equation_analyzer::utils::{sample_count, x_at, Point};
+use std::collections::VecDeque;
+
+/// The samples of a plot, computed lazily; from
+/// [`plot_iter`](crate::equation_analyzer::calculator::plot_iter).
+///
+/// Samples are the same `x` values, in the same order, as
+/// [`plot`](crate::equation_analyzer::calculator::plot) produces, and
+/// each result is what `plot` computes there.
+///
+/// When the deadline in its [`EvalLimits`] passes, or the evaluation is
+/// cancelled, the iterator yields that error once and ends.
+pub struct PlotIter {
+    code: Bytecode,
+    /// Where memoized calls recall from, for the whole range.
+    memo: Option<MemoizedDefinitions>,
+    seed: u64,
+    limits: EvalLimits,
+    x_min: f32,
+    step_size: f32,
+    /// The index of the next sample; `count` once the range is exhausted.
+    next: u64,
+    count: u64,
+    xs: Vec<f32>,
+    results: Vec<Result<f32, EquationError>>,
+    ready: VecDeque<Sample>,
+    scratch: BatchScratch,
+}
+
+impl PlotIter {
+    pub(crate) fn new(
+        code: Bytecode,
+        memo: Option<MemoizedDefinitions>,
+        seed: u64,
+        limits: EvalLimits,
+        x_min: f32,
+        x_max: f32,
+        step_size: f32,
+    ) -> Self {
+        let scratch = code.batch_scratch();
+        PlotIter {
+            code,
+            memo,
+            seed,
+            limits,
+            x_min,
+            step_size,
+            next: 0,
+            count: sample_count(x_min, x_max, step_size) as u64,
+            xs: Vec::with_capacity(LANES),
+            results: Vec::with_capacity(LANES),
+            ready: VecDeque::with_capacity(LANES),
+            scratch,
+        }
+    }
+
+    /// Evaluates the next block of samples into `ready`.
+    fn refill(&mut self) {
+        self.xs.clear();
+        // Generates exactly as `get_x_values` does, so the samples match
+        // `plot`'s bit for bit.
+        while self.xs.len() < LANES && self.next < self.count {
+            self.xs.push(x_at(self.x_min, self.step_size, self.next));
+            self.next += 1;
+        }
+        self.results.clear();
+        let ctx = self.memo.as_ref().map(MemoizedDefinitions::compile);
+        let env = BatchEnv {
+            seed: self.seed,
+            ctx: ctx.as_ref(),
+            limits: &self.limits,
+        };
+        if let Err(e) = self
+            .code
+            .run_points(&self.xs, &env, &mut self.scratch, &mut self.results)
+        {
+            if let Some(&x) = self.xs.first() {
+                self.ready.push_back(Sample { x, y: Err(e) });
+            }
+            // Nothing more to sample.
+            self.next = self.count;
+            return;
+        }
+        let samples = self
+            .xs
+            .iter()
+            .zip(self.results.drain(..))
+            .map(|(&x, y)| Sample { x, y });
+        self.ready.extend(samples);
+    }
+
+    /// Advances like [`next`](Iterator::next), but keeps the `x` of a
+    /// failing sample.
+    pub fn next_sample(&mut self) -> Option<Sample> {
+        if self.ready.is_empty() {
+            self.refill();
+        }
+        self.ready.pop_front()
+    }
+}
+
+impl Iterator for PlotIter {
+    type Item = Result<Point, EquationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_sample()
+            .map(|Sample { x, y }| y.map(|y| Point { x, y }))
+    }
+}
+
+/// One sample of a [`TolerantPlot`]: `y` is the equation's value at `x`,
+/// or the error evaluating it there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// The x-coordinate
+    pub x: f32,
+    /// The value at `x`, or why there is none
+    pub y: Result<f32, EquationError>,
+}
+
+impl Sample {
+    /// The value at `x`; `None` for a gap.
+    pub fn value(&self) -> Option<f32> {
+        self.y.as_ref().ok().copied()
+    }
+}
+
+/// A run of consecutive failing samples in a [`TolerantPlot`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    /// The first failing `x` of the run
+    pub from: f32,
+    /// The last failing `x` of the run
+    pub to: f32,
+    /// How many samples failed
+    pub samples: usize,
+    /// The error at `from`
+    pub error: EquationError,
+}
+
+/// A plot that records failing samples as gaps instead of failing as a
+/// whole; from
+/// [`plot_tolerant`](crate::equation_analyzer::calculator::plot_tolerant).
+///
+/// Also collects from [`Sample`]s, such as a [`PlotIter`]'s:
+///
+/// ```
+/// use rusty_maths::equation_analyzer::calculator::plot_iter;
+/// use rusty_maths::equation_analyzer::plot::TolerantPlot;
+///
+/// let mut points = plot_iter("root(x, 2)", -2.0, 2.0, 1.0).unwrap();
+/// let plot: TolerantPlot = std::iter::from_fn(|| points.next_sample()).collect();
+/// assert_eq!((plot.gaps[0].from, plot.gaps[0].to), (-2.0, -1.0));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TolerantPlot {
+    /// Every sample, in increasing `x`
+    pub samples: Vec<Sample>,
+    /// The runs of failing samples, in increasing `x`
+    pub gaps: Vec<Gap>,
+}
+
+impl TolerantPlot {
+    /// The samples that have a value, as points.
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        self.samples
+            .iter()
+            .filter_map(|s| s.value().map(|y| Point { x: s.x, y }))
+    }
+
+    /// The runs of consecutive valid samples — the pieces of the curve to
+    /// draw without joining across a gap.
+    pub fn segments(&self) -> Vec<Vec<Point>> {
+        let mut segments = Vec::new();
+        let mut current = Vec::new();
+        for s in &self.samples {
+            match s.value() {
+                Some(y) => current.push(Point { x: s.x, y }),
+                None if !current.is_empty() => segments.push(std::mem::take(&mut current)),
+                None => {}
+            }
+        }
+        if !current.is_empty() {
+            segments.push(current);
+        }
+        segments
+    }
+
+    /// Whether every sample has a value.
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty()
+    }
+}
+
+impl FromIterator<Sample> for TolerantPlot {
+    fn from_iter<I: IntoIterator<Item = Sample>>(iter: I) -> Self {
+        let samples: Vec<Sample> = iter.into_iter().collect();
+        let mut gaps: Vec<Gap> = Vec::new();
+        let mut previous_failed = false;
+        for s in &samples {
+            match (&s.y, gaps.last_mut()) {
+                (Ok(_), _) => previous_failed = false,
+                (Err(_), Some(gap)) if previous_failed => {
+                    gap.to = s.x;
+                    gap.samples += 1;
+                }
+                (Err(e), _) => {
+                    gaps.push(Gap {
+                        from: s.x,
+                        to: s.x,
+                        samples: 1,
+                        error: e.clone(),
+                    });
+                    previous_failed = true;
+                }
+            }
+        }
+        TolerantPlot { samples, gaps }
+    }
+}
@@ -1,6 +1,6 @@
 use super::token::{SpannedToken, Token};
 use crate::equation_analyzer::catalog::{self, SymbolKind};
-use crate::equation_analyzer::errors::EquationError;
+use crate::equation_analyzer::errors::{EquationError, ErrorKind};
 use std::sync::OnceLock;
 
 // One Assoc for the whole crate — the catalog owns the definition, the
@@ -51,7 +51,10 @@ fn operator_table() -> Result<&'static OperatorTable, EquationError> {
             })
         })
         .as_ref()
-        .ok_or_else(|| EquationError::new("Internal error: operator missing from catalog"))
+        .ok_or_else(|| {
+            EquationError::new("Internal error: operator missing from catalog")
+                .with_kind(ErrorKind::Internal)
+        })
 }
 
 pub(crate) fn get_operator(spanned: SpannedToken) -> Result<Operand, EquationError> {
@@ -67,6 +70,7 @@ pub(crate) fn get_operator(spanned: SpannedToken) -> Result<Operand, EquationErr
         Token::Plus => operator_table()?.plus,
         Token::Minus => operator_table()?.minus,
         Token::Star => operator_table()?.star,
+        Token::UnitProduct => (operator_table()?.star.0, Assoc::Right),
         Token::Slash => operator_table()?.slash,
         Token::Modulo => operator_table()?.modulo,
         Token::Power => operator_table()?.power,
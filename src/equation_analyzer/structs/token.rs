@@ -1,5 +1,6 @@
 use crate::equation_analyzer::catalog::Symbol;
 use crate::equation_analyzer::errors::Span;
+use crate::equation_analyzer::units::Unit;
 
 /// What a call token dispatches to: a built-in catalog symbol, or a
 /// user-defined function referenced by its index into the `Definitions`
@@ -9,6 +10,17 @@ use crate::equation_analyzer::errors::Span;
 pub(crate) enum Callee {
     Catalog(&'static Symbol),
     User(usize),
+    /// `taylor(f, a, n)`: the tokenizer resolves the function name `f`
+    /// into the callee, leaving `a` and `n` as the frame's arguments.
+    Taylor(FunctionRef),
+}
+
+/// A unary function named without being called — `taylor`'s first
+/// argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FunctionRef {
+    Catalog(&'static Symbol),
+    User(usize),
 }
 
 /// A token plus the character range of the source equation it came from.
@@ -84,4 +96,19 @@ pub(crate) enum Token {
 
     /// A named constant (π, e, …) — the value comes from the Symbol.
     Constant(&'static Symbol),
+
+    /// A physical unit (`m`, `km`, `mph`, …) — an operand worth one of the
+    /// unit. Only produced in unit-aware mode; the tokenizer inserts the
+    /// `UnitProduct` that makes `3 m` mean 3 × m.
+    Unit(&'static Unit),
+
+    /// The implicit multiplication joining a quantity to its unit (`3 m`).
+    /// Right-associative at `*`'s precedence, so `10 km / 5 km` divides by
+    /// the whole `5 km` while `2 m^2` still squares only the unit.
+    UnitProduct,
+
+    /// A fitted parameter, by index into the evaluation's parameter values.
+    /// Substituted into a parsed template by `fit`, which re-evaluates it
+    /// with new values every trial. Never produced by the tokenizer.
+    Parameter(usize),
 }
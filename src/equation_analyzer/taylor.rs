@@ -0,0 +1,692 @@
+//! Taylor series: truncated power series through the evaluator.
+//!
+//! A [`Jet`] holds the Taylor coefficients `f(a), f'(a), f''(a)/2!, …` of a
+//! value as a function of `x` about an expansion point `a`. Seeding `x` with
+//! `a + t` and evaluating with the generic [`Domain`] walker propagates the
+//! coefficients through every operation — [`dual`](super::dual)'s trick,
+//! carried to any order — so expansions are exact up to floating-point
+//! rounding, through user functions, variadics and the solved `rate` and
+//! `irr` alike.
+//!
+//! Elementary functions use the standard recurrences: `exp`, powers and the
+//! `sin`/`cos` pair directly; everything else by integrating `f'(u)·u'`.
+//! Where a function is not smooth at the expansion point — `sqrt` or `ln`
+//! at 0, a pole — there is no series, and that is an error rather than a
+//! polynomial of infinities. Piecewise-constant functions (`floor`, `!`,
+//! `ch`, …) expand to their value, and `abs` to the branch at the value,
+//! as in [`dual`](super::dual).
+
+use crate::equation_analyzer::catalog::{Category, Symbol, SymbolKind};
+use crate::equation_analyzer::errors::{EquationError, Span};
+use crate::equation_analyzer::finance::{self, Real};
+use crate::equation_analyzer::pipeline::domain::{self, Domain};
+use crate::equation_analyzer::pipeline::evaluator::EvalState;
+use crate::equation_analyzer::structs::token::FunctionRef;
+use std::f64::consts::PI;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// The highest order `taylor` expands to.
+pub(crate) const MAX_ORDER: usize = 32;
+
+const TERMS: usize = MAX_ORDER + 1;
+
+/// A Taylor polynomial about `around`: `coefficients[k]` multiplies
+/// `(x - around)^k`, so `coefficients[k]` is `f⁽ᵏ⁾(around) / k!`.
+///
+/// Produced by [`taylor`](crate::equation_analyzer::calculator::taylor);
+/// [`polynomial`](Self::polynomial) (also the `Display` form) is an equation
+/// this crate parses, ready to plot against the original.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaylorSeries {
+    pub around: f32,
+    pub coefficients: Vec<f32>,
+}
+
+impl TaylorSeries {
+    /// The polynomial as an equation in `x`, lowest order first, with zero
+    /// terms left out: `x - 0.16666667x^3` for `sin` about 0, or
+    /// `1 + 2 * (x - 1) + (x - 1)^2` for `x^2` about 1.
+    pub fn polynomial(&self) -> String {
+        let base = if self.around == 0.0 {
+            String::from("x")
+        } else if self.around > 0.0 {
+            format!("(x - {})", self.around)
+        } else {
+            format!("(x + {})", -self.around)
+        };
+
+        let mut out = String::new();
+        for (k, &c) in self.coefficients.iter().enumerate() {
+            if c == 0.0 {
+                continue;
+            }
+            let magnitude = c.abs();
+            let power = match k {
+                0 => String::new(),
+                1 => base.clone(),
+                _ => format!("{base}^{k}"),
+            };
+            let term = if k == 0 {
+                magnitude.to_string()
+            } else if magnitude == 1.0 {
+                power
+            } else if self.around == 0.0 {
+                // Juxtaposed, like the `2x^3` users write.
+                format!("{magnitude}{power}")
+            } else {
+                format!("{magnitude} * {power}")
+            };
+            match (out.is_empty(), c < 0.0) {
+                (true, false) => out.push_str(&term),
+                (true, true) => out.push_str(&format!("-{term}")),
+                (false, false) => out.push_str(&format!(" + {term}")),
+                (false, true) => out.push_str(&format!(" - {term}")),
+            }
+        }
+        if out.is_empty() {
+            out.push('0');
+        }
+        out
+    }
+
+    /// The polynomial's value at `x`.
+    pub fn eval(&self, x: f32) -> f32 {
+        let coefficients: Vec<f64> = self.coefficients.iter().map(|&c| f64::from(c)).collect();
+        horner(&coefficients, f64::from(x) - f64::from(self.around)) as f32
+    }
+}
+
+impl fmt::Display for TaylorSeries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.polynomial())
+    }
+}
+
+/// `Σ c[k]·t^k`.
+pub(crate) fn horner(coefficients: &[f64], t: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, &c| acc * t + c)
+}
+
+/// Validates `taylor`'s order argument.
+pub(crate) fn order_of(n: f32) -> Result<usize, String> {
+    if n % 1.0 == 0.0 && (0.0..=MAX_ORDER as f32).contains(&n) {
+        Ok(n as usize)
+    } else {
+        Err(format!(
+            "taylor order must be a whole number from 0 to {MAX_ORDER}, got {n}"
+        ))
+    }
+}
+
+/// The coefficients of `taylor(f, a, n)`: the series of `f` about `a`,
+/// ready for [`horner`] at `x - a`. `depth` is the caller's call depth, so
+/// expanding a user function counts against the recursion limit.
+pub(crate) fn expand(
+    f: FunctionRef,
+    a: f32,
+    n: f32,
+    state: &mut EvalState,
+    depth: u8,
+    span: Span,
+) -> Result<Vec<f64>, EquationError> {
+    let fail = |message: String| EquationError::domain(message, span);
+    let order = order_of(n).map_err(fail)?;
+    if !a.is_finite() {
+        return Err(fail(format!(
+            "taylor expansion point must be finite, got {a}"
+        )));
+    }
+    let t = Jet::variable(f64::from(a), order);
+    let jet = match f {
+        FunctionRef::Catalog(sym) => Jet::call(sym, &[t], state).map_err(fail)?,
+        FunctionRef::User(i) => domain::call_user(state, i, &t, depth, span)?,
+    };
+    Ok(jet.padded(order))
+}
+
+/// Taylor coefficients of a value about the expansion point.
+///
+/// Coefficients past `len` are not tracked: exactly zero for constants
+/// (`len` 1), truncated for anything that depends on `x`. Entries past
+/// `len` are kept at zero so the arithmetic can run over whole arrays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Jet {
+    c: [f64; TERMS],
+    len: usize,
+}
+
+impl Jet {
+    fn zeros(len: usize) -> Jet {
+        Jet {
+            c: [0.0; TERMS],
+            len,
+        }
+    }
+
+    fn constant(v: f64) -> Jet {
+        let mut j = Jet::zeros(1);
+        j.c[0] = v;
+        j
+    }
+
+    /// `x` itself, expanded about `a` to `order`.
+    pub(crate) fn variable(a: f64, order: usize) -> Jet {
+        let mut j = Jet::zeros(order.min(MAX_ORDER) + 1);
+        j.c[0] = a;
+        if order > 0 {
+            j.c[1] = 1.0;
+        }
+        j
+    }
+
+    /// The first `order + 1` coefficients, zero-filled past `len`.
+    pub(crate) fn padded(&self, order: usize) -> Vec<f64> {
+        self.c[..=order.min(MAX_ORDER)].to_vec()
+    }
+
+    fn is_constant(&self) -> bool {
+        self.c[1..self.len].iter().all(|&c| c == 0.0)
+    }
+
+    fn scale(self, k: f64) -> Jet {
+        let mut r = self;
+        for c in &mut r.c[..r.len] {
+            *c *= k;
+        }
+        r
+    }
+
+    /// A series with non-finite coefficients is not a series at all: the
+    /// function isn't smooth here. Constants pass through, so `1/0` is
+    /// `inf` as in the plain evaluator.
+    fn checked(self, what: impl FnOnce() -> String) -> Result<Jet, String> {
+        if self.len > 1 && self.c[..self.len].iter().any(|c| !c.is_finite()) {
+            Err(format!("{} has no Taylor expansion at this point", what()))
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// `d/dt`, one coefficient shorter.
+    fn derivative(self) -> Jet {
+        let mut r = Jet::zeros((self.len - 1).max(1));
+        for k in 0..self.len - 1 {
+            r.c[k] = (k + 1) as f64 * self.c[k + 1];
+        }
+        r
+    }
+
+    /// `c0 + ∫ self dt`, to `len` coefficients.
+    fn integral(self, c0: f64, len: usize) -> Jet {
+        let mut r = Jet::zeros(len);
+        r.c[0] = c0;
+        for k in 1..len {
+            r.c[k] = self.c[k - 1] / k as f64;
+        }
+        r
+    }
+
+    /// `f(self)` from `f(u₀)` and the series `h = f'(u)`:
+    /// `f(u) = f(u₀) + ∫ h·u' dt`.
+    fn compose(self, f0: f64, h: impl FnOnce(Jet) -> Jet) -> Jet {
+        if self.is_constant() {
+            return Jet::constant(f0);
+        }
+        (h(self) * self.derivative()).integral(f0, self.len)
+    }
+
+    fn exp(self) -> Jet {
+        let mut r = Jet::zeros(self.len);
+        r.c[0] = self.c[0].exp();
+        for k in 1..self.len {
+            let s: f64 = (1..=k).map(|j| j as f64 * self.c[j] * r.c[k - j]).sum();
+            r.c[k] = s / k as f64;
+        }
+        r
+    }
+
+    fn ln(self) -> Jet {
+        self.compose(self.c[0].ln(), |u| Jet::constant(1.0) / u)
+    }
+
+    /// `(sin u, cos u)`, from the coupled recurrences.
+    fn sin_cos(self) -> (Jet, Jet) {
+        let (mut s, mut c) = (Jet::zeros(self.len), Jet::zeros(self.len));
+        s.c[0] = self.c[0].sin();
+        c.c[0] = self.c[0].cos();
+        for k in 1..self.len {
+            let ds: f64 = (1..=k).map(|j| j as f64 * self.c[j] * c.c[k - j]).sum();
+            let dc: f64 = (1..=k).map(|j| j as f64 * self.c[j] * s.c[k - j]).sum();
+            s.c[k] = ds / k as f64;
+            c.c[k] = -dc / k as f64;
+        }
+        (s, c)
+    }
+
+    /// `(sinh u, cosh u)`.
+    fn sinh_cosh(self) -> (Jet, Jet) {
+        let (e, f) = (self.exp(), (-self).exp());
+        ((e - f).scale(0.5), (e + f).scale(0.5))
+    }
+
+    fn powi(self, n: i64) -> Jet {
+        let mut result = Jet::constant(1.0);
+        let mut base = self;
+        let mut e = n.unsigned_abs();
+        while e > 0 {
+            if e & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            e >>= 1;
+        }
+        if n < 0 {
+            Jet::constant(1.0) / result
+        } else {
+            result
+        }
+    }
+
+    /// `self^p` for a constant `p`: repeated squaring for integers (smooth
+    /// everywhere), else the recurrence from `u·(u^p)' = p·u'·u^p`, which
+    /// needs `u₀ ≠ 0`.
+    fn powc(self, p: f64) -> Jet {
+        if p % 1.0 == 0.0 && p.abs() <= f64::from(i32::MAX) {
+            return self.powi(p as i64);
+        }
+        if self.is_constant() {
+            return Jet::constant(self.c[0].powf(p));
+        }
+        let a0 = self.c[0];
+        let mut r = Jet::zeros(self.len);
+        r.c[0] = a0.powf(p);
+        for k in 1..self.len {
+            let s: f64 = (1..=k)
+                .map(|j| ((p + 1.0) * j as f64 - k as f64) * self.c[j] * r.c[k - j])
+                .sum();
+            r.c[k] = s / (k as f64 * a0);
+        }
+        r
+    }
+
+    fn pow(self, e: Jet) -> Jet {
+        if e.is_constant() {
+            self.powc(e.c[0])
+        } else {
+            (e * self.ln()).exp()
+        }
+    }
+
+    /// `root(u, n)`: the real root, odd roots of negatives included.
+    fn root(self, n: Jet) -> Jet {
+        let principal = |u: Jet| {
+            if n.is_constant() {
+                u.powc(1.0 / n.c[0])
+            } else {
+                (u.ln() / n).exp()
+            }
+        };
+        if self.c[0] < 0.0 {
+            -principal(-self)
+        } else {
+            principal(self)
+        }
+    }
+}
+
+impl Add for Jet {
+    type Output = Jet;
+    fn add(self, rhs: Jet) -> Jet {
+        let mut r = Jet::zeros(self.len.max(rhs.len));
+        for k in 0..r.len {
+            r.c[k] = self.c[k] + rhs.c[k];
+        }
+        r
+    }
+}
+
+impl Sub for Jet {
+    type Output = Jet;
+    fn sub(self, rhs: Jet) -> Jet {
+        self + -rhs
+    }
+}
+
+impl Mul for Jet {
+    type Output = Jet;
+    fn mul(self, rhs: Jet) -> Jet {
+        let mut r = Jet::zeros(self.len.max(rhs.len));
+        for k in 0..r.len {
+            r.c[k] = (0..=k).map(|j| self.c[j] * rhs.c[k - j]).sum();
+        }
+        r
+    }
+}
+
+impl Div for Jet {
+    type Output = Jet;
+    fn div(self, rhs: Jet) -> Jet {
+        let mut r = Jet::zeros(self.len.max(rhs.len));
+        for k in 0..r.len {
+            let s: f64 = (1..=k).map(|j| rhs.c[j] * r.c[k - j]).sum();
+            r.c[k] = (self.c[k] - s) / rhs.c[0];
+        }
+        r
+    }
+}
+
+impl Neg for Jet {
+    type Output = Jet;
+    fn neg(self) -> Jet {
+        self.scale(-1.0)
+    }
+}
+
+impl Real for Jet {
+    fn constant(v: f64) -> Self {
+        Jet::constant(v)
+    }
+    fn value(self) -> f64 {
+        self.c[0]
+    }
+    fn order(self) -> usize {
+        self.len - 1
+    }
+    fn powf(self, e: Self) -> Self {
+        self.pow(e)
+    }
+    fn ln(self) -> Self {
+        Jet::ln(self)
+    }
+}
+
+/// The argument whose value the function returned — `min`, `max` and
+/// `choose` pick one.
+fn picked(args: &[Jet], v: f64) -> Jet {
+    args.iter()
+        .copied()
+        .find(|a| a.c[0] == v)
+        .unwrap_or(Jet::constant(v))
+}
+
+fn median(args: &[Jet]) -> Jet {
+    let mut sorted = args.to_vec();
+    sorted.sort_by(|a, b| a.c[0].total_cmp(&b.c[0]));
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[mid]
+    } else {
+        (sorted[mid - 1] + sorted[mid]).scale(0.5)
+    }
+}
+
+fn plain_args(args: &[Jet]) -> Vec<f32> {
+    args.iter().map(|a| a.c[0] as f32).collect()
+}
+
+impl Domain for Jet {
+    fn number(v: f32) -> Self {
+        Jet::constant(f64::from(v))
+    }
+
+    fn to_number(&self) -> Option<f32> {
+        self.is_constant().then_some(self.c[0] as f32)
+    }
+
+    fn add(&self, rhs: &Self) -> Result<Self, String> {
+        Ok(*self + *rhs)
+    }
+
+    fn sub(&self, rhs: &Self) -> Result<Self, String> {
+        Ok(*self - *rhs)
+    }
+
+    fn mul(&self, rhs: &Self) -> Result<Self, String> {
+        Ok(*self * *rhs)
+    }
+
+    fn div(&self, rhs: &Self) -> Result<Self, String> {
+        (*self / *rhs).checked(|| String::from("Division by zero"))
+    }
+
+    fn rem(&self, rhs: &Self) -> Result<Self, String> {
+        // x % y = x − trunc(x/y)·y, with trunc locally constant.
+        let k = (self.c[0] / rhs.c[0]).trunc();
+        let mut r = *self - rhs.scale(k);
+        r.c[0] = self.c[0] % rhs.c[0];
+        r.checked(|| String::from("mod"))
+    }
+
+    fn pow(&self, rhs: &Self) -> Result<Self, String> {
+        Jet::pow(*self, *rhs).checked(|| String::from("^"))
+    }
+
+    fn neg(&self) -> Self {
+        -*self
+    }
+
+    fn factorial(&self) -> Result<Self, String> {
+        let v = self.c[0];
+        if v < 0.0 || v % 1.0 != 0.0 {
+            return Err(String::from(
+                "Factorial is only defined for non-negative integers",
+            ));
+        }
+        crate::utilities::factorial(v as isize).map(|f| Jet::constant(f as f64))
+    }
+
+    fn log(&self, base: f32) -> Result<Self, String> {
+        self.ln()
+            .scale(1.0 / f64::from(base).ln())
+            .checked(|| format!("log_{base}"))
+    }
+
+    fn call(sym: &'static Symbol, args: &[Self], state: &mut EvalState) -> Result<Self, String> {
+        let name = sym.name;
+        if sym.category == Category::Finance {
+            return finance::eval(name, args).and_then(|r| r.checked(|| name.to_string()));
+        }
+        if let SymbolKind::Random { run, .. } = sym.kind {
+            // Draw exactly as the plain evaluator would; the result moves
+            // with the arguments it was drawn from.
+            let v = f64::from(run(state.rng(), &plain_args(args))?);
+            return Ok(match name {
+                "randn" => {
+                    let (mu, sigma) = (args[0], args[1]);
+                    let z = if sigma.c[0] == 0.0 {
+                        0.0
+                    } else {
+                        (v - mu.c[0]) / sigma.c[0]
+                    };
+                    let mut r = mu + sigma.scale(z);
+                    r.c[0] = v;
+                    r
+                }
+                "choose" => picked(args, v),
+                _ => Jet::constant(v),
+            });
+        }
+        let Some(&u) = args.first() else {
+            return Err(format!("{name} requires at least 1 parameter, got 0"));
+        };
+        let v = u.c[0];
+        let one = Jet::constant(1.0);
+        let result = match name {
+            "sin" => u.sin_cos().0,
+            "cos" => u.sin_cos().1,
+            "tan" => {
+                let (s, c) = u.sin_cos();
+                s / c
+            }
+            "sec" => one / u.sin_cos().1,
+            "csc" => one / u.sin_cos().0,
+            "cot" => {
+                let (s, c) = u.sin_cos();
+                c / s
+            }
+            "asin" => u.compose(v.asin(), |u| (one - u * u).powc(-0.5)),
+            "acos" => u.compose(v.acos(), |u| -(one - u * u).powc(-0.5)),
+            "atan" => u.compose(v.atan(), |u| one / (one + u * u)),
+            "atan2" => {
+                // atan2(y, x): dθ = (x·dy − y·dx) / (x² + y²).
+                let (y, x) = (args[0], args[1]);
+                let len = y.len.max(x.len);
+                let slope = (x * y.derivative() - y * x.derivative()) / (x * x + y * y);
+                if len == 1 {
+                    Jet::constant(y.c[0].atan2(x.c[0]))
+                } else {
+                    slope.integral(y.c[0].atan2(x.c[0]), len)
+                }
+            }
+            "sinh" => u.sinh_cosh().0,
+            "cosh" => u.sinh_cosh().1,
+            "tanh" => {
+                let (s, c) = u.sinh_cosh();
+                s / c
+            }
+            "asinh" => u.compose(v.asinh(), |u| (u * u + one).powc(-0.5)),
+            "acosh" => u.compose(v.acosh(), |u| (u * u - one).powc(-0.5)),
+            "atanh" => u.compose(v.atanh(), |u| one / (one - u * u)),
+            "deg" => u.scale(180.0 / PI),
+            "rad" => u.scale(PI / 180.0),
+            "abs" => {
+                if v < 0.0 {
+                    -u
+                } else {
+                    u
+                }
+            }
+            "sqrt" => u.powc(0.5),
+            "floor" => Jet::constant(v.floor()),
+            "ceil" => Jet::constant(v.ceil()),
+            "round" => Jet::constant(v.round()),
+            "ln" => u.ln(),
+            "exp" => u.exp(),
+            "pow" => u.pow(args[1]),
+            "root" => {
+                // The catalog version rejects n = 0 and even roots of
+                // negatives; reuse its checks.
+                if let SymbolKind::Variadic { run, .. } = sym.kind {
+                    run(&plain_args(args))?;
+                }
+                u.root(args[1])
+            }
+            "min" => picked(
+                args,
+                args.iter().map(|a| a.c[0]).fold(f64::INFINITY, f64::min),
+            ),
+            "max" => picked(
+                args,
+                args.iter()
+                    .map(|a| a.c[0])
+                    .fold(f64::NEG_INFINITY, f64::max),
+            ),
+            "sum" => args[1..].iter().fold(u, |acc, &a| acc + a),
+            "avg" => args[1..]
+                .iter()
+                .fold(u, |acc, &a| acc + a)
+                .scale(1.0 / args.len() as f64),
+            "med" => median(args),
+            "mode" => {
+                let value = match sym.kind {
+                    SymbolKind::Variadic { run, .. } => f64::from(run(&plain_args(args))?),
+                    _ => return Err(format!("'{name}' is not callable")),
+                };
+                if value.is_nan() {
+                    Jet::constant(value)
+                } else {
+                    // The mean of the modes moves with the arguments that tie.
+                    let count = |a: &Jet| args.iter().filter(|b| b.c[0] == a.c[0]).count();
+                    let max_count = args.iter().map(count).max().unwrap_or(0);
+                    let tied: Vec<Jet> = args
+                        .iter()
+                        .copied()
+                        .filter(|a| count(a) == max_count)
+                        .collect();
+                    let mut r = tied
+                        .iter()
+                        .fold(Jet::constant(0.0), |acc, &a| acc + a)
+                        .scale(1.0 / tied.len() as f64);
+                    r.c[0] = value;
+                    r
+                }
+            }
+            _ => match sym.kind {
+                // Integer-valued, so locally constant.
+                SymbolKind::Variadic { run, .. } => {
+                    Jet::constant(f64::from(run(&plain_args(args))?))
+                }
+                SymbolKind::UnitConversion => {
+                    return Err(format!(
+                        "'{name}' requires unit-aware evaluation (calculate_units)"
+                    ))
+                }
+                _ => return Err(format!("No Taylor expansion for '{name}'")),
+            },
+        };
+        result.checked(|| name.to_string())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn close(a: &[f64], b: &[f64]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-12)
+    }
+
+    #[test]
+    fn elementary_series() {
+        let t = Jet::variable(0.0, 5);
+        assert!(close(
+            &t.exp().padded(5),
+            &[1.0, 1.0, 0.5, 1.0 / 6.0, 1.0 / 24.0, 1.0 / 120.0]
+        ));
+        let (s, c) = t.sin_cos();
+        assert!(close(
+            &s.padded(5),
+            &[0.0, 1.0, 0.0, -1.0 / 6.0, 0.0, 1.0 / 120.0]
+        ));
+        assert!(close(&c.padded(4), &[1.0, 0.0, -0.5, 0.0, 1.0 / 24.0]));
+        // 1/(1 - t) = 1 + t + t² + …
+        let geometric = Jet::constant(1.0) / (Jet::constant(1.0) - t);
+        assert!(close(&geometric.padded(5), &[1.0; 6]));
+        // ln(1 + t) = t − t²/2 + t³/3 − …
+        let log = (Jet::constant(1.0) + t).ln();
+        assert!(close(&log.padded(3), &[0.0, 1.0, -0.5, 1.0 / 3.0]));
+    }
+
+    #[test]
+    fn powers() {
+        // (1 + t)^½ = 1 + t/2 − t²/8 + t³/16
+        let t = Jet::variable(0.0, 3);
+        let r = (Jet::constant(1.0) + t).powc(0.5);
+        assert!(close(&r.padded(3), &[1.0, 0.5, -0.125, 0.0625]));
+        // Integer powers are smooth at 0; fractional ones are not.
+        assert!(close(&t.powc(2.0).padded(3), &[0.0, 0.0, 1.0, 0.0]));
+        assert!(t.powc(0.5).checked(|| String::from("sqrt")).is_err());
+    }
+
+    #[test]
+    fn polynomial_strings() {
+        let series = TaylorSeries {
+            around: 0.0,
+            coefficients: vec![0.0, 1.0, 0.0, -0.5],
+        };
+        assert_eq!(series.polynomial(), "x - 0.5x^3");
+        let series = TaylorSeries {
+            around: -2.0,
+            coefficients: vec![-1.0, 2.0, 1.0],
+        };
+        assert_eq!(series.to_string(), "-1 + 2 * (x + 2) + (x + 2)^2");
+        assert_eq!(series.eval(-1.0), 2.0);
+        let zero = TaylorSeries {
+            around: 1.0,
+            coefficients: vec![0.0, 0.0],
+        };
+        assert_eq!(zero.polynomial(), "0");
+    }
+}
@@ -7,6 +7,12 @@
     clippy::panic
 )]
 mod rm_tests {
+    use crate::equation_analyzer::completion::{complete, signature_help};
+    use crate::equation_analyzer::diagnostics::Severity;
+    use crate::equation_analyzer::lexer::{lex, TokenKind};
+    use crate::equation_analyzer::limits::{CancelToken, EvalLimits};
+    use crate::equation_analyzer::plot::TolerantPlot;
+    use crate::equation_analyzer::trace::Operation;
     use crate::equation_analyzer::utils::Point;
 
     // Import calculator
@@ -14,8 +20,8 @@ mod rm_tests {
 
     // Internal testing utilities
     use crate::equation_analyzer::catalog;
-    use crate::equation_analyzer::errors::{EquationError, Span};
-    use crate::equation_analyzer::pipeline::evaluator::evaluate_with;
+    use crate::equation_analyzer::errors::{EquationError, ErrorKind, Span};
+    use crate::equation_analyzer::pipeline::evaluator::{evaluate_with, EvalState};
     use crate::equation_analyzer::pipeline::parser::parse;
     use crate::equation_analyzer::pipeline::tokenizer::StreamingTokenizer;
     use crate::equation_analyzer::structs::token::Token::{
@@ -25,6 +31,7 @@ mod rm_tests {
     use crate::equation_analyzer::structs::token::{Callee, SpannedToken, Token};
     use crate::utilities::abs_f32;
     use std::f32::consts::{E, PI};
+    use std::time::{Duration, Instant};
 
     fn is_close(x1: f32, x2: f32) -> bool {
         abs_f32(x1 - x2) < f32::EPSILON
@@ -36,7 +43,7 @@ mod rm_tests {
         tokens: I,
         x: impl Into<Option<f32>>,
     ) -> Result<f32, EquationError> {
-        evaluate_with(tokens, x, None)
+        evaluate_with(tokens, x, &mut EvalState::new(None, 0))
     }
 
     // Helper function to get tokens from an equation. Strips spans and
@@ -3602,7 +3609,7 @@ mod rm_tests {
 
     #[test]
     fn every_catalog_symbol_is_reachable_through_the_tokenizer() {
-        use crate::equation_analyzer::catalog::SymbolKind;
+        use crate::equation_analyzer::catalog::{Category, SymbolKind};
 
         for sym in catalog::all() {
             let labels = std::iter::once(sym.name).chain(sym.aliases.iter().copied());
@@ -3614,17 +3621,36 @@ mod rm_tests {
                         let args = vec!["1"; min_args.max(1) as usize].join(", ");
                         format!("{label}({args})")
                     }
+                    SymbolKind::Random { min_args, .. } => {
+                        let args = vec!["1"; min_args as usize].join(", ");
+                        format!("{label}({args})")
+                    }
                     SymbolKind::LogBase => format!("{label}_2(8)"),
+                    SymbolKind::Series => format!("{label}(sin, 0, 3)"),
                     // Operator glyphs aren't identifiers; their syntax is
                     // exercised by catalog_examples_are_true_equalities.
                     SymbolKind::Operator { .. } => continue,
+                    // Only callable in unit-aware evaluation.
+                    SymbolKind::UnitConversion => {
+                        let expr = format!("{label}(1 km, m)");
+                        let result = calculator::calculate_units(&expr, &Definitions::new());
+                        assert!(result.is_ok(), "'{expr}' failed: {result:?}");
+                        continue;
+                    }
                 };
                 let result = calculator::calculate(&expr);
+                // A solver may find no solution for placeholder cash flows;
+                // failing inside the function still shows the call reached it.
+                let reached = match &result {
+                    Ok(_) => true,
+                    Err(e) => {
+                        sym.category == Category::Finance && *e.kind() == ErrorKind::DomainError
+                    }
+                };
                 assert!(
-                    result.is_ok(),
+                    reached,
                     "catalog entry '{}' (via label '{label}') failed to evaluate '{expr}': {:?}",
-                    sym.name,
-                    result
+                    sym.name, result
                 );
             }
         }
@@ -3639,6 +3665,12 @@ mod rm_tests {
             if matches!(sym.kind, SymbolKind::Variable) {
                 continue;
             }
+            if matches!(sym.kind, SymbolKind::UnitConversion) {
+                let (lhs, rhs) = sym.example.split_once(" = ").unwrap();
+                let eval = |s: &str| calculator::calculate_units(s, &Definitions::new()).unwrap();
+                assert_eq!(eval(lhs).to_string(), eval(rhs).to_string());
+                continue;
+            }
             if let Some((lhs, rhs)) = sym.example.split_once(" = ") {
                 let l = calculator::calculate(lhs).unwrap_or_else(|e| {
                     panic!("example LHS '{lhs}' of '{}' failed: {e}", sym.name)
@@ -3991,6 +4023,31 @@ mod rm_tests {
         assert_eq!(calculator::calculate_with("g(2)", &defs).unwrap(), 10.0);
     }
 
+    #[test]
+    fn user_definitions_shadow_newer_catalog_functions_test() {
+        let defs = defs_with(
+            &[("pv", 2.0), ("rand", 3.0), ("convert", 4.0)],
+            &[("choose", "x + 1"), ("taylor", "x * 10")],
+        );
+        assert_eq!(calculator::calculate_with("pv * rand", &defs).unwrap(), 6.0);
+        assert_eq!(calculator::calculate_with("choose(1)", &defs).unwrap(), 2.0);
+        assert_eq!(
+            calculator::calculate_with("2 |> taylor", &defs).unwrap(),
+            20.0
+        );
+        let q = calculator::calculate_units("convert * 1 m", &defs).unwrap();
+        assert_eq!(q.to_string(), "4 m");
+        // The built-ins are back once nothing shadows them.
+        assert_eq!(calculator::calculate("choose(7)").unwrap(), 7.0);
+
+        // The core vocabulary stays reserved.
+        let mut defs = Definitions::new();
+        for name in ["sin", "pi", "log", "mod"] {
+            let err = defs.define_value(name, 1.0).unwrap_err();
+            assert_eq!(err.message, format!("Cannot redefine built-in '{name}'"));
+        }
+    }
+
     #[test]
     fn user_function_calls_user_function_test() {
         let defs = defs_with(&[], &[("g", "x + 1"), ("h", "g(x) * 2")]);
@@ -4302,4 +4359,2345 @@ mod rm_tests {
         let err = calculator::calculate("perm(-5, 2)").unwrap_err();
         assert_eq!(err.message, "Parameter 1 must be non-negative, got -5");
     }
+
+    // ---- Random: rand/randint/randn/choose and seeding ----
+
+    use crate::equation_analyzer::options::EvalOptions;
+
+    fn seeded(eq: &str, seed: u64) -> Result<f32, EquationError> {
+        calculator::calculate_with_options(
+            eq,
+            &Definitions::new(),
+            &EvalOptions::new().with_seed(seed),
+        )
+    }
+
+    #[test]
+    fn random_functions_stay_in_range_test() {
+        for seed in 0..200 {
+            let r = seeded("rand()", seed).unwrap();
+            assert!((0.0..1.0).contains(&r), "rand() = {r}");
+
+            let n = seeded("randint(-2, 3)", seed).unwrap();
+            assert!((-2.0..=3.0).contains(&n) && n % 1.0 == 0.0, "randint = {n}");
+
+            let c = seeded("choose(1, 5, 9)", seed).unwrap();
+            assert!([1.0, 5.0, 9.0].contains(&c), "choose = {c}");
+
+            assert!(seeded("randn(10, 1)", seed).unwrap().is_finite());
+        }
+        // Degenerate ranges are exact.
+        assert_eq!(seeded("randint(4, 4)", 1).unwrap(), 4.0);
+        assert_eq!(seeded("randn(7, 0)", 1).unwrap(), 7.0);
+        assert_eq!(seeded("choose(2)", 1).unwrap(), 2.0);
+    }
+
+    #[test]
+    fn seeded_calculate_is_reproducible_test() {
+        let eq = "rand() + randint(1, 100) + randn(0, 1) + choose(1, 2, 3)";
+        assert_eq!(seeded(eq, 42).unwrap(), seeded(eq, 42).unwrap());
+        // Each call advances the generator: two draws in one equation differ.
+        assert_ne!(seeded("rand() - rand()", 42).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn random_draws_share_state_through_user_functions_test() {
+        let defs = defs_with(&[], &[("noise", "rand() + x")]);
+        let opts = EvalOptions::new().with_seed(5);
+        let twice = calculator::calculate_with_options("noise(0) - noise(0)", &defs, &opts);
+        assert_ne!(twice.unwrap(), 0.0);
+    }
+
+    #[test]
+    fn seeded_plot_is_reproducible_per_x_test() {
+        let defs = Definitions::new();
+        let opts = EvalOptions::new().with_seed(9);
+        let a = calculator::plot_with_options("randn(x, 1)", -5.0, 5.0, 0.5, &defs, &opts).unwrap();
+        let b = calculator::plot_with_options("randn(x, 1)", -5.0, 5.0, 0.5, &defs, &opts).unwrap();
+        assert_eq!(a, b);
+
+        // A sample's noise depends on (seed, x) alone, not on the range.
+        let sub =
+            calculator::plot_with_options("randn(x, 1)", 0.0, 1.0, 0.5, &defs, &opts).unwrap();
+        let at = |pts: &[Point], x: f32| pts.iter().find(|p| p.x == x).map(|p| p.y);
+        assert_eq!(at(&sub, 1.0), at(&a, 1.0));
+
+        // Neighbouring samples don't share a stream.
+        let flat = calculator::plot_with_options("rand()", 0.0, 1.0, 0.5, &defs, &opts).unwrap();
+        assert_ne!(flat[0].y, flat[1].y);
+    }
+
+    #[test]
+    fn random_argument_errors_test() {
+        let err = seeded("randint(1.5, 3)", 0).unwrap_err();
+        assert_eq!(err.message, "Parameter 1 must be an integer, got 1.5");
+        let err = seeded("randint(5, 1)", 0).unwrap_err();
+        assert!(err.message.contains("lower bound exceeds"), "got: {err}");
+        let err = seeded("randn(0, -1)", 0).unwrap_err();
+        assert!(err.message.contains("non-negative"), "got: {err}");
+        let err = seeded("rand(1)", 0).unwrap_err();
+        assert_eq!(err.message, "rand accepts at most 0 parameters, got 1");
+        assert_eq!(err.span, Some(Span::new(0, 7)));
+        let err = seeded("choose()", 0).unwrap_err();
+        assert_eq!(err.message, "choose requires at least 1 parameter, got 0");
+    }
+
+    #[test]
+    fn unseeded_random_still_evaluates_test() {
+        let r = calculator::calculate("rand()").unwrap();
+        assert!((0.0..1.0).contains(&r));
+    }
+
+    // ---- Finance: time value of money, npv/irr, interest helpers ----
+
+    #[test]
+    fn mortgage_payment_with_percent_rate_test() {
+        // 6% a year, monthly, 30 years — percent composes inside arguments.
+        let payment = calculator::calculate("pmt(6%/12, 360, 200000)").unwrap();
+        assert!((payment + 1199.10).abs() < 0.01, "pmt = {payment}");
+        let back = calculator::calculate("pv(6%/12, 360, -1199.1010503)").unwrap();
+        assert!((back - 200_000.0).abs() < 1.0, "pv = {back}");
+    }
+
+    #[test]
+    fn npv_and_irr_test() {
+        let npv = calculator::calculate("-1000 + npv(10%, 300, 400, 500)").unwrap();
+        assert!((npv - (-21.04)).abs() < 0.01, "npv = {npv}");
+        let irr = calculator::calculate("irr(-1000, 300, 400, 500)").unwrap();
+        assert!((irr - 0.0890).abs() < 1e-3, "irr = {irr}");
+    }
+
+    #[test]
+    fn interest_helpers_test() {
+        assert!(is_close(
+            calculator::calculate("simpleint(1000, 5%, 3)").unwrap(),
+            150.0
+        ));
+        let monthly = calculator::calculate("compoundint(1000, 12%, 1, 12)").unwrap();
+        assert!((monthly - 126.825).abs() < 0.01, "compoundint = {monthly}");
+    }
+
+    #[test]
+    fn finance_errors_point_at_the_call_test() {
+        let err = calculator::calculate("1 + irr(100, 200)").unwrap_err();
+        assert_eq!(
+            err.message,
+            "irr needs at least one positive and one negative cash flow"
+        );
+        assert_eq!(err.span, Some(Span::new(4, 17)));
+        let err = calculator::calculate("pmt(5%, 10, 1000, 0, 3)").unwrap_err();
+        assert!(err.message.contains("type must be 0"), "got: {err}");
+        let err = calculator::calculate("pv(1, 2)").unwrap_err();
+        assert_eq!(err.message, "pv requires at least 3 parameters, got 2");
+    }
+
+    // ---- Units: unit-aware evaluation and dimensional analysis ----
+
+    fn units(eq: &str) -> Result<crate::equation_analyzer::Quantity, EquationError> {
+        calculator::calculate_units(eq, &Definitions::new())
+    }
+
+    #[test]
+    fn unit_arithmetic_keeps_the_left_unit_test() {
+        let q = units("3 m + 20 cm").unwrap();
+        assert!((q.value() - 3.2).abs() < 1e-6);
+        assert_eq!(q.unit(), "m");
+        let q = units("20 cm + 3 m").unwrap();
+        assert!((q.value() - 320.0).abs() < 1e-4);
+        assert_eq!(q.unit(), "cm");
+        assert_eq!(units("100 m - 20%").unwrap().to_string(), "80 m");
+    }
+
+    #[test]
+    fn units_combine_through_products_and_powers_test() {
+        let q = units("9.8 m/s^2 * 2 s").unwrap();
+        assert!((q.value() - 19.6).abs() < 1e-5);
+        assert_eq!(q.unit(), "m/s");
+        // Same-dimension units fold into the first one written.
+        let q = units("3 m * 20 cm").unwrap();
+        assert!((q.value() - 0.6).abs() < 1e-6);
+        assert_eq!(q.unit(), "m^2");
+        assert_eq!(units("sqrt(16 m^2)").unwrap().to_string(), "4 m");
+        // Cancelling units leave a plain number.
+        let q = units("10 km / 5 km").unwrap();
+        assert!(q.is_dimensionless());
+        assert_eq!(q.value(), 2.0);
+    }
+
+    #[test]
+    fn convert_rescales_within_a_dimension_test() {
+        let q = units("convert(60 mph, km/h)").unwrap();
+        assert!((q.value() - 96.56064).abs() < 1e-3);
+        assert_eq!(q.unit(), "km/h");
+        let q = units("convert(1 kWh, J)").unwrap();
+        assert_eq!(q.value(), 3.6e6);
+        let q = units("convert(2 lbf, N)").unwrap();
+        assert!((q.value() - 8.896443).abs() < 1e-4);
+        // The SI magnitude is unit-independent.
+        assert_eq!(q.si_value(), units("2 lbf").unwrap().si_value());
+    }
+
+    #[test]
+    fn dimension_errors_are_spanned_test() {
+        let err = units("3 m + 2 s").unwrap_err();
+        assert_eq!(err.message, "Dimension mismatch: cannot add m and s");
+        assert_eq!(err.span, Some(Span::new(4, 5)));
+
+        let err = units("sin(2 m)").unwrap_err();
+        assert_eq!(err.message, "sin expects a dimensionless argument, got m");
+        assert_eq!(err.span, Some(Span::new(0, 8)));
+
+        let err = units("convert(3 kg, m)").unwrap_err();
+        assert_eq!(err.message, "Cannot convert kg to m");
+
+        let err = units("2 m ^ 0.5").unwrap_err();
+        assert!(err.message.contains("non-integer power"), "got: {err}");
+    }
+
+    #[test]
+    fn unit_exponent_overflow_is_an_error_test() {
+        for eq in [
+            "m^100 * m^100",
+            "m^127 * m",
+            "(m^64)^2",
+            "1/(s^100) / s^100",
+        ] {
+            let err = units(eq).unwrap_err();
+            assert!(
+                err.message.starts_with("Unit exponent out of range"),
+                "{eq}: {err}"
+            );
+            assert_eq!(*err.kind(), ErrorKind::Units, "{eq}");
+            assert!(err.span.is_some(), "{eq}");
+        }
+        let err = units("m^100 * m^100").unwrap_err();
+        assert_eq!(
+            err.message,
+            "Unit exponent out of range: cannot multiply m^100 by m^100"
+        );
+        let err = units("2 m ^ 1000").unwrap_err();
+        assert_eq!(
+            err.message,
+            "Unit exponent out of range: cannot raise m to the power 1000"
+        );
+        assert_eq!(*err.kind(), ErrorKind::Units);
+        // The largest exponents still work.
+        assert_eq!(units("m^127").unwrap().unit(), "m^127");
+        assert_eq!(units("m^100 / m^100").unwrap().to_string(), "1");
+    }
+
+    #[test]
+    fn units_resolve_after_catalog_and_definitions_test() {
+        // A user value named like a unit wins, as does the catalog.
+        let defs = defs_with(&[("m", 5.0)], &[]);
+        let q = calculator::calculate_units("2 * m", &defs).unwrap();
+        assert_eq!(q.to_string(), "10");
+        assert!(units("e").unwrap().is_dimensionless());
+        // Function bodies may use units too.
+        let defs = defs_with(&[], &[("fall", "9.8 m/s^2 * x s")]);
+        let q = calculator::calculate_units("fall(3)", &defs).unwrap();
+        assert_eq!(q.unit(), "m/s");
+    }
+
+    #[test]
+    fn plain_evaluation_rejects_unit_syntax_test() {
+        let err = calculator::calculate("3 m").unwrap_err();
+        assert_eq!(err.message, "Unknown name 'm'");
+        let err = calculator::calculate("convert(1, 2)").unwrap_err();
+        assert_eq!(
+            err.message,
+            "'convert' requires unit-aware evaluation (calculate_units)"
+        );
+    }
+
+    // ---- Intervals: sound enclosures via calculate_interval ----
+
+    use crate::equation_analyzer::interval::Interval;
+
+    /// `v` lies in `iv`, allowing for the plain evaluator's own f32 rounding.
+    fn encloses(iv: Interval, v: f32) -> bool {
+        let tol = 1e-5 * v.abs().max(1.0);
+        iv.lo - tol <= v && v <= iv.hi + tol
+    }
+
+    #[test]
+    fn interval_enclosures_contain_every_sampled_value_test() {
+        let cases = [
+            ("x^2 - 2x", [-2.0, 1.5]),
+            ("sin(x) * cos(2x)", [-2.0, 1.5]),
+            ("exp(-x^2) + 1 / (x + 3)", [-2.0, 1.5]),
+            ("sqrt(abs(x)) + ln(x + 5)", [-2.0, 1.5]),
+            ("tan(x / 2) - atan(x)", [-2.0, 1.5]),
+            ("atan2(x, 1 - x)", [-2.0, 1.5]),
+            ("x mod 0.7 + floor(3x) / 2", [-2.0, 1.5]),
+            ("root(x, 3) + x^3", [-2.0, 1.5]),
+            ("max(x, x^2, -x) - med(x, 2x, 1)", [-2.0, 1.5]),
+            ("cosh(x) - sinh(x) + tanh(x)", [-2.0, 1.5]),
+            ("x^x + 2^x", [0.1, 3.0]),
+            ("sec(x) + csc(x + 4)", [-1.0, 1.0]),
+            ("100 - x%", [0.0, 50.0]),
+        ];
+        for (eq, [lo, hi]) in cases {
+            let iv = calculator::calculate_interval(eq, [lo, hi]).unwrap();
+            for p in calculator::plot(eq, lo, hi, 0.01).unwrap() {
+                if p.y.is_finite() {
+                    assert!(
+                        encloses(iv, p.y),
+                        "{eq}: {} at x = {} escapes {iv}",
+                        p.y,
+                        p.x
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_catalog_function_has_an_interval_extension_test() {
+        use crate::equation_analyzer::catalog::SymbolKind;
+        let opts = crate::equation_analyzer::options::EvalOptions::new().with_seed(7);
+        for sym in catalog::all() {
+            if matches!(sym.kind, SymbolKind::Variable | SymbolKind::UnitConversion) {
+                continue;
+            }
+            let lhs = sym.example.split(['=', '≈']).next().unwrap().trim();
+            let lhs = lhs.strip_prefix("y ").unwrap_or(lhs);
+            let plain =
+                calculator::calculate_with_options(lhs, &Definitions::new(), &opts).unwrap();
+            let iv = calculator::calculate_interval(lhs, [0.0, 0.0])
+                .unwrap_or_else(|e| panic!("'{lhs}' has no enclosure: {e}"));
+            assert!(encloses(iv, plain), "'{lhs}' = {plain} escapes {iv}");
+        }
+    }
+
+    #[test]
+    fn interval_bounds_are_tight_where_exact_test() {
+        let iv = |eq: &str, x: [f32; 2]| calculator::calculate_interval(eq, x).unwrap();
+        assert_eq!(iv("x^2 + 1", [-1.0, 2.0]), Interval::new(1.0, 5.0));
+        assert_eq!(iv("3x - 1", [0.0, 1.0]), Interval::new(-1.0, 2.0));
+        assert_eq!(iv("sqrt(x)", [4.0, 9.0]), Interval::new(2.0, 3.0));
+        assert_eq!(iv("x!", [2.5, 5.0]), Interval::new(6.0, 120.0));
+        assert_eq!(iv("sin(x)", [-10.0, 10.0]), Interval::new(-1.0, 1.0));
+        assert_eq!(iv("7 mod 3", [0.0, 0.0]), Interval::point(1.0));
+        // Each x varies independently: sound, but not tight.
+        assert_eq!(iv("x - x", [0.0, 1.0]), Interval::new(-1.0, 1.0));
+    }
+
+    #[test]
+    fn interval_poles_and_undefined_regions_test() {
+        let iv = calculator::calculate_interval("1 / x", [-1.0, 1.0]).unwrap();
+        assert_eq!((iv.lo, iv.hi), (f32::NEG_INFINITY, f32::INFINITY));
+        let iv = calculator::calculate_interval("1 / x", [0.0, 2.0]).unwrap();
+        assert_eq!((iv.lo, iv.hi), (0.5, f32::INFINITY));
+        let iv = calculator::calculate_interval("tan(x)", [1.0, 2.0]).unwrap();
+        assert!(iv.lo.is_infinite() && iv.hi.is_infinite());
+
+        // Undefined parts are skipped...
+        let iv = calculator::calculate_interval("sqrt(x)", [-4.0, 9.0]).unwrap();
+        assert_eq!(iv, Interval::new(0.0, 3.0));
+        // ...but undefined everywhere is an error at the call.
+        let err = calculator::calculate_interval("1 + sqrt(x)", [-4.0, -1.0]).unwrap_err();
+        assert_eq!(err.message, "sqrt is undefined on [-4, -1]");
+        assert_eq!(err.span, Some(Span::new(4, 11)));
+
+        let err = calculator::calculate_interval("x", [2.0, 1.0]).unwrap_err();
+        assert!(
+            err.message.starts_with("Invalid interval [2, 1]"),
+            "got: {err}"
+        );
+    }
+
+    #[test]
+    fn interval_user_functions_and_finance_test() {
+        let defs = defs_with(&[("a", 2.0)], &[("g", "a * x^2")]);
+        let iv = calculator::calculate_interval_with("g(x) + a", [-1.0, 3.0], &defs).unwrap();
+        assert_eq!(iv, Interval::new(2.0, 20.0));
+
+        // irr with an uncertain final cash flow brackets both extremes.
+        let iv = calculator::calculate_interval("irr(-100, x)", [105.0, 115.0]).unwrap();
+        assert!(iv.contains(0.05) && iv.contains(0.15), "{iv}");
+        assert!(iv.lo > 0.04 && iv.hi < 0.16, "{iv}");
+
+        let plain = calculator::calculate("rate(360, -1199.10, 200000)").unwrap();
+        let iv = calculator::calculate_interval("rate(360, -1199.10, 200000)", [0.0, 0.0]).unwrap();
+        assert!(encloses(iv, plain) && iv.width() < 1e-6, "{iv}");
+
+        let iv = calculator::calculate_interval("pmt(x, 360, 200000)", [0.004, 0.006]).unwrap();
+        assert!(iv.contains(-1199.1) && iv.hi < 0.0, "{iv}");
+    }
+
+    // ---- Derivatives: eval_with_derivative ----
+
+    fn derivative(eq: &str, x: f32) -> f32 {
+        calculator::eval_with_derivative(eq, x, &Definitions::new())
+            .unwrap()
+            .1
+    }
+
+    /// Central difference in f64 through the finance functions, which
+    /// already compute in f64.
+    fn central_difference(f: impl Fn(f64) -> f64, x: f64) -> f64 {
+        let h = 1e-6 * x.abs().max(1.0);
+        (f(x + h) - f(x - h)) / (2.0 * h)
+    }
+
+    #[test]
+    fn derivative_elementary_test() {
+        let cases: &[(&str, f32, f32)] = &[
+            ("x^3 - 2x", 2.0, 10.0),
+            ("3", 1.0, 0.0),
+            ("1 / x", 2.0, -0.25),
+            ("sin(x)", 0.0, 1.0),
+            ("cos(x)", PI / 2.0, -1.0),
+            ("exp(2x)", 0.0, 2.0),
+            ("ln(x)", 4.0, 0.25),
+            ("log_2(x)", 1.0, 1.0 / std::f32::consts::LN_2),
+            ("sqrt(x)", 4.0, 0.25),
+            ("2^x", 0.0, std::f32::consts::LN_2),
+            ("x^x", 1.0, 1.0),
+            ("root(x, 3)", -8.0, 1.0 / 12.0),
+            ("abs(x)", -3.0, -1.0),
+            ("floor(x)", 2.5, 0.0),
+            ("x mod 3", 7.0, 1.0),
+            ("50%", 1.0, 0.0),
+            ("x! + x", 3.0, 1.0),
+            ("atan2(x, 1)", 0.0, 1.0),
+        ];
+        for &(eq, x, expected) in cases {
+            let d = derivative(eq, x);
+            assert!(
+                abs_f32(d - expected) < 1e-5,
+                "d/dx {eq} at {x}: expected {expected}, got {d}"
+            );
+        }
+    }
+
+    #[test]
+    fn derivative_value_matches_calculate_test() {
+        let defs = defs_with(&[("a", 2.0)], &[("g", "a * x^2")]);
+        for eq in ["g(x) + sin(x)", "max(x, 3) * avg(x, 1)", "npv(0.1, x, 50)"] {
+            for x in [-1.5, 0.5, 2.0] {
+                let (v, _) = calculator::eval_with_derivative(eq, x, &defs).unwrap();
+                let plain = calculator::plot_with(eq, x, x, 1.0, &defs).unwrap()[0].y;
+                assert!(abs_f32(v - plain) < 1e-4, "{eq} at {x}: {v} vs {plain}");
+            }
+        }
+    }
+
+    #[test]
+    fn derivative_through_user_functions_test() {
+        let defs = defs_with(&[("k", 3.0)], &[("g", "k * x^2"), ("h", "g(sin(x))")]);
+        // h(x) = 3 sin²x, h'(x) = 6 sin x cos x
+        let (v, d) = calculator::eval_with_derivative("h(x)", 1.0, &defs).unwrap();
+        assert!(abs_f32(v - 3.0 * 1f32.sin().powi(2)) < 1e-6);
+        assert!(abs_f32(d - 6.0 * 1f32.sin() * 1f32.cos()) < 1e-6);
+
+        let err = calculator::eval_with_derivative("q(x)", 1.0, &defs).unwrap_err();
+        assert_eq!(err.span, Some(Span::new(0, 1)));
+    }
+
+    #[test]
+    fn derivative_variadic_test() {
+        assert_eq!(derivative("max(x^2, 2x, 1)", 3.0), 6.0);
+        assert_eq!(derivative("min(x^2, 2x, 1)", 3.0), 0.0);
+        assert_eq!(derivative("sum(x, x^2, 5)", 2.0), 5.0);
+        assert_eq!(derivative("avg(x, 3x)", 1.0), 2.0);
+        assert_eq!(derivative("med(1, 5x, x)", 1.0), 1.0);
+        assert_eq!(derivative("med(x, 2x)", 1.0), 1.5);
+        assert_eq!(derivative("mode(x, x, 4)", 1.0), 1.0);
+        assert_eq!(derivative("pow(x, 3)", 2.0), 12.0);
+        assert_eq!(derivative("ch(5, 2) * x", 1.0), 10.0);
+        // Random draws move with their arguments.
+        assert_eq!(derivative("randn(2x, 0)", 1.0), 2.0);
+        assert_eq!(derivative("choose(3x, 3x)", 1.0), 3.0);
+        assert_eq!(derivative("rand() + x", 1.0), 1.0);
+    }
+
+    #[test]
+    fn derivative_finance_test() {
+        use crate::equation_analyzer::finance;
+
+        // (function, arguments, index of the argument that is x)
+        let cases: &[(&str, &[f64], usize)] = &[
+            ("pmt", &[0.005, 360.0, 200000.0], 0),
+            ("pv", &[0.05, 10.0, -100.0], 1),
+            ("fv", &[0.04, 10.0, -100.0, -1000.0, 1.0], 0),
+            ("nper", &[0.01, -50.0, 1000.0], 1),
+            ("npv", &[0.1, -100.0, 60.0, 60.0], 0),
+            ("rate", &[10.0, -120.0, 1000.0], 1),
+            ("irr", &[-100.0, 60.0, 60.0], 2),
+            ("compoundint", &[1000.0, 0.05, 12.0, 5.0], 1),
+        ];
+        for &(name, args, i) in cases {
+            let rendered: Vec<String> = (0..args.len())
+                .map(|j| {
+                    if j == i {
+                        String::from("x")
+                    } else {
+                        args[j].to_string()
+                    }
+                })
+                .collect();
+            let eq = format!("{name}({})", rendered.join(", "));
+            let (_, d) =
+                calculator::eval_with_derivative(&eq, args[i] as f32, &Definitions::new()).unwrap();
+            let numeric = central_difference(
+                |t| {
+                    let mut moved = args.to_vec();
+                    moved[i] = t;
+                    finance::eval(name, &moved).unwrap()
+                },
+                args[i],
+            );
+            assert!(
+                (f64::from(d) - numeric).abs() < 1e-4 * numeric.abs().max(1.0),
+                "d/dx {eq}: exact {d}, numeric {numeric}"
+            );
+        }
+    }
+
+    // ---- Taylor series: taylor / taylor_with and taylor(f, a, n) ----
+
+    fn assert_coefficients(eq: &str, around: f32, expected: &[f32]) {
+        let series = calculator::taylor(eq, around, expected.len() - 1).unwrap();
+        for (k, (&c, &e)) in series.coefficients.iter().zip(expected).enumerate() {
+            assert!(
+                abs_f32(c - e) <= 1e-5 * e.abs().max(1.0),
+                "{eq} about {around}: coefficient {k} is {c}, expected {e}"
+            );
+        }
+    }
+
+    #[test]
+    fn taylor_known_series_test() {
+        let e = std::f32::consts::E;
+        assert_coefficients("exp(x)", 1.0, &[e, e, e / 2.0, e / 6.0]);
+        assert_coefficients("ln(x)", 1.0, &[0.0, 1.0, -0.5, 1.0 / 3.0, -0.25]);
+        assert_coefficients("tan(x)", 0.0, &[0.0, 1.0, 0.0, 1.0 / 3.0, 0.0, 2.0 / 15.0]);
+        assert_coefficients("atan(x)", 0.0, &[0.0, 1.0, 0.0, -1.0 / 3.0, 0.0, 0.2]);
+        assert_coefficients("sqrt(x)", 4.0, &[2.0, 0.25, -1.0 / 64.0]);
+        assert_coefficients("cosh(x)", 0.0, &[1.0, 0.0, 0.5, 0.0, 1.0 / 24.0]);
+        assert_coefficients("1 / (1 + x^2)", 0.0, &[1.0, 0.0, -1.0, 0.0, 1.0]);
+        let ln2 = std::f32::consts::LN_2;
+        assert_coefficients("2^x", 0.0, &[1.0, ln2, ln2 * ln2 / 2.0]);
+        assert_coefficients("x^3 - 2x", -1.0, &[1.0, 1.0, -3.0, 1.0, 0.0]);
+        assert_coefficients("log_10(x)", 1.0, &[0.0, 1.0 / std::f32::consts::LN_10]);
+        assert_coefficients("root(x, 3)", -8.0, &[-2.0, 1.0 / 12.0, 1.0 / 288.0]);
+        assert_coefficients("avg(x, 3x) * max(x^2, 0)", 1.0, &[2.0, 6.0, 6.0, 2.0]);
+        // Piecewise-constant functions and constants expand to themselves.
+        assert_coefficients("floor(x) + 5! + pi", 2.5, &[122.0 + PI, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn taylor_agrees_with_value_and_derivative_test() {
+        let defs = Definitions::new();
+        for sym in catalog::all().iter().filter(|s| s.kind.is_unary()) {
+            let at = if sym.name == "acosh" { 1.5 } else { 0.5 };
+            let eq = format!("{}(x)", sym.name);
+            let series = calculator::taylor(&eq, at, 2)
+                .unwrap_or_else(|e| panic!("'{eq}' has no series about {at}: {e}"));
+            let (v, d) = calculator::eval_with_derivative(&eq, at, &defs).unwrap();
+            let (c0, c1) = (series.coefficients[0], series.coefficients[1]);
+            assert!(abs_f32(c0 - v) < 1e-5, "{eq}: {c0} vs {v}");
+            assert!(abs_f32(c1 - d) < 1e-5, "{eq}: {c1} vs {d}");
+        }
+    }
+
+    #[test]
+    fn taylor_finance_second_order_test() {
+        use crate::equation_analyzer::finance;
+
+        // Second derivative by central differences in f64.
+        let second = |f: &dyn Fn(f64) -> f64, x: f64| {
+            let h = 1e-3 * x.abs().max(1.0);
+            (f(x + h) - 2.0 * f(x) + f(x - h)) / (h * h)
+        };
+        let cases: &[(&str, &[f64], usize)] = &[
+            ("pmt", &[0.005, 360.0, 200000.0], 0),
+            ("irr", &[-100.0, 60.0, 60.0], 2),
+            ("rate", &[10.0, -120.0, 1000.0], 1),
+        ];
+        for &(name, args, i) in cases {
+            let rendered: Vec<String> = (0..args.len())
+                .map(|j| {
+                    if j == i {
+                        String::from("x")
+                    } else {
+                        args[j].to_string()
+                    }
+                })
+                .collect();
+            let eq = format!("{name}({})", rendered.join(", "));
+            let series = calculator::taylor(&eq, args[i] as f32, 2).unwrap();
+            let numeric = second(
+                &|t| {
+                    let mut moved = args.to_vec();
+                    moved[i] = t;
+                    finance::eval(name, &moved).unwrap()
+                },
+                args[i],
+            ) / 2.0;
+            let c2 = f64::from(series.coefficients[2]);
+            assert!(
+                (c2 - numeric).abs() < 1e-3 * numeric.abs().max(1e-9),
+                "{eq}: c2 = {c2}, numeric {numeric}"
+            );
+        }
+    }
+
+    #[test]
+    fn taylor_user_definitions_and_polynomial_test() {
+        let defs = defs_with(&[("k", 2.0)], &[("g", "exp(k * x)"), ("h", "g(x) * x")]);
+        let series = calculator::taylor_with("h(x)", 0.0, 3, &defs).unwrap();
+        assert_eq!(series.coefficients, vec![0.0, 1.0, 2.0, 2.0]);
+        assert_eq!(series.polynomial(), "x + 2x^2 + 2x^3");
+
+        // The polynomial is an equation: plotting it matches eval().
+        for around in [0.0, 0.5, -2.0] {
+            let series = calculator::taylor("cos(x) + x", around, 6).unwrap();
+            let points =
+                calculator::plot(&series.polynomial(), around - 1.0, around + 1.0, 0.5).unwrap();
+            for p in points {
+                assert!(
+                    abs_f32(p.y - series.eval(p.x)) < 1e-4,
+                    "{series} at {}: {} vs {}",
+                    p.x,
+                    p.y,
+                    series.eval(p.x)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn taylor_in_equations_test() {
+        // Plotting an approximation against the original.
+        let approx = calculator::plot("y = taylor(sin, 0, 9)", -1.0, 1.0, 0.25).unwrap();
+        for p in approx {
+            assert!(abs_f32(p.y - p.x.sin()) < 1e-6, "{p:?}");
+        }
+        let defs = defs_with(&[], &[("g", "x^3 + 1")]);
+        let points = calculator::plot_with("taylor(g, 1, 1)", 0.0, 2.0, 1.0, &defs).unwrap();
+        let ys: Vec<f32> = points.iter().map(|p| p.y).collect();
+        assert_eq!(ys, vec![-1.0, 2.0, 5.0]);
+        // Arguments are expressions; calculate evaluates at x = 0.
+        assert_eq!(
+            calculator::calculate("taylor(exp, 2 - 2, 1 + 2)").unwrap(),
+            1.0
+        );
+        // Nested inside a user function body and other evaluation modes.
+        let defs = defs_with(&[], &[("p", "taylor(cos, 0, 2)")]);
+        assert_eq!(calculator::calculate_with("p(2)", &defs).unwrap(), -1.0);
+        let (v, d) = calculator::eval_with_derivative("taylor(sin, 0, 3)", 1.0, &defs).unwrap();
+        assert!(abs_f32(v - 5.0 / 6.0) < 1e-6 && abs_f32(d - 0.5) < 1e-6);
+        let iv = calculator::calculate_interval("taylor(exp, 0, 1)", [0.0, 1.0]).unwrap();
+        assert_eq!(iv, Interval::new(1.0, 2.0));
+        let s = calculator::taylor("taylor(exp, 0, 2) * 2", 0.0, 3).unwrap();
+        assert_eq!(s.coefficients, vec![2.0, 2.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn taylor_errors_test() {
+        let err = calculator::taylor("sqrt(x)", 0.0, 3).unwrap_err();
+        assert_eq!(err.message, "sqrt has no Taylor expansion at this point");
+        assert_eq!(err.span, Some(Span::new(0, 7)));
+        let err = calculator::taylor("1 + 1 / x", 0.0, 2).unwrap_err();
+        assert_eq!(
+            err.message,
+            "Division by zero has no Taylor expansion at this point"
+        );
+        assert_eq!(err.span, Some(Span::new(6, 7)));
+        assert!(calculator::taylor("ln(x)", 0.0, 1).is_err());
+        assert!(calculator::taylor("x", 0.0, 33).is_err());
+
+        let err = calculator::calculate("taylor(2, 0, 3)").unwrap_err();
+        assert_eq!(
+            err.message,
+            "taylor expects a unary function name first, as in taylor(sin, 0, 5)"
+        );
+        assert_eq!(err.span, Some(Span::new(7, 8)));
+        let err = calculator::calculate("taylor(max, 0, 3)").unwrap_err();
+        assert_eq!(err.span, Some(Span::new(7, 10)));
+        let err = calculator::calculate("taylor(sin 0, 3)").unwrap_err();
+        assert_eq!(err.message, "Expected ',' after 'sin' in taylor(f, a, n)");
+
+        let err = calculator::calculate("taylor(sin, 0)").unwrap_err();
+        assert_eq!(
+            err.message,
+            "taylor takes exactly 3 parameters (f, a, n), got 2"
+        );
+        assert_eq!(err.span, Some(Span::new(0, 14)));
+        let err = calculator::calculate("taylor(sin, 0, 1.5)").unwrap_err();
+        assert_eq!(
+            err.message,
+            "taylor order must be a whole number from 0 to 32, got 1.5"
+        );
+        let err = calculator::calculate("1 + taylor(sqrt, 0, 2)").unwrap_err();
+        assert_eq!(err.message, "sqrt has no Taylor expansion at this point");
+        assert_eq!(err.span, Some(Span::new(4, 22)));
+        let err = calculator::calculate("taylor").unwrap_err();
+        assert_eq!(err.message, "Function 'taylor' requires parentheses");
+        let err = calculator::calculate_interval("taylor(sin, x, 2)", [0.0, 1.0]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "taylor's expansion point and order must be plain numbers"
+        );
+    }
+
+    // ---- Curve fitting: fit ----
+
+    fn sample(f: impl Fn(f32) -> f32, from: f32, step: f32, n: usize) -> (Vec<f32>, Vec<f32>) {
+        let xs: Vec<f32> = (0..n).map(|i| from + step * i as f32).collect();
+        let ys = xs.iter().map(|&x| f(x)).collect();
+        (xs, ys)
+    }
+
+    fn assert_parameters(fit: &crate::equation_analyzer::Fit, expected: &[(&str, f32)], tol: f32) {
+        for &(name, want) in expected {
+            let got = fit.parameter(name).unwrap();
+            assert!(
+                (got - want).abs() <= tol * want.abs().max(1.0),
+                "{name}: got {got}, want {want}"
+            );
+        }
+    }
+
+    #[test]
+    fn fit_exponential() {
+        let (xs, ys) = sample(|x| 2.5 * (-0.8 * x).exp() + 0.5, 0.0, 0.25, 20);
+        let fit = calculator::fit(
+            "a * exp(b*x) + c",
+            &xs,
+            &ys,
+            &[("a", 1.0), ("b", -0.1), ("c", 0.0)],
+        )
+        .unwrap();
+        assert_parameters(&fit, &[("a", 2.5), ("b", -0.8), ("c", 0.5)], 1e-3);
+        assert!(fit.r_squared > 0.99999);
+        assert_eq!(fit.fitted.len(), xs.len());
+        assert!(fit.residuals.iter().all(|r| r.abs() < 1e-4));
+        let names: Vec<&str> = fit.parameters.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn fit_logistic() {
+        let (xs, ys) = sample(|x| 10.0 / (1.0 + (-1.5 * (x - 3.0)).exp()), 0.0, 0.5, 13);
+        let fit = calculator::fit(
+            "L / (1 + exp(-k*(x - m)))",
+            &xs,
+            &ys,
+            &[("L", 8.0), ("k", 1.0), ("m", 2.0)],
+        )
+        .unwrap();
+        assert_parameters(&fit, &[("L", 10.0), ("k", 1.5), ("m", 3.0)], 1e-3);
+        assert!(fit.r_squared > 0.99999);
+    }
+
+    #[test]
+    fn fit_power_law() {
+        let (xs, ys) = sample(|x| 3.0 * x.powf(1.7), 0.5, 0.5, 12);
+        let fit = calculator::fit("a * x^b", &xs, &ys, &[("a", 1.0), ("b", 1.0)]).unwrap();
+        assert_parameters(&fit, &[("a", 3.0), ("b", 1.7)], 1e-3);
+        assert!(fit.r_squared > 0.99999);
+    }
+
+    #[test]
+    fn fit_noisy_data() {
+        let noise = [0.3, -0.2, 0.1, -0.4, 0.25, -0.1, 0.35, -0.3];
+        let xs: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        let ys: Vec<f32> = xs
+            .iter()
+            .zip(noise)
+            .map(|(x, n)| 2.0 * x + 1.0 + n)
+            .collect();
+        let fit = calculator::fit("m*x + k", &xs, &ys, &[("m", 0.0), ("k", 0.0)]).unwrap();
+        assert!(fit.r_squared < 1.0 && fit.r_squared > 0.98);
+        assert_parameters(&fit, &[("m", 2.0), ("k", 1.0)], 0.1);
+        for ((y, f), r) in ys.iter().zip(&fit.fitted).zip(&fit.residuals) {
+            assert!((y - f - r).abs() < 1e-6);
+        }
+
+        let fit = calculator::fit("k", &[0.0, 1.0], &[4.0, 4.0], &[("k", 0.0)]).unwrap();
+        assert_parameters(&fit, &[("k", 4.0)], 1e-4);
+        assert!(fit.r_squared.is_nan());
+    }
+
+    #[test]
+    fn fit_with_definitions() {
+        let mut defs = Definitions::new();
+        defs.define_function("bump", "exp(-x^2)").unwrap();
+        defs.define_value("a", 100.0).unwrap();
+        let (xs, ys) = sample(|x| 4.0 * (-(x - 1.0).powi(2)).exp(), -2.0, 0.25, 17);
+        let fit = calculator::fit_with(
+            "a * bump(x - m)",
+            &xs,
+            &ys,
+            &[("a", 1.0), ("m", 0.5)],
+            &defs,
+        )
+        .unwrap();
+        assert_parameters(&fit, &[("a", 4.0), ("m", 1.0)], 1e-3);
+
+        // Parameters belong to the template, not to function bodies.
+        defs.define_function("shifted", "bump(x - m)").unwrap();
+        let err =
+            calculator::fit_with("a * shifted(x)", &xs, &ys, &[("a", 1.0), ("m", 0.5)], &defs)
+                .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Parameter 'm' is used inside 'shifted'; parameters can only appear in the template"
+        );
+    }
+
+    #[test]
+    fn fit_errors() {
+        let err = calculator::fit("a*x", &[1.0, 2.0], &[1.0], &[("a", 1.0)]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "fit needs the same, non-zero number of x and y values, got 2 and 1"
+        );
+        let err = calculator::fit("a*x", &[], &[], &[("a", 1.0)]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "fit needs the same, non-zero number of x and y values, got 0 and 0"
+        );
+        let err = calculator::fit("a*x", &[1.0], &[f32::NAN], &[("a", 1.0)]).unwrap_err();
+        assert_eq!(err.message, "fit data must be finite, got NaN");
+        let err = calculator::fit("a*x", &[1.0], &[1.0], &[("a", 1.0), ("a", 2.0)]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "Parameter 'a' is listed more than once (position 2)"
+        );
+
+        let err = calculator::fit("a*x + b", &[1.0], &[1.0], &[("a", 1.0)]).unwrap_err();
+        assert!(err.message.contains("'b'"), "{}", err.message);
+        assert_eq!(err.span, Some(Span::new(6, 7)));
+        assert!(calculator::fit("sin*x", &[1.0], &[1.0], &[("sin", 1.0)]).is_err());
+        assert!(calculator::fit("x", &[1.0], &[1.0], &[("x", 1.0)]).is_err());
+
+        let err = calculator::fit("a / x", &[0.0, 1.0], &[1.0, 1.0], &[("a", 1.0)]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "'a / x' is not finite at x = 0 for the initial parameters"
+        );
+    }
+
+    // ---- Bytecode: compile / CompiledEquation ----
+
+    /// The interpreter's result at `x`, for comparison with bytecode.
+    fn interpret(eq: &str, x: f32, defs: &Definitions) -> Result<f32, EquationError> {
+        let parsed = parse(StreamingTokenizer::new_with(eq, Some(defs))?)?;
+        let ctx = defs.compile();
+        let mut state = EvalState::new(Some(&ctx), 0);
+        evaluate_with(parsed.iter().copied(), x, &mut state)
+    }
+
+    fn assert_same_as_interpreter(eq: &str, defs: &Definitions) {
+        let compiled = calculator::compile_with(eq, defs);
+        for x in [-2.5, -1.0, 0.0, 0.5, 1.0, 3.0] {
+            let expected = interpret(eq, x, defs);
+            let got = compiled.clone().and_then(|c| c.eval(x));
+            match (&expected, &got) {
+                (Ok(a), Ok(b)) => assert!(
+                    a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan()),
+                    "{eq} at {x}: interpreter {a}, bytecode {b}"
+                ),
+                _ => assert_eq!(expected, got, "{eq} at {x}"),
+            }
+        }
+    }
+
+    /// Equations exercising every bytecode path, with the definitions
+    /// they use.
+    fn bytecode_corpus() -> (Definitions, Vec<&'static str>) {
+        let defs = defs_with(
+            &[("a", 3.0), ("k", 0.05)],
+            &[
+                ("g", "a * x^2"),
+                ("h", "g(x) + g(x + 1)"),
+                ("p", "x%"),
+                ("fact", "fact(x - 1) * x"),
+                ("even", "odd(x - 1)"),
+                ("odd", "even(x - 1)"),
+                ("broken", "sin(2x"),
+                ("short", "x +"),
+                ("callsbroken", "broken(x) + 1"),
+                ("sq", "root(x, 2)"),
+                ("t", "taylor(g, 1, 2)"),
+                ("spiral", "g(spiral(x)) + h(x)"),
+            ],
+        );
+        let equations = vec![
+            "sqrt(16) + abs(-5) * 3 - 2^4",
+            "3x^4 - 2x^3 + 5x^2 - x + 7",
+            "sin(π/4) * cos(π/3) + tan(π/6)",
+            "sqrt(abs(ln(e^2) + log_2(16)))",
+            "sin(x) * cos(2x) + tan(x/2) - sqrt(abs(x))",
+            "avg(10, 20, 30) + min(5, 15, 25) * max(2, x, 8)",
+            "y = -x^2 + x mod 2",
+            "100 - 20% + x",
+            "x + 10%",
+            "x - x%",
+            "50% * x",
+            "(x + 1)!",
+            "3! + x!",
+            "x |> sin |> abs",
+            "2 |> g",
+            "x |> g",
+            "g(2) * x",
+            "h(x) - h(1)",
+            "100 - p(20)",
+            "100 - p(x)",
+            "fact(x)",
+            "even(x)",
+            "broken(x)",
+            "1 + callsbroken(2)",
+            "short(x)",
+            "sq(x)",
+            "root(x, 2) + 1",
+            "ch(x, 2)",
+            "max()",
+            "g(1, 2)",
+            "sin(1, 2)",
+            "atan2(x)",
+            "taylor(sin, 0, 5)",
+            "taylor(g, x, 2)",
+            "taylor(sqrt, 0, 2)",
+            "taylor(cos, 1, x)",
+            "t(x)",
+            "spiral(x)",
+            "g(spiral(x))",
+            "x x",
+            "1 + g(x) + x + sin(2)",
+            "k * 2 + pmt(k / 12, 360, 200000)",
+        ];
+        (defs, equations)
+    }
+
+    #[test]
+    fn compiled_matches_interpreter() {
+        let (defs, equations) = bytecode_corpus();
+        for eq in equations {
+            assert_same_as_interpreter(eq, &defs);
+        }
+    }
+
+    #[test]
+    fn compile_reports_only_syntax_errors() {
+        let err = calculator::compile("2 + foo(3)").unwrap_err();
+        assert_eq!(err.span, Some(Span::new(4, 7)));
+
+        let f = calculator::compile("max()").unwrap();
+        let err = f.eval(0.0).unwrap_err();
+        assert_eq!(err.message, "max requires at least 1 parameter, got 0");
+        assert_eq!(err.span, Some(Span::new(0, 5)));
+    }
+
+    #[test]
+    fn compiled_errors_in_functions_are_tagged() {
+        let defs = defs_with(&[], &[("g", "root(x, 2)"), ("r", "r(x) + 1")]);
+        let f = calculator::compile_with("1 + g(x)", &defs).unwrap();
+        assert_eq!(f.eval(4.0).unwrap(), 3.0);
+        let err = f.eval(-4.0).unwrap_err();
+        assert_eq!(err.in_function.as_deref(), Some("g"));
+        assert_eq!(err.span, Some(Span::new(0, 10)));
+
+        let err = calculator::compile_with("r(x)", &defs)
+            .unwrap()
+            .eval(0.0)
+            .unwrap_err();
+        assert!(err.message.starts_with("Call depth limit (32) exceeded"));
+        assert_eq!(err.in_function.as_deref(), Some("r"));
+    }
+
+    #[test]
+    fn compiled_binds_definitions_early() {
+        let mut defs = defs_with(&[("a", 2.0)], &[("g", "a * x")]);
+        let f = calculator::compile_with("g(x)", &defs).unwrap();
+        defs.define_value("a", 10.0).unwrap();
+        assert_eq!(f.eval(3.0).unwrap(), 6.0);
+        assert_eq!(calculator::calculate_with("g(3)", &defs).unwrap(), 30.0);
+    }
+
+    #[test]
+    fn compiled_seeded_draws_match_plot() {
+        let opts = EvalOptions::new().with_seed(5);
+        let defs = Definitions::new();
+        let f = calculator::compile_with_options("x + randn(0, 1)", &defs, &opts).unwrap();
+        let points =
+            calculator::plot_with_options("x + randn(0, 1)", 0.0, 1.0, 0.25, &defs, &opts).unwrap();
+        for p in points {
+            assert_eq!(f.eval(p.x).unwrap(), p.y);
+        }
+    }
+
+    #[test]
+    fn eval_batch_matches_eval() {
+        let (defs, equations) = bytecode_corpus();
+        // Several lane groups, the last one partial.
+        let xs: Vec<f32> = (0..150).map(|i| i as f32 / 25.0 - 3.0).collect();
+        for eq in equations {
+            let Ok(f) = calculator::compile_with(eq, &defs) else {
+                continue;
+            };
+            let mut ys = vec![0.0; xs.len()];
+            let batch = calculator::eval_batch(&f, &xs, &mut ys);
+            let single: Result<Vec<f32>, EquationError> = xs.iter().map(|&x| f.eval(x)).collect();
+            match (batch, single) {
+                (Ok(()), Ok(expected)) => {
+                    for ((x, y), e) in xs.iter().zip(&ys).zip(&expected) {
+                        assert!(
+                            y.to_bits() == e.to_bits() || (y.is_nan() && e.is_nan()),
+                            "{eq} at {x}: batch {y}, eval {e}"
+                        );
+                    }
+                }
+                (batch, single) => assert_eq!(batch.err(), single.err(), "{eq}"),
+            }
+        }
+    }
+
+    #[test]
+    fn eval_batch_reports_first_failure() {
+        let f = calculator::compile("root(x, 2)").unwrap();
+        let xs: Vec<f32> = (0..5000).map(|i| 4000.0 - i as f32).collect();
+        let mut ys = vec![0.0; xs.len()];
+        let err = calculator::eval_batch(&f, &xs, &mut ys).unwrap_err();
+        assert_eq!(err.message, "root(-1, 2) is not a real number");
+        assert_eq!(ys[3999], 1.0);
+
+        let err = calculator::eval_batch(&f, &[1.0, 2.0], &mut [0.0]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "eval_batch needs one output per input, got 2 x values and 1 y slots"
+        );
+        assert!(calculator::eval_batch(&f, &[], &mut []).is_ok());
+    }
+
+    #[test]
+    fn eval_batch_seeded_draws_match_plot() {
+        let opts = EvalOptions::new().with_seed(11);
+        let defs = defs_with(&[], &[("noise", "x + rand()")]);
+        let eq = "noise(x) + randn(0, 1) * randint(1, 3)";
+        let f = calculator::compile_with_options(eq, &defs, &opts).unwrap();
+        let points = calculator::plot_with_options(eq, -2.0, 2.0, 0.01, &defs, &opts).unwrap();
+        let xs: Vec<f32> = points.iter().map(|p| p.x).collect();
+        let mut ys = vec![0.0; xs.len()];
+        calculator::eval_batch(&f, &xs, &mut ys).unwrap();
+        for (p, y) in points.iter().zip(ys) {
+            assert_eq!(p.y, y);
+        }
+    }
+
+    // ---- Lazy plots: plot_iter ----
+
+    #[test]
+    fn plot_iter_matches_plot() {
+        let (defs, equations) = bytecode_corpus();
+        for eq in equations {
+            let Ok(points) = calculator::plot_iter_with(eq, -3.0, 3.0, 0.04, &defs) else {
+                assert!(
+                    calculator::plot_with(eq, -3.0, 3.0, 0.04, &defs).is_err(),
+                    "{eq}"
+                );
+                continue;
+            };
+            let streamed: Vec<_> = points.collect();
+            match calculator::plot_with(eq, -3.0, 3.0, 0.04, &defs) {
+                Ok(expected) => {
+                    assert_eq!(streamed.len(), expected.len(), "{eq}");
+                    for (s, e) in streamed.iter().zip(&expected) {
+                        let s = s.as_ref().unwrap();
+                        assert_eq!(s.x, e.x, "{eq}");
+                        assert!(
+                            s.y.to_bits() == e.y.to_bits() || (s.y.is_nan() && e.y.is_nan()),
+                            "{eq} at {}: iter {}, plot {}",
+                            e.x,
+                            s.y,
+                            e.y
+                        );
+                    }
+                }
+                Err(err) => {
+                    let first = streamed.into_iter().find_map(Result::err);
+                    assert_eq!(first, Some(err), "{eq}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn plot_iter_keeps_points_around_errors() {
+        let results: Vec<_> = calculator::plot_iter("root(x, 2)", -100.0, 100.0, 1.0)
+            .unwrap()
+            .collect();
+        assert_eq!(results.len(), 201);
+        assert_eq!(
+            results[0].as_ref().unwrap_err().message,
+            "root(-100, 2) is not a real number"
+        );
+        assert_eq!(
+            results[99].as_ref().unwrap_err().span,
+            Some(Span::new(0, 10))
+        );
+        assert!(results[..100].iter().all(Result::is_err));
+        let right: Vec<Point> = results[100..].iter().map(|r| r.clone().unwrap()).collect();
+        assert_eq!(right[0], Point { x: 0.0, y: 0.0 });
+        assert_eq!(right[100], Point { x: 100.0, y: 10.0 });
+    }
+
+    #[test]
+    fn plot_iter_streams_lazily() {
+        // Materializing this range would need billions of points.
+        let mut points = calculator::plot_iter("x", 0.0, 1e12, 0.5).unwrap();
+        assert_eq!(points.next().unwrap().unwrap(), Point { x: 0.0, y: 0.0 });
+        let tenth = points.nth(8).unwrap().unwrap();
+        assert_eq!(tenth, Point { x: 4.5, y: 4.5 });
+
+        assert_eq!(
+            calculator::plot_iter("x", 1.0, 0.0, 1.0).unwrap().count(),
+            0
+        );
+    }
+
+    #[test]
+    fn plot_iter_setup_errors() {
+        let err = calculator::plot_iter("x", 0.0, 1.0, 0.0).err().unwrap();
+        assert_eq!(
+            err.message,
+            "Invalid step size 0: step size must be a positive number"
+        );
+        assert!(calculator::plot_iter("x", 0.0, 1.0, f32::NAN).is_err());
+        assert!(calculator::plot_iter("nope(x)", 0.0, 1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn plot_iter_seeded_draws_match_plot() {
+        let opts = EvalOptions::new().with_seed(5);
+        let defs = defs_with(&[], &[("noise", "x + rand()")]);
+        let eq = "noise(x) + randn(0, 1)";
+        let expected = calculator::plot_with_options(eq, -2.0, 2.0, 0.01, &defs, &opts).unwrap();
+        let streamed: Vec<Point> =
+            calculator::plot_iter_with_options(eq, -2.0, 2.0, 0.01, &defs, &opts)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+        assert_eq!(streamed, expected);
+    }
+
+    // ---- Gap-tolerant plots: plot_tolerant ----
+
+    #[test]
+    fn plot_tolerant_records_gaps() {
+        let plot =
+            calculator::plot_tolerant("root(x, 2) + root(4 - x, 2)", -2.0, 6.0, 1.0).unwrap();
+        assert_eq!(plot.samples.len(), 9);
+        assert!(!plot.is_complete());
+        assert_eq!(plot.gaps.len(), 2);
+        assert_eq!(
+            (plot.gaps[0].from, plot.gaps[0].to, plot.gaps[0].samples),
+            (-2.0, -1.0, 2)
+        );
+        assert_eq!(
+            plot.gaps[0].error.message,
+            "root(-2, 2) is not a real number"
+        );
+        assert_eq!(
+            (plot.gaps[1].from, plot.gaps[1].to, plot.gaps[1].samples),
+            (5.0, 6.0, 2)
+        );
+        assert_eq!(
+            plot.gaps[1].error.message,
+            "root(-1, 2) is not a real number"
+        );
+        assert_eq!(plot.gaps[1].error.span, Some(Span::new(13, 27)));
+
+        assert_eq!(plot.samples[2].value(), Some(2.0));
+        assert_eq!(plot.samples[1].value(), None);
+        assert_eq!(plot.points().count(), 5);
+        let segments = plot.segments();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].first(), Some(&Point { x: 0.0, y: 2.0 }));
+        assert_eq!(segments[0].last(), Some(&Point { x: 4.0, y: 2.0 }));
+    }
+
+    #[test]
+    fn plot_tolerant_splits_segments_at_gaps() {
+        let defs = defs_with(&[], &[("band", "root(sin(x), 2)")]);
+        // sin < 0 on [-1, 0) and (π, 2π): gap, curve, gap, curve.
+        let plot = calculator::plot_tolerant_with("band(x)", -1.0, 9.0, 0.5, &defs).unwrap();
+        let segments = plot.segments();
+        assert_eq!((plot.gaps.len(), segments.len()), (2, 2));
+        assert_eq!((plot.gaps[0].from, plot.gaps[0].to), (-1.0, -0.5));
+        assert_eq!(
+            (segments[0][0].x, segments[0].last().unwrap().x),
+            (0.0, 3.0)
+        );
+        assert_eq!((plot.gaps[1].from, plot.gaps[1].to), (3.5, 6.0));
+        assert_eq!(
+            (segments[1][0].x, segments[1].last().unwrap().x),
+            (6.5, 9.0)
+        );
+        assert!(plot.gaps.iter().all(|g| g.error.in_function.is_some()));
+        let gap_samples: usize = plot.gaps.iter().map(|g| g.samples).sum();
+        assert_eq!(gap_samples + plot.points().count(), plot.samples.len());
+    }
+
+    #[test]
+    fn plot_tolerant_matches_plot_and_iter() {
+        let (defs, equations) = bytecode_corpus();
+        for eq in equations {
+            let Ok(plot) = calculator::plot_tolerant_with(eq, -3.0, 3.0, 0.04, &defs) else {
+                assert!(
+                    calculator::plot_with(eq, -3.0, 3.0, 0.04, &defs).is_err(),
+                    "{eq}"
+                );
+                continue;
+            };
+            let mut iter = calculator::plot_iter_with(eq, -3.0, 3.0, 0.04, &defs).unwrap();
+            let streamed: TolerantPlot = std::iter::from_fn(|| iter.next_sample()).collect();
+            assert_eq!(plot.samples.len(), streamed.samples.len(), "{eq}");
+            for (a, b) in plot.samples.iter().zip(&streamed.samples) {
+                assert_eq!(a.x, b.x, "{eq}");
+                match (&a.y, &b.y) {
+                    (Ok(a), Ok(b)) => assert!(
+                        a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan()),
+                        "{eq}"
+                    ),
+                    (a, b) => assert_eq!(a, b, "{eq}"),
+                }
+            }
+            assert_eq!(plot.gaps, streamed.gaps, "{eq}");
+            match calculator::plot_with(eq, -3.0, 3.0, 0.04, &defs) {
+                Ok(_) => assert!(plot.is_complete(), "{eq}"),
+                Err(err) => assert_eq!(plot.gaps[0].error, err, "{eq}"),
+            }
+        }
+    }
+
+    #[test]
+    fn plot_tolerant_setup_errors() {
+        assert!(calculator::plot_tolerant("x", 0.0, 1.0, -1.0).is_err());
+        assert!(calculator::plot_tolerant("nope(x)", 0.0, 1.0, 1.0).is_err());
+        let empty = calculator::plot_tolerant("root(x, 2)", 1.0, 0.0, 1.0).unwrap();
+        assert!(empty.samples.is_empty() && empty.is_complete());
+    }
+
+    // ---- Evaluation limits: EvalLimits / CancelToken ----
+
+    /// `h0(x) = x`, `hk(x) = h(k-1)(x) + h(k-1)(x)`: `h{depth}(x)` makes
+    /// 2^depth calls without recursing.
+    fn doubling_defs(depth: usize) -> Definitions {
+        let mut defs = defs_with(&[], &[("h0", "x")]);
+        for k in 1..=depth {
+            let body = format!("h{0}(x) + h{0}(x)", k - 1);
+            defs.define_function(&format!("h{k}"), &body).unwrap();
+        }
+        defs
+    }
+
+    fn limited(limits: EvalLimits) -> EvalOptions {
+        EvalOptions::new().with_limits(limits)
+    }
+
+    #[test]
+    fn step_limit_stops_exponential_definitions() {
+        let defs = doubling_defs(30);
+        let opts = limited(EvalLimits::new().with_max_steps(100_000));
+
+        let err = calculator::calculate_with_options("h30(2)", &defs, &opts).unwrap_err();
+        assert_eq!(err.message, "Step limit (100000) exceeded");
+        assert!(err.in_function.is_some());
+        // Cheap equations fit the same budget.
+        assert_eq!(
+            calculator::calculate_with_options("h5(2)", &defs, &opts).unwrap(),
+            64.0
+        );
+
+        let f = calculator::compile_with_options("h30(x)", &defs, &opts).unwrap();
+        assert_eq!(
+            f.eval(1.0).unwrap_err().message,
+            "Step limit (100000) exceeded"
+        );
+        let f = calculator::compile_with_options("h30(2)", &defs, &opts).unwrap();
+        assert!(f.eval(0.0).is_err());
+        let f = calculator::compile_with_options("h5(x)", &defs, &opts).unwrap();
+        assert_eq!(f.eval(1.0).unwrap(), 32.0);
+    }
+
+    #[test]
+    fn step_limit_applies_per_plot_sample() {
+        let defs = doubling_defs(30);
+        let opts = limited(EvalLimits::new().with_max_steps(100_000));
+
+        let err = calculator::plot_with_options("h30(x)", 0.0, 1.0, 0.5, &defs, &opts).unwrap_err();
+        assert_eq!(err.message, "Step limit (100000) exceeded");
+
+        // Too expensive a sample is a gap, not a failed plot.
+        let plot =
+            calculator::plot_tolerant_with_options("h30(x)", 0.0, 1.0, 0.5, &defs, &opts).unwrap();
+        assert_eq!(plot.gaps.len(), 1);
+        assert_eq!(plot.gaps[0].samples, 3);
+        assert_eq!(plot.gaps[0].error.message, "Step limit (100000) exceeded");
+
+        let points = calculator::plot_with_options("h4(x)", 0.0, 300.0, 1.0, &defs, &opts).unwrap();
+        assert_eq!(points[300].y, 4800.0);
+
+        let f = calculator::compile_with_options("h30(x)", &defs, &opts).unwrap();
+        let mut ys = vec![0.0; 200];
+        let xs: Vec<f32> = (0..200).map(|i| i as f32).collect();
+        let err = calculator::eval_batch(&f, &xs, &mut ys).unwrap_err();
+        assert_eq!(err.message, "Step limit (100000) exceeded");
+    }
+
+    #[test]
+    fn step_limit_matches_between_batch_and_scalar() {
+        let defs = doubling_defs(10);
+        for steps in [1, 3, 10, 100, 1000, 5000, 10_000] {
+            let opts = limited(EvalLimits::new().with_max_steps(steps));
+            for eq in ["h10(x)", "h3(x) + h6(x)", "x + 1", "h8(x) * rand()"] {
+                let f = calculator::compile_with_options(eq, &defs, &opts).unwrap();
+                let xs: Vec<f32> = (0..100).map(|i| i as f32).collect();
+                let mut ys = vec![0.0; xs.len()];
+                let batch = calculator::eval_batch(&f, &xs, &mut ys);
+                let single: Result<Vec<f32>, EquationError> =
+                    xs.iter().map(|&x| f.eval(x)).collect();
+                assert_eq!(batch.is_ok(), single.is_ok(), "{eq} with {steps} steps");
+                if let (Err(a), Err(b)) = (batch, single) {
+                    assert_eq!(a, b, "{eq} with {steps} steps");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn limits_bound_the_other_domains() {
+        let defs = doubling_defs(30);
+        let opts = limited(
+            EvalLimits::new()
+                .with_max_steps(100_000)
+                .with_max_input_len(20),
+        );
+        let step_limit = |err: EquationError| {
+            assert_eq!(err.message, "Step limit (100000) exceeded");
+        };
+
+        step_limit(calculator::calculate_units_with_options("h30(2) m", &defs, &opts).unwrap_err());
+        step_limit(
+            calculator::eval_with_derivative_with_options("h30(x)", 1.0, &defs, &opts).unwrap_err(),
+        );
+        step_limit(calculator::taylor_with_options("h30(x)", 0.0, 2, &defs, &opts).unwrap_err());
+        step_limit(
+            calculator::calculate_interval_with_options("h30(x)", [0.0, 1.0], &defs, &opts)
+                .unwrap_err(),
+        );
+        let (xs, ys) = ([0.0, 1.0, 2.0], [0.0, 2.0, 4.0]);
+        step_limit(
+            calculator::fit_with_options("a * h30(x)", &xs, &ys, &[("a", 1.0)], &defs, &opts)
+                .unwrap_err(),
+        );
+
+        // Cheap equations fit the same budget.
+        assert_eq!(
+            calculator::eval_with_derivative_with_options("h5(x)", 1.0, &defs, &opts).unwrap(),
+            (32.0, 32.0)
+        );
+        let fit = calculator::fit_with_options("a * h1(x)", &xs, &ys, &[("a", 0.0)], &defs, &opts)
+            .unwrap();
+        assert!((fit.parameter("a").unwrap() - 1.0).abs() < 1e-4);
+
+        let long = "h1(x) + h1(x) + h1(x) + h1(x)";
+        let err = calculator::taylor_with_options(long, 0.0, 2, &defs, &opts).unwrap_err();
+        assert_eq!(
+            err.message,
+            "Equation is 29 characters long; the limit is 20"
+        );
+        assert!(calculator::calculate_units_with_options(long, &defs, &opts).is_err());
+        assert!(calculator::eval_with_derivative_with_options(long, 0.0, &defs, &opts).is_err());
+        assert!(
+            calculator::calculate_interval_with_options(long, [0.0, 1.0], &defs, &opts).is_err()
+        );
+        assert!(calculator::fit_with_options(long, &xs, &ys, &[], &defs, &opts).is_err());
+
+        let token = CancelToken::new();
+        token.cancel();
+        let cancelled = limited(EvalLimits::new().with_cancel(token));
+        let err = calculator::fit_with_options("a * x", &xs, &ys, &[("a", 1.0)], &defs, &cancelled)
+            .unwrap_err();
+        assert_eq!(err.message, "Evaluation cancelled");
+    }
+
+    #[test]
+    fn input_and_point_limits_are_checked_up_front() {
+        let defs = Definitions::new();
+        let opts = limited(
+            EvalLimits::new()
+                .with_max_input_len(5)
+                .with_max_points(1000),
+        );
+
+        let err = calculator::calculate_with_options("1 + 2 + 3", &defs, &opts).unwrap_err();
+        assert_eq!(err.message, "Equation is 9 characters long; the limit is 5");
+        assert!(calculator::compile_with_options("1 + 2 + 3", &defs, &opts).is_err());
+        assert!(calculator::plot_with_options("x + x + x", 0.0, 1.0, 1.0, &defs, &opts).is_err());
+        // Characters, not bytes.
+        assert_eq!(
+            calculator::calculate_with_options("π * π", &defs, &opts).unwrap(),
+            PI * PI
+        );
+
+        // A billion points: rejected before any are allocated.
+        let err = calculator::plot_with_options("x", 0.0, 1e9, 1.0, &defs, &opts).unwrap_err();
+        assert_eq!(
+            err.message,
+            "Plot of 1000000001 points exceeds the limit of 1000"
+        );
+        assert!(calculator::plot_tolerant_with_options("x", 0.0, 1e9, 1.0, &defs, &opts).is_err());
+        assert!(calculator::plot_iter_with_options("x", 0.0, 1e9, 1.0, &defs, &opts).is_err());
+        let points = calculator::plot_with_options("x", 0.0, 999.0, 1.0, &defs, &opts).unwrap();
+        assert_eq!(points.len(), 1000);
+    }
+
+    #[test]
+    fn samples_match_the_point_count_when_steps_are_below_f32_spacing() {
+        // Adjacent f32 values near 1e8 are 8 apart, so adding a step of 1
+        // to x would never move it; the samplers index from x_min instead.
+        let defs = Definitions::new();
+        let opts = limited(
+            EvalLimits::new()
+                .with_max_points(1000)
+                .with_timeout(Duration::from_secs(2)),
+        );
+        let points =
+            calculator::plot_with_options("x", 1e8, 1e8 + 100.0, 1.0, &defs, &opts).unwrap();
+        assert_eq!(points.len(), 97);
+        assert_eq!(points.last().unwrap().x, 1e8 + 96.0);
+        let streamed: Vec<Point> =
+            calculator::plot_iter_with_options("x", 1e8, 1e8 + 100.0, 1.0, &defs, &opts)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(streamed, points);
+        let tolerant =
+            calculator::plot_tolerant_with_options("x", 1e8, 1e8 + 100.0, 1.0, &defs, &opts)
+                .unwrap();
+        assert_eq!(tolerant.samples.len(), 97);
+    }
+
+    #[test]
+    fn deadline_stops_evaluation() {
+        let defs = doubling_defs(30);
+        let opts = limited(EvalLimits::new().with_timeout(Duration::from_millis(20)));
+
+        let started = Instant::now();
+        let err = calculator::calculate_with_options("h30(2)", &defs, &opts).unwrap_err();
+        assert_eq!(err.message, "Evaluation timed out");
+        let err = calculator::plot_with_options("h30(x)", 0.0, 1.0, 1.0, &defs, &opts).unwrap_err();
+        assert_eq!(err.message, "Evaluation timed out");
+        let f = calculator::compile_with_options("h30(x)", &defs, &opts).unwrap();
+        assert_eq!(f.eval(1.0).unwrap_err().message, "Evaluation timed out");
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        // Past the deadline, nothing runs at all.
+        let expired = limited(EvalLimits::new().with_deadline(Instant::now()));
+        assert!(calculator::calculate_with_options("1", &defs, &expired).is_err());
+        let plot = calculator::plot_tolerant_with_options("x", 0.0, 1.0, 0.5, &defs, &expired);
+        assert_eq!(plot.unwrap_err().message, "Evaluation timed out");
+    }
+
+    #[test]
+    fn cancel_token_stops_evaluation() {
+        let defs = doubling_defs(30);
+        let token = CancelToken::new();
+        let opts = limited(EvalLimits::new().with_cancel(token.clone()));
+
+        let canceller = {
+            let token = token.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                token.cancel();
+            })
+        };
+        let err = calculator::calculate_with_options("h30(2)", &defs, &opts).unwrap_err();
+        assert_eq!(err.message, "Evaluation cancelled");
+        canceller.join().unwrap();
+        let err = calculator::plot_with_options("x", 0.0, 1.0, 0.5, &defs, &opts).unwrap_err();
+        assert_eq!(err.message, "Evaluation cancelled");
+    }
+
+    #[test]
+    fn cancelling_a_plot_iter_ends_it() {
+        let token = CancelToken::new();
+        let opts = limited(EvalLimits::new().with_cancel(token.clone()));
+        let mut points =
+            calculator::plot_iter_with_options("x", 0.0, 1e6, 1.0, &Definitions::new(), &opts)
+                .unwrap();
+        assert_eq!(points.next().unwrap().unwrap(), Point { x: 0.0, y: 0.0 });
+        token.cancel();
+        // The block already computed drains first.
+        let rest: Vec<_> = points.collect();
+        assert!(rest.len() < 100);
+        let (last, computed) = rest.split_last().unwrap();
+        assert!(computed.iter().all(Result::is_ok));
+        assert_eq!(last.as_ref().unwrap_err().message, "Evaluation cancelled");
+    }
+
+    // ---- Evaluation traces: trace ----
+
+    fn traced(eq: &str, defs: &Definitions) -> Vec<String> {
+        let t = calculator::trace(eq, defs).unwrap();
+        t.steps.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn trace_follows_precedence() {
+        let t = calculator::trace("2 + 3 * 4^2", &Definitions::new()).unwrap();
+        assert_eq!(t.result, Ok(50.0));
+        let ops: Vec<(Operation, Vec<f32>, f32, Span)> = t
+            .steps
+            .into_iter()
+            .map(|s| (s.operation, s.inputs, s.output, s.span))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (
+                    Operation::Binary("^"),
+                    vec![4.0, 2.0],
+                    16.0,
+                    Span::new(9, 10)
+                ),
+                (
+                    Operation::Binary("*"),
+                    vec![3.0, 16.0],
+                    48.0,
+                    Span::new(6, 7)
+                ),
+                (
+                    Operation::Binary("+"),
+                    vec![2.0, 48.0],
+                    50.0,
+                    Span::new(2, 3)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn trace_shows_operators_and_functions() {
+        let defs = Definitions::new();
+        assert_eq!(traced("200 + 10%", &defs), ["10% = 0.1", "200 + 10% = 220"]);
+        assert_eq!(traced("-2^2", &defs), ["2 ^ 2 = 4", "-(4) = -4"]);
+        assert_eq!(traced("3! - 1", &defs), ["3! = 6", "6 - 1 = 5"]);
+        assert_eq!(traced("7 mod 4", &defs), ["7 mod 4 = 3"]);
+        assert_eq!(traced("max(1, 2, 3)", &defs), ["max(1, 2, 3) = 3"]);
+        assert_eq!(traced("log_2(8)", &defs), ["log_2(8) = 3"]);
+        assert_eq!(traced("9 |> sqrt", &defs), ["sqrt(9) = 3"]);
+        assert_eq!(
+            traced("taylor(sin, 0, 3)", &defs),
+            ["taylor(sin, 0, 3) = 0"]
+        );
+        // Operands alone take no steps.
+        assert!(traced("π", &defs).is_empty());
+
+        let t = calculator::trace("randint(1, 6)", &defs).unwrap();
+        assert_eq!(t.steps[0].operation, Operation::Call("randint".to_string()));
+        assert_eq!(t.steps[0].inputs, vec![1.0, 6.0]);
+        assert_eq!(t.result, Ok(t.steps[0].output));
+    }
+
+    #[test]
+    fn trace_enters_user_functions() {
+        let defs = defs_with(&[("a", 2.0)], &[("g", "x^2 + a"), ("h", "g(x) * 2")]);
+        assert_eq!(
+            traced("h(3) - 1", &defs),
+            [
+                "in g(x): 3 ^ 2 = 9",
+                "in g(x): 9 + 2 = 11",
+                "in h(x): g(3) = 11",
+                "in h(x): 11 * 2 = 22",
+                "h(3) = 22",
+                "22 - 1 = 21",
+            ]
+        );
+        let t = calculator::trace("h(3) - 1", &defs).unwrap();
+        // Body spans are in the body's source.
+        assert_eq!(t.steps[0].span, Span::new(1, 2));
+        assert_eq!(t.steps[0].in_function.as_deref(), Some("g"));
+        assert_eq!(t.steps[4].span, Span::new(0, 4));
+        assert_eq!(t.steps[4].in_function, None);
+    }
+
+    #[test]
+    fn trace_keeps_steps_before_an_error() {
+        let defs = defs_with(&[], &[("g", "root(x, 2)")]);
+        let t = calculator::trace("2 * 3 + g(0 - 4)", &defs).unwrap();
+        let steps: Vec<String> = t.steps.iter().map(ToString::to_string).collect();
+        assert_eq!(steps, ["2 * 3 = 6", "0 - 4 = -4"]);
+        let err = t.result.unwrap_err();
+        assert_eq!(err.message, "root(-4, 2) is not a real number");
+        assert_eq!(err.in_function.as_deref(), Some("g"));
+
+        assert!(calculator::trace("nope(2)", &defs).is_err());
+    }
+
+    #[test]
+    fn trace_results_match_calculate() {
+        let (defs, equations) = bytecode_corpus();
+        for eq in equations {
+            let Ok(t) = calculator::trace(eq, &defs) else {
+                assert!(calculator::calculate_with(eq, &defs).is_err(), "{eq}");
+                continue;
+            };
+            if eq.contains("rand") {
+                continue;
+            }
+            match (&t.result, calculator::calculate_with(eq, &defs)) {
+                (Ok(a), Ok(b)) => assert!(*a == b || (a.is_nan() && b.is_nan()), "{eq}"),
+                (a, b) => assert_eq!(a.clone(), b, "{eq}"),
+            }
+            // The last step produces the result, at the top level.
+            if let (Ok(v), Some(last)) = (t.result, t.steps.last()) {
+                assert_eq!(last.output.to_bits(), v.to_bits(), "{eq}");
+                assert_eq!(last.in_function, None, "{eq}");
+            }
+        }
+    }
+
+    #[test]
+    fn trace_is_bounded_by_the_step_limit() {
+        let defs = doubling_defs(30);
+        let opts = limited(EvalLimits::new().with_max_steps(1000));
+        let t = calculator::trace_with_options("h30(1)", &defs, &opts).unwrap();
+        assert!(!t.steps.is_empty() && t.steps.len() <= 1000);
+        assert_eq!(t.result.unwrap_err().message, "Step limit (1000) exceeded");
+
+        let t = calculator::trace_with_options("h2(1)", &defs, &opts).unwrap();
+        assert_eq!(t.result, Ok(4.0));
+        assert_eq!(t.steps, calculator::trace("h2(1)", &defs).unwrap().steps);
+
+        let long = limited(EvalLimits::new().with_max_input_len(3));
+        let err = calculator::trace_with_options("h2(1)", &defs, &long).unwrap_err();
+        assert_eq!(err.message, "Equation is 5 characters long; the limit is 3");
+    }
+
+    // ---- Diagnostics: every problem at once ----
+
+    fn diagnosed(eq: &str, defs: &Definitions) -> Vec<(Severity, String, Option<Span>)> {
+        calculator::diagnostics(eq, defs)
+            .into_iter()
+            .map(|d| (d.severity, d.message, d.span))
+            .collect()
+    }
+
+    #[test]
+    fn diagnostics_report_every_error() {
+        let defs = defs_with(&[("a", 2.0)], &[("g", "x + 1")]);
+        assert_eq!(
+            diagnosed("sine(x) + 1 +* 3 + g(1, 2) + a(3)", &defs),
+            [
+                (
+                    Severity::Error,
+                    "Invalid function name sine — did you mean 'sin'?".to_string(),
+                    Some(Span::new(0, 4))
+                ),
+                (
+                    Severity::Error,
+                    "Invalid expression".to_string(),
+                    Some(Span::new(12, 13))
+                ),
+                (
+                    Severity::Error,
+                    "g takes exactly 1 parameter (x), got 2".to_string(),
+                    Some(Span::new(19, 26))
+                ),
+                (
+                    Severity::Error,
+                    "'a' is a value, not a function".to_string(),
+                    Some(Span::new(29, 30))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn diagnostics_skip_to_the_next_operator() {
+        let defs = Definitions::new();
+        // `$` and `@` each cost one error; the operands after them are
+        // skipped, not reported as stray.
+        let found = diagnosed("2 $ 3 @ 4", &defs);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].2, Some(Span::new(2, 3)));
+        assert_eq!(found[1].2, Some(Span::new(6, 7)));
+
+        // A bad call's arguments are skipped along with it.
+        let found = diagnosed("nope(1, (2)) * 2", &defs);
+        assert_eq!(found.len(), 1);
+
+        // A failed pipe target isn't reported again as a dangling pipe.
+        let found = diagnosed("2 |> nope + 1", &defs);
+        assert_eq!(found.len(), 1);
+    }
+
+    #[test]
+    fn diagnostics_report_unclosed_parens() {
+        let found = diagnosed("sqrt(4 + (1", &Definitions::new());
+        let messages: Vec<&str> = found.iter().map(|d| d.1.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["Invalid function", "Invalid opening parenthesis"]
+        );
+        assert_eq!(found[0].2, Some(Span::new(0, 5)));
+        assert_eq!(found[1].2, Some(Span::new(9, 10)));
+    }
+
+    #[test]
+    fn diagnostics_warn_about_ambiguous_negation() {
+        let found = diagnosed("-x^2 + (-x)^2 - 2^-e^2", &Definitions::new());
+        assert_eq!(
+            found,
+            [
+                (
+                    Severity::Warning,
+                    "Ambiguous '-x^…': parsed as -(x^…), not (-x)^…".to_string(),
+                    Some(Span::new(0, 3))
+                ),
+                (
+                    Severity::Warning,
+                    "Ambiguous '-e^…': parsed as -(e^…), not (-e)^…".to_string(),
+                    Some(Span::new(18, 21))
+                ),
+            ]
+        );
+        // Warnings don't stop evaluation.
+        assert_eq!(calculator::calculate("-2^2").unwrap(), -4.0);
+    }
+
+    #[test]
+    fn diagnostics_warn_about_comma_grouping() {
+        let found = diagnosed("max(1,000,000, 12,50, 3, 400)", &Definitions::new());
+        assert_eq!(
+            found,
+            [(
+                Severity::Warning,
+                "Commas in '1,000,000' separate arguments, not digit groups; write 1_000_000 for one number"
+                    .to_string(),
+                Some(Span::new(4, 13))
+            )]
+        );
+        // Outside a call the comma is an error too.
+        let found = diagnosed("1,000", &Definitions::new());
+        assert_eq!(found[0].0, Severity::Warning);
+        assert_eq!(found[1].1, "Unexpected ','");
+    }
+
+    #[test]
+    fn diagnostics_agree_with_calculate() {
+        let (defs, equations) = bytecode_corpus();
+        let extra = [
+            "",
+            "-",
+            "2 3",
+            "y = ",
+            "1 +",
+            "(",
+            ")",
+            "avg()",
+            "sin(1, 2)",
+            "2 |> 3",
+        ];
+        for eq in equations.iter().copied().chain(extra) {
+            let errors: Vec<String> = calculator::diagnostics(eq, &defs)
+                .into_iter()
+                .filter(|d| d.is_error())
+                .map(|d| d.message)
+                .collect();
+            let parsed = StreamingTokenizer::new_with(eq, Some(&defs)).and_then(parse);
+            match parsed {
+                Err(e) => assert!(errors.contains(&e.message), "{eq}: {errors:?}"),
+                // Value-dependent failures aside, a clean equation evaluates.
+                Ok(_) if errors.is_empty() => {}
+                Ok(_) => {
+                    let e = calculator::calculate_with(eq, &defs).unwrap_err();
+                    assert_eq!(errors, [e.message], "{eq}");
+                }
+            }
+        }
+    }
+
+    // ---- Error kinds and fixes ----
+
+    #[test]
+    fn unknown_names_carry_their_suggestion_and_fix() {
+        let eq = "1 + sqr(9)";
+        let err = calculator::calculate(eq).unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnknownName {
+                name: "sqr".to_string(),
+                suggestion: Some("sqrt".to_string())
+            }
+        );
+        let fixed = err.fix().unwrap().apply(eq);
+        assert_eq!(fixed, "1 + sqrt(9)");
+        assert_eq!(calculator::calculate(&fixed).unwrap(), 4.0);
+
+        let err = calculator::calculate("2 * nope").unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::UnknownName {
+                suggestion: None,
+                ..
+            }
+        ));
+        assert_eq!(err.fix(), None);
+    }
+
+    #[test]
+    fn arity_mismatches_report_counts() {
+        let defs = defs_with(&[], &[("g", "x + 1")]);
+        let cases = [
+            ("ch(1)", ErrorKind::arity("ch", 2, Some(2), 1)),
+            ("sin(1, 2)", ErrorKind::arity("sin", 1, Some(1), 2)),
+            ("g(1, 2, 3)", ErrorKind::arity("g", 1, Some(1), 3)),
+            ("taylor(sin, 0)", ErrorKind::arity("taylor", 3, Some(3), 2)),
+        ];
+        for (eq, kind) in cases {
+            let err = calculator::calculate_with(eq, &defs).unwrap_err();
+            assert_eq!(*err.kind(), kind, "{eq}");
+            // Compiled code reports the same kind.
+            let compiled = calculator::compile_with(eq, &defs).unwrap().eval(0.0);
+            assert_eq!(*compiled.unwrap_err().kind(), kind, "{eq}");
+        }
+        let err = calculator::calculate("avg()").unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::ArityMismatch {
+                min: 1,
+                max: None,
+                actual: 0,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn domain_and_depth_errors_are_kinded() {
+        let err = calculator::calculate("root(-4, 2)").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::DomainError);
+        let err = calculator::plot("(x - 3)!", 0.0, 1.0, 1.0).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::DomainError);
+
+        let defs = defs_with(&[], &[("f", "f(x) + 1")]);
+        let err = calculator::calculate_with("f(1)", &defs).unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::DepthLimit {
+                function: "f".to_string()
+            }
+        );
+
+        let err = calculator::calculate_units("2 m + 3 s", &Definitions::new()).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::Units);
+        let err = calculator::calculate_units("root(0 - 4, 2)", &Definitions::new()).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::DomainError);
+    }
+
+    #[test]
+    fn unbalanced_parens_offer_fixes() {
+        let eq = "2 * sqrt(4 + (5";
+        let err = calculator::calculate(eq).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::UnbalancedParens);
+        let fixed = err.fix().unwrap().apply(eq);
+        assert_eq!(fixed, "2 * sqrt(4 + (5)");
+
+        let eq = "(1 + 2)) * 3";
+        let err = calculator::calculate(eq).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::UnbalancedParens);
+        let fixed = err.fix().unwrap().apply(eq);
+        assert_eq!(calculator::calculate(&fixed).unwrap(), 9.0);
+    }
+
+    #[test]
+    fn limit_errors_are_kinded() {
+        let opts = limited(EvalLimits::new().with_max_input_len(3));
+        let err = calculator::calculate_with_options("1 + 2", &Definitions::new(), &opts);
+        assert_eq!(
+            *err.unwrap_err().kind(),
+            ErrorKind::InputTooLong { len: 5, max: 3 }
+        );
+
+        let opts = limited(EvalLimits::new().with_max_points(5));
+        let err = calculator::plot_with_options("x", 0.0, 9.0, 1.0, &Definitions::new(), &opts);
+        assert_eq!(
+            *err.unwrap_err().kind(),
+            ErrorKind::TooManyPoints { points: 10, max: 5 }
+        );
+
+        let opts = limited(EvalLimits::new().with_max_steps(10));
+        let err = calculator::calculate_with_options("h12(1)", &doubling_defs(12), &opts);
+        assert_eq!(*err.unwrap_err().kind(), ErrorKind::StepLimit { max: 10 });
+
+        let token = CancelToken::new();
+        token.cancel();
+        let opts = limited(EvalLimits::new().with_cancel(token));
+        let err = calculator::calculate_with_options("1", &Definitions::new(), &opts);
+        assert_eq!(*err.unwrap_err().kind(), ErrorKind::Cancelled);
+    }
+
+    #[test]
+    fn error_kinds_leave_display_alone() {
+        let err = calculator::calculate("sine(1)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid function name sine — did you mean 'sin'? at character 1"
+        );
+        let err = calculator::calculate("2 +* 3").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::Syntax);
+        assert_eq!(err.to_string(), "Invalid expression at character 3");
+    }
+
+    #[test]
+    fn diagnostics_carry_kinds_and_fixes() {
+        let eq = "max(1,000, 2) + ch(1)";
+        let found = calculator::diagnostics(eq, &Definitions::new());
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].kind, None);
+        assert_eq!(
+            found[0].fix.as_ref().unwrap().apply(eq),
+            "max(1_000, 2) + ch(1)"
+        );
+        assert_eq!(found[1].kind, Some(ErrorKind::arity("ch", 2, Some(2), 1)));
+    }
+
+    // ---- Lexing for syntax highlighting ----
+
+    /// Each lexeme as its kind and source text.
+    fn lexed(eq: &str, defs: &Definitions) -> Vec<(TokenKind, String)> {
+        let chars: Vec<char> = eq.chars().collect();
+        lex(eq, defs)
+            .map(|l| (l.kind, chars[l.span.start..l.span.end].iter().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn lex_classifies_every_piece() {
+        use TokenKind::*;
+        let defs = defs_with(&[("a", 2.0)], &[("g", "a * x")]);
+        let got = lexed("y = a * g(x) - log_2(8)! |> sin", &defs);
+        let want = [
+            (Variable, "y"),
+            (Operator, "="),
+            (UserValue, "a"),
+            (Operator, "*"),
+            (UserFunction, "g"),
+            (Paren, "("),
+            (Variable, "x"),
+            (Paren, ")"),
+            (Operator, "-"),
+            (Function, "log_2"),
+            (Paren, "("),
+            (Number, "8"),
+            (Paren, ")"),
+            (Operator, "!"),
+            (Operator, "|>"),
+            (Function, "sin"),
+        ];
+        let want: Vec<(TokenKind, String)> =
+            want.iter().map(|&(k, t)| (k, t.to_string())).collect();
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn lex_keeps_going_past_errors() {
+        use TokenKind::*;
+        let got = lexed("2 $ foo + bar_ + sine(π)", &Definitions::new());
+        let kinds: Vec<TokenKind> = got.iter().map(|(k, _)| *k).collect();
+        assert_eq!(
+            kinds,
+            [Number, Error, Error, Operator, Error, Operator, Error, Paren, Constant, Paren]
+        );
+        assert_eq!(got[2].1, "foo");
+        assert_eq!(got[4].1, "bar_");
+        assert_eq!(got[6].1, "sine");
+    }
+
+    #[test]
+    fn lex_errors_carry_the_tokenizer_error() {
+        let lexemes: Vec<_> = lex("cosh(1) + sinq(2)", &Definitions::new()).collect();
+        let err = lexemes.iter().find_map(|l| l.error.as_ref()).unwrap();
+        assert!(
+            matches!(err.kind(), ErrorKind::UnknownName { suggestion: Some(s), .. } if s == "sin")
+        );
+        assert!(lexemes
+            .iter()
+            .all(|l| (l.kind == TokenKind::Error) == l.error.is_some()));
+    }
+
+    #[test]
+    fn lex_spans_are_ordered_and_disjoint() {
+        let defs = defs_with(&[], &[("f", "x + 1")]);
+        for eq in [
+            "1/2x^2x",
+            "taylor(f, 0, 3) + 3x",
+            "(((",
+            "max(1,, 2) ¤ x",
+            "mod mod 2",
+        ] {
+            let spans: Vec<Span> = lex(eq, &defs).map(|l| l.span).collect();
+            assert!(spans.iter().all(|s| !s.is_empty()), "{eq}");
+            assert!(spans.windows(2).all(|w| w[0].end <= w[1].start), "{eq}");
+        }
+        assert_eq!(lex("", &defs).count(), 0);
+    }
+
+    // ---- Completion and signature help ----
+
+    fn labels(eq: &str, cursor: usize, defs: &Definitions) -> Vec<String> {
+        complete(eq, cursor, defs)
+            .into_iter()
+            .map(|c| c.label)
+            .collect()
+    }
+
+    #[test]
+    fn complete_offers_catalog_aliases_and_definitions() {
+        let defs = defs_with(&[("rate0", 0.05)], &[("ramp", "2x")]);
+        let found = complete("1 + ra", 6, &defs);
+        let names: Vec<&str> = found.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(
+            names,
+            ["rad", "ramp", "rand", "rate", "randn", "rate0", "randint"]
+        );
+        let ramp = &found[1];
+        assert_eq!(ramp.kind, TokenKind::UserFunction);
+        assert_eq!(ramp.detail, "ramp(x) = 2x");
+        assert_eq!(ramp.span, Span::new(4, 6));
+        assert_eq!(found[5].detail, "rate0 = 0.05");
+
+        // Aliases complete under their own names.
+        assert!(labels("arcs", 4, &defs).contains(&"arcsin".to_string()));
+
+        // A definition shadowing a catalog function replaces it.
+        let defs = defs_with(&[("rate", 0.05)], &[]);
+        let found = complete("rat", 3, &defs);
+        let rates: Vec<_> = found.iter().filter(|c| c.label == "rate").collect();
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0].kind, TokenKind::UserValue);
+    }
+
+    #[test]
+    fn complete_ranks_near_misses_after_prefixes() {
+        let defs = defs_with(&[], &[("cube", "x^3")]);
+        assert_eq!(labels("cubq", 4, &defs), ["cube"]);
+        let names = labels("sqrtt", 5, &Definitions::new());
+        assert_eq!(names[0], "sqrt");
+        // The cursor, not the end of the equation, bounds the name.
+        assert_eq!(
+            labels("mo(1) + 2", 2, &Definitions::new())[..2],
+            ["mod", "mode"]
+        );
+        // A coefficient isn't part of the name.
+        assert_eq!(
+            complete("2si", 3, &Definitions::new())[0].span,
+            Span::new(1, 3)
+        );
+        // Nothing typed: everything, shortest first.
+        let all = complete("1 + ", 4, &defs);
+        assert_eq!(all[0].label, "e");
+        assert!(all.iter().any(|c| c.label == "cube"));
+    }
+
+    #[test]
+    fn signature_help_tracks_calls_and_arguments() {
+        let defs = defs_with(&[], &[("g", "x + 1")]);
+        let eq = "max(1, g(2), (3 + 4), log_2(8";
+        let at = |cursor| signature_help(eq, cursor, &defs);
+
+        assert_eq!(at(3), None); // on the name, before the paren
+        let max = at(4).unwrap();
+        assert_eq!((max.function.as_str(), max.argument), ("max", 0));
+        assert_eq!(max.span, Span::new(0, 3));
+        assert_eq!(
+            max.example.as_deref(),
+            Some(catalog::find("max").unwrap().example)
+        );
+
+        let g = at(9).unwrap();
+        assert_eq!((g.function.as_str(), g.argument), ("g", 0));
+        assert_eq!((g.summary.as_str(), g.example), ("g(x) = x + 1", None));
+
+        // Back in max after g's call closes, and inside a plain group.
+        assert_eq!(at(11).unwrap().argument, 1);
+        assert_eq!(at(16).unwrap().argument, 2);
+
+        let log = at(eq.len()).unwrap();
+        assert_eq!((log.function.as_str(), log.argument), ("log_2", 0));
+        assert_eq!(log.summary, catalog::find("log").unwrap().summary);
+    }
+
+    #[test]
+    fn signature_help_outside_known_calls_is_none() {
+        let defs = Definitions::new();
+        assert_eq!(signature_help("sin(1) + 2", 10, &defs), None);
+        assert_eq!(signature_help("sine(1, ", 8, &defs), None);
+        assert_eq!(signature_help("", 0, &defs), None);
+        let taylor = signature_help("taylor(sin, 0, ", 15, &defs).unwrap();
+        assert_eq!(taylor.argument, 2);
+    }
+
+    // ---- Serde ----
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn definitions_round_trip_through_json() {
+        let mut defs = Definitions::new();
+        defs.define_value("a", 3.0).unwrap();
+        defs.define_function("g", "a * x^2").unwrap();
+        let json = serde_json::to_string(&defs).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"kind":"value","name":"a","value":3.0},{"kind":"function","name":"g","body":"a * x^2"}]"#
+        );
+        let back: Definitions = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_source_string(), defs.to_source_string());
+        assert_eq!(calculator::calculate_with("g(2)", &back).unwrap(), 12.0);
+
+        let bad = r#"[{"kind":"value","name":"sin","value":1.0}]"#;
+        assert!(serde_json::from_str::<Definitions>(bad).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn errors_round_trip_with_their_detail() {
+        let err = calculator::calculate("2 + sinq(3)").unwrap_err();
+        let back: EquationError =
+            serde_json::from_str(&serde_json::to_string(&err).unwrap()).unwrap();
+        assert_eq!(back, err);
+        assert_eq!(back.kind(), err.kind());
+        assert_eq!(back.fix(), err.fix());
+
+        let mut defs = Definitions::new();
+        let err = defs.load_str("a = 1\nb = (").unwrap_err();
+        let back: EquationError =
+            serde_json::from_str(&serde_json::to_string(&err).unwrap()).unwrap();
+        assert_eq!(back.line(), Some(2));
+        assert_eq!(back.to_string(), err.to_string());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn points_and_symbols_serialize() {
+        let json = serde_json::to_string(&Point::new(1.0, 2.5)).unwrap();
+        assert_eq!(json, r#"{"x":1.0,"y":2.5}"#);
+        assert_eq!(
+            serde_json::from_str::<Point>(&json).unwrap(),
+            Point::new(1.0, 2.5)
+        );
+
+        let sin = catalog::find("sin").unwrap();
+        let json = serde_json::to_value(sin).unwrap();
+        assert_eq!(json["name"], "sin");
+        assert_eq!(json["summary"], sin.summary);
+        let back: &catalog::Symbol = serde_json::from_value(json).unwrap();
+        assert!(std::ptr::eq(back, sin));
+    }
+
+    // ---- Modules ----
+
+    fn phys() -> Definitions {
+        let mut phys = Definitions::new();
+        phys.define_value("g", 9.8).unwrap();
+        phys.define_function("fall", "g * x^2 / 2").unwrap();
+        phys.define_function("drop", "fall(x) + base").unwrap();
+        phys
+    }
+
+    #[test]
+    fn imported_modules_are_called_by_qualified_name() {
+        let mut defs = Definitions::new();
+        defs.define_value("g", 1.0).unwrap();
+        defs.define_value("base", 3.0).unwrap();
+        defs.import_module("phys", &phys()).unwrap();
+
+        // Module bodies reach their own `g`; `base` binds to the importer's.
+        assert_eq!(defs.function_body("phys.drop"), Some("phys.fall(x) + base"));
+        assert_eq!(
+            calculator::calculate_with("phys.drop(2)", &defs).unwrap(),
+            22.6
+        );
+        assert_eq!(
+            calculator::calculate_with("g + phys.g", &defs).unwrap(),
+            10.8
+        );
+        assert_eq!(
+            calculator::calculate_with("2 |> phys.fall", &defs).unwrap(),
+            19.6
+        );
+        assert_eq!(
+            calculator::calculate_with("taylor(phys.fall, 0, 3)", &defs).unwrap(),
+            0.0
+        );
+        // A dot before a digit is still a decimal point.
+        assert_eq!(calculator::calculate("1.5 + 2.25").unwrap(), 3.75);
+
+        // Re-importing replaces the old module wholesale.
+        let mut smaller = Definitions::new();
+        smaller.define_value("g", 1.6).unwrap();
+        defs.import_module("phys", &smaller).unwrap();
+        assert!(!defs.contains("phys.fall"));
+        assert_eq!(defs.value("phys.g"), Some(1.6));
+
+        assert!(defs.import_module("sin", &smaller).is_err());
+        assert!(defs.import_module("x", &smaller).is_err());
+        assert!(defs.define_value("phys.", 1.0).is_err());
+        assert!(defs.define_value("x.a", 1.0).is_err());
+    }
+
+    #[test]
+    fn qualified_names_in_suggestions_and_editors() {
+        let mut defs = Definitions::new();
+        defs.import_module("phys", &phys()).unwrap();
+
+        let err = calculator::calculate_with("phys.fal(1)", &defs).unwrap_err();
+        assert_eq!(
+            err.message,
+            "Invalid function name phys.fal — did you mean 'phys.fall'?"
+        );
+        let err = calculator::calculate_with("1 + fall(1)", &defs).unwrap_err();
+        assert_eq!(err.fix().unwrap().replacement, "phys.fall");
+        assert_eq!(err.span, Some(Span::new(4, 8)));
+
+        let kinds: Vec<(TokenKind, usize, usize)> = lex("phys.fall(phys.g)", &defs)
+            .map(|l| (l.kind, l.span.start, l.span.end))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (TokenKind::UserFunction, 0, 9),
+                (TokenKind::Paren, 9, 10),
+                (TokenKind::UserValue, 10, 16),
+                (TokenKind::Paren, 16, 17),
+            ]
+        );
+        let labels: Vec<String> = complete("2 * phys.f", 10, &defs)
+            .into_iter()
+            .map(|c| c.label)
+            .collect();
+        assert_eq!(labels, ["phys.fall", "phys.g"]);
+    }
+
+    #[test]
+    fn qualified_names_round_trip_as_text() {
+        let mut defs = Definitions::new();
+        defs.import_module("phys", &phys()).unwrap();
+        let source = defs.to_source_string();
+        assert_eq!(
+            source,
+            "phys.g = 9.8\nphys.fall(x) = phys.g * x^2 / 2\nphys.drop(x) = phys.fall(x) + base\n"
+        );
+        let mut back = Definitions::new();
+        back.load_str(&source).unwrap();
+        assert_eq!(back.to_source_string(), source);
+    }
+
+    // ---- Memoization and the compile cache ----
+
+    #[test]
+    fn memoized_plots_match_plain_ones() {
+        use crate::equation_analyzer::EvalOptions;
+        let mut defs = Definitions::new();
+        defs.define_function("g", "sin(x)^2 + ln(x^2 + 1)").unwrap();
+        defs.define_function("h", "g(x) * g(x + 1)").unwrap();
+        let plain = EvalOptions::new().with_seed(3);
+        let memoized = plain.clone().with_memoization(true);
+        assert!(memoized.memoizes() && !plain.memoizes());
+
+        for eq in ["h(floor(x)) + x", "g(x) - h(x / 2)", "1 / h(floor(x) - 2)"] {
+            assert_eq!(
+                calculator::plot_with_options(eq, -4.0, 4.0, 0.125, &defs, &memoized).unwrap(),
+                calculator::plot_with_options(eq, -4.0, 4.0, 0.125, &defs, &plain).unwrap(),
+                "{eq}"
+            );
+            let (a, b) = (
+                calculator::plot_tolerant_with_options(eq, -4.0, 4.0, 0.5, &defs, &memoized)
+                    .unwrap(),
+                calculator::plot_tolerant_with_options(eq, -4.0, 4.0, 0.5, &defs, &plain).unwrap(),
+            );
+            assert_eq!(a.samples.len(), b.samples.len());
+            assert!(
+                a.samples.iter().zip(&b.samples).all(|(a, b)| a == b),
+                "{eq}"
+            );
+        }
+    }
+
+    #[test]
+    fn memoization_reaches_plot_iter_and_compiled_equations() {
+        use crate::equation_analyzer::EvalOptions;
+        let mut defs = Definitions::new();
+        defs.define_function("g", "sin(x)^2 + cos(x)^2 + tan(x)^2")
+            .unwrap();
+        let eq = "g(x) + g(x) + g(x) + g(x) + g(x) + g(x)";
+        let plain = EvalOptions::new().with_limits(EvalLimits::new().with_max_steps(40));
+        let memoized = plain.clone().with_memoization(true);
+
+        let iter = |opts| {
+            calculator::plot_iter_with_options(eq, 0.0, 1.0, 1.0, &defs, opts)
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+        assert!(iter(&plain).iter().all(Result::is_err));
+        let points: Vec<Point> = iter(&memoized).into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            points,
+            calculator::plot_with_options(eq, 0.0, 1.0, 1.0, &defs, &memoized).unwrap()
+        );
+
+        let f = calculator::compile_with_options(eq, &defs, &plain).unwrap();
+        assert!(f.eval(1.0).is_err());
+        let f = calculator::compile_with_options(eq, &defs, &memoized).unwrap();
+        assert_eq!(f.eval(1.0).unwrap(), points[1].y);
+        let mut ys = [0.0; 2];
+        calculator::eval_batch(&f, &[0.0, 1.0], &mut ys).unwrap();
+        assert_eq!(ys.to_vec(), points.iter().map(|p| p.y).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn random_functions_are_never_memoized() {
+        use crate::equation_analyzer::EvalOptions;
+        let mut defs = Definitions::new();
+        defs.define_function("r", "x + rand()").unwrap();
+        defs.define_function("s", "r(x) * 2").unwrap();
+        let opts = EvalOptions::new().with_seed(9).with_memoization(true);
+        let diff = calculator::calculate_with_options("s(1) - s(1)", &defs, &opts).unwrap();
+        assert_ne!(diff, 0.0);
+        let ys: Vec<f32> = calculator::plot_with_options("r(1)", 0.0, 1.0, 0.25, &defs, &opts)
+            .unwrap()
+            .iter()
+            .map(|p| p.y)
+            .collect();
+        assert!(ys.windows(2).any(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn cached_bodies_follow_redefinitions() {
+        let mut defs = Definitions::new();
+        defs.define_value("a", 2.0).unwrap();
+        defs.define_function("g", "a * x").unwrap();
+        assert_eq!(calculator::calculate_with("g(3)", &defs).unwrap(), 6.0);
+
+        defs.define_value("a", 5.0).unwrap();
+        assert_eq!(calculator::calculate_with("g(3)", &defs).unwrap(), 15.0);
+        defs.push_scope();
+        defs.define_value("a", 1.0).unwrap();
+        assert_eq!(calculator::calculate_with("g(3)", &defs).unwrap(), 3.0);
+        defs.pop_scope();
+        assert_eq!(calculator::calculate_with("g(3)", &defs).unwrap(), 15.0);
+        defs.undefine("a");
+        assert!(calculator::calculate_with("g(3)", &defs).is_err());
+        defs.load_str("a = 10").unwrap();
+        assert_eq!(calculator::calculate_with("g(3)", &defs).unwrap(), 30.0);
+    }
 }
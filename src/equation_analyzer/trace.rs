@@ -0,0 +1,118 @@
+//! Step-by-step evaluation traces.
+//!
+//! [`trace`](crate::equation_analyzer::calculator::trace) evaluates an
+//! equation as [`calculate_with`](crate::equation_analyzer::calculator::calculate_with)
+//! does, recording every operator and function applied — what went in,
+//! what came out, and where in the source it was written — in evaluation
+//! order:
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::trace;
+//! use rusty_maths::equation_analyzer::Definitions;
+//!
+//! let t = trace("2 + 3 * 4^2", &Definitions::new()).unwrap();
+//! let steps: Vec<String> = t.steps.iter().map(|s| s.to_string()).collect();
+//! assert_eq!(steps, ["4 ^ 2 = 16", "3 * 16 = 48", "2 + 48 = 50"]);
+//! assert_eq!(t.result, Ok(50.0));
+//! ```
+//!
+//! Steps inside a user function's body are tagged with the function's
+//! name, as [`EquationError::in_function`] is, and their spans refer to
+//! the body source; the call itself is a step of its caller, after its
+//! body's steps.
+//!
+//! A trace grows with the work done, so one of untrusted input belongs
+//! under [`trace_with_options`](crate::equation_analyzer::calculator::trace_with_options),
+//! whose step limit bounds the trace as well as the evaluation.
+
+use crate::equation_analyzer::errors::{EquationError, Span};
+use std::fmt;
+
+/// What a [`TraceStep`] applied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// A binary operator: `+`, `-`, `*`, `/`, `^` or `mod`.
+    Binary(&'static str),
+    /// `+` or `-` with a percentage on the right, relative to the left
+    /// operand: `200 + 10%` = 220. The percentage input is already divided
+    /// by 100.
+    RelativePercent(&'static str),
+    /// Unary minus.
+    Negate,
+    /// Postfix `!`.
+    Factorial,
+    /// Postfix `%`: divides by 100.
+    Percent,
+    /// A catalog or user function; `log_2` and friends include the base.
+    Call(String),
+    /// `taylor(f, a, n)` evaluated at `x`; the inputs are `a` and `n`.
+    Taylor(String),
+}
+
+/// One operator or function application.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    pub operation: Operation,
+    /// The operands, left to right.
+    pub inputs: Vec<f32>,
+    pub output: f32,
+    /// Where the operator or call was written. Inside a user function, a
+    /// span in that function's body source.
+    pub span: Span,
+    /// The innermost user function whose body this step ran in.
+    pub in_function: Option<String>,
+}
+
+/// An evaluation's steps and its outcome; from
+/// [`trace`](crate::equation_analyzer::calculator::trace).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    /// Every step that completed, in evaluation order.
+    pub steps: Vec<TraceStep>,
+    /// The value, or the error that stopped evaluation after the last step.
+    pub result: Result<f32, EquationError>,
+}
+
+/// Renders the step as arithmetic: `3 * 16 = 48`, `sin(0) = 0`, and
+/// `in g(x): 2 ^ 2 = 4` inside user functions.
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.in_function {
+            write!(f, "in {name}(x): ")?;
+        }
+        let args = |f: &mut fmt::Formatter<'_>| -> fmt::Result {
+            for (i, v) in self.inputs.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{v}")?;
+            }
+            Ok(())
+        };
+        match (&self.operation, self.inputs.as_slice()) {
+            (Operation::Binary(op), [l, r]) => write!(f, "{l} {op} {r}")?,
+            (Operation::RelativePercent(op), [l, r]) => write!(f, "{l} {op} {}%", r * 100.0)?,
+            (Operation::Negate, [v]) => write!(f, "-({v})")?,
+            (Operation::Factorial, [v]) => write!(f, "{v}!")?,
+            (Operation::Percent, [v]) => write!(f, "{v}%")?,
+            (Operation::Taylor(name), _) => {
+                write!(f, "taylor({name}, ")?;
+                args(f)?;
+                write!(f, ")")?;
+            }
+            (Operation::Call(name), _) => {
+                write!(f, "{name}(")?;
+                args(f)?;
+                write!(f, ")")?;
+            }
+            // Operators always record their operand count; this is only
+            // reachable for hand-built steps.
+            (op, _) => {
+                write!(f, "{op:?}(")?;
+                args(f)?;
+                write!(f, ")")?;
+            }
+        }
+        write!(f, " = {}", self.output)
+    }
+}
@@ -0,0 +1,731 @@
+//! Physical units and dimensional analysis for the equation analyzer.
+//!
+//! The unit registry sits alongside [`catalog`](crate::equation_analyzer::catalog):
+//! every unit the analyzer recognizes is one entry in [`UNITS`], with its
+//! dimension (exponents over the seven SI base dimensions) and its scale to
+//! the coherent SI unit. Units only resolve in unit-aware evaluation —
+//! [`calculate_units`](crate::equation_analyzer::calculator::calculate_units) —
+//! so plain `calculate` keeps every name free for user definitions.
+//!
+//! ```
+//! use rusty_maths::equation_analyzer::calculator::calculate_units;
+//! use rusty_maths::equation_analyzer::Definitions;
+//!
+//! let defs = Definitions::new();
+//! let q = calculate_units("3 m + 20 cm", &defs).unwrap();
+//! assert!((q.value() - 3.2).abs() < 1e-6);
+//! assert_eq!(q.unit(), "m");
+//!
+//! let q = calculate_units("convert(60 mph, km/h)", &defs).unwrap();
+//! assert!((q.value() - 96.56064).abs() < 1e-3);
+//! assert_eq!(q.unit(), "km/h");
+//! ```
+//!
+//! # Semantics
+//!
+//! - A unit written after an operand multiplies it: `3 m` is `3 * m`.
+//! - `+` and `-` require matching dimensions; the result keeps the left
+//!   operand's unit (`3 m + 20 cm` is 3.2 m, `20 cm + 3 m` is 320 cm).
+//! - `*`, `/` and integer powers combine units; a unit whose dimension is
+//!   already present folds into it (`3 m * 20 cm` is 0.6 m^2).
+//! - Catalog functions need dimensionless arguments, except `abs`, `sqrt`
+//!   (even powers only), and `min`/`max`/`sum`/`avg`, which accept any
+//!   matching dimensions.
+//! - Temperatures are absolute (`K`): offset scales such as °C are not
+//!   representable as a pure scale factor and are deliberately absent.
+
+use crate::equation_analyzer::catalog::{Symbol, SymbolKind};
+use crate::equation_analyzer::errors::ErrorKind;
+use crate::equation_analyzer::pipeline::domain::Domain;
+use crate::equation_analyzer::pipeline::evaluator::EvalState;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Exponents over the SI base dimensions, in the order
+/// length, mass, time, current, temperature, amount, luminous intensity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Dimension(pub [i8; 7]);
+
+const BASE_SYMBOLS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+impl Dimension {
+    pub const NONE: Dimension = Dimension([0; 7]);
+
+    pub fn is_dimensionless(&self) -> bool {
+        *self == Dimension::NONE
+    }
+
+    /// `self * other^sign`, or `None` if an exponent leaves the `i8` range.
+    fn combine(self, other: Dimension, sign: i8) -> Option<Dimension> {
+        let mut out = self.0;
+        for (o, b) in out.iter_mut().zip(other.0) {
+            *o = o.checked_add(sign.checked_mul(b)?)?;
+        }
+        Some(Dimension(out))
+    }
+
+    /// `self^by`, or `None` if an exponent leaves the `i8` range.
+    fn scaled(self, by: i8) -> Option<Dimension> {
+        let mut out = self.0;
+        for e in &mut out {
+            *e = e.checked_mul(by)?;
+        }
+        Some(Dimension(out))
+    }
+}
+
+impl fmt::Display for Dimension {
+    /// The dimension in coherent SI units, e.g. `kg*m/s^2`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<(&str, i8)> = BASE_SYMBOLS
+            .iter()
+            .copied()
+            .zip(self.0)
+            .filter(|&(_, e)| e != 0)
+            .collect();
+        write!(f, "{}", format_terms(&terms))
+    }
+}
+
+/// One entry in the unit registry.
+#[derive(Debug)]
+pub struct Unit {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub summary: &'static str,
+    pub dimension: Dimension,
+    /// Value of one of this unit in the coherent SI unit of its dimension.
+    pub scale: f64,
+}
+
+// Interned like catalog symbols: identity is entry identity.
+impl PartialEq for Unit {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Unit {}
+
+macro_rules! unit {
+    ($name:literal, [$($alias:literal),* $(,)?], $summary:literal, [$l:literal, $m:literal, $t:literal, $i:literal, $th:literal, $n:literal, $j:literal], $scale:expr) => {
+        Unit { name: $name, aliases: &[$($alias),*], summary: $summary, dimension: Dimension([$l, $m, $t, $i, $th, $n, $j]), scale: $scale }
+    };
+}
+
+const FOOT: f64 = 0.3048;
+const POUND: f64 = 0.453_592_37;
+const GRAVITY: f64 = 9.806_65;
+
+pub const UNITS: &[Unit] = &[
+    // Length
+    unit!("m", ["meter", "metre"], "metre", [1, 0, 0, 0, 0, 0, 0], 1.0),
+    unit!("km", [], "kilometre", [1, 0, 0, 0, 0, 0, 0], 1e3),
+    unit!("cm", [], "centimetre", [1, 0, 0, 0, 0, 0, 0], 1e-2),
+    unit!("mm", [], "millimetre", [1, 0, 0, 0, 0, 0, 0], 1e-3),
+    unit!("um", ["µm"], "micrometre", [1, 0, 0, 0, 0, 0, 0], 1e-6),
+    unit!("nm", [], "nanometre", [1, 0, 0, 0, 0, 0, 0], 1e-9),
+    unit!("inch", ["inches"], "inch", [1, 0, 0, 0, 0, 0, 0], 0.0254),
+    unit!("ft", ["foot", "feet"], "foot", [1, 0, 0, 0, 0, 0, 0], FOOT),
+    unit!("yd", ["yard"], "yard", [1, 0, 0, 0, 0, 0, 0], 3.0 * FOOT),
+    unit!(
+        "mi",
+        ["mile", "miles"],
+        "statute mile",
+        [1, 0, 0, 0, 0, 0, 0],
+        5280.0 * FOOT
+    ),
+    unit!("nmi", [], "nautical mile", [1, 0, 0, 0, 0, 0, 0], 1852.0),
+    // Mass
+    unit!("kg", [], "kilogram", [0, 1, 0, 0, 0, 0, 0], 1.0),
+    unit!("g", ["gram"], "gram", [0, 1, 0, 0, 0, 0, 0], 1e-3),
+    unit!("mg", [], "milligram", [0, 1, 0, 0, 0, 0, 0], 1e-6),
+    unit!("t", ["tonne"], "metric tonne", [0, 1, 0, 0, 0, 0, 0], 1e3),
+    unit!(
+        "lb",
+        ["lbs", "pound"],
+        "avoirdupois pound",
+        [0, 1, 0, 0, 0, 0, 0],
+        POUND
+    ),
+    unit!(
+        "oz",
+        ["ounce"],
+        "avoirdupois ounce",
+        [0, 1, 0, 0, 0, 0, 0],
+        POUND / 16.0
+    ),
+    // Time
+    unit!("s", ["second"], "second", [0, 0, 1, 0, 0, 0, 0], 1.0),
+    unit!("ms", [], "millisecond", [0, 0, 1, 0, 0, 0, 0], 1e-3),
+    unit!("us", ["µs"], "microsecond", [0, 0, 1, 0, 0, 0, 0], 1e-6),
+    unit!(
+        "minute",
+        ["minutes"],
+        "minute (`min` is the catalog's minimum)",
+        [0, 0, 1, 0, 0, 0, 0],
+        60.0
+    ),
+    unit!("h", ["hr", "hour"], "hour", [0, 0, 1, 0, 0, 0, 0], 3600.0),
+    unit!("day", ["days"], "day", [0, 0, 1, 0, 0, 0, 0], 86_400.0),
+    unit!("week", ["weeks"], "week", [0, 0, 1, 0, 0, 0, 0], 604_800.0),
+    unit!(
+        "yr",
+        ["year"],
+        "Julian year",
+        [0, 0, 1, 0, 0, 0, 0],
+        31_557_600.0
+    ),
+    // Speed
+    unit!(
+        "mph",
+        [],
+        "miles per hour",
+        [1, 0, -1, 0, 0, 0, 0],
+        5280.0 * FOOT / 3600.0
+    ),
+    unit!(
+        "kph",
+        [],
+        "kilometres per hour",
+        [1, 0, -1, 0, 0, 0, 0],
+        1e3 / 3600.0
+    ),
+    unit!(
+        "knot",
+        ["knots", "kn"],
+        "nautical miles per hour",
+        [1, 0, -1, 0, 0, 0, 0],
+        1852.0 / 3600.0
+    ),
+    // Area and volume
+    unit!("ha", ["hectare"], "hectare", [2, 0, 0, 0, 0, 0, 0], 1e4),
+    unit!(
+        "acre",
+        ["acres"],
+        "international acre",
+        [2, 0, 0, 0, 0, 0, 0],
+        4_046.856_422_4
+    ),
+    unit!(
+        "L",
+        ["l", "litre", "liter"],
+        "litre",
+        [3, 0, 0, 0, 0, 0, 0],
+        1e-3
+    ),
+    unit!("mL", ["ml"], "millilitre", [3, 0, 0, 0, 0, 0, 0], 1e-6),
+    unit!(
+        "gal",
+        ["gallon"],
+        "US liquid gallon",
+        [3, 0, 0, 0, 0, 0, 0],
+        3.785_411_784e-3
+    ),
+    // Force, energy, power, pressure
+    unit!("N", ["newton"], "newton", [1, 1, -2, 0, 0, 0, 0], 1.0),
+    unit!("kN", [], "kilonewton", [1, 1, -2, 0, 0, 0, 0], 1e3),
+    unit!(
+        "lbf",
+        [],
+        "pound-force",
+        [1, 1, -2, 0, 0, 0, 0],
+        POUND * GRAVITY
+    ),
+    unit!("J", ["joule"], "joule", [2, 1, -2, 0, 0, 0, 0], 1.0),
+    unit!("kJ", [], "kilojoule", [2, 1, -2, 0, 0, 0, 0], 1e3),
+    unit!(
+        "cal",
+        [],
+        "thermochemical calorie",
+        [2, 1, -2, 0, 0, 0, 0],
+        4.184
+    ),
+    unit!("kcal", [], "kilocalorie", [2, 1, -2, 0, 0, 0, 0], 4184.0),
+    unit!("Wh", [], "watt-hour", [2, 1, -2, 0, 0, 0, 0], 3600.0),
+    unit!("kWh", [], "kilowatt-hour", [2, 1, -2, 0, 0, 0, 0], 3.6e6),
+    unit!(
+        "eV",
+        [],
+        "electronvolt",
+        [2, 1, -2, 0, 0, 0, 0],
+        1.602_176_634e-19
+    ),
+    unit!("W", ["watt"], "watt", [2, 1, -3, 0, 0, 0, 0], 1.0),
+    unit!("kW", [], "kilowatt", [2, 1, -3, 0, 0, 0, 0], 1e3),
+    unit!(
+        "hp",
+        [],
+        "mechanical horsepower",
+        [2, 1, -3, 0, 0, 0, 0],
+        745.699_871_582_270_2
+    ),
+    unit!("Pa", ["pascal"], "pascal", [-1, 1, -2, 0, 0, 0, 0], 1.0),
+    unit!("kPa", [], "kilopascal", [-1, 1, -2, 0, 0, 0, 0], 1e3),
+    unit!("bar", [], "bar", [-1, 1, -2, 0, 0, 0, 0], 1e5),
+    unit!(
+        "atm",
+        [],
+        "standard atmosphere",
+        [-1, 1, -2, 0, 0, 0, 0],
+        101_325.0
+    ),
+    unit!(
+        "psi",
+        [],
+        "pound-force per square inch",
+        [-1, 1, -2, 0, 0, 0, 0],
+        POUND * GRAVITY / (0.0254 * 0.0254)
+    ),
+    // Electromagnetism
+    unit!("A", ["amp", "ampere"], "ampere", [0, 0, 0, 1, 0, 0, 0], 1.0),
+    unit!("mA", [], "milliampere", [0, 0, 0, 1, 0, 0, 0], 1e-3),
+    unit!("C", ["coulomb"], "coulomb", [0, 0, 1, 1, 0, 0, 0], 1.0),
+    unit!("V", ["volt"], "volt", [2, 1, -3, -1, 0, 0, 0], 1.0),
+    unit!("ohm", ["Ω"], "ohm", [2, 1, -3, -2, 0, 0, 0], 1.0),
+    // Frequency, temperature, amount, luminosity
+    unit!("Hz", ["hertz"], "hertz", [0, 0, -1, 0, 0, 0, 0], 1.0),
+    unit!("kHz", [], "kilohertz", [0, 0, -1, 0, 0, 0, 0], 1e3),
+    unit!(
+        "K",
+        ["kelvin"],
+        "kelvin (absolute temperature)",
+        [0, 0, 0, 0, 1, 0, 0],
+        1.0
+    ),
+    unit!("mol", [], "mole", [0, 0, 0, 0, 0, 1, 0], 1.0),
+    unit!("cd", ["candela"], "candela", [0, 0, 0, 0, 0, 0, 1], 1.0),
+];
+
+/// Every unit the analyzer understands.
+pub fn all() -> &'static [Unit] {
+    UNITS
+}
+
+/// Look up a unit by its symbol or any alias. Case-sensitive: `mA` is a
+/// milliampere, `Ma` is nothing.
+pub fn find(name: &str) -> Option<&'static Unit> {
+    static INDEX: std::sync::OnceLock<HashMap<&'static str, &'static Unit>> =
+        std::sync::OnceLock::new();
+    INDEX
+        .get_or_init(|| {
+            let mut index = HashMap::new();
+            for unit in UNITS {
+                index.insert(unit.name, unit);
+                for alias in unit.aliases {
+                    index.insert(*alias, unit);
+                }
+            }
+            index
+        })
+        .get(name)
+        .copied()
+}
+
+/// `m`, `m^2`, `kg*m/s^2` — positive powers joined by `*`, then negative
+/// powers after a `/`. The output is itself valid unit-aware input.
+fn format_terms(terms: &[(&str, i8)]) -> String {
+    let power = |name: &str, e: i8| {
+        if e == 1 {
+            name.to_string()
+        } else {
+            format!("{name}^{e}")
+        }
+    };
+    let num: Vec<String> = terms
+        .iter()
+        .filter(|t| t.1 > 0)
+        .map(|&(n, e)| power(n, e))
+        .collect();
+    let den: Vec<String> = terms
+        .iter()
+        .filter(|t| t.1 < 0)
+        .map(|&(n, e)| power(n, -e))
+        .collect();
+    match (num.is_empty(), den.is_empty()) {
+        (_, true) => num.join("*"),
+        (true, false) => format!("1/{}", den.join("/")),
+        (false, false) => format!("{}/{}", num.join("*"), den.join("/")),
+    }
+}
+
+/// A number with a dimension, and the unit it's expressed in.
+///
+/// The magnitude is held in coherent SI units (f64, so chained
+/// conversions don't drift); `unit` records how to display it — the units
+/// as written, combined by the rules in the [module docs](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    si: f64,
+    dimension: Dimension,
+    unit: Vec<(&'static Unit, i8)>,
+}
+
+impl Quantity {
+    fn plain(v: f64) -> Self {
+        Quantity {
+            si: v,
+            dimension: Dimension::NONE,
+            unit: Vec::new(),
+        }
+    }
+
+    /// The magnitude in [`unit`](Self::unit).
+    pub fn value(&self) -> f32 {
+        (self.si / self.display_scale()) as f32
+    }
+
+    /// The magnitude in coherent SI units (`m`, `kg*m/s^2`, …).
+    pub fn si_value(&self) -> f32 {
+        self.si as f32
+    }
+
+    /// The display unit, e.g. `km/h`; empty for a dimensionless number.
+    pub fn unit(&self) -> String {
+        let terms: Vec<(&str, i8)> = self.unit.iter().map(|&(u, e)| (u.name, e)).collect();
+        format_terms(&terms)
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        self.dimension
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.dimension.is_dimensionless()
+    }
+
+    fn display_scale(&self) -> f64 {
+        self.unit
+            .iter()
+            .map(|&(u, e)| u.scale.powi(i32::from(e)))
+            .product()
+    }
+
+    /// Multiplies the display units, folding each incoming unit into an
+    /// existing entry of the same unit — or, failing that, the same
+    /// dimension — so `m * cm` reads as `m^2` rather than `m*cm`.
+    fn combined_unit(&self, other: &Quantity, sign: i8) -> Vec<(&'static Unit, i8)> {
+        self.folded_unit(other, sign).unwrap_or_default()
+    }
+
+    fn folded_unit(&self, other: &Quantity, sign: i8) -> Option<Vec<(&'static Unit, i8)>> {
+        let mut out = self.unit.clone();
+        for &(u, e) in &other.unit {
+            let e = sign.checked_mul(e)?;
+            let slot = out
+                .iter()
+                .position(|&(v, _)| v == u)
+                .or_else(|| out.iter().position(|&(v, _)| v.dimension == u.dimension));
+            match slot {
+                Some(i) => out[i].1 = out[i].1.checked_add(e)?,
+                None => out.push((u, e)),
+            }
+        }
+        out.retain(|&(_, e)| e != 0);
+        // Folding can leave a display unit that disagrees with the
+        // dimension only if two units of different dimensions cancel
+        // oddly, or overflow an exponent the dimension doesn't; fall back
+        // to SI rather than show a wrong unit.
+        let dim = out.iter().try_fold(Dimension::NONE, |d, &(u, e)| {
+            d.combine(u.dimension.scaled(e)?, 1)
+        })?;
+        (Some(dim) == self.dimension.combine(other.dimension, sign)).then_some(out)
+    }
+
+    /// `self * other^sign`, or an error if a unit exponent overflows.
+    fn product(&self, other: &Quantity, sign: i8, si: f64) -> Result<Quantity, String> {
+        let dimension = self
+            .dimension
+            .combine(other.dimension, sign)
+            .ok_or_else(|| {
+                let verb = if sign < 0 { "divide" } else { "multiply" };
+                format!(
+                    "Unit exponent out of range: cannot {verb} {} by {}",
+                    self.describe(),
+                    other.describe()
+                )
+            })?;
+        Ok(Quantity {
+            si,
+            dimension,
+            unit: self.combined_unit(other, sign),
+        })
+    }
+
+    fn require_dimensionless(&self, what: &str) -> Result<f64, String> {
+        if self.is_dimensionless() {
+            Ok(self.si)
+        } else {
+            Err(format!(
+                "{what} expects a dimensionless argument, got {}",
+                self.describe()
+            ))
+        }
+    }
+
+    fn require_same(&self, other: &Quantity, verb: &str) -> Result<(), String> {
+        if self.dimension == other.dimension {
+            Ok(())
+        } else {
+            Err(format!(
+                "Dimension mismatch: cannot {verb} {} and {}",
+                self.describe(),
+                other.describe()
+            ))
+        }
+    }
+
+    /// The unit for messages: the display unit if any, else the SI form.
+    fn describe(&self) -> String {
+        match (self.unit.is_empty(), self.is_dimensionless()) {
+            (_, true) => String::from("a dimensionless number"),
+            (false, false) => self.unit(),
+            (true, false) => self.dimension.to_string(),
+        }
+    }
+
+    fn raised(&self, n: i8) -> Result<Quantity, String> {
+        let dimension = self
+            .dimension
+            .scaled(n)
+            .ok_or_else(|| self.exponent_out_of_range(f64::from(n)))?;
+        let unit = self
+            .unit
+            .iter()
+            .map(|&(u, e)| Some((u, e.checked_mul(n)?)))
+            .collect::<Option<_>>()
+            .unwrap_or_default();
+        Ok(Quantity {
+            si: self.si.powi(i32::from(n)),
+            dimension,
+            unit,
+        })
+    }
+
+    fn exponent_out_of_range(&self, exponent: f64) -> String {
+        format!(
+            "Unit exponent out of range: cannot raise {} to the power {exponent}",
+            self.describe()
+        )
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_dimensionless() {
+            return write!(f, "{}", self.value());
+        }
+        let unit = if self.unit.is_empty() {
+            self.dimension.to_string()
+        } else {
+            self.unit()
+        };
+        write!(f, "{} {unit}", self.value())
+    }
+}
+
+impl Domain for Quantity {
+    fn number(v: f32) -> Self {
+        Quantity::plain(f64::from(v))
+    }
+
+    fn to_number(&self) -> Option<f32> {
+        self.is_dimensionless().then_some(self.si as f32)
+    }
+
+    fn unit(unit: &'static Unit) -> Result<Self, String> {
+        Ok(Quantity {
+            si: unit.scale,
+            dimension: unit.dimension,
+            unit: vec![(unit, 1)],
+        })
+    }
+
+    fn add(&self, rhs: &Self) -> Result<Self, String> {
+        self.require_same(rhs, "add")?;
+        Ok(Quantity {
+            si: self.si + rhs.si,
+            ..self.clone()
+        })
+    }
+
+    fn sub(&self, rhs: &Self) -> Result<Self, String> {
+        self.require_same(rhs, "subtract")?;
+        Ok(Quantity {
+            si: self.si - rhs.si,
+            ..self.clone()
+        })
+    }
+
+    fn mul(&self, rhs: &Self) -> Result<Self, String> {
+        self.product(rhs, 1, self.si * rhs.si)
+    }
+
+    fn div(&self, rhs: &Self) -> Result<Self, String> {
+        self.product(rhs, -1, self.si / rhs.si)
+    }
+
+    fn rem(&self, rhs: &Self) -> Result<Self, String> {
+        self.require_same(rhs, "take the remainder of")?;
+        Ok(Quantity {
+            si: self.si % rhs.si,
+            ..self.clone()
+        })
+    }
+
+    fn pow(&self, rhs: &Self) -> Result<Self, String> {
+        let exponent = rhs.require_dimensionless("An exponent")?;
+        if self.is_dimensionless() {
+            return Ok(Quantity::plain(self.si.powf(exponent)));
+        }
+        if exponent % 1.0 != 0.0 {
+            return Err(format!(
+                "Cannot raise {} to the non-integer power {exponent}",
+                self.describe()
+            ));
+        }
+        if exponent.abs() > f64::from(i8::MAX) {
+            return Err(self.exponent_out_of_range(exponent));
+        }
+        self.raised(exponent as i8)
+    }
+
+    fn neg(&self) -> Self {
+        Quantity {
+            si: -self.si,
+            ..self.clone()
+        }
+    }
+
+    fn factorial(&self) -> Result<Self, String> {
+        let v = self.require_dimensionless("Factorial")?;
+        if v < 0.0 || v % 1.0 != 0.0 {
+            return Err(String::from(
+                "Factorial is only defined for non-negative integers",
+            ));
+        }
+        crate::utilities::factorial(v as isize).map(|f| Quantity::plain(f as f64))
+    }
+
+    fn log(&self, base: f32) -> Result<Self, String> {
+        let v = self.require_dimensionless("log")?;
+        Ok(Quantity::plain(v.log(f64::from(base))))
+    }
+
+    fn call(sym: &'static Symbol, args: &[Self], state: &mut EvalState) -> Result<Self, String> {
+        match (sym.name, sym.kind) {
+            (_, SymbolKind::UnitConversion) => {
+                let (q, target) = (&args[0], &args[1]);
+                if q.dimension != target.dimension {
+                    return Err(format!(
+                        "Cannot convert {} to {}",
+                        q.describe(),
+                        target.describe()
+                    ));
+                }
+                Ok(Quantity {
+                    unit: target.unit.clone(),
+                    ..q.clone()
+                })
+            }
+            ("abs", _) => Ok(Quantity {
+                si: args[0].si.abs(),
+                ..args[0].clone()
+            }),
+            ("sqrt", _) if !args[0].is_dimensionless() => {
+                let q = &args[0];
+                if q.dimension.0.iter().any(|e| e % 2 != 0)
+                    || q.unit.iter().any(|&(_, e)| e % 2 != 0)
+                {
+                    return Err(format!("Cannot take the square root of {}", q.describe()));
+                }
+                Ok(Quantity {
+                    si: q.si.sqrt(),
+                    dimension: Dimension(q.dimension.0.map(|e| e / 2)),
+                    unit: q.unit.iter().map(|&(u, e)| (u, e / 2)).collect(),
+                })
+            }
+            ("min" | "max" | "sum" | "avg", _) if !args[0].is_dimensionless() => {
+                for other in &args[1..] {
+                    args[0].require_same(other, "combine")?;
+                }
+                let values = args.iter().map(|q| q.si);
+                let si = match sym.name {
+                    "min" => values.fold(f64::INFINITY, f64::min),
+                    "max" => values.fold(f64::NEG_INFINITY, f64::max),
+                    "sum" => values.sum(),
+                    _ => values.sum::<f64>() / args.len() as f64,
+                };
+                Ok(Quantity {
+                    si,
+                    ..args[0].clone()
+                })
+            }
+            _ => {
+                let plain = args
+                    .iter()
+                    .map(|q| q.require_dimensionless(sym.name).map(|v| v as f32))
+                    .collect::<Result<Vec<f32>, String>>()?;
+                let v = match sym.kind {
+                    SymbolKind::Unary(f) => f(plain[0]),
+                    SymbolKind::UnaryChecked(f) => f(plain[0])?,
+                    SymbolKind::Variadic { run, .. } => run(&plain)?,
+                    SymbolKind::Random { run, .. } => run(state.rng(), &plain)?,
+                    _ => return Err(format!("'{}' is not callable", sym.name)),
+                };
+                Ok(Quantity::plain(f64::from(v)))
+            }
+        }
+    }
+
+    /// The messages of `require_dimensionless`, `require_same` and the
+    /// dimension and exponent-range checks in `mul`, `div`, `pow` and
+    /// `call` are unit errors; the rest come
+    /// from the catalog's own domain checks.
+    fn error_kind(message: &str) -> ErrorKind {
+        const UNIT_ERRORS: [&str; 5] = [
+            "Dimension mismatch",
+            "Cannot convert",
+            "Cannot take the square root of",
+            "Cannot raise",
+            "Unit exponent out of range",
+        ];
+        if UNIT_ERRORS.iter().any(|p| message.starts_with(p))
+            || message.contains("expects a dimensionless argument")
+        {
+            ErrorKind::Units
+        } else {
+            ErrorKind::DomainError
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::equation_analyzer::catalog;
+
+    #[test]
+    fn unit_names_are_unique_and_never_shadow_the_catalog() {
+        let mut seen: Vec<&str> = Vec::new();
+        for u in UNITS {
+            for label in std::iter::once(u.name).chain(u.aliases.iter().copied()) {
+                assert!(!seen.contains(&label), "duplicate unit label '{label}'");
+                assert!(
+                    catalog::find(label).is_none(),
+                    "unit label '{label}' is unreachable: the catalog claims it"
+                );
+                assert!(label != "x" && label != "y", "unit '{label}' is reserved");
+                seen.push(label);
+            }
+        }
+    }
+
+    #[test]
+    fn dimension_display_is_si() {
+        let newton = find("N").unwrap().dimension;
+        assert_eq!(newton.to_string(), "m*kg/s^2");
+        assert_eq!(find("Hz").unwrap().dimension.to_string(), "1/s");
+    }
+}
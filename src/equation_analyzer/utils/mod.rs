@@ -1,9 +1,13 @@
+use crate::equation_analyzer::errors::EquationError;
+use crate::equation_analyzer::limits::EvalLimits;
+
 /// Shared utilities for the equation analyzer pipeline.
 ///
 /// Represents a point in 2D space for plotting equations.
 ///
 /// This struct is used by all pipeline calculators to return plot results.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     /// The x-coordinate
     pub x: f32,
@@ -31,36 +35,84 @@ impl Point {
 /// * `x_min` - Minimum x value
 /// * `x_max` - Maximum x value
 /// * `step_size` - Step size between x values
+/// * `limits` - Checked for a deadline or cancellation while generating
 ///
 /// # Returns
 /// Vector of x values from x_min to x_max (inclusive) with the given step size
-pub fn get_x_values(x_min: f32, x_max: f32, step_size: f32) -> Vec<f32> {
-    let x_range = ((x_max - x_min) / step_size).ceil() as usize + 1;
-    let mut x_values = Vec::with_capacity(x_range);
+pub fn get_x_values(
+    x_min: f32,
+    x_max: f32,
+    step_size: f32,
+    limits: &EvalLimits,
+) -> Result<Vec<f32>, EquationError> {
+    // Check the deadline and cancel token now and then, so that a huge
+    // range without a point limit can still be interrupted.
+    const CHECK_EVERY: u64 = 4096;
+    let count = sample_count(x_min, x_max, step_size) as u64;
+    let mut x_values = Vec::new();
+    for i in 0..count {
+        if i % CHECK_EVERY == 0 {
+            limits.check_interrupt()?;
+        }
+        x_values.push(x_at(x_min, step_size, i));
+    }
+    Ok(x_values)
+}
 
-    let mut x_cur = x_min;
-    while x_cur <= x_max {
-        x_values.push(x_cur);
-        x_cur += step_size;
+/// The number of samples `x_min..=x_max` by `step_size` has, counted in
+/// f64 so that it is exact for any f32 range; zero for an empty range.
+/// Infinite for an infinite range.
+pub(crate) fn sample_count(x_min: f32, x_max: f32, step_size: f32) -> f64 {
+    if x_max >= x_min {
+        ((f64::from(x_max) - f64::from(x_min)) / f64::from(step_size)).floor() + 1.0
+    } else {
+        0.0
     }
-    x_values
+}
+
+/// The `i`th sample from `x_min`. Computed from the index rather than by
+/// accumulating steps, so it neither drifts nor stalls once `step_size`
+/// drops below the spacing of f32 values near `x`.
+pub(crate) fn x_at(x_min: f32, step_size: f32, i: u64) -> f32 {
+    (f64::from(x_min) + i as f64 * f64::from(step_size)) as f32
 }
 
 #[cfg(test)]
+#[allow(clippy::unwrap_used)]
 mod tests {
     use super::*;
 
+    fn x_values(x_min: f32, x_max: f32, step_size: f32) -> Vec<f32> {
+        get_x_values(x_min, x_max, step_size, &EvalLimits::default()).unwrap()
+    }
+
     #[test]
     fn test_get_x_values() {
-        let values = get_x_values(-2.0, 2.0, 1.0);
+        let values = x_values(-2.0, 2.0, 1.0);
         assert_eq!(values.len(), 5);
         assert_eq!(values, vec![-2.0, -1.0, 0.0, 1.0, 2.0]);
     }
 
     #[test]
     fn test_get_x_values_fractional_step() {
-        let values = get_x_values(0.0, 1.0, 0.25);
+        let values = x_values(0.0, 1.0, 0.25);
         assert_eq!(values.len(), 5);
         assert_eq!(values, vec![0.0, 0.25, 0.5, 0.75, 1.0]);
     }
+
+    #[test]
+    fn test_get_x_values_step_below_f32_spacing() {
+        // Near 1e8 adjacent f32 values are 8 apart: accumulating a step of
+        // 1 would never advance.
+        let values = x_values(1e8, 1e8 + 100.0, 1.0);
+        assert_eq!(values.len(), 97);
+        assert!(values.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(values.last(), Some(&(1e8 + 96.0)));
+    }
+
+    #[test]
+    fn test_get_x_values_checks_the_deadline() {
+        let limits = EvalLimits::new().with_timeout(std::time::Duration::ZERO);
+        assert!(get_x_values(0.0, 1e9, 1.0, &limits).is_err());
+    }
 }
@@ -1,8 +1,22 @@
-use crate::linear_algebra::{dot_product, scalar_multiply, vec_add, Vector};
+use crate::equation_analyzer::calculator::eval_with_derivative;
+use crate::equation_analyzer::{Definitions, EquationError};
+use crate::linear_algebra::{
+    dot_product, get_column, make_matrix, scalar_multiply, vec_add, vec_subtract, Matrix, Vector,
+};
 use crate::utilities::shuffle_vector;
 
 const H_RES: f64 = 0.0001;
 
+/// Relative step for the central-difference Jacobian in Levenberg–Marquardt
+const JACOBIAN_STEP: f64 = 1e-5;
+
+/// Levenberg–Marquardt stops once λ has grown past this without progress
+const MAX_DAMPING: f64 = 1e12;
+
+/// Levenberg–Marquardt stops once a step improves the cost by less than this
+/// fraction
+const CONVERGED: f64 = 1e-12;
+
 ///Computes the sum of squared elements in v
 pub fn sum_of_squares(v: &Vector) -> f64 {
     dot_product(v, v)
@@ -23,6 +37,35 @@ pub fn difference_quotient(f: &dyn Fn(f64) -> f64, x: f64, h: f64) -> f64 {
     (f(x + h) - f(x)) / h
 }
 
+/// Computes the exact derivative of an equation in `x`
+///
+/// The exact counterpart of `difference_quotient` for objectives written as
+/// equations: no step size, no truncation error.
+///
+/// # Arguments
+///
+/// * `eq` - The equation to differentiate, which may call functions in `defs`
+/// * `x` - The point at which to take the derivative
+/// * `defs` - User definitions the equation may refer to
+///
+/// # Examples
+///
+/// ```
+/// use rusty_maths::equation_analyzer::Definitions;
+/// use rusty_maths::gradient_descent::{expression_derivative, gradient_step};
+///
+/// let defs = Definitions::new();
+/// let mut v = vec![0.0];
+/// for _ in 0..100 {
+///     let grad = vec![expression_derivative("(x - 3)^2", v[0], &defs).unwrap()];
+///     v = gradient_step(&v, &grad, -0.1);
+/// }
+/// assert!((v[0] - 3.0).abs() < 1e-3);
+/// ```
+pub fn expression_derivative(eq: &str, x: f64, defs: &Definitions) -> Result<f64, EquationError> {
+    eval_with_derivative(eq, x as f32, defs).map(|(_, d)| f64::from(d))
+}
+
 /// Returns the i-th partial difference quotient of f at v
 ///
 /// Computes the partial derivative with respect to the i-th variable
@@ -98,6 +141,141 @@ pub fn linear_gradient(x: f64, y: f64, theta: &Vector) -> Vector {
     vec![2_f64 * error * x, 2_f64 * error]
 }
 
+/// Estimates the Jacobian of a vector-valued function by central differences
+///
+/// Row i holds the partial derivatives of the i-th output; the step for each
+/// input is relative to its size, so large and small parameters are probed
+/// alike.
+fn estimate_jacobian(f: &dyn Fn(&Vector) -> Vector, v: &Vector) -> Matrix {
+    let mut columns = vec![];
+    for j in 0..v.len() {
+        let h = JACOBIAN_STEP * v[j].abs().max(1_f64);
+        let mut forward = v.clone();
+        forward[j] += h;
+        let mut backward = v.clone();
+        backward[j] -= h;
+        let column = vec_subtract(&f(&forward), &f(&backward));
+        columns.push(scalar_multiply(0.5 / h, &column));
+    }
+    let rows = columns.first().map_or(0, |c| c.len());
+    make_matrix(rows, v.len(), &|(i, j)| columns[j][i])
+}
+
+/// Solves the square system a·x = b by Gaussian elimination with partial
+/// pivoting; None if a is singular
+fn solve_linear(mut a: Matrix, mut b: Vector) -> Option<Vector> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col] == 0_f64 || !a[pivot][col].is_finite() {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            let (upper, lower) = a.split_at_mut(row);
+            for (target, &source) in lower[0][col..].iter_mut().zip(&upper[col][col..]) {
+                *target -= factor * source;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0_f64; n];
+    for row in (0..n).rev() {
+        let tail: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - tail) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Minimizes the sum of squared residuals by Levenberg–Marquardt
+///
+/// Each step solves (JᵀJ + λ·diag(JᵀJ))·δ = −Jᵀr, with the Jacobian J
+/// estimated by central differences. λ shrinks after a step that lowers the
+/// cost and grows after one that doesn't, so the method moves like
+/// Gauss–Newton near a minimum and like short gradient steps far from one.
+///
+/// # Arguments
+///
+/// * `residuals` - The residual vector at a parameter vector; a step whose
+///   residuals are not all finite is rejected
+/// * `initial` - The starting parameters
+/// * `max_iterations` - An upper bound on the number of steps tried
+///
+/// # Returns
+///
+/// The parameters with the lowest sum of squared residuals found
+///
+/// # Examples
+///
+/// ```
+/// use rusty_maths::gradient_descent::levenberg_marquardt;
+///
+/// // Fit y = a·exp(b·x) through three exact points
+/// let data = [(0.0, 2.0), (1.0, 2.0 * 0.5_f64.exp()), (2.0, 2.0 * 1_f64.exp())];
+/// let residuals = |p: &Vec<f64>| data.iter().map(|(x, y)| y - p[0] * (p[1] * x).exp()).collect();
+/// let p = levenberg_marquardt(&residuals, &vec![1.0, 0.0], 100);
+/// assert!((p[0] - 2.0).abs() < 1e-6 && (p[1] - 0.5).abs() < 1e-6);
+/// ```
+pub fn levenberg_marquardt(
+    residuals: &dyn Fn(&Vector) -> Vector,
+    initial: &Vector,
+    max_iterations: usize,
+) -> Vector {
+    let cost_of = |r: &Vector| {
+        if r.iter().all(|v| v.is_finite()) {
+            sum_of_squares(r)
+        } else {
+            f64::INFINITY
+        }
+    };
+    let mut p = initial.clone();
+    let mut r = residuals(&p);
+    let mut cost = cost_of(&r);
+    let mut lambda = 1e-3_f64;
+    let mut jacobian = estimate_jacobian(residuals, &p);
+
+    for _ in 0..max_iterations {
+        if cost == 0_f64 || !cost.is_finite() || lambda > MAX_DAMPING {
+            break;
+        }
+        let n = p.len();
+        let columns: Matrix = (0..n).map(|j| get_column(&jacobian, j)).collect();
+        // Normal equations: JᵀJ and −Jᵀr.
+        let mut normal = make_matrix(n, n, &|(i, j)| dot_product(&columns[i], &columns[j]));
+        let gradient: Vector = columns.iter().map(|c| -dot_product(c, &r)).collect();
+        for (i, row) in normal.iter_mut().enumerate() {
+            // Floor the scaling so a parameter the residuals ignore still
+            // gets a damped (zero) step instead of a singular system.
+            row[i] += lambda * row[i].max(1e-12);
+        }
+
+        let Some(step) = solve_linear(normal, gradient) else {
+            lambda *= 10_f64;
+            continue;
+        };
+        let trial = vec_add(&p, &step);
+        let trial_r = residuals(&trial);
+        let trial_cost = cost_of(&trial_r);
+
+        if trial_cost < cost {
+            let improvement = (cost - trial_cost) / cost;
+            p = trial;
+            r = trial_r;
+            cost = trial_cost;
+            lambda = (lambda / 10_f64).max(1e-12);
+            if improvement < CONVERGED {
+                break;
+            }
+            jacobian = estimate_jacobian(residuals, &p);
+        } else {
+            lambda *= 10_f64;
+        }
+    }
+    p
+}
+
 /// Splits a dataset into mini-batches for stochastic gradient descent
 ///
 /// # Arguments
@@ -241,4 +419,39 @@ mod tests {
         assert!(19.9 < slope && slope < 20.1);
         assert!(4.9 < intercept && intercept < 5.1);
     }
+
+    #[test]
+    fn levenberg_marquardt_test() {
+        // Logistic curve through exact samples, from a rough start
+        let logistic = |p: &Vector, x: f64| p[0] / (1_f64 + (-p[1] * (x - p[2])).exp());
+        let truth = vec![10_f64, 1.5_f64, 2_f64];
+        let data: Vec<(f64, f64)> = (0..20)
+            .map(|i| {
+                let x = i as f64 * 0.25;
+                (x, logistic(&truth, x))
+            })
+            .collect();
+        let residuals =
+            |p: &Vector| -> Vector { data.iter().map(|(x, y)| y - logistic(p, *x)).collect() };
+
+        let p = levenberg_marquardt(&residuals, &vec![5_f64, 1_f64, 1_f64], 200);
+
+        assert!(distance(&p, &truth) < 1e-6);
+    }
+
+    #[test]
+    fn levenberg_marquardt_linear_test() {
+        // A linear least-squares problem is solved in one Gauss–Newton step
+        let inputs = get_inputs();
+        let residuals = |p: &Vector| -> Vector {
+            inputs
+                .iter()
+                .map(|(x, y)| y - p[0].mul_add(*x, p[1]))
+                .collect()
+        };
+
+        let theta = levenberg_marquardt(&residuals, &vec![0_f64, 0_f64], 50);
+
+        assert!(distance(&theta, &vec![20_f64, 5_f64]) < 1e-6);
+    }
 }
@@ -17,6 +17,13 @@ pub trait Activation: Clone {
     fn derivative_vector(&self, v: &Vector) -> Vector {
         v.iter().map(|&x| self.derivative(x)).collect()
     }
+
+    /// The name a serialized network records for this activation, or
+    /// `None` if it can't be restored by name
+    #[cfg(feature = "serde")]
+    fn name(&self) -> Option<&'static str> {
+        None
+    }
 }
 
 /// ReLU (Rectified Linear Unit) activation function
@@ -38,6 +45,11 @@ impl Activation for ReLU {
             0.0
         }
     }
+
+    #[cfg(feature = "serde")]
+    fn name(&self) -> Option<&'static str> {
+        Some("relu")
+    }
 }
 
 /// Sigmoid activation function
@@ -56,6 +68,11 @@ impl Activation for Sigmoid {
         let fx = self.activate(x);
         fx * (1.0 - fx)
     }
+
+    #[cfg(feature = "serde")]
+    fn name(&self) -> Option<&'static str> {
+        Some("sigmoid")
+    }
 }
 
 /// Tanh (Hyperbolic Tangent) activation function
@@ -74,6 +91,11 @@ impl Activation for Tanh {
         let tanh_x = x.tanh();
         1.0 - tanh_x * tanh_x
     }
+
+    #[cfg(feature = "serde")]
+    fn name(&self) -> Option<&'static str> {
+        Some("tanh")
+    }
 }
 
 /// Linear activation function (identity)
@@ -91,6 +113,11 @@ impl Activation for Linear {
     fn derivative(&self, _x: f64) -> f64 {
         1.0
     }
+
+    #[cfg(feature = "serde")]
+    fn name(&self) -> Option<&'static str> {
+        Some("linear")
+    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,7 @@
 use crate::linear_algebra::{dot_product, Vector};
 use crate::neural_network::activations::Activation;
+#[cfg(feature = "serde")]
+use crate::neural_network::activations::{Linear, ReLU, Sigmoid, Tanh};
 use rand::Rng;
 
 /// Trait for neural network layers
@@ -12,6 +14,47 @@ pub trait Layer {
 
     /// Get the output size of this layer
     fn output_size(&self) -> usize;
+
+    /// The layer's learned state as plain data, or `None` if the layer
+    /// can't be serialized
+    #[cfg(feature = "serde")]
+    fn record(&self) -> Option<LayerRecord> {
+        None
+    }
+}
+
+/// One layer of a serialized network (with the `serde` feature)
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LayerRecord {
+    /// A [`Dense`] layer's weights (one row per output neuron) and biases
+    Dense {
+        weights: Vec<Vector>,
+        biases: Vector,
+    },
+    /// An [`ActivationLayer`] by activation name (`relu`, `sigmoid`,
+    /// `tanh`, `linear`) and size
+    Activation { function: String, size: usize },
+}
+
+#[cfg(feature = "serde")]
+impl LayerRecord {
+    /// Rebuilds the layer this record describes
+    pub fn into_layer(self) -> Result<Box<dyn Layer>, String> {
+        match self {
+            LayerRecord::Dense { weights, biases } => {
+                Ok(Box::new(Dense::checked(weights, biases)?))
+            }
+            LayerRecord::Activation { function, size } => Ok(match function.as_str() {
+                "relu" => Box::new(ActivationLayer::new(ReLU, size)),
+                "sigmoid" => Box::new(ActivationLayer::new(Sigmoid, size)),
+                "tanh" => Box::new(ActivationLayer::new(Tanh, size)),
+                "linear" => Box::new(ActivationLayer::new(Linear, size)),
+                _ => return Err(format!("unknown activation '{function}'")),
+            }),
+        }
+    }
 }
 
 /// Dense (Fully Connected) layer
@@ -81,6 +124,50 @@ impl Dense {
     }
 }
 
+#[cfg(feature = "serde")]
+impl Dense {
+    /// Like [`Dense::with_weights`], rejecting ragged rows and a bias count
+    /// that doesn't match the row count
+    fn checked(weights: Vec<Vector>, biases: Vector) -> Result<Self, String> {
+        if biases.len() != weights.len() {
+            return Err(format!(
+                "{} bias values for {} weight rows",
+                biases.len(),
+                weights.len()
+            ));
+        }
+        if weights.windows(2).any(|w| w[0].len() != w[1].len()) {
+            return Err("weight rows differ in length".to_string());
+        }
+        Ok(Dense::with_weights(weights, biases))
+    }
+}
+
+/// Serializes as `{"weights": [[…], …], "biases": […]}`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Dense {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Dense", 2)?;
+        state.serialize_field("weights", &self.weights)?;
+        state.serialize_field("biases", &self.biases)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Dense {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct Weights {
+            weights: Vec<Vector>,
+            biases: Vector,
+        }
+        let Weights { weights, biases } = Weights::deserialize(deserializer)?;
+        Dense::checked(weights, biases).map_err(serde::de::Error::custom)
+    }
+}
+
 impl Layer for Dense {
     fn forward(&mut self, input: &Vector) -> Vector {
         assert_eq!(
@@ -129,6 +216,14 @@ impl Layer for Dense {
     fn output_size(&self) -> usize {
         self.output_size
     }
+
+    #[cfg(feature = "serde")]
+    fn record(&self) -> Option<LayerRecord> {
+        Some(LayerRecord::Dense {
+            weights: self.weights.clone(),
+            biases: self.biases.clone(),
+        })
+    }
 }
 
 /// Activation layer - applies an activation function element-wise
@@ -191,6 +286,14 @@ impl<A: Activation> Layer for ActivationLayer<A> {
     fn output_size(&self) -> usize {
         self.size
     }
+
+    #[cfg(feature = "serde")]
+    fn record(&self) -> Option<LayerRecord> {
+        Some(LayerRecord::Activation {
+            function: self.activation.name()?.to_string(),
+            size: self.size,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -182,6 +182,38 @@ impl Default for Network {
     }
 }
 
+/// Serializes as the list of its layers' [`LayerRecord`]s; fails if a
+/// layer can't be recorded
+///
+/// [`LayerRecord`]: crate::neural_network::layer::LayerRecord
+#[cfg(feature = "serde")]
+impl serde::Serialize for Network {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let records = self
+            .layers
+            .iter()
+            .map(|layer| layer.record())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                serde::ser::Error::custom("network has a layer that can't be serialized")
+            })?;
+        serializer.collect_seq(records)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Network {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use crate::neural_network::layer::LayerRecord;
+        let layers = Vec::<LayerRecord>::deserialize(deserializer)?
+            .into_iter()
+            .map(LayerRecord::into_layer)
+            .collect::<Result<_, _>>()
+            .map_err(serde::de::Error::custom)?;
+        Ok(Network { layers })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -622,4 +654,28 @@ mod tests {
 
         println!("\n✓ Network successfully learned circle classification!");
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn network_round_trips_through_json() {
+        let mut network = Network::new();
+        network.add(Box::new(Dense::new(2, 3)));
+        network.add(Box::new(ActivationLayer::new(Tanh, 3)));
+        network.add(Box::new(Dense::new(3, 1)));
+        network.add(Box::new(ActivationLayer::new(Linear, 1)));
+
+        let json = serde_json::to_string(&network).unwrap();
+        let mut restored: Network = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.num_layers(), 4);
+        let input = vec![0.3, -0.7];
+        // JSON keeps weights to within an ulp or so.
+        let (a, b) = (restored.predict(&input), network.predict(&input));
+        assert!((a[0] - b[0]).abs() < 1e-12);
+
+        let ragged = r#"[{"type":"dense","weights":[[1.0],[1.0,2.0]],"biases":[0.0,0.0]}]"#;
+        assert!(serde_json::from_str::<Network>(ragged).is_err());
+        let unknown = r#"[{"type":"activation","function":"gelu","size":2}]"#;
+        assert!(serde_json::from_str::<Network>(unknown).is_err());
+    }
 }